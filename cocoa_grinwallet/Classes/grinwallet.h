@@ -18,13 +18,72 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+typedef struct {
+    uint8_t *ptr;
+    size_t len;
+    size_t cap;
+} GrinBuffer;
+
 void cstr_free(const char *s);
 
+void grin_buffer_free(GrinBuffer buffer);
+
+uint32_t grin_last_error_code(void);
+
+const char* grin_last_error_message(void);
+
+const char* grin_last_error_info(void);
+
+const char* grin_set_locale(
+    const char* locale,
+    uint8_t *error
+);
+
+const char* grin_init(
+    const char* global_cfg,
+    uint8_t *error
+);
+
+void grin_shutdown(void);
+
+const char* grin_get_version(uint8_t *error);
+
+const char* grin_capabilities(uint8_t *error);
+
+const char* grin_default_config(
+    const char* chain_type,
+    const char* data_dir,
+    uint8_t *error
+);
+
+const char* grin_config_migrate(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_update_server_list(
+    const char* url,
+    uint8_t *error
+);
+
+const char* grin_set_network(
+    const char* network,
+    uint8_t *error
+);
+
+const char* grin_server_list(uint8_t *error);
+
 const char*  select_nearest_node(
     const char* check_node_api_http_addr,
     uint8_t *error
 );
 
+const char* grin_probe_relays(
+    const char* json_cfg,
+    const char* relays_json,
+    uint8_t *error
+);
+
 const char*  grin_check_password(
     const char* json_cfg,
     const char* password,
@@ -38,6 +97,28 @@ const char*  grin_wallet_change_password(
     uint8_t *error
 );
 
+const char* grin_password_attempts(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_password_strength(
+    const char* password,
+    uint8_t *error
+);
+
+const char* grin_seed_kdf_get(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_seed_kdf_migrate(
+    const char* json_cfg,
+    const char* password,
+    const char* params_json,
+    uint8_t *error
+);
+
 const char* grin_init_wallet_seed(uint8_t *error);
 
 const char* grin_wallet_init(
@@ -68,27 +149,109 @@ const char* grin_wallet_check(
     uint8_t *error
 );
 
+const char* grin_wallet_check_full_start(
+    const char* json_cfg,
+    bool delete_unconfirmed,
+    uint8_t *error
+);
+
+const char* grin_wallet_check_full_status(
+    const char* task_id,
+    uint8_t *error
+);
+
+const char* grin_wallet_check_full_cancel(
+    const char* task_id,
+    uint8_t *error
+);
+
+const char* grin_wallet_doctor(
+    const char* json_cfg,
+    bool repair,
+    uint8_t *error
+);
+
+const char* grin_export_diagnostics(
+    const char* json_cfg,
+    uint8_t *error
+);
+
 const char* grin_get_wallet_mnemonic(
     const char* json_cfg,
     uint8_t *error
 );
 
+const char* grin_wallet_rotate_seed(
+    const char* json_cfg,
+    const char* new_data_dir,
+    bool is_12_phrases,
+    uint8_t *error
+);
+
+const char* grin_wallet_rotate_sweep(
+    const char* json_cfg,
+    const char* new_data_dir,
+    const char* mnemonic,
+    uint8_t *error
+);
+
+const char* grin_wallet_network_info(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_wallet_migrate(
+    const char* data_dir,
+    uint8_t *error
+);
+
 const char* grin_get_balance(
     const char* json_cfg,
     uint8_t *error
 );
 
+const char* grin_account_balances(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_refresh(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
+
+const char* grin_get_balance_for_account(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
 const char* grin_tx_retrieve(
     const char* json_cfg,
     const char* tx_slate_id,
     uint8_t *error
 );
 
+const char* grin_tx_retrieve_for_account(
+    const char* json_cfg,
+    const char* account,
+    const char* tx_slate_id,
+    uint8_t *error
+);
+
 const char* grin_txs_retrieve(
     const char* json_cfg,
     uint8_t *error
 );
 
+const char* grin_txs_retrieve_for_account(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
 const char* grin_output_retrieve(
     const char* json_cfg,
     uint32_t id,
@@ -100,22 +263,202 @@ const char* grin_outputs_retrieve(
     uint8_t *error
 );
 
+const char* grin_outputs_retrieve_for_account(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
+GrinBuffer grin_txs_retrieve_buffer(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
+GrinBuffer grin_outputs_retrieve_buffer(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
+const char* grin_tx_label_set(
+    const char* json_cfg,
+    const char* tx_slate_id,
+    const char* label,
+    uint8_t *error
+);
+
+const char* grin_balance_history(
+    const char* json_cfg,
+    const char* account,
+    const char* granularity,
+    uint8_t *error
+);
+
+const char* grin_wallet_stats(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
+const char* grin_txs_iter_open(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
+const char* grin_txs_iter_next(
+    uint64_t handle,
+    uint32_t page_size,
+    uint8_t *error
+);
+
+void grin_txs_iter_close(uint64_t handle);
+
 const char* grin_listen(
     const char* json_cfg,
     uint8_t *error
 );
 
+const char* grin_listen_at(
+    const char* json_cfg,
+    uint32_t key_index,
+    uint8_t *error
+);
+
+const char* grin_listen_for_account(
+    const char* json_cfg,
+    const char* account,
+    int64_t key_index,
+    uint8_t *error
+);
+
+const char* grin_known_senders(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_listeners_list(
+    uint8_t *error
+);
+
+const char* grin_incoming_slates(
+    const char* json_cfg,
+    const char* account,
+    uint8_t *error
+);
+
+const char* grin_network_changed(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_pending_receives(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_pending_receive_accept(
+    const char* json_cfg,
+    const char* slate_id,
+    uint8_t *error
+);
+
+const char* grin_pending_receive_reject(
+    const char* json_cfg,
+    const char* slate_id,
+    uint8_t *error
+);
+
+const char* grin_returned_slates(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_returned_slate_post(
+    const char* json_cfg,
+    const char* slate_id,
+    uint8_t *error
+);
+
+void grin_set_receive_message_callback(
+    const char* (*callback)(const char* sender, uint64_t amount)
+);
+
+const char* grin_receive_failures(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_receive_failures_clear(
+    const char* json_cfg,
+    uint8_t *error
+);
+
 const char* my_grin_relay_addr(
     const char* json_cfg,
     uint8_t *error
 );
 
+const char* grin_pos_create_charge(
+    const char* json_cfg,
+    uint64_t amount,
+    const char* memo,
+    uint64_t expiry_secs,
+    uint8_t *error
+);
+
+const char* grin_pos_charge_status(
+    const char* json_cfg,
+    const char* charge_id,
+    uint8_t *error
+);
+
+const char* grin_new_address(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_addresses_list(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_relay_addresses_derive(
+    const char* json_cfg,
+    uint32_t start_index,
+    uint32_t count,
+    uint8_t *error
+);
+
 const char* grin_relay_addr_query(
     const char* json_cfg,
     const char* six_code_suffix,
     uint8_t *error
 );
 
+const char* grin_relay_addr_resolve(
+    const char* json_cfg,
+    const char* six_code_suffix,
+    uint8_t *error
+);
+
+const char* grin_relay_addr_cache_list(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_address_validate(
+    const char* input,
+    uint8_t *error
+);
+
+const char* grin_slate_message_validate(
+    const char* json_cfg,
+    const char* message,
+    uint8_t *error
+);
+
 const char* grin_init_tx(
     const char* json_cfg,
     uint64_t amount,
@@ -135,18 +478,193 @@ const char* grin_send_tx(
     uint8_t *error
 );
 
+const char* grin_send_to_resolved(
+    const char* json_cfg,
+    const char* six_code_suffix,
+    const char* full_addr,
+    uint64_t amount,
+    const char* selection_strategy,
+    int16_t target_slate_version,
+    const char* message,
+    uint8_t *error
+);
+
+const char* grin_send_tx_for_account(
+    const char* json_cfg,
+    const char* account,
+    uint64_t amount,
+    const char* receiver_wallet_url,
+    const char* selection_strategy,
+    int16_t target_slate_version,
+    const char* message,
+    uint8_t *error
+);
+
+const char* grin_send_tx_with_result(
+    const char* json_cfg,
+    const char* account,
+    uint64_t amount,
+    const char* receiver_wallet_url,
+    const char* selection_strategy,
+    int16_t target_slate_version,
+    const char* message,
+    uint64_t lock_height,
+    uint8_t *error
+);
+
+const char* grin_send_duplicate_allow(
+    const char* json_cfg,
+    const char* receiver_addr_or_url,
+    uint64_t amount,
+    uint8_t *error
+);
+
+const char* grin_contacts_list(const char* json_cfg, uint8_t *error);
+
+const char* grin_contact_save(
+    const char* json_cfg,
+    const char* contact_json,
+    uint8_t *error
+);
+
+const char* grin_contact_delete(
+    const char* json_cfg,
+    const char* name,
+    uint8_t *error
+);
+
+const char* grin_send_to_contact(
+    const char* json_cfg,
+    const char* contact_name,
+    uint64_t amount,
+    const char* selection_strategy,
+    int16_t target_slate_version,
+    const char* message,
+    uint8_t *error
+);
+
+const char* grin_send_batch(
+    const char* json_cfg,
+    const char* recipients_json,
+    const char* selection_strategy,
+    int16_t target_slate_version,
+    uint8_t *error
+);
+
+const char* grin_send_tx_split(
+    const char* json_cfg,
+    const char* account,
+    uint64_t amount,
+    const char* receiver_addr_or_url,
+    const char* selection_strategy,
+    int16_t target_slate_version,
+    const char* message,
+    uint8_t *error
+);
+
+const char* grin_refund(
+    const char* json_cfg,
+    const char* tx_slate_id,
+    uint64_t amount,
+    uint8_t *error
+);
+
+const char* grin_self_spend(
+    const char* json_cfg,
+    const char* output_commitments,
+    uint8_t *error
+);
+
+const char* grin_recover_pending(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_tx_schedule(
+    const char* json_cfg,
+    const char* args_json,
+    uint64_t not_before,
+    uint8_t *error
+);
+
+const char* grin_tx_queue_list(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_tx_queue_cancel(
+    const char* json_cfg,
+    const char* id,
+    uint8_t *error
+);
+
+const char* grin_tx_queue_process(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_tx_queue_start(
+    const char* json_cfg,
+    uint64_t interval_secs,
+    uint8_t *error
+);
+
+const char* grin_tx_queue_stop(uint8_t *error);
+
+const char* grin_start_services(
+    const char* json_cfg,
+    const char* options,
+    uint8_t *error
+);
+
+const char* grin_stop_services(uint8_t *error);
+
+const char* grin_wallet_close(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_services_status(uint8_t *error);
+
+
+const char* grin_send_progress(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_cancel_token_new(uint8_t *error);
+
+const char* grin_send_cancel(
+    const char* token,
+    uint8_t *error
+);
+
 const char* grin_cancel_tx(
     const char* json_cfg,
     const char* tx_slate_id,
     uint8_t *error
 );
 
+const char* grin_cancel_tx_with_options(
+    const char* json_cfg,
+    const char* tx_slate_id,
+    bool force,
+    bool dry_run,
+    uint8_t *error
+);
+
 const char* grin_post_tx(
     const char* json_cfg,
     const char* tx_slate_id,
     uint8_t *error
 );
 
+const char* grin_tx_export_raw(
+    const char* json_cfg,
+    const char* tx_slate_id,
+    uint8_t *error
+);
+
 const char* grin_tx_file_receive(
     const char* json_cfg,
     const char* slate_file_path,
@@ -160,6 +678,218 @@ const char*  grin_tx_file_finalize(
     uint8_t *error
 );
 
+const char* grin_capability_issue(
+    const char* json_cfg,
+    const char* scope,
+    uint64_t ttl_secs,
+    uint8_t *error
+);
+
+const char* grin_capability_revoke(
+    const char* token,
+    uint8_t *error
+);
+
+const char* grin_biometric_enroll(
+    const char* json_cfg,
+    const char* password,
+    uint8_t *error
+);
+
+const char* grin_unlock_with_blob(
+    const char* json_cfg,
+    const char* blob,
+    uint8_t *error
+);
+
+const char* grin_biometric_revoke(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_owner_token_issue(
+    const char* json_cfg,
+    const char* password,
+    uint8_t *error
+);
+
+const char* grin_owner_token_require(
+    const char* json_cfg,
+    bool required,
+    uint8_t *error
+);
+
+const char* grin_owner_get_balance(
+    const char* token,
+    uint8_t *error
+);
+
+const char* grin_owner_txs_retrieve(
+    const char* token,
+    uint8_t *error
+);
+
+const char* grin_owner_outputs_retrieve(
+    const char* token,
+    uint8_t *error
+);
+
+const char* grin_owner_send_tx(
+    const char* token,
+    uint64_t amount,
+    const char* receiver_addr_or_url,
+    const char* selection_strategy,
+    int16_t target_slate_version,
+    const char* message,
+    uint8_t *error
+);
+
+const char* grin_owner_cancel_tx(
+    const char* token,
+    const char* tx_slate_id,
+    uint8_t *error
+);
+
+const char* grin_owner_post_tx(
+    const char* token,
+    const char* tx_slate_id,
+    uint8_t *error
+);
+
+const char* grin_foreign_check_version(
+    const char* token,
+    uint8_t *error
+);
+
+const char* grin_foreign_verify_slate_messages(
+    const char* token,
+    const char* slate_json,
+    uint8_t *error
+);
+
+const char* grin_foreign_receive_tx(
+    const char* token,
+    const char* slate_json,
+    const char* message,
+    uint8_t *error
+);
+
+const char* grin_fee_breakdown(
+    const char* json_cfg,
+    const char* slate_json,
+    uint8_t *error
+);
+
+const char* grin_slate_to_words(
+    const char* slate_json,
+    const char* format,
+    uint8_t *error
+);
+
+const char* grin_slate_from_words(
+    const char* words,
+    uint8_t *error
+);
+
+const char* grin_slate_to_nfc(
+    const char* slate_json,
+    uint8_t *error
+);
+
+const char* grin_slate_from_nfc(
+    const char* frame_hex,
+    uint8_t *error
+);
+
+const char* grin_audit_log_read(
+    const char* json_cfg,
+    uint64_t since,
+    uint32_t limit,
+    uint8_t *error
+);
+
+const char* grin_signer_challenge(
+    const char* slate_json,
+    uint32_t participant_id,
+    uint32_t frame_size,
+    uint8_t *error
+);
+
+const char* grin_signer_response(
+    const char* frames_json,
+    uint8_t *error
+);
+
+
+const char* grin_events_poll(uint8_t *error);
+
+void grin_set_event_callback(void (*callback)(const char* event_json));
+
+void grin_set_log_callback(void (*callback)(const char* log_json));
+
+const char* grin_trace_timeline(
+    const char* id,
+    uint8_t *error
+);
+
+void grin_set_send_approval_callback(bool (*callback)(const char* tx_json));
+
+const char* grin_node_peers(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_node_check_genesis(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_output_on_chain(
+    const char* json_cfg,
+    const char* commitment,
+    uint8_t *error
+);
+
+const char* grin_get_header(
+    const char* json_cfg,
+    const char* height_or_hash,
+    uint8_t *error
+);
+
+const char* grin_get_kernel(
+    const char* json_cfg,
+    const char* excess,
+    uint8_t *error
+);
+
+const char* grin_node_mempool_stats(
+    const char* json_cfg,
+    uint8_t *error
+);
+
+const char* grin_testing_enable(bool enabled, uint8_t *error);
+
+const char* grin_testing_fixture(
+    const char* chain_type,
+    const char* data_dir,
+    uint64_t blocks,
+    uint8_t *error
+);
+
+const char* grin_selftest(uint8_t *error);
+
+const char* grin_network_stats(uint8_t *error);
+
+void grin_network_stats_reset(void);
+
+void grin_set_amounts_as_strings(bool as_strings);
+
+void grin_metrics_enable(bool enabled);
+
+const char* grin_metrics_snapshot(uint8_t *error);
+
+void grin_metrics_reset(void);
+
 const char* grin_chain_height(
     const char* json_cfg,
     uint8_t *error
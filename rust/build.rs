@@ -0,0 +1,33 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Build hooks to record the build information
+
+use std::fs;
+
+fn main() {
+    built::write_built_file().expect("Failed to acquire build-time information");
+
+    // The grin-wallet commit this lib is built against, as locked in Cargo.lock
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let commit = fs::read_to_string("Cargo.lock")
+        .ok()
+        .and_then(|lock| {
+            lock.lines()
+                .find(|l| l.starts_with("source = \"git+https://github.com/gottstech/grin-wallet"))
+                .and_then(|l| l.trim_end_matches('"').rsplit('#').next().map(|s| s.to_owned()))
+        })
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=GRIN_WALLET_COMMIT={}", commit);
+}
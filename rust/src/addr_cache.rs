@@ -0,0 +1,227 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The resolved 6-code addresses kept across restarts: a 6-code once resolved by the relay
+//! keeps its full address, re-verified from time to time, so a later address with the same
+//! 6-code is refused instead of silently taking its place. The ambiguous 6-codes resolve to
+//! their candidates, for the user to pick the address to send to.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::{address, contacts, new_wallet_config, senders, store, MobileWalletCfg};
+
+pub const ADDR_CACHE_FILE: &str = "relay_addr_cache.json";
+
+/// How long a stored 6-code address is used without asking the relay again
+pub const REVERIFY_SECS: u64 = 7 * 24 * 3600;
+
+/// A resolved 6-code address
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedAddr {
+    pub six_code: String,
+    pub address: String,
+    pub first_resolved_at: u64,
+    pub last_verified_at: u64,
+}
+
+impl CachedAddr {
+    /// Whether the relay should be asked again for this 6-code
+    pub fn needs_verify(&self) -> bool {
+        store::now_secs().saturating_sub(self.last_verified_at) >= REVERIFY_SECS
+    }
+}
+
+/// The outcome of a 6-code lookup
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolveStatus {
+    Resolved,
+    Conflict,
+    NotFound,
+}
+
+/// A full address matching a 6-code
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub address: String,
+    /// When the address was first seen, resolved or sending to this wallet, if ever
+    pub first_seen_at: Option<u64>,
+    /// The name of the contact with this address, if any
+    pub contact: Option<String>,
+    /// Whether it is the address stored for the 6-code
+    pub stored: bool,
+}
+
+/// A 6-code lookup with all its matched addresses
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Resolution {
+    pub six_code: String,
+    pub status: ResolveStatus,
+    /// The address of a resolved 6-code
+    pub address: Option<String>,
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct AddrCache {
+    addresses: Vec<CachedAddr>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the address cache file
+    static ref ADDR_CACHE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        ADDR_CACHE_FILE,
+    ))
+}
+
+/// The stored full address of a 6-code
+pub fn get(config: &MobileWalletCfg, six_code: &str) -> Result<Option<CachedAddr>, Error> {
+    let _guard = ADDR_CACHE_LOCK.lock();
+    let cache: AddrCache = store::load(&data_path(config)?)?;
+    Ok(cache.addresses.into_iter().find(|a| a.six_code == six_code))
+}
+
+/// Store a full address just resolved by the relay, or refresh its verification time. An
+/// address different from the stored one of the same 6-code is a collision, and refused.
+pub fn confirm(config: &MobileWalletCfg, six_code: &str, address: &str) -> Result<(), Error> {
+    let _guard = ADDR_CACHE_LOCK.lock();
+    let path = data_path(config)?;
+    let mut cache: AddrCache = store::load(&path)?;
+    let now = store::now_secs();
+    match cache.addresses.iter_mut().find(|a| a.six_code == six_code) {
+        Some(cached) if cached.address != address => {
+            return Err(ErrorKind::ArgumentError(format!(
+                "address conflict, the 6-code {} was resolved to {} before, now to {}",
+                six_code, cached.address, address
+            ))
+            .into());
+        }
+        Some(cached) => cached.last_verified_at = now,
+        None => cache.addresses.push(CachedAddr {
+            six_code: six_code.to_owned(),
+            address: address.to_owned(),
+            first_resolved_at: now,
+            last_verified_at: now,
+        }),
+    }
+    store::save(&path, &cache)
+}
+
+/// All the stored 6-code addresses, the latest verified first
+pub fn list(config: &MobileWalletCfg) -> Result<Vec<CachedAddr>, Error> {
+    let _guard = ADDR_CACHE_LOCK.lock();
+    let mut addresses = store::load::<AddrCache>(&data_path(config)?)?.addresses;
+    addresses.sort_by(|a, b| b.last_verified_at.cmp(&a.last_verified_at));
+    Ok(addresses)
+}
+
+/// Store the address the user picked for a 6-code, replacing the stored one if any
+pub fn pin(config: &MobileWalletCfg, six_code: &str, address: &str) -> Result<(), Error> {
+    if !address::is_six_code(six_code) || !address.ends_with(six_code) {
+        return Err(ErrorKind::ArgumentError(format!(
+            "the address {} doesn't match the 6-code {}",
+            address, six_code
+        ))
+        .into());
+    }
+    let _guard = ADDR_CACHE_LOCK.lock();
+    let path = data_path(config)?;
+    let mut cache: AddrCache = store::load(&path)?;
+    let now = store::now_secs();
+    let first_resolved_at = cache
+        .addresses
+        .iter()
+        .find(|a| a.six_code == six_code && a.address == address)
+        .map(|a| a.first_resolved_at)
+        .unwrap_or(now);
+    cache.addresses.retain(|a| a.six_code != six_code);
+    cache.addresses.push(CachedAddr {
+        six_code: six_code.to_owned(),
+        address: address.to_owned(),
+        first_resolved_at,
+        last_verified_at: now,
+    });
+    store::save(&path, &cache)
+}
+
+/// The resolution of a 6-code from the addresses matched by the relay. The stored address of
+/// the 6-code is a candidate too, a different matched address being a conflict with it.
+pub fn resolve(
+    config: &MobileWalletCfg,
+    six_code: &str,
+    matched: Vec<String>,
+) -> Result<Resolution, Error> {
+    let stored = get(config, six_code)?;
+    let mut addresses = matched;
+    if let Some(stored) = stored.as_ref() {
+        if !addresses.contains(&stored.address) {
+            addresses.insert(0, stored.address.clone());
+        }
+    }
+
+    let senders = senders::list(config)?;
+    let contacts = contacts::list(config)?;
+    let candidates: Vec<Candidate> = addresses
+        .into_iter()
+        .map(|address| {
+            let stored = stored.as_ref().filter(|s| s.address == address);
+            let first_seen_at = senders
+                .iter()
+                .find(|s| s.address == address)
+                .map(|s| s.first_received_at)
+                .into_iter()
+                .chain(stored.map(|s| s.first_resolved_at))
+                .min();
+            Candidate {
+                first_seen_at,
+                contact: contacts
+                    .iter()
+                    .find(|c| c.relay_address.as_deref() == Some(address.as_str()))
+                    .map(|c| c.name.clone()),
+                stored: stored.is_some(),
+                address,
+            }
+        })
+        .collect();
+
+    let status = match candidates.len() {
+        0 => ResolveStatus::NotFound,
+        1 => ResolveStatus::Resolved,
+        _ => ResolveStatus::Conflict,
+    };
+    let address = match status {
+        ResolveStatus::Resolved => Some(candidates[0].address.clone()),
+        _ => None,
+    };
+    Ok(Resolution {
+        six_code: six_code.to_owned(),
+        status,
+        address,
+        candidates,
+    })
+}
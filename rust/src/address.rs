@@ -0,0 +1,135 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Classification of the send destinations, so the app can validate an input and pick the
+//! transport before the send call.
+
+use regex::Regex;
+use serde::Serialize;
+
+/// The bech32 data characters, of the relay addresses and their 6-code abbreviations
+const BECH32_CHARSET: &str = "[02-9ac-hj-np-z]";
+
+lazy_static! {
+    static ref SIX_CODE: Regex = Regex::new(&format!("^{}{{6}}$", BECH32_CHARSET)).unwrap();
+    /// A relay address, the "gn" human readable part on mainnet and "tn" on floonet
+    static ref RELAY_ADDRESS: Regex =
+        Regex::new(&format!("^(gn|tn)1{}{{20,87}}$", BECH32_CHARSET)).unwrap();
+    /// A v3 onion service address
+    static ref ONION_ADDRESS: Regex = Regex::new("^[a-z2-7]{56}\\.onion$").unwrap();
+}
+
+/// The kind of a send destination
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressType {
+    Http,
+    Onion,
+    Grinrelay,
+    SixCode,
+    Invalid,
+}
+
+/// A classified send destination
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedAddress {
+    #[serde(rename = "type")]
+    pub address_type: AddressType,
+    /// The input trimmed and lowercased where the format is case insensitive
+    pub normalized: String,
+    /// The network of a relay address
+    pub chain_type: Option<String>,
+    /// Whether this lib can send to it
+    pub supported: bool,
+    pub error: Option<String>,
+}
+
+fn parsed(address_type: AddressType, normalized: String) -> ParsedAddress {
+    ParsedAddress {
+        address_type,
+        normalized,
+        chain_type: None,
+        supported: true,
+        error: None,
+    }
+}
+
+fn invalid(normalized: String, error: &str) -> ParsedAddress {
+    ParsedAddress {
+        address_type: AddressType::Invalid,
+        normalized,
+        chain_type: None,
+        supported: false,
+        error: Some(error.to_owned()),
+    }
+}
+
+/// Whether an input is a 6-code relay address abbreviation
+pub fn is_six_code(input: &str) -> bool {
+    SIX_CODE.is_match(input)
+}
+
+/// Classify a send destination
+pub fn parse(input: &str) -> ParsedAddress {
+    let trimmed = input.trim();
+    let lower = trimmed.to_lowercase();
+    if trimmed.is_empty() {
+        return invalid(lower, "empty address");
+    }
+
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        // Only the scheme is case insensitive, the path is kept as is
+        let (scheme, rest) = trimmed.split_at(trimmed.find("://").unwrap() + 3);
+        let normalized = format!("{}{}", scheme.to_lowercase(), rest);
+        let host = lower[scheme.len()..]
+            .split(|c| c == '/' || c == ':')
+            .next()
+            .unwrap_or_default();
+        if host.is_empty() {
+            return invalid(normalized, "missing host");
+        }
+        if host.ends_with(".onion") {
+            let mut onion = parsed(AddressType::Onion, normalized);
+            onion.supported = false;
+            return onion;
+        }
+        return parsed(AddressType::Http, normalized);
+    }
+
+    if ONION_ADDRESS.is_match(&lower) {
+        let mut onion = parsed(AddressType::Onion, lower);
+        onion.supported = false;
+        return onion;
+    }
+
+    // The relay addresses are bech32, which is case insensitive but never mixed case
+    if trimmed != lower && trimmed != trimmed.to_uppercase() {
+        return invalid(lower, "mixed case address");
+    }
+    if is_six_code(&lower) {
+        return parsed(AddressType::SixCode, lower);
+    }
+    if RELAY_ADDRESS.is_match(&lower) {
+        let chain_type = if lower.starts_with("gn1") {
+            "mainnet"
+        } else {
+            "floonet"
+        };
+        let mut relay = parsed(AddressType::Grinrelay, lower);
+        relay.chain_type = Some(chain_type.to_owned());
+        return relay;
+    }
+    invalid(lower, "unrecognized address")
+}
@@ -0,0 +1,160 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The json form of the nanogrin amounts. A u64 amount above 2^53 loses precision parsed as a
+//! JS or Swift double, so the amounts of the response schema can be returned as decimal
+//! strings instead, and the amounts of the json inputs take both forms. The amount fields opt
+//! in with `serialize_with` and `deserialize_with`.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serializer};
+
+lazy_static! {
+    static ref AS_STRINGS: AtomicBool = AtomicBool::new(false);
+}
+
+pub fn set_as_strings(as_strings: bool) {
+    AS_STRINGS.store(as_strings, Ordering::SeqCst);
+}
+
+pub fn as_strings() -> bool {
+    AS_STRINGS.load(Ordering::SeqCst)
+}
+
+/// Serialize an amount in the chosen form
+pub fn serialize<S: Serializer>(amount: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    if as_strings() {
+        serializer.collect_str(amount)
+    } else {
+        serializer.serialize_u64(*amount)
+    }
+}
+
+/// Serialize an optional amount in the chosen form
+pub fn serialize_opt<S: Serializer>(
+    amount: &Option<u64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match amount {
+        Some(amount) => serialize(amount, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+struct AmountVisitor;
+
+impl<'de> Visitor<'de> for AmountVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an amount as an integer or a decimal string")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<u64, E> {
+        Ok(v)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<u64, E> {
+        if v < 0 {
+            return Err(E::custom("negative amount"));
+        }
+        Ok(v as u64)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<u64, E> {
+        v.parse::<u64>().map_err(E::custom)
+    }
+}
+
+/// Deserialize an amount of an integer or a decimal string
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    deserializer.deserialize_any(AmountVisitor)
+}
+
+/// Deserialize an optional amount of an integer or a decimal string
+pub fn deserialize_opt<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    struct Amount(#[serde(deserialize_with = "deserialize")] u64);
+
+    Ok(Option::<Amount>::deserialize(deserializer)?.map(|a| a.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Amounts {
+        #[serde(
+            serialize_with = "super::serialize",
+            deserialize_with = "super::deserialize"
+        )]
+        amount: u64,
+        #[serde(
+            default,
+            serialize_with = "super::serialize_opt",
+            deserialize_with = "super::deserialize_opt"
+        )]
+        fee: Option<u64>,
+        sent: u64,
+    }
+
+    #[test]
+    fn amounts_take_both_forms() {
+        let expected = Amounts {
+            amount: 1 << 60,
+            fee: Some(7),
+            sent: 3,
+        };
+        let numbers = json!({ "amount": 1u64 << 60, "fee": 7, "sent": 3 });
+        let strings = json!({ "amount": (1u64 << 60).to_string(), "fee": "7", "sent": 3 });
+        assert_eq!(
+            serde_json::from_value::<Amounts>(numbers).unwrap(),
+            expected
+        );
+        assert_eq!(
+            serde_json::from_value::<Amounts>(strings).unwrap(),
+            expected
+        );
+        let missing: Amounts = serde_json::from_value(json!({ "amount": 1, "sent": 3 })).unwrap();
+        assert_eq!(missing.fee, None);
+        assert!(serde_json::from_value::<Amounts>(json!({ "amount": -1, "sent": 3 })).is_err());
+    }
+
+    #[test]
+    fn only_the_amount_fields_turn_to_strings() {
+        let amounts = Amounts {
+            amount: 1 << 60,
+            fee: None,
+            sent: 3,
+        };
+        assert_eq!(
+            serde_json::to_value(&amounts).unwrap(),
+            json!({ "amount": 1u64 << 60, "fee": null, "sent": 3 })
+        );
+        super::set_as_strings(true);
+        let value = serde_json::to_value(&amounts).unwrap();
+        super::set_as_strings(false);
+        assert_eq!(
+            value,
+            json!({ "amount": (1u64 << 60).to_string(), "fee": null, "sent": 3 })
+        );
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Confirmation of the outgoing transactions by the app, i.e. biometric or 2FA, before they
+//! are finalized.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use serde_json::json;
+
+use grin_wallet_libwallet::Slate;
+use grin_wallet_util::grin_util::Mutex;
+
+/// The send approval callback, called with the tx json `{"slateId", "amount", "fee",
+/// "confirmation"}`, the confirmation being the reason of an extra confirmation, or null.
+/// It's called on the sending thread, which blocks until it returns true to approve.
+/// The string is only valid during the call.
+pub type SendApprovalCallback = extern "C" fn(tx_json: *const c_char) -> bool;
+
+lazy_static! {
+    static ref CALLBACK: Mutex<Option<SendApprovalCallback>> = Mutex::new(None);
+}
+
+/// Set or clear the send approval callback
+pub fn set_callback(callback: Option<SendApprovalCallback>) {
+    *CALLBACK.lock() = callback;
+}
+
+/// Whether a send approval callback is set
+pub fn is_set() -> bool {
+    CALLBACK.lock().is_some()
+}
+
+/// Ask the app to approve an outgoing tx, approved if no callback is set unless an extra
+/// `confirmation` is required
+pub fn approve(slate: &Slate, confirmation: Option<&str>) -> bool {
+    // Not holding the lock while the app asks the user
+    let callback = *CALLBACK.lock();
+    match callback {
+        Some(callback) => {
+            let tx_json = json!({
+                "slateId": slate.id.to_string(),
+                "amount": slate.amount,
+                "fee": slate.fee,
+                "confirmation": confirmation,
+            })
+            .to_string();
+            match CString::new(tx_json) {
+                Ok(tx_json) => callback(tx_json.as_ptr()),
+                Err(_) => false,
+            }
+        }
+        None => confirmation.is_none(),
+    }
+}
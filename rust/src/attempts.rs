@@ -0,0 +1,152 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The brute-force protection of the password. The failed attempts are counted in the wallet
+//! data directory, after `FREE_ATTEMPTS` of them each next one waits for an exponentially
+//! growing delay, and the seed file can be wiped after a configured number of them. It's
+//! enforced here, on the interactive unlock calls, not left to the UI. The wallet opens of
+//! the background services, with a possibly stale password, are not attempts.
+
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::errors::LibErrorKind;
+use crate::password::{self, SEED_FILE};
+use crate::{audit, biometric, events, new_wallet_config, store, MobileWalletCfg};
+
+const ATTEMPTS_FILE: &str = "password_attempts.json";
+
+/// The failed attempts without any delay
+const FREE_ATTEMPTS: u32 = 3;
+
+/// The delay after the first delayed failure, doubled on each next one
+const BASE_DELAY_SECS: u64 = 2;
+
+const MAX_DELAY_SECS: u64 = 3600;
+
+/// The failed attempts since the last successful one
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Attempts {
+    pub failures: u32,
+    pub last_failure_at: u64,
+    /// No attempt is accepted before this timestamp
+    pub locked_until: u64,
+}
+
+lazy_static! {
+    /// Serializes the updates of the attempts file
+    static ref ATTEMPTS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn delay_secs(failures: u32) -> u64 {
+    if failures < FREE_ATTEMPTS {
+        return 0;
+    }
+    let shift = (failures - FREE_ATTEMPTS).min(16);
+    (BASE_DELAY_SECS << shift).min(MAX_DELAY_SECS)
+}
+
+/// The failed attempts of the wallet of `data_file_dir`
+pub fn state(data_file_dir: &str) -> Result<Attempts, Error> {
+    store::load(&store::data_file(data_file_dir, ATTEMPTS_FILE))
+}
+
+/// Refuse an attempt during the delay of the last failures
+fn check(data_file_dir: &str) -> Result<(), Error> {
+    let attempts = state(data_file_dir)?;
+    let now = store::now_secs();
+    if attempts.locked_until > now {
+        return Err(LibErrorKind::LockedOut(attempts.locked_until - now).into());
+    }
+    Ok(())
+}
+
+/// Remove the seed file and the biometric escrow of the password, the wallet then only being
+/// recoverable from its mnemonic
+fn wipe(data_file_dir: &str) -> Result<(), Error> {
+    password::clean_up(data_file_dir);
+    biometric::revoke(data_file_dir)?;
+    let seed_file = store::data_file(data_file_dir, SEED_FILE);
+    if seed_file.exists() {
+        fs::remove_file(&seed_file)
+            .map_err(|e| ErrorKind::GenericError(format!("fail to wipe the seed: {}", e)))?;
+    }
+    Ok(())
+}
+
+fn failed(config: &MobileWalletCfg, data_file_dir: &str) -> Result<(), Error> {
+    let path = store::data_file(data_file_dir, ATTEMPTS_FILE);
+    let mut attempts: Attempts = store::load(&path)?;
+    let now = store::now_secs();
+    attempts.failures += 1;
+    attempts.last_failure_at = now;
+    attempts.locked_until = now + delay_secs(attempts.failures);
+    store::save(&path, &attempts)?;
+
+    let wipe_after = config.wipe_after_failed_attempts;
+    if wipe_after > 0 && attempts.failures >= wipe_after {
+        wipe(data_file_dir)?;
+        store::save(&path, &Attempts::default())?;
+        audit::record(
+            config,
+            "seed_wiped",
+            json!({ "failures": attempts.failures }),
+        );
+        events::emit("seed_wiped", json!({ "failures": attempts.failures }));
+    } else if attempts.locked_until > now {
+        audit::record(
+            config,
+            "password_locked_out",
+            json!({ "failures": attempts.failures, "lockedUntil": attempts.locked_until }),
+        );
+    }
+    Ok(())
+}
+
+fn succeeded(data_file_dir: &str) -> Result<(), Error> {
+    let path = store::data_file(data_file_dir, ATTEMPTS_FILE);
+    if path.exists() {
+        fs::remove_file(&path)
+            .map_err(|e| ErrorKind::GenericError(format!("fail to remove {:?}: {}", path, e)))?;
+    }
+    Ok(())
+}
+
+/// Run `open`, a call decrypting the seed with the password of `config`, under the
+/// brute-force protection: refused during the delay of the last failures, a decryption
+/// failure counted, a success resetting the count.
+pub fn guard<T, F>(config: &MobileWalletCfg, open: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    let data_file_dir = new_wallet_config(config.clone())?.data_file_dir;
+    let _lock = ATTEMPTS_LOCK.lock();
+    check(&data_file_dir)?;
+    let res = open();
+    match &res {
+        Ok(_) => succeeded(&data_file_dir)?,
+        Err(e) => {
+            if let ErrorKind::Encryption = e.kind() {
+                failed(config, &data_file_dir)?;
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,121 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The audit log of the security relevant events of a wallet: the wallet unlocked, the
+//! password changed, the seed displayed, the sends initiated and cancelled. It's append only,
+//! one json entry per line, so the user or the support can reconstruct what happened on a
+//! device. No secret is ever written to it.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::{new_wallet_config, store, MobileWalletCfg};
+
+const AUDIT_LOG_FILE: &str = "audit.log";
+
+/// The previous audit log, once the current one reached `MAX_AUDIT_LOG_BYTES`
+const AUDIT_LOG_OLD_FILE: &str = "audit.log.1";
+
+const MAX_AUDIT_LOG_BYTES: u64 = 1024 * 1024;
+
+/// An audit log entry
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub event: String,
+    pub account: String,
+    #[serde(default)]
+    pub data: Value,
+}
+
+lazy_static! {
+    /// Serializes the appends and the rotation of the audit log
+    static ref AUDIT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_dir(config: &MobileWalletCfg) -> Result<String, Error> {
+    Ok(new_wallet_config(config.clone())?.data_file_dir)
+}
+
+fn append(dir: &str, entry: &AuditEntry) -> std::io::Result<()> {
+    let path = store::data_file(dir, AUDIT_LOG_FILE);
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if size >= MAX_AUDIT_LOG_BYTES {
+        fs::rename(&path, store::data_file(dir, AUDIT_LOG_OLD_FILE))?;
+    }
+    let line = serde_json::to_string(entry)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Append an event to the audit log of the wallet. A failure is ignored, the audited
+/// operation is already done.
+pub fn record(config: &MobileWalletCfg, event: &str, data: Value) {
+    let entry = AuditEntry {
+        timestamp: store::now_secs(),
+        event: event.to_owned(),
+        account: config.account.clone(),
+        data,
+    };
+    let _guard = AUDIT_LOCK.lock();
+    if let Ok(dir) = data_dir(config) {
+        let _ = append(&dir, &entry);
+    }
+}
+
+fn read_file(path: &Path, since: u64, entries: &mut Vec<AuditEntry>) -> Result<(), Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(()),
+    };
+    for line in BufReader::new(file).lines() {
+        let line =
+            line.map_err(|e| ErrorKind::GenericError(format!("fail to read audit log: {}", e)))?;
+        // A line cut by a crash is skipped, the next ones are still readable
+        if let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) {
+            if entry.timestamp >= since {
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The audit log entries since a unix timestamp, the latest `limit` ones or all of them if 0,
+/// the oldest first
+pub fn read(config: &MobileWalletCfg, since: u64, limit: usize) -> Result<Vec<AuditEntry>, Error> {
+    let dir = data_dir(config)?;
+    let paths: Vec<PathBuf> = vec![
+        store::data_file(&dir, AUDIT_LOG_OLD_FILE),
+        store::data_file(&dir, AUDIT_LOG_FILE),
+    ];
+    let mut entries = vec![];
+    let _guard = AUDIT_LOCK.lock();
+    for path in paths {
+        read_file(&path, since, &mut entries)?;
+    }
+    if limit > 0 {
+        let excess = entries.len().saturating_sub(limit);
+        entries.drain(..excess);
+    }
+    Ok(entries)
+}
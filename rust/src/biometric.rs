@@ -0,0 +1,129 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The key escrow of the biometric unlock. On enrollment the wallet password is encrypted by a
+//! random key, the encrypted password kept in the wallet data directory and the key handed to
+//! the app as an opaque blob, to store behind LocalAuthentication. The blob given back after a
+//! biometric success unlocks the wallet, the app never holding the password itself. Neither
+//! the blob nor the data directory alone is enough to get the password.
+
+use std::fs;
+
+use ring::aead;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_util::{from_hex, to_hex};
+
+use crate::store;
+
+const ESCROW_FILE: &str = "biometric_escrow.json";
+
+const BLOB_VERSION: u8 = 1;
+
+const ID_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// The encrypted wallet password of a biometric enrollment
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Escrow {
+    /// Also in the blob, so a blob of a revoked enrollment is told apart from a wrong one
+    id: String,
+    nonce: String,
+    encrypted_password: String,
+    created_at: u64,
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| ErrorKind::GenericError("fail to generate a random key".to_owned()))?;
+    Ok(bytes)
+}
+
+fn load(data_file_dir: &str) -> Result<Option<Escrow>, Error> {
+    store::load(&store::data_file(data_file_dir, ESCROW_FILE))
+}
+
+/// Encrypt a password verified by the caller, replacing any previous enrollment, and return
+/// the blob of its key
+pub fn enroll(data_file_dir: &str, password: &str) -> Result<String, Error> {
+    let id = random_bytes(ID_LEN)?;
+    let key = random_bytes(KEY_LEN)?;
+    let nonce = random_bytes(NONCE_LEN)?;
+
+    let sealing_key =
+        aead::SealingKey::new(&aead::CHACHA20_POLY1305, &key).map_err(|_| ErrorKind::Encryption)?;
+    let tag_len = aead::CHACHA20_POLY1305.tag_len();
+    let mut data = password.as_bytes().to_vec();
+    data.resize(data.len() + tag_len, 0);
+    aead::seal_in_place(&sealing_key, &nonce, &[], &mut data, tag_len)
+        .map_err(|_| ErrorKind::Encryption)?;
+
+    let escrow = Escrow {
+        id: to_hex(id.clone()),
+        nonce: to_hex(nonce),
+        encrypted_password: to_hex(data),
+        created_at: store::now_secs(),
+    };
+    store::save(&store::data_file(data_file_dir, ESCROW_FILE), &Some(escrow))?;
+
+    let mut blob = vec![BLOB_VERSION];
+    blob.extend_from_slice(&id);
+    blob.extend_from_slice(&key);
+    Ok(to_hex(blob))
+}
+
+/// The wallet password of a blob of `enroll`, an `Encryption` error if the blob doesn't open
+/// the escrow
+pub fn unwrap(data_file_dir: &str, blob: &str) -> Result<String, Error> {
+    let blob = from_hex(blob.trim().to_owned())
+        .map_err(|_| ErrorKind::ArgumentError("invalid biometric blob".to_owned()))?;
+    if blob.len() != 1 + ID_LEN + KEY_LEN || blob[0] != BLOB_VERSION {
+        return Err(ErrorKind::ArgumentError("invalid biometric blob".to_owned()).into());
+    }
+    let (id, key) = blob[1..].split_at(ID_LEN);
+    let escrow = match load(data_file_dir)? {
+        Some(escrow) if escrow.id == to_hex(id.to_vec()) => escrow,
+        _ => {
+            return Err(ErrorKind::GenericError(
+                "biometric enrollment revoked, enroll again with the password".to_owned(),
+            )
+            .into())
+        }
+    };
+
+    let nonce = from_hex(escrow.nonce).map_err(|_| ErrorKind::Encryption)?;
+    let mut data = from_hex(escrow.encrypted_password).map_err(|_| ErrorKind::Encryption)?;
+    let opening_key =
+        aead::OpeningKey::new(&aead::CHACHA20_POLY1305, key).map_err(|_| ErrorKind::Encryption)?;
+    let password = aead::open_in_place(&opening_key, &nonce, &[], 0, &mut data)
+        .map_err(|_| ErrorKind::Encryption)?;
+    String::from_utf8(password.to_vec()).map_err(|_| ErrorKind::Encryption.into())
+}
+
+/// Drop the enrollment, so its blob no longer unlocks the wallet
+pub fn revoke(data_file_dir: &str) -> Result<bool, Error> {
+    let path = store::data_file(data_file_dir, ESCROW_FILE);
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to remove {:?}: {}", path, e)))?;
+    Ok(true)
+}
@@ -0,0 +1,245 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The safety checks of a tx cancellation. A cancelled tx which still gets confirmed leaves
+//! the wallet out of sync with the chain until a full check, so the cancel is refused once
+//! the tx is found on chain, by its outputs or its kernel, unless forced.
+//!
+//! And the cancellation tokens of the in-flight sends: a send with the `cancel_token` of its
+//! config is aborted once the token is cancelled, up to the post of the tx.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{NodeClient, OutputStatus, TxLogEntryType};
+use grin_wallet_util::grin_util::{to_hex, Mutex};
+
+use crate::errors::{self, LibErrorKind};
+use crate::upstream::{self, WalletApi};
+use crate::{coalesce, get_wallet_instance, node, services, MobileWalletCfg};
+
+/// How often a wait checks its token
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    /// The issued tokens, and whether they're cancelled
+    static ref TOKENS: Mutex<HashMap<String, bool>> = Mutex::new(HashMap::new());
+}
+
+/// What a cancel would do, and why it's risky
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelReport {
+    pub tx_id: u32,
+    pub tx_slate_id: String,
+    pub tx_type: String,
+    /// Why the tx is found on chain, the cancel is refused if any unless forced
+    pub risks: Vec<String>,
+    /// What the node can't tell, i.e. a finalized tx still in a mempool, not refusing the
+    /// cancel
+    pub warnings: Vec<String>,
+    /// The locked inputs which get spendable again, by commitment
+    pub outputs_unlocked: Vec<String>,
+    /// The unconfirmed outputs of the tx which get deleted, by commitment
+    pub outputs_deleted: Vec<String>,
+    pub cancelled: bool,
+}
+
+/// Check a tx cancellation, and do it unless `dry_run`. A risky cancel is refused unless
+/// `force`.
+pub fn cancel(
+    config: MobileWalletCfg,
+    tx_slate_id: &str,
+    force: bool,
+    dry_run: bool,
+) -> Result<CancelReport, Error> {
+    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let api = upstream::api(wallet.clone());
+    let (_, txs) = api.txs(false, None, Some(uuid))?;
+    let tx = txs
+        .first()
+        .ok_or_else(|| Error::from(ErrorKind::ArgumentError("tx not found".to_owned())))?;
+    if tx.confirmed {
+        return Err(ErrorKind::ArgumentError("tx already confirmed".to_owned()).into());
+    }
+    match tx.tx_type {
+        TxLogEntryType::TxSent | TxLogEntryType::TxReceived => {}
+        _ => return Err(ErrorKind::ArgumentError("tx can't be cancelled".to_owned()).into()),
+    }
+
+    let (_, outputs) = api.outputs(true, false, Some(tx.id))?;
+    let mut report = CancelReport {
+        tx_id: tx.id,
+        tx_slate_id: tx_slate_id.to_owned(),
+        tx_type: tx.tx_type.to_string(),
+        risks: vec![],
+        warnings: vec![],
+        outputs_unlocked: vec![],
+        outputs_deleted: vec![],
+        cancelled: false,
+    };
+    for o in outputs.iter() {
+        let commit = to_hex(o.commit.0.to_vec());
+        match o.output.status {
+            OutputStatus::Locked => report.outputs_unlocked.push(commit),
+            OutputStatus::Unconfirmed => report.outputs_deleted.push(commit),
+            _ => {}
+        }
+    }
+
+    // The outputs of the tx in the utxo set, or its kernel, mean it's on chain, the wallet
+    // just didn't refresh yet. The node api has no query of the mempool by tx.
+    let stored_tx = api.stored_tx(tx)?;
+    let mut commits: Vec<_> = outputs
+        .iter()
+        .filter(|o| o.output.status == OutputStatus::Unconfirmed)
+        .map(|o| o.commit)
+        .collect();
+    if let Some(stored_tx) = stored_tx.as_ref() {
+        commits.extend(stored_tx.outputs().iter().map(|o| o.commitment()));
+    }
+    if !commits.is_empty() {
+        let on_chain = wallet
+            .lock()
+            .w2n_client()
+            .get_outputs_from_node(commits)
+            .map_err(|e| ErrorKind::GenericError(format!("fail to check the chain: {}", e)))?;
+        if !on_chain.is_empty() {
+            report
+                .risks
+                .push("outputs of the tx are on chain, refresh the wallet".to_owned());
+        }
+    }
+    if let Some(stored_tx) = stored_tx.as_ref() {
+        for kernel in stored_tx.kernels() {
+            let excess = to_hex(kernel.excess.0.to_vec());
+            if !node::kernel(&config, &excess)?.is_null() {
+                report.risks.push("kernel of the tx is on chain".to_owned());
+            }
+        }
+        if report.risks.is_empty() {
+            report
+                .warnings
+                .push("tx was finalized and might still be in a mempool".to_owned());
+        }
+    }
+    if tx.tx_type == TxLogEntryType::TxReceived && report.risks.is_empty() {
+        report
+            .warnings
+            .push("the sender might still post the tx".to_owned());
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+    if !report.risks.is_empty() && !force {
+        return Err(ErrorKind::ArgumentError(format!(
+            "cancel refused, {}",
+            report.risks.join(", ")
+        ))
+        .into());
+    }
+    api.cancel(None, Some(uuid))?;
+    coalesce::invalidate();
+    report.cancelled = true;
+    Ok(report)
+}
+
+/// Issue a cancellation token, for the `cancel_token` of a send config
+pub fn new_token() -> String {
+    let token = Uuid::new_v4().to_simple().to_string();
+    TOKENS.lock().insert(token.clone(), false);
+    token
+}
+
+/// Cancel the send of a token. False if the token is unknown or its send is already done.
+pub fn request(token: &str) -> bool {
+    match TOKENS.lock().get_mut(token) {
+        Some(cancelled) => {
+            *cancelled = true;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Drop a token once its send is done
+pub fn release(token: Option<&str>) {
+    if let Some(token) = token {
+        TOKENS.lock().remove(token);
+    }
+}
+
+/// Releases its token when dropped, so the token is released on every return of its send
+pub struct TokenGuard(Option<String>);
+
+impl Drop for TokenGuard {
+    fn drop(&mut self) {
+        release(self.0.as_deref());
+    }
+}
+
+/// Release a token once the returned guard is dropped
+pub fn guard(token: Option<&str>) -> TokenGuard {
+    TokenGuard(token.map(|token| token.to_owned()))
+}
+
+/// A `LibErrorKind::Cancelled` error if the token is cancelled
+pub fn check(token: Option<&str>) -> Result<(), Error> {
+    let cancelled = token
+        .and_then(|token| TOKENS.lock().get(token).cloned())
+        .unwrap_or(false);
+    if cancelled {
+        return Err(LibErrorKind::Cancelled.into());
+    }
+    Ok(())
+}
+
+pub fn is_cancelled_error(e: &Error) -> bool {
+    errors::kind_of(e) == Some(&LibErrorKind::Cancelled)
+}
+
+/// Run a blocking call, i.e. the wait for the recipient, on a background thread, abandoned
+/// with a `LibErrorKind::Cancelled` error once the token is cancelled. The thread is one of the wallet of
+/// the `wallet` data dir.
+pub fn wait<T, F>(token: Option<&str>, wallet: &str, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    let token = match token {
+        Some(token) => token,
+        None => return f(),
+    };
+    let (sender, receiver) = channel();
+    services::spawn_for(wallet, "cancelable wait", move || {
+        let _ = sender.send(f());
+    })?;
+    loop {
+        check(Some(token))?;
+        match receiver.recv_timeout(CANCEL_POLL_INTERVAL) {
+            Ok(res) => return res,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(ErrorKind::GenericError("wait aborted".to_owned()).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The capability tokens of the `grin_owner_*` and `grin_foreign_*` calls. A token stands for
+//! a wallet config kept in this lib, so a less trusted component of the app can be handed a
+//! foreign token to receive slates without ever seeing the wallet password. The tokens live in
+//! the memory of the process, an app extension running in its own process can't use them.
+//!
+//! An owner token keeps the seed password derived from the wallet password, never the wallet
+//! password itself. Whether the spending calls of a wallet require an owner token is a setting
+//! of the wallet, in its data dir.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::store::{self, now_secs};
+
+const OWNER_TOKEN_FILE: &str = "owner_token.json";
+
+/// The api surface a token grants
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// The whole wallet, the foreign calls included
+    Owner,
+    /// Receiving slates only
+    Foreign,
+}
+
+impl Scope {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        match s {
+            "owner" => Ok(Scope::Owner),
+            "foreign" => Ok(Scope::Foreign),
+            _ => Err(ErrorKind::ArgumentError(format!("invalid scope '{}'", s)).into()),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Scope::Owner => "owner",
+            Scope::Foreign => "foreign",
+        }
+    }
+
+    fn grants(self, scope: Scope) -> bool {
+        self == Scope::Owner || self == scope
+    }
+}
+
+struct Capability {
+    scope: Scope,
+    json_cfg: String,
+    /// The seed password of an owner token, whose config has no password
+    seed_password: Option<String>,
+    /// Unix timestamp, never expires if None
+    expires_at: Option<u64>,
+}
+
+/// The owner token setting of a wallet
+#[derive(Serialize, Deserialize, Default)]
+struct OwnerTokenSetting {
+    required: bool,
+}
+
+lazy_static! {
+    static ref CAPABILITIES: Mutex<HashMap<String, Capability>> = Mutex::new(HashMap::new());
+}
+
+/// Issue a token of a scope for a wallet config, valid for `ttl_secs` or until revoked if 0.
+/// The seed password is the one of an owner token.
+pub fn issue(json_cfg: &str, scope: Scope, ttl_secs: u64, seed_password: Option<String>) -> String {
+    let token = Uuid::new_v4().to_simple().to_string();
+    let expires_at = if ttl_secs > 0 {
+        Some(now_secs() + ttl_secs)
+    } else {
+        None
+    };
+    CAPABILITIES.lock().insert(
+        token.clone(),
+        Capability {
+            scope,
+            json_cfg: json_cfg.to_owned(),
+            seed_password,
+            expires_at,
+        },
+    );
+    token
+}
+
+/// Revoke a token, false if it's unknown
+pub fn revoke(token: &str) -> bool {
+    CAPABILITIES.lock().remove(token).is_some()
+}
+
+/// Revoke all the tokens
+pub fn clear() {
+    CAPABILITIES.lock().clear();
+}
+
+/// The wallet config of a token granting a scope. An expired token is dropped.
+pub fn resolve(token: &str, scope: Scope) -> Result<String, Error> {
+    with_capability(token, scope, |c| c.json_cfg.clone())
+}
+
+/// The wallet config and the seed password of an owner token
+pub fn resolve_owner(token: &str) -> Result<(String, Option<String>), Error> {
+    with_capability(token, Scope::Owner, |c| {
+        (c.json_cfg.clone(), c.seed_password.clone())
+    })
+}
+
+fn with_capability<T, F: FnOnce(&Capability) -> T>(
+    token: &str,
+    scope: Scope,
+    f: F,
+) -> Result<T, Error> {
+    let mut capabilities = CAPABILITIES.lock();
+    let expired = match capabilities.get(token) {
+        Some(c) => c.expires_at.map(|at| now_secs() >= at).unwrap_or(false),
+        None => false,
+    };
+    if expired {
+        capabilities.remove(token);
+    }
+    match capabilities.get(token) {
+        Some(c) if c.scope.grants(scope) => Ok(f(c)),
+        Some(_) => Err(ErrorKind::ArgumentError(format!(
+            "token doesn't grant the {} scope",
+            scope.as_str()
+        ))
+        .into()),
+        None => Err(ErrorKind::ArgumentError("invalid or expired token".to_owned()).into()),
+    }
+}
+
+/// Whether the spending calls of the wallet of a data dir require an owner token
+pub fn is_owner_token_required(data_file_dir: &str) -> Result<bool, Error> {
+    let setting: OwnerTokenSetting =
+        store::load(&store::data_file(data_file_dir, OWNER_TOKEN_FILE))?;
+    Ok(setting.required)
+}
+
+/// Set whether the spending calls of the wallet of a data dir require an owner token
+pub fn set_owner_token_required(data_file_dir: &str, required: bool) -> Result<(), Error> {
+    store::save(
+        &store::data_file(data_file_dir, OWNER_TOKEN_FILE),
+        &OwnerTokenSetting { required },
+    )
+}
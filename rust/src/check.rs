@@ -0,0 +1,228 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Full wallet check in a background task, with cancellation and a report of the changes
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{NodeClient, OutputCommitMapping, WalletInst};
+use grin_wallet_util::grin_keychain::ExtKeychain;
+use grin_wallet_util::grin_util::{to_hex, Mutex};
+
+use crate::upstream::{self, WalletApi};
+use crate::{events, get_wallet_instance, netstats, MobileWalletCfg};
+
+/// Number of outputs checked per round, the cancellation is checked between the rounds
+const CHECK_BATCH_SIZE: u64 = 1000;
+
+/// An output as reported to the app
+#[derive(Serialize, Clone)]
+pub struct OutputSummary {
+    pub commit: String,
+    pub value: u64,
+    pub height: u64,
+    pub status: String,
+}
+
+impl OutputSummary {
+    fn from_mapping(m: &OutputCommitMapping) -> Self {
+        OutputSummary {
+            commit: to_hex(m.commit.0.to_vec()),
+            value: m.output.value,
+            height: m.output.height,
+            status: m.output.status.to_string(),
+        }
+    }
+}
+
+/// The outputs changed by a check or restore
+#[derive(Serialize, Default)]
+pub struct OutputChanges {
+    /// Outputs which are new in the wallet
+    pub restored: Vec<OutputSummary>,
+    /// Outputs with a fixed status, the status is the new one
+    pub fixed: Vec<OutputSummary>,
+    /// Outputs removed from the wallet, i.e. duplicates and the ones not on chain
+    pub removed: Vec<OutputSummary>,
+}
+
+/// All the outputs of the current account, including the spent ones, by commitment
+pub fn snapshot<C>(
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+) -> Result<HashMap<String, OutputSummary>, Error>
+where
+    C: NodeClient + 'static,
+{
+    let api = upstream::api(wallet);
+    let (_, outputs) = api.outputs(true, false, None)?;
+    Ok(outputs
+        .iter()
+        .map(OutputSummary::from_mapping)
+        .map(|o| (o.commit.clone(), o))
+        .collect())
+}
+
+/// The changes between two snapshots
+pub fn diff(
+    before: &HashMap<String, OutputSummary>,
+    after: &HashMap<String, OutputSummary>,
+) -> OutputChanges {
+    let mut changes = OutputChanges::default();
+    for (commit, output) in after {
+        match before.get(commit) {
+            None => changes.restored.push(output.clone()),
+            Some(old) if old.status != output.status => changes.fixed.push(output.clone()),
+            Some(_) => {}
+        }
+    }
+    for (commit, output) in before {
+        if !after.contains_key(commit) {
+            changes.removed.push(output.clone());
+        }
+    }
+    changes
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+struct CheckTask {
+    status: TaskStatus,
+    cancel: Arc<AtomicBool>,
+    highest_index: u64,
+    last_retrieved_index: u64,
+    error: Option<String>,
+    changes: Option<serde_json::Value>,
+}
+
+lazy_static! {
+    static ref TASKS: Mutex<HashMap<String, CheckTask>> = Mutex::new(HashMap::new());
+}
+
+fn finish(
+    task_id: &str,
+    status: TaskStatus,
+    error: Option<String>,
+    changes: Option<OutputChanges>,
+) {
+    if let Some(task) = TASKS.lock().get_mut(task_id) {
+        task.status = status;
+        task.error = error;
+        task.changes = changes.map(|c| serde_json::to_value(c).unwrap());
+    }
+    events::emit(
+        "check_finished",
+        json!({ "taskId": task_id, "status": status }),
+    );
+}
+
+/// Start a full check_repair in background, returns the task id
+pub fn start(config: MobileWalletCfg, delete_unconfirmed: bool) -> Result<String, Error> {
+    let batch_size = netstats::batch_size(&config, CHECK_BATCH_SIZE);
+    let wallet = get_wallet_instance(config)?;
+    let task_id = Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+    TASKS.lock().insert(
+        task_id.clone(),
+        CheckTask {
+            status: TaskStatus::Running,
+            cancel: cancel.clone(),
+            highest_index: 0,
+            last_retrieved_index: 0,
+            error: None,
+            changes: None,
+        },
+    );
+
+    let id = task_id.clone();
+    thread::spawn(move || {
+        let before = match snapshot(wallet.clone()) {
+            Ok(before) => before,
+            Err(e) => return finish(&id, TaskStatus::Failed, Some(e.to_string()), None),
+        };
+        let api = upstream::api(wallet.clone());
+        let mut start_index = 1;
+        loop {
+            if cancel.load(Ordering::SeqCst) {
+                let changes = snapshot(wallet.clone()).map(|after| diff(&before, &after));
+                return finish(&id, TaskStatus::Cancelled, None, changes.ok());
+            }
+            match api.check_repair_batch(delete_unconfirmed, start_index, batch_size, true) {
+                Ok((highest_index, last_retrieved_index)) => {
+                    if let Some(task) = TASKS.lock().get_mut(&id) {
+                        task.highest_index = highest_index;
+                        task.last_retrieved_index = last_retrieved_index;
+                    }
+                    if last_retrieved_index >= highest_index {
+                        break;
+                    }
+                    start_index = last_retrieved_index + 1;
+                }
+                Err(e) => return finish(&id, TaskStatus::Failed, Some(e.to_string()), None),
+            }
+        }
+        match snapshot(wallet) {
+            Ok(after) => finish(
+                &id,
+                TaskStatus::Completed,
+                None,
+                Some(diff(&before, &after)),
+            ),
+            Err(e) => finish(&id, TaskStatus::Failed, Some(e.to_string()), None),
+        }
+    });
+    Ok(task_id)
+}
+
+/// The status, progress and the final report of a check task
+pub fn status(task_id: &str) -> Result<serde_json::Value, Error> {
+    let tasks = TASKS.lock();
+    let task = tasks
+        .get(task_id)
+        .ok_or_else(|| Error::from(ErrorKind::ArgumentError("check task not found".to_owned())))?;
+    Ok(json!({
+        "taskId": task_id,
+        "status": task.status,
+        "highestIndex": task.highest_index,
+        "lastRetrievedIndex": task.last_retrieved_index,
+        "error": task.error,
+        "changes": task.changes,
+    }))
+}
+
+/// Request the cancellation of a running check task, effective after its current round
+pub fn cancel(task_id: &str) -> Result<(), Error> {
+    let tasks = TASKS.lock();
+    let task = tasks
+        .get(task_id)
+        .ok_or_else(|| Error::from(ErrorKind::ArgumentError("check task not found".to_owned())))?;
+    if task.status == TaskStatus::Running {
+        task.cancel.store(true, Ordering::SeqCst);
+    }
+    Ok(())
+}
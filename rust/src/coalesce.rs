@@ -0,0 +1,152 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Coalescing of the identical node-heavy calls, i.e. a `grin_get_balance` fired by several
+//! views at once: the calls arriving while one is running wait for it and share its result,
+//! which is also reused by the calls of a short window after it, unless the wallet is
+//! written meanwhile, i.e. by a send.
+
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_util::Mutex;
+
+/// How long the result of a call is reused by the identical calls
+pub const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Max number of remembered calls, the finished ones are dropped when it's full
+const MAX_CALLS: usize = 100;
+
+/// The result shared by the coalesced calls, the error without its cause chain
+type Shared<T> = Result<T, ErrorKind>;
+
+/// A running or finished call, with its finish time and result
+struct Call {
+    result: Mutex<Option<(Instant, Box<dyn Any + Send>)>>,
+    /// The calls waiting for the result, woken once it's set
+    waiters: Mutex<Vec<Sender<()>>>,
+}
+
+lazy_static! {
+    static ref CALLS: Mutex<HashMap<u64, Arc<Call>>> = Mutex::new(HashMap::new());
+}
+
+/// Wakes the waiting calls once the running one ends, with an error if it panicked
+struct Finish<T: Send + 'static> {
+    call: Arc<Call>,
+    result: Option<Shared<T>>,
+}
+
+impl<T: Send + 'static> Drop for Finish<T> {
+    fn drop(&mut self) {
+        let shared = self
+            .result
+            .take()
+            .unwrap_or_else(|| Err(ErrorKind::GenericError("coalesced call failed".to_owned())));
+        {
+            let mut result = self.call.result.lock();
+            *result = Some((Instant::now(), Box::new(shared)));
+        }
+        for waiter in self.call.waiters.lock().drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+}
+
+/// The key of a call, from its name and arguments. Only a hash is kept, the config json of
+/// the arguments holds the password.
+pub fn key<H: Hash>(name: &str, args: H) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    args.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn is_fresh(call: &Call, window: Duration) -> bool {
+    match call.result.lock().as_ref() {
+        Some((at, _)) => at.elapsed() < window,
+        // Running
+        None => true,
+    }
+}
+
+/// Forget the results of the finished calls, after a write to the wallet. The running calls
+/// are still shared, the calls arriving after them run again.
+pub fn invalidate() {
+    CALLS.lock().clear();
+}
+
+/// Run `f` unless an identical call is running or finished within `window`, whose result
+/// is then returned.
+pub fn run<T, F>(key: u64, window: Duration, f: F) -> Result<T, Error>
+where
+    T: Clone + Send + 'static,
+    F: FnOnce() -> Result<T, Error>,
+{
+    let (call, leader) = {
+        let mut calls = CALLS.lock();
+        match calls.get(&key) {
+            Some(call) if is_fresh(call, window) => (call.clone(), false),
+            _ => {
+                if calls.len() >= MAX_CALLS {
+                    calls.retain(|_, call| call.result.lock().is_none());
+                }
+                let call = Arc::new(Call {
+                    result: Mutex::new(None),
+                    waiters: Mutex::new(vec![]),
+                });
+                calls.insert(key, call.clone());
+                (call, true)
+            }
+        }
+    };
+
+    if leader {
+        let mut finish = Finish::<T> { call, result: None };
+        let res = f();
+        finish.result = Some(match res.as_ref() {
+            Ok(value) => Ok(value.clone()),
+            Err(e) => Err(e.kind()),
+        });
+        return res;
+    }
+
+    let wake = {
+        let result = call.result.lock();
+        if result.is_some() {
+            None
+        } else {
+            let (tx, rx) = channel();
+            call.waiters.lock().push(tx);
+            Some(rx)
+        }
+    };
+    if let Some(wake) = wake {
+        let _ = wake.recv();
+    }
+    let result = call.result.lock();
+    match result
+        .as_ref()
+        .and_then(|(_, r)| r.downcast_ref::<Shared<T>>())
+    {
+        Some(shared) => shared.clone().map_err(Error::from),
+        None => Err(ErrorKind::GenericError("coalesced call of another type".to_owned()).into()),
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The compact slate encodings, for the channels which can't carry the slate json as is,
+//! i.e. the chat apps reformatting the text.
+//!
+//! A slate is compressed as the crc32 of its json, big endian, followed by the raw deflate
+//! of the json. The checksum catches a slate mangled in transit, before its deserialization.
+//!
+//! The NFC frame of a compressed slate is the magic "GS", the frame version, the payload
+//! length as a big endian u16, the payload, then the big endian crc32 of all the previous
+//! bytes, so a truncated tag read is detected.
+
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_keychain::mnemonic::WORDS;
+
+use crate::slates;
+
+/// Bits of a word index, the bip39 list has 2048 words
+const WORD_BITS: usize = 11;
+
+/// The emojis of the byte values, a block of pictographs with an emoji presentation
+const EMOJI_BASE: u32 = 0x1F400;
+
+/// The emoji variation selector, which some apps insert after each emoji
+const VARIATION_SELECTOR: char = '\u{FE0F}';
+
+/// The magic of the NFC frames
+const NFC_MAGIC: &[u8] = b"GS";
+
+/// The version of the NFC frame layout
+const NFC_VERSION: u8 = 1;
+
+/// Max size of an NFC frame, the payload of an NDEF record read in one go by the phones
+pub const MAX_NFC_FRAME_SIZE: usize = 32 * 1024;
+
+/// Size of the NFC frame header, magic, version and payload length
+const NFC_HEADER_SIZE: usize = 5;
+
+/// Max size of a decompressed slate json, far above a slate of a few hundred outputs, so a
+/// small crafted payload can't inflate to exhaust the memory
+const MAX_SLATE_JSON_SIZE: u64 = 4 * 1024 * 1024;
+
+fn invalid(msg: &str) -> Error {
+    ErrorKind::ArgumentError(msg.to_owned()).into()
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// Compress a slate json, refused if its version can't be read back
+pub fn compress(slate_json: &str) -> Result<Vec<u8>, Error> {
+    slates::check_readable(slate_json)?;
+    let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+    encoder
+        .write_all(slate_json.as_bytes())
+        .map_err(|e| ErrorKind::GenericError(format!("fail to compress slate: {}", e)))?;
+    let deflated = encoder
+        .finish()
+        .map_err(|e| ErrorKind::GenericError(format!("fail to compress slate: {}", e)))?;
+    let mut payload = crc32(slate_json.as_bytes()).to_be_bytes().to_vec();
+    payload.extend(deflated);
+    Ok(payload)
+}
+
+/// Decompress a slate json of at most `MAX_SLATE_JSON_SIZE`, checking its checksum. Any data
+/// after the deflate stream, i.e. the padding of the word encoding, is ignored.
+pub fn decompress(payload: &[u8]) -> Result<String, Error> {
+    if payload.len() <= 4 {
+        return Err(invalid("slate data too short"));
+    }
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&payload[..4]);
+    let mut slate_json = String::new();
+    DeflateDecoder::new(&payload[4..])
+        .take(MAX_SLATE_JSON_SIZE + 1)
+        .read_to_string(&mut slate_json)
+        .map_err(|_| invalid("slate data corrupted"))?;
+    if slate_json.len() as u64 > MAX_SLATE_JSON_SIZE {
+        return Err(invalid("slate data too large"));
+    }
+    if crc32(slate_json.as_bytes()) != u32::from_be_bytes(checksum) {
+        return Err(invalid("slate data checksum mismatch"));
+    }
+    Ok(slate_json)
+}
+
+/// The words of a payload, 11 bits each, the last one zero padded
+fn to_words(payload: &[u8]) -> Vec<&'static str> {
+    let mut words = vec![];
+    let (mut acc, mut bits) = (0u32, 0usize);
+    for byte in payload {
+        acc = (acc << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= WORD_BITS {
+            bits -= WORD_BITS;
+            words.push(WORDS[(acc >> bits) as usize & 0x7ff].as_str());
+        }
+    }
+    if bits > 0 {
+        words.push(WORDS[(acc << (WORD_BITS - bits)) as usize & 0x7ff].as_str());
+    }
+    words
+}
+
+/// The payload of words. The padding might add a zero byte, ignored by the decompression.
+fn from_words(text: &str) -> Result<Vec<u8>, Error> {
+    let mut payload = vec![];
+    let (mut acc, mut bits) = (0u32, 0usize);
+    for word in text
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|w| !w.is_empty())
+    {
+        let word = word.to_lowercase();
+        let index = WORDS
+            .binary_search_by(|w| w.as_str().cmp(&word.as_str()))
+            .map_err(|_| invalid(&format!("unknown word '{}'", word)))?;
+        acc = ((acc << WORD_BITS) | index as u32) & 0x3f_ffff;
+        bits += WORD_BITS;
+        while bits >= 8 {
+            bits -= 8;
+            payload.push((acc >> bits) as u8);
+        }
+    }
+    Ok(payload)
+}
+
+fn to_emojis(payload: &[u8]) -> String {
+    payload
+        .iter()
+        .filter_map(|b| std::char::from_u32(EMOJI_BASE + u32::from(*b)))
+        .collect()
+}
+
+fn from_emojis(text: &str) -> Result<Vec<u8>, Error> {
+    text.chars()
+        .filter(|c| !c.is_whitespace() && *c != VARIATION_SELECTOR)
+        .map(|c| match (c as u32).checked_sub(EMOJI_BASE) {
+            Some(b) if b <= 0xff => Ok(b as u8),
+            _ => Err(invalid(&format!("unexpected character '{}'", c))),
+        })
+        .collect()
+}
+
+/// The text encodings of a compressed slate
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextFormat {
+    /// Space separated bip39 english words
+    Words,
+    /// One emoji per byte
+    Emoji,
+}
+
+impl TextFormat {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        match s {
+            "" | "words" => Ok(TextFormat::Words),
+            "emoji" => Ok(TextFormat::Emoji),
+            _ => Err(invalid(&format!("invalid slate text format '{}'", s))),
+        }
+    }
+}
+
+/// Encode a slate json as words or emojis
+pub fn slate_to_text(slate_json: &str, format: TextFormat) -> Result<String, Error> {
+    let payload = compress(slate_json)?;
+    Ok(match format {
+        TextFormat::Words => to_words(&payload).join(" "),
+        TextFormat::Emoji => to_emojis(&payload),
+    })
+}
+
+/// Decode a slate json from its words or emojis, the format being detected
+pub fn slate_from_text(text: &str) -> Result<String, Error> {
+    let text = text.trim();
+    let payload = if text.chars().any(|c| c as u32 >= EMOJI_BASE) {
+        from_emojis(text)?
+    } else {
+        from_words(text)?
+    };
+    decompress(&payload)
+}
+
+/// The NFC frame of a slate json
+pub fn slate_to_nfc(slate_json: &str) -> Result<Vec<u8>, Error> {
+    let payload = compress(slate_json)?;
+    let size = NFC_HEADER_SIZE + payload.len() + 4;
+    if size > MAX_NFC_FRAME_SIZE {
+        return Err(invalid(&format!(
+            "slate too large for nfc, {} bytes compressed",
+            payload.len()
+        )));
+    }
+    let mut frame = Vec::with_capacity(size);
+    frame.extend_from_slice(NFC_MAGIC);
+    frame.push(NFC_VERSION);
+    frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    frame.extend(payload);
+    let checksum = crc32(&frame);
+    frame.extend_from_slice(&checksum.to_be_bytes());
+    Ok(frame)
+}
+
+/// The slate json of an NFC frame
+pub fn slate_from_nfc(frame: &[u8]) -> Result<String, Error> {
+    if frame.len() < NFC_HEADER_SIZE + 4 || &frame[..2] != NFC_MAGIC {
+        return Err(invalid("not a slate nfc frame"));
+    }
+    if frame[2] != NFC_VERSION {
+        return Err(invalid(&format!(
+            "unsupported nfc frame version {}",
+            frame[2]
+        )));
+    }
+    let len = u16::from_be_bytes([frame[3], frame[4]]) as usize;
+    if frame.len() != NFC_HEADER_SIZE + len + 4 {
+        return Err(invalid("truncated slate nfc frame"));
+    }
+    let (data, checksum) = frame.split_at(NFC_HEADER_SIZE + len);
+    if crc32(data) != u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) {
+        return Err(invalid("slate nfc frame checksum mismatch"));
+    }
+    decompress(&data[NFC_HEADER_SIZE..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The payload of any json, the slate check of `compress` skipped
+    fn payload(json: &str) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+        encoder.write_all(json.as_bytes()).unwrap();
+        let mut payload = crc32(json.as_bytes()).to_be_bytes().to_vec();
+        payload.extend(encoder.finish().unwrap());
+        payload
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = r#"{"amount":"1000000000"}"#;
+        assert_eq!(decompress(&payload(json)).unwrap(), json);
+    }
+
+    #[test]
+    fn oversized_slate_is_refused() {
+        let json = "0".repeat(MAX_SLATE_JSON_SIZE as usize + 1);
+        let e = decompress(&payload(&json)).unwrap_err();
+        assert!(e.to_string().contains("too large"));
+    }
+}
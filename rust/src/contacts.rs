@@ -0,0 +1,218 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The address book, with the payment policy of each contact enforced by the sends to it,
+//! whether by contact name or by one of its addresses.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::InitTxArgs;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::address::{self, AddressType};
+use crate::{amounts, approval, new_wallet_config, store, MobileWalletCfg};
+
+pub const CONTACTS_FILE: &str = "contacts.json";
+
+/// The transport of the sends to a contact
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Http,
+    Relay,
+}
+
+/// The payment policy of a contact
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContactPolicy {
+    /// A send above this amount needs the confirmation of the send approval callback
+    #[serde(deserialize_with = "amounts::deserialize_opt")]
+    pub max_amount_without_confirmation: Option<u64>,
+    /// The transport of the sends by contact name, if the contact has both addresses
+    pub preferred_transport: Option<Transport>,
+    /// The message of the sends without one
+    pub default_message: Option<String>,
+}
+
+/// A contact of the address book, with an http url, a relay address, or both
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub name: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub relay_address: Option<String>,
+    #[serde(default)]
+    pub policy: ContactPolicy,
+}
+
+impl Contact {
+    fn has_address(&self, receiver: &str) -> bool {
+        self.url.as_deref() == Some(receiver) || self.relay_address.as_deref() == Some(receiver)
+    }
+
+    /// The receiver of a send to this contact, by the preferred transport if it has both
+    fn receiver(&self) -> Option<&str> {
+        match (
+            self.policy.preferred_transport,
+            &self.url,
+            &self.relay_address,
+        ) {
+            (Some(Transport::Http), Some(url), _) => Some(url.as_str()),
+            (_, _, Some(relay_address)) => Some(relay_address.as_str()),
+            (_, url, None) => url.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Contacts {
+    contacts: Vec<Contact>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the contacts file
+    static ref CONTACTS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        CONTACTS_FILE,
+    ))
+}
+
+/// Check a contact and normalize its addresses
+fn validate(mut contact: Contact) -> Result<Contact, Error> {
+    contact.name = contact.name.trim().to_owned();
+    if contact.name.is_empty() {
+        return Err(ErrorKind::ArgumentError("contact name is required".to_owned()).into());
+    }
+    if let Some(url) = contact.url.take().filter(|u| !u.trim().is_empty()) {
+        let parsed = address::parse(&url);
+        if parsed.address_type != AddressType::Http {
+            return Err(ErrorKind::ArgumentError(format!("invalid contact url '{}'", url)).into());
+        }
+        contact.url = Some(parsed.normalized);
+    }
+    if let Some(relay_address) = contact
+        .relay_address
+        .take()
+        .filter(|a| !a.trim().is_empty())
+    {
+        let parsed = address::parse(&relay_address);
+        match parsed.address_type {
+            AddressType::Grinrelay | AddressType::SixCode => {
+                contact.relay_address = Some(parsed.normalized)
+            }
+            _ => {
+                return Err(ErrorKind::ArgumentError(format!(
+                    "invalid contact relay address '{}'",
+                    relay_address
+                ))
+                .into())
+            }
+        }
+    }
+    if contact.url.is_none() && contact.relay_address.is_none() {
+        return Err(ErrorKind::ArgumentError("contact address is required".to_owned()).into());
+    }
+    Ok(contact)
+}
+
+/// All the contacts
+pub fn list(config: &MobileWalletCfg) -> Result<Vec<Contact>, Error> {
+    let _guard = CONTACTS_LOCK.lock();
+    let contacts: Contacts = store::load(&data_path(config)?)?;
+    Ok(contacts.contacts)
+}
+
+/// Add a contact, or replace the one of the same name
+pub fn save(config: &MobileWalletCfg, contact: Contact) -> Result<Contact, Error> {
+    let contact = validate(contact)?;
+    let _guard = CONTACTS_LOCK.lock();
+    let path = data_path(config)?;
+    let mut contacts: Contacts = store::load(&path)?;
+    contacts.contacts.retain(|c| c.name != contact.name);
+    contacts.contacts.push(contact.clone());
+    store::save(&path, &contacts)?;
+    Ok(contact)
+}
+
+/// Delete a contact by name
+pub fn delete(config: &MobileWalletCfg, name: &str) -> Result<(), Error> {
+    let _guard = CONTACTS_LOCK.lock();
+    let path = data_path(config)?;
+    let mut contacts: Contacts = store::load(&path)?;
+    let count = contacts.contacts.len();
+    contacts.contacts.retain(|c| c.name != name);
+    if contacts.contacts.len() == count {
+        return Err(ErrorKind::ArgumentError("contact not found".to_owned()).into());
+    }
+    store::save(&path, &contacts)
+}
+
+/// The receiver of a send to a contact by name
+pub fn receiver_of(config: &MobileWalletCfg, name: &str) -> Result<String, Error> {
+    let contact = list(config)?
+        .into_iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| Error::from(ErrorKind::ArgumentError("contact not found".to_owned())))?;
+    contact
+        .receiver()
+        .map(|r| r.to_owned())
+        .ok_or_else(|| ErrorKind::ArgumentError("contact has no address".to_owned()).into())
+}
+
+/// Apply the policy of the contact of a receiver to a send, if any: its default message, and
+/// its amount limit. A send above the limit is refused without the send approval callback,
+/// otherwise the returned reason is passed to the callback.
+pub fn apply_policy(
+    config: &MobileWalletCfg,
+    receiver: &str,
+    args: &mut InitTxArgs,
+) -> Result<Option<String>, Error> {
+    let contact = match list(config)?.into_iter().find(|c| c.has_address(receiver)) {
+        Some(contact) => contact,
+        None => return Ok(None),
+    };
+    if args.message.as_deref().map(str::is_empty).unwrap_or(true) {
+        if let Some(message) = contact.policy.default_message.as_ref() {
+            args.message = Some(message.clone());
+        }
+    }
+    match contact.policy.max_amount_without_confirmation {
+        Some(max) if args.amount > max => {
+            let reason = format!(
+                "amount {} above the limit {} of contact '{}'",
+                args.amount, max, contact.name
+            );
+            if !approval::is_set() {
+                return Err(ErrorKind::ArgumentError(format!(
+                    "{}, the send approval is required",
+                    reason
+                ))
+                .into());
+            }
+            Ok(Some(reason))
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The deadline of the network calls, the `operation_timeout_ms` of the config. The call runs
+//! on a background thread and the caller gets an error at the deadline, so the app stays
+//! within the iOS watchdog limits and background task budgets whatever the network does. A
+//! call past its deadline is cancelled by its cancel token, a send then stopping before its
+//! finalization or its post.
+
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+use serde_json::json;
+
+use grin_wallet_impls::{Error, ErrorKind};
+
+use crate::errors::LibErrorKind;
+use crate::{cancel, events, services};
+
+/// Run `f` and return its result within `timeout_ms`, or a `LibErrorKind::Timeout` error at the deadline,
+/// no deadline if 0. A timed out call gets its `cancel_token` cancelled, the steps checking it
+/// then stop, i.e. the finalization and the post of a send. The rest of the call completes in
+/// the background and its outcome is notified by an `operation_completed_late` event, i.e. a
+/// send already posted. The thread is one of the wallet of the `wallet` data dir.
+pub fn run<T, F>(
+    operation: &str,
+    wallet: &str,
+    timeout_ms: u64,
+    cancel_token: Option<&str>,
+    f: F,
+) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    if timeout_ms == 0 {
+        return f();
+    }
+    let (sender, receiver) = channel();
+    let name = operation.to_owned();
+    services::spawn_for(wallet, &format!("deadline {}", operation), move || {
+        let res = f();
+        let error = res.as_ref().err().map(|e| e.to_string());
+        if sender.send(res).is_err() {
+            events::emit(
+                "operation_completed_late",
+                json!({ "operation": name, "ok": error.is_none(), "error": error }),
+            );
+        }
+    })?;
+    match receiver.recv_timeout(Duration::from_millis(timeout_ms)) {
+        Ok(res) => res,
+        Err(RecvTimeoutError::Timeout) => {
+            if let Some(token) = cancel_token {
+                cancel::request(token);
+            }
+            Err(LibErrorKind::Timeout(format!(
+                "{} after {} ms, it may still complete",
+                operation, timeout_ms
+            ))
+            .into())
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(ErrorKind::GenericError(format!("{} aborted", operation)).into())
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The support bundle for the bug reports, with all the secrets redacted so it's safe to
+//! paste anywhere: the log tail, the config, the wallet doctor report and the node status.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use serde_json::{json, Value};
+
+use crate::upstream::{self, WalletApi};
+use crate::{
+    doctor, get_version, get_wallet_instance, netstats, node, receive, relay, runtime, services,
+    store, MobileWalletCfg,
+};
+
+/// Replacement of the redacted values
+pub const REDACTED: &str = "<redacted>";
+
+/// The config keys holding a secret
+const SECRET_CFG_KEYS: &[&str] = &["password", "node_api_secret", "owner_token"];
+
+/// Max size of the log tail in the bundle
+const MAX_LOG_BYTES: u64 = 64 * 1024;
+
+/// Replace every occurrence of the secrets in a text. The empty and very short secrets are
+/// skipped, they would garble the text without protecting anything.
+pub fn redact(text: &str, secrets: &[&str]) -> String {
+    secrets
+        .iter()
+        .filter(|s| s.len() >= 4)
+        .fold(text.to_owned(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+}
+
+/// Redact the secrets in all the strings of a json value
+fn redact_value(value: &mut Value, secrets: &[&str]) {
+    match value {
+        Value::String(s) => *s = redact(s, secrets),
+        Value::Array(values) => values.iter_mut().for_each(|v| redact_value(v, secrets)),
+        Value::Object(obj) => obj.values_mut().for_each(|v| redact_value(v, secrets)),
+        _ => {}
+    }
+}
+
+/// The config json with its secrets redacted
+fn redacted_config(config: &MobileWalletCfg) -> Value {
+    let mut value = serde_json::to_value(config).unwrap_or(Value::Null);
+    if let Some(obj) = value.as_object_mut() {
+        for key in SECRET_CFG_KEYS {
+            if let Some(v) = obj.get_mut(*key) {
+                if v.as_str().map(|s| !s.is_empty()).unwrap_or(false) {
+                    *v = json!(REDACTED);
+                }
+            }
+        }
+    }
+    value
+}
+
+/// The last `MAX_LOG_BYTES` of the log file, from the first full line
+fn log_tail(path: &str) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let start = len.saturating_sub(MAX_LOG_BYTES);
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| e.to_string())?;
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    if start > 0 {
+        if let Some(pos) = text.find('\n') {
+            return Ok(text[pos + 1..].to_owned());
+        }
+    }
+    Ok(text)
+}
+
+fn or_error(res: Result<Value, String>) -> Value {
+    res.unwrap_or_else(|e| json!({ "error": e }))
+}
+
+/// Build the support bundle of a wallet. A failing section holds its error instead, so the
+/// bundle is produced for a broken wallet too.
+pub fn export(config: &MobileWalletCfg) -> Value {
+    let secrets = [
+        config.password.as_str(),
+        config.node_api_secret.as_str(),
+        config.owner_token.as_str(),
+    ];
+
+    let logs = match runtime::log_file_path() {
+        Some(path) => or_error(log_tail(&path).map(|tail| json!(tail))),
+        None => json!({ "error": "logging not enabled" }),
+    };
+    let version = or_error(
+        get_version()
+            .map_err(|e| e.to_string())
+            .and_then(|v| serde_json::from_str(&v).map_err(|e| e.to_string())),
+    );
+    let doctor = or_error(
+        doctor::wallet_doctor(config.clone(), false)
+            .map(|report| serde_json::to_value(&report).unwrap_or(Value::Null))
+            .map_err(|e| e.to_string()),
+    );
+    let chain_height = or_error(
+        get_wallet_instance(config.clone())
+            .and_then(|wallet| upstream::api(wallet).node_height())
+            .map(|(height, updated_from_node)| {
+                json!({ "height": height, "updated_from_node": updated_from_node })
+            })
+            .map_err(|e| e.to_string()),
+    );
+    let mempool = or_error(node::mempool_stats(config).map_err(|e| e.to_string()));
+    let receive_failures = or_error(
+        receive::failures(config)
+            .map(|failures| serde_json::to_value(&failures).unwrap_or(Value::Null))
+            .map_err(|e| e.to_string()),
+    );
+
+    let mut bundle = json!({
+        "createdAt": store::now_secs(),
+        "version": version,
+        "config": redacted_config(config),
+        "doctor": doctor,
+        "node": {
+            "address": config.node_api_addr,
+            "height": chain_height,
+            "mempool": mempool,
+        },
+        "services": services::tasks(),
+        "listeners": relay::listeners(),
+        "receiveFailures": receive_failures,
+        "networkStats": netstats::snapshot(),
+        "logs": logs,
+    });
+
+    // The logs and the errors might quote a secret too
+    redact_value(&mut bundle, &secrets);
+    bundle
+}
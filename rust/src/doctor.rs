@@ -0,0 +1,179 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wallet data integrity self-check
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use grin_wallet_impls::{Error, WalletSeed};
+use grin_wallet_libwallet::{TxLogEntryType, WalletBackend};
+
+use crate::queue::{self, ScheduledTxStatus};
+use crate::upstream::{self, WalletApi};
+use crate::{get_wallet_instance, journal, new_wallet_config, store, MobileWalletCfg};
+
+/// The min age of an unconfirmed tx without outputs to be orphaned, a younger one may be
+/// still built or posted by a running call
+const ORPHAN_MIN_AGE_SECS: i64 = 3600;
+
+/// Result of one check item
+#[derive(Serialize)]
+pub struct CheckItem {
+    pub name: String,
+    pub ok: bool,
+    pub detail: Option<String>,
+}
+
+/// The full doctor report
+#[derive(Serialize)]
+pub struct DoctorReport {
+    pub healthy: bool,
+    pub checks: Vec<CheckItem>,
+    pub orphaned_tx_ids: Vec<u32>,
+    pub missing_stored_tx_ids: Vec<u32>,
+    pub repaired_tx_ids: Vec<u32>,
+}
+
+impl DoctorReport {
+    fn push(&mut self, name: &str, res: Result<(), String>) -> bool {
+        let ok = res.is_ok();
+        self.checks.push(CheckItem {
+            name: name.to_owned(),
+            ok,
+            detail: res.err(),
+        });
+        ok
+    }
+}
+
+/// Run all checks. With `repair`, the orphaned unconfirmed tx log entries are cancelled,
+/// which also unlocks any output still locked by them. A tx is orphaned once older than
+/// `ORPHAN_MIN_AGE_SECS`, and neither journaled nor being sent by the tx queue.
+pub fn wallet_doctor(config: MobileWalletCfg, repair: bool) -> Result<DoctorReport, Error> {
+    let mut report = DoctorReport {
+        healthy: false,
+        checks: vec![],
+        orphaned_tx_ids: vec![],
+        missing_stored_tx_ids: vec![],
+        repaired_tx_ids: vec![],
+    };
+
+    let wallet_config = new_wallet_config(config.clone())?;
+    let seed_res = config
+        .seed_password()
+        .and_then(|password| {
+            Ok(WalletSeed::from_file(
+                &wallet_config.data_file_dir,
+                &password,
+            )?)
+        })
+        .map(|_| ())
+        .map_err(|e| e.to_string());
+    if !report.push("seed_decrypt", seed_res) {
+        return Ok(report);
+    }
+
+    // The slates of the operations in progress, whose tx may have no outputs yet
+    let mut in_progress: HashSet<String> = journal::entries(&config)?
+        .into_iter()
+        .filter_map(|e| e.slate_id)
+        .collect();
+    in_progress.extend(
+        queue::list(&config)?
+            .into_iter()
+            .filter(|t| t.status == ScheduledTxStatus::Sending)
+            .filter_map(|t| t.tx_slate_id),
+    );
+    let orphan_before = store::now_secs() as i64 - ORPHAN_MIN_AGE_SECS;
+
+    let wallet = match get_wallet_instance(config.clone()) {
+        Ok(wallet) => wallet,
+        Err(e) => {
+            report.push("lmdb_open", Err(e.to_string()));
+            return Ok(report);
+        }
+    };
+    report.push("lmdb_open", Ok(()));
+
+    {
+        let mut w = wallet.lock();
+        let accounts: Vec<_> = w.acct_path_iter().collect();
+        let account_res = if accounts.iter().any(|a| a.label == config.account) {
+            Ok(())
+        } else {
+            Err(format!("account '{}' not found", config.account))
+        };
+        report.push("account_keys", account_res);
+
+        let parent_key_id = w.parent_key_id();
+        let referenced: HashSet<u32> = w
+            .iter()
+            .filter(|o| o.root_key_id == parent_key_id)
+            .filter_map(|o| o.tx_log_entry)
+            .collect();
+
+        for tx in w.tx_log_iter().filter(|t| t.parent_key_id == parent_key_id) {
+            let unconfirmed = !tx.confirmed
+                && (tx.tx_type == TxLogEntryType::TxSent
+                    || tx.tx_type == TxLogEntryType::TxReceived);
+            let busy = tx
+                .tx_slate_id
+                .map(|id| in_progress.contains(&id.to_string()))
+                .unwrap_or(false);
+            let settled = tx.creation_ts.timestamp() < orphan_before;
+            if unconfirmed && settled && !busy && !referenced.contains(&tx.id) {
+                report.orphaned_tx_ids.push(tx.id);
+            }
+            if tx.stored_tx.is_some() {
+                match w.get_stored_tx(&tx) {
+                    Ok(Some(_)) => {}
+                    _ => report.missing_stored_tx_ids.push(tx.id),
+                }
+            }
+        }
+    }
+
+    let orphaned_res = if report.orphaned_tx_ids.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} orphaned tx entries",
+            report.orphaned_tx_ids.len()
+        ))
+    };
+    report.push("tx_log_orphans", orphaned_res);
+    let stored_tx_res = if report.missing_stored_tx_ids.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} unreadable stored tx",
+            report.missing_stored_tx_ids.len()
+        ))
+    };
+    report.push("stored_tx", stored_tx_res);
+
+    if repair && !report.orphaned_tx_ids.is_empty() {
+        let api = upstream::api(wallet);
+        for id in report.orphaned_tx_ids.clone() {
+            if api.cancel(Some(id), None).is_ok() {
+                report.repaired_tx_ids.push(id);
+            }
+        }
+    }
+
+    report.healthy = report.checks.iter().all(|c| c.ok);
+    Ok(report)
+}
@@ -0,0 +1,175 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The versioned response schema of the balance and tx calls, owned by this lib, so an
+//! upstream change of the wallet types doesn't silently change the json parsed by the app.
+//! A config with `response_schema` 0 keeps the upstream serde form. The slates keep their
+//! versioned slate form, told by their `version_info`. The amounts are integers, or decimal
+//! strings once `grin_set_amounts_as_strings` is on.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use grin_wallet_libwallet::{TxLogEntry, TxLogEntryType, WalletInfo};
+
+use crate::amounts;
+
+/// The current version of the response schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The balance of an account
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Balance {
+    pub schema_version: u32,
+    /// Whether the balance was refreshed from the node
+    pub validated: bool,
+    /// Whether the refresh failed, the balance being the last known one
+    pub stale: bool,
+    pub last_confirmed_height: u64,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub total: u64,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_spendable: u64,
+    /// Spendable with the display confirmations
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_confirmed: u64,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_awaiting_confirmation: u64,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_awaiting_finalization: u64,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_immature: u64,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_locked: u64,
+    pub minimum_confirmations: u64,
+    pub display_confirmations: u64,
+}
+
+impl Balance {
+    /// The balance of the wallet info with the minimum confirmations, and the one with the
+    /// display confirmations
+    pub fn new(
+        info: &WalletInfo,
+        display_info: &WalletInfo,
+        display_confirmations: u64,
+        validated: bool,
+        stale: bool,
+    ) -> Self {
+        Balance {
+            schema_version: SCHEMA_VERSION,
+            validated,
+            stale,
+            last_confirmed_height: info.last_confirmed_height,
+            total: info.total,
+            amount_spendable: info.amount_currently_spendable,
+            amount_confirmed: display_info.amount_currently_spendable,
+            amount_awaiting_confirmation: info.amount_awaiting_confirmation,
+            amount_awaiting_finalization: info.amount_awaiting_finalization,
+            amount_immature: info.amount_immature,
+            amount_locked: info.amount_locked,
+            minimum_confirmations: info.minimum_confirmations,
+            display_confirmations,
+        }
+    }
+}
+
+/// A tx of the wallet
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tx {
+    pub id: u32,
+    pub slate_id: Option<String>,
+    /// "sent", "received", "coinbase", "sent_cancelled" or "received_cancelled"
+    #[serde(rename = "type")]
+    pub tx_type: String,
+    pub confirmed: bool,
+    /// Unix timestamps in seconds
+    pub created_at: i64,
+    pub confirmed_at: Option<i64>,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_credited: u64,
+    #[serde(serialize_with = "amounts::serialize")]
+    pub amount_debited: u64,
+    #[serde(serialize_with = "amounts::serialize_opt")]
+    pub fee: Option<u64>,
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+    /// The relay address it was received from, if known
+    pub sender_address: Option<String>,
+    pub label: Option<String>,
+}
+
+fn tx_type(tx_type: &TxLogEntryType) -> String {
+    match tx_type {
+        TxLogEntryType::TxSent => "sent".to_owned(),
+        TxLogEntryType::TxReceived => "received".to_owned(),
+        TxLogEntryType::ConfirmedCoinbase => "coinbase".to_owned(),
+        TxLogEntryType::TxSentCancelled => "sent_cancelled".to_owned(),
+        TxLogEntryType::TxReceivedCancelled => "received_cancelled".to_owned(),
+    }
+}
+
+impl Tx {
+    /// The tx of a log entry, with the `sender_address` and `label` of its annotated json
+    pub fn new(tx: &TxLogEntry, annotated: Option<&Value>) -> Self {
+        let annotation = |key: &str| {
+            annotated
+                .and_then(|a| a.get(key))
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned())
+        };
+        Tx {
+            id: tx.id,
+            slate_id: tx.tx_slate_id.map(|id| id.to_string()),
+            tx_type: tx_type(&tx.tx_type),
+            confirmed: tx.confirmed,
+            created_at: tx.creation_ts.timestamp(),
+            confirmed_at: tx.confirmation_ts.map(|ts| ts.timestamp()),
+            amount_credited: tx.amount_credited,
+            amount_debited: tx.amount_debited,
+            fee: tx.fee,
+            num_inputs: tx.num_inputs,
+            num_outputs: tx.num_outputs,
+            sender_address: annotation("sender_address"),
+            label: annotation("label"),
+        }
+    }
+}
+
+/// The txs of an account
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxList {
+    pub schema_version: u32,
+    /// Whether the txs were refreshed from the node
+    pub refreshed: bool,
+    pub txs: Vec<Tx>,
+}
+
+impl TxList {
+    /// The txs of a retrieval `(refreshed, txs)`, with its annotated json `[refreshed, [txs]]`
+    pub fn new(refreshed: bool, txs: &[TxLogEntry], annotated: &Value) -> Self {
+        let entries = annotated.get(1).and_then(|e| e.as_array());
+        TxList {
+            schema_version: SCHEMA_VERSION,
+            refreshed,
+            txs: txs
+                .iter()
+                .enumerate()
+                .map(|(i, tx)| Tx::new(tx, entries.and_then(|e| e.get(i))))
+                .collect(),
+        }
+    }
+}
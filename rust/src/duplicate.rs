@@ -0,0 +1,125 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The duplicate payment detection, for the accidental double taps of the send button: a send
+//! of the same amount to the same receiver within the configured window is refused, until the
+//! user confirms it.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use grin_wallet_impls::Error;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::errors::LibErrorKind;
+use crate::{new_wallet_config, store, MobileWalletCfg};
+
+const RECENT_SENDS_FILE: &str = "recent_sends.json";
+
+/// Max number of remembered sends, the oldest ones are dropped
+const MAX_RECENT_SENDS: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct RecentSend {
+    receiver: String,
+    amount: u64,
+    slate_id: String,
+    sent_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RecentSends {
+    sends: Vec<RecentSend>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the recent sends file
+    static ref DUPLICATE_LOCK: Mutex<()> = Mutex::new(());
+    /// The confirmed duplicates, as (data dir, receiver, amount), each allowing one send
+    static ref ALLOWED: Mutex<Vec<(String, String, u64)>> = Mutex::new(vec![]);
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        RECENT_SENDS_FILE,
+    ))
+}
+
+/// Refuse a send of the same amount to the same receiver as a recent one, unless the
+/// duplicate was allowed. No check if the window is not configured.
+pub fn check(config: &MobileWalletCfg, receiver: &str, amount: u64) -> Result<(), Error> {
+    if config.duplicate_send_window_secs == 0 {
+        return Ok(());
+    }
+    {
+        let mut allowed = ALLOWED.lock();
+        let allowance = (config.data_dir.clone(), receiver.to_owned(), amount);
+        if let Some(index) = allowed.iter().position(|a| *a == allowance) {
+            allowed.remove(index);
+            return Ok(());
+        }
+    }
+
+    let _guard = DUPLICATE_LOCK.lock();
+    let recent: RecentSends = store::load(&data_path(config)?)?;
+    let since = store::now_secs().saturating_sub(config.duplicate_send_window_secs);
+    match recent
+        .sends
+        .iter()
+        .rev()
+        .find(|s| s.sent_at >= since && s.receiver == receiver && s.amount == amount)
+    {
+        Some(send) => Err(LibErrorKind::DuplicatePayment(format!(
+            "{} was sent to {} {}s ago in tx {}",
+            amount,
+            receiver,
+            store::now_secs().saturating_sub(send.sent_at),
+            send.slate_id
+        ))
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Remember a successful send for the duplicate check
+pub fn record(config: &MobileWalletCfg, receiver: &str, amount: u64, slate_id: &str) {
+    if config.duplicate_send_window_secs == 0 {
+        return;
+    }
+    // The tx is already sent, a failure to remember it only weakens the next check
+    let _guard = DUPLICATE_LOCK.lock();
+    if let Ok(path) = data_path(config) {
+        if let Ok(mut recent) = store::load::<RecentSends>(&path) {
+            recent.sends.push(RecentSend {
+                receiver: receiver.to_owned(),
+                amount,
+                slate_id: slate_id.to_owned(),
+                sent_at: store::now_secs(),
+            });
+            let excess = recent.sends.len().saturating_sub(MAX_RECENT_SENDS);
+            recent.sends.drain(..excess);
+            let _ = store::save(&path, &recent);
+        }
+    }
+}
+
+/// Allow the next send of an amount to a receiver, once the user confirmed the duplicate
+pub fn allow(config: &MobileWalletCfg, receiver: &str, amount: u64) {
+    ALLOWED
+        .lock()
+        .push((config.data_dir.clone(), receiver.to_owned(), amount));
+}
@@ -0,0 +1,62 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The errors of this lib with their own error code. An error is carried as the cause of a
+//! `GenericError` of the same message, so `last_error` tells its code by its kind, not by its
+//! message.
+
+use failure::Fail;
+
+use grin_wallet_impls::{Error, ErrorKind};
+
+#[derive(Clone, Debug, Eq, PartialEq, Fail)]
+pub enum LibErrorKind {
+    /// A send refused as a likely duplicate of a recent one
+    #[fail(display = "duplicate payment, {}", _0)]
+    DuplicatePayment(String),
+    /// A spending call refused by the read-only profile, with the refused operation
+    #[fail(display = "read-only wallet, {} is disabled", _0)]
+    ReadOnly(String),
+    /// A password attempt refused during the delay of the last failed ones, with the seconds
+    /// left
+    #[fail(display = "too many failed password attempts, retry in {} seconds", _0)]
+    LockedOut(u64),
+    /// A node call failed on the node, not on the wallet
+    #[fail(display = "node unreachable: {}", _0)]
+    NodeUnreachable(String),
+    /// A call past its deadline
+    #[fail(display = "operation timed out: {}", _0)]
+    Timeout(String),
+    /// A send cancelled by its token
+    #[fail(display = "cancelled by user")]
+    Cancelled,
+    /// A send refused for its change below the dust threshold
+    #[fail(display = "dust change: {}", _0)]
+    DustChange(String),
+}
+
+impl From<LibErrorKind> for Error {
+    fn from(kind: LibErrorKind) -> Error {
+        let message = kind.to_string();
+        Error::from(kind.context(ErrorKind::GenericError(message)))
+    }
+}
+
+/// The kind of an error of this lib, None for an upstream error
+pub fn kind_of(e: &Error) -> Option<&LibErrorKind> {
+    (e as &dyn Fail)
+        .iter_chain()
+        .filter_map(|f| f.downcast_ref::<LibErrorKind>())
+        .next()
+}
@@ -0,0 +1,77 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wallet events for the app, from the background services and listeners.
+//! The events are queued for polling, and also delivered to the callback if one is set.
+
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::store::now_secs;
+
+/// Max number of queued events, the oldest ones are dropped if the app doesn't poll
+const MAX_QUEUED_EVENTS: usize = 1000;
+
+/// The event callback, called with the event json. The string is only valid during the call.
+pub type EventCallback = extern "C" fn(event_json: *const c_char);
+
+#[derive(Serialize, Clone)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub timestamp: u64,
+    pub data: Value,
+}
+
+lazy_static! {
+    static ref EVENTS: Mutex<VecDeque<Event>> = Mutex::new(VecDeque::new());
+    static ref CALLBACK: Mutex<Option<EventCallback>> = Mutex::new(None);
+}
+
+/// Emit an event
+pub fn emit(event_type: &str, data: Value) {
+    let event = Event {
+        event_type: event_type.to_owned(),
+        timestamp: now_secs(),
+        data,
+    };
+
+    if let Some(callback) = *CALLBACK.lock() {
+        if let Ok(event_json) = CString::new(serde_json::to_string(&event).unwrap()) {
+            callback(event_json.as_ptr());
+        }
+    }
+
+    let mut events = EVENTS.lock();
+    if events.len() >= MAX_QUEUED_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Take all the queued events
+pub fn drain() -> Vec<Event> {
+    EVENTS.lock().drain(..).collect()
+}
+
+/// Set or clear the event callback
+pub fn set_callback(callback: Option<EventCallback>) {
+    *CALLBACK.lock() = callback;
+}
@@ -0,0 +1,170 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The fee of a tx, broken down by what it pays for.
+//!
+//! A send leaving a change below the dust threshold of the config is refused, or its change
+//! given to the recipient, so the wallet doesn't pile up outputs worth less than spending them.
+
+use serde::Serialize;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{InitTxArgs, Slate};
+use grin_wallet_util::grin_core::libtx::tx_fee;
+
+use crate::errors::LibErrorKind;
+
+/// The fee of a sent tx, with the tx shape it's computed on
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeBreakdown {
+    pub fee: u64,
+    pub inputs: usize,
+    /// All the outputs, the recipient one included
+    pub outputs: usize,
+    /// The outputs back to the sender, none if the inputs match the amount and fee
+    pub change_outputs: usize,
+    pub kernels: usize,
+    /// The tx weight, 4 per output and 1 per kernel, less 1 per input, at least 1
+    pub weight: u64,
+}
+
+/// The weight of a tx, as the fee is computed on
+pub fn tx_weight(inputs: usize, outputs: usize, kernels: usize) -> u64 {
+    let weight = 4 * outputs as i64 + kernels as i64 - inputs as i64;
+    weight.max(1) as u64
+}
+
+impl FeeBreakdown {
+    /// The breakdown of a sent slate, from its tx once the recipient added its outputs. The
+    /// `change_outputs` are the outputs of the tx log entry of the sender: the slate can't tell
+    /// them from the recipient outputs, a recipient may split its receive into several ones.
+    pub fn of(slate: &Slate, change_outputs: usize) -> Self {
+        let inputs = slate.tx.inputs().len();
+        let outputs = slate.tx.outputs().len();
+        let kernels = slate.tx.kernels().len();
+        FeeBreakdown {
+            fee: slate.fee,
+            inputs,
+            outputs,
+            change_outputs,
+            kernels,
+            weight: tx_weight(inputs, outputs, kernels),
+        }
+    }
+}
+
+/// Init a send tx by `init`, then check its change against `dust_threshold`, no
+/// check if 0. A dust change is given to the recipient if `donate_dust`, the tx then spending
+/// the same inputs without change output, otherwise the send is refused with a
+/// `LibErrorKind::DustChange` error. `input_total` sums the values of the inputs of a slate.
+pub fn init_send<F, T>(
+    args: InitTxArgs,
+    dust_threshold: u64,
+    donate_dust: bool,
+    init: F,
+    input_total: T,
+) -> Result<Slate, Error>
+where
+    F: Fn(InitTxArgs) -> Result<Slate, Error>,
+    T: Fn(&Slate) -> Result<u64, Error>,
+{
+    let slate = init(args.clone())?;
+    if dust_threshold == 0 {
+        return Ok(slate);
+    }
+    let total = input_total(&slate)?;
+    let change = total.saturating_sub(slate.amount + slate.fee);
+    if change == 0 || change >= dust_threshold {
+        return Ok(slate);
+    }
+    if !donate_dust {
+        return Err(LibErrorKind::DustChange(format!(
+            "change {} below the dust threshold {}, send more or donate it",
+            change, dust_threshold
+        ))
+        .into());
+    }
+
+    // The selection fee of a tx without change output, so the change is exactly 0. The fee is
+    // set by the selection, the dust can only go to the recipient.
+    let inputs = slate.tx.inputs().len();
+    let donated = init(InitTxArgs {
+        amount: total - tx_fee(inputs, 1, 1, None),
+        num_change_outputs: 0,
+        ..args
+    })?;
+    if donated.tx.inputs().len() != inputs || input_total(&donated)? != total {
+        return Err(ErrorKind::GenericError(
+            "fail to donate the dust change, other inputs selected".to_owned(),
+        )
+        .into());
+    }
+    Ok(donated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors;
+
+    const TOTAL: u64 = 10_000_000;
+
+    fn init(args: InitTxArgs) -> Result<Slate, Error> {
+        let mut slate = Slate::blank(2);
+        slate.amount = args.amount;
+        slate.fee = 1_000_000;
+        Ok(slate)
+    }
+
+    fn args(amount: u64) -> InitTxArgs {
+        InitTxArgs {
+            amount,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_threshold_skips_the_check() {
+        let slate = init_send(args(8_999_999), 0, false, init, |_| Ok(TOTAL)).unwrap();
+        assert_eq!(slate.amount, 8_999_999);
+    }
+
+    #[test]
+    fn change_above_threshold_is_kept() {
+        let slate = init_send(args(8_000_000), 500_000, false, init, |_| Ok(TOTAL)).unwrap();
+        assert_eq!(slate.amount, 8_000_000);
+    }
+
+    #[test]
+    fn dust_change_is_refused() {
+        let e = init_send(args(8_999_999), 500_000, false, init, |_| Ok(TOTAL)).unwrap_err();
+        assert!(match errors::kind_of(&e) {
+            Some(LibErrorKind::DustChange(_)) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn dust_change_is_donated() {
+        let slate = init_send(args(8_999_999), 500_000, true, init, |_| Ok(TOTAL)).unwrap();
+        assert_eq!(slate.amount, TOTAL - tx_fee(0, 1, 1, None));
+    }
+
+    #[test]
+    fn weight_is_at_least_one() {
+        assert_eq!(tx_weight(2, 2, 1), 7);
+        assert_eq!(tx_weight(10, 1, 1), 1);
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Page by page iteration of the tx log, for the wallets with too many txs to list at once.
+//! The txs of the account are read once into a snapshot when the iterator is opened, so a
+//! page is a slice of it instead of a new scan of the log, and the pages are consistent with
+//! each other. The returned txs are dropped from the snapshot.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::json;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::TxLogEntry;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::upstream::{self, WalletApi};
+use crate::{get_wallet_instance, netstats, MobileWalletCfg};
+
+/// Max number of txs of a page
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Max number of open iterators, an app leaking them gets an error
+const MAX_OPEN_ITERATORS: usize = 16;
+
+struct TxIterator {
+    /// The txs not returned yet
+    txs: VecDeque<TxLogEntry>,
+}
+
+lazy_static! {
+    static ref NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+    static ref ITERATORS: Mutex<HashMap<u64, TxIterator>> = Mutex::new(HashMap::new());
+}
+
+/// Refresh the wallet from the node, and open an iterator over the txs of the account.
+/// Returns the iterator handle.
+pub fn open(config: MobileWalletCfg) -> Result<u64, Error> {
+    if ITERATORS.lock().len() >= MAX_OPEN_ITERATORS {
+        return Err(ErrorKind::GenericError("too many open tx iterators".to_owned()).into());
+    }
+    let refresh_from_node = netstats::should_refresh(&config);
+    let wallet = get_wallet_instance(config.clone())?;
+    upstream::api(wallet.clone()).summary_info(refresh_from_node, config.minimum_confirmations)?;
+
+    let txs: VecDeque<TxLogEntry> = {
+        let w = wallet.lock();
+        let parent_key_id = w.parent_key_id();
+        w.tx_log_iter()
+            .filter(|t| t.parent_key_id == parent_key_id)
+            .collect()
+    };
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::SeqCst);
+    ITERATORS.lock().insert(handle, TxIterator { txs });
+    Ok(handle)
+}
+
+/// The next page of at most `page_size` txs, and whether the iteration is done
+pub fn next(handle: u64, page_size: usize) -> Result<serde_json::Value, Error> {
+    let page_size = page_size.max(1).min(MAX_PAGE_SIZE);
+    let mut iterators = ITERATORS.lock();
+    let it = iterators
+        .get_mut(&handle)
+        .ok_or_else(|| Error::from(ErrorKind::ArgumentError("tx iterator not found".to_owned())))?;
+    let count = page_size.min(it.txs.len());
+    let txs: Vec<TxLogEntry> = it.txs.drain(..count).collect();
+    let done = it.txs.is_empty();
+    Ok(json!({
+        "txs": txs,
+        "done": done,
+    }))
+}
+
+/// Release an iterator
+pub fn close(handle: u64) {
+    ITERATORS.lock().remove(&handle);
+}
@@ -0,0 +1,286 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The journal of the multi-step operations, so the ones interrupted by an app crash are
+//! rolled forward or back by `grin_recover_pending` on the next start, instead of leaving
+//! locked outputs or a finalized tx never posted.
+//!
+//! An entry is written before each step changing the wallet, and removed once the operation
+//! is complete, whether it succeeded or failed cleanly.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{OutputStatus, TxLogEntryType};
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::returned::{self, ReturnedState};
+use crate::upstream::{self, WalletApi};
+use crate::{
+    coalesce, get_wallet_instance, new_wallet_config, password, set_outputs_status, store,
+    MobileWalletCfg,
+};
+
+const JOURNAL_FILE: &str = "journal.json";
+
+/// A send, its outputs get locked then it's finalized and posted
+pub const OP_SEND: &str = "send";
+/// Outputs temporarily locked to control the inputs of a send, i.e. a consolidation
+pub const OP_OUTPUTS_LOCK: &str = "outputs_lock";
+/// A batch of a wallet restore
+pub const OP_RESTORE: &str = "restore";
+/// A password change, the seed file being replaced
+pub const OP_PASSWORD_CHANGE: &str = "password_change";
+/// The finalize and the post of a slate returned to a relay listener, see `returned`
+pub const OP_RETURNED_POST: &str = "returned_post";
+
+/// Send phases
+pub const PHASE_INITIATED: &str = "initiated";
+pub const PHASE_LOCKED: &str = "locked";
+pub const PHASE_FINALIZED: &str = "finalized";
+
+/// An operation not completed yet
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub id: String,
+    pub op: String,
+    pub phase: String,
+    pub account: String,
+    #[serde(default)]
+    pub slate_id: Option<String>,
+    /// The data of the op to roll it back, i.e. the locked output commitments
+    #[serde(default)]
+    pub data: Value,
+    pub started_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the journal file
+    static ref JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(&wallet_config.data_file_dir, JOURNAL_FILE))
+}
+
+fn update<F: FnOnce(&mut Journal)>(config: &MobileWalletCfg, f: F) -> Result<(), Error> {
+    let _guard = JOURNAL_LOCK.lock();
+    let path = data_path(config)?;
+    let mut journal: Journal = store::load(&path)?;
+    f(&mut journal);
+    store::save(&path, &journal)
+}
+
+/// Journal the start of an operation, returning its id. Failing to write it fails the
+/// operation, which could otherwise not be recovered.
+pub fn begin(
+    config: &MobileWalletCfg,
+    op: &str,
+    phase: &str,
+    slate_id: Option<&str>,
+    data: Value,
+) -> Result<String, Error> {
+    let now = store::now_secs();
+    let entry = JournalEntry {
+        id: Uuid::new_v4().to_string(),
+        op: op.to_owned(),
+        phase: phase.to_owned(),
+        account: config.account.clone(),
+        slate_id: slate_id.map(|s| s.to_owned()),
+        data,
+        started_at: now,
+        updated_at: now,
+    };
+    let id = entry.id.clone();
+    update(config, |journal| journal.entries.push(entry))?;
+    Ok(id)
+}
+
+/// Journal the next phase of an operation. A failure leaves the previous phase, rolled back
+/// instead of forward on recovery.
+pub fn advance(config: &MobileWalletCfg, id: &str, phase: &str) {
+    let _ = update(config, |journal| {
+        if let Some(entry) = journal.entries.iter_mut().find(|e| e.id == id) {
+            entry.phase = phase.to_owned();
+            entry.updated_at = store::now_secs();
+        }
+    });
+}
+
+/// Remove a completed operation. A failure only leaves an entry whose recovery finds
+/// nothing to do.
+pub fn finish(config: &MobileWalletCfg, id: &str) {
+    let _ = update(config, |journal| journal.entries.retain(|e| e.id != id));
+}
+
+/// The operations not completed, the oldest first
+pub fn entries(config: &MobileWalletCfg) -> Result<Vec<JournalEntry>, Error> {
+    let _guard = JOURNAL_LOCK.lock();
+    let journal: Journal = store::load(&data_path(config)?)?;
+    Ok(journal.entries)
+}
+
+/// The recovery of an interrupted operation
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recovery {
+    pub op: String,
+    pub phase: String,
+    pub account: String,
+    pub slate_id: Option<String>,
+    /// "rolled_back", "rolled_forward", "resume", "cleaned_up" or "failed"
+    pub action: &'static str,
+    pub detail: Option<String>,
+}
+
+/// Roll back a send not finalized, its outputs get unlocked by the cancel. Roll forward a
+/// finalized one by posting it, it's cancelled by the user if the post keeps failing.
+fn recover_send(config: &MobileWalletCfg, entry: &JournalEntry) -> Result<&'static str, Error> {
+    let slate_id = entry
+        .slate_id
+        .as_ref()
+        .ok_or_else(|| Error::from(ErrorKind::GenericError("no slate id".to_owned())))?;
+    let uuid = Uuid::parse_str(slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
+    let api = upstream::api(get_wallet_instance(config.clone())?);
+    let (_, txs) = api.txs(false, None, Some(uuid))?;
+    let tx = match txs.first() {
+        Some(tx) if !tx.confirmed && tx.tx_type == TxLogEntryType::TxSent => tx,
+        // Confirmed or already cancelled, nothing left to do
+        _ => return Ok("rolled_forward"),
+    };
+    if entry.phase != PHASE_FINALIZED {
+        api.cancel(None, Some(uuid))?;
+        return Ok("rolled_back");
+    }
+    match api.stored_tx(tx)? {
+        Some(stored_tx) => {
+            api.post(Some(uuid), &stored_tx, true)?;
+            Ok("rolled_forward")
+        }
+        None => {
+            api.cancel(None, Some(uuid))?;
+            Ok("rolled_back")
+        }
+    }
+}
+
+/// Roll forward a returned slate finalized before the crash by posting it. One not finalized
+/// yet keeps its outputs locked, its finalize is resumed by the next post of the app.
+fn recover_returned_post(
+    config: &MobileWalletCfg,
+    entry: &JournalEntry,
+) -> Result<&'static str, Error> {
+    let slate_id = entry
+        .slate_id
+        .as_ref()
+        .ok_or_else(|| Error::from(ErrorKind::GenericError("no slate id".to_owned())))?;
+    let returned = match returned::list(config)?
+        .into_iter()
+        .find(|r| &r.slate_id == slate_id)
+    {
+        Some(returned) => returned,
+        None => return Ok("rolled_back"),
+    };
+    if returned.state == ReturnedState::Posted {
+        return Ok("rolled_forward");
+    }
+    let api = upstream::api(get_wallet_instance(config.clone())?);
+    match returned::finalized_tx(&api, &returned.slate)? {
+        Some(tx) => {
+            returned::set_state(config, slate_id, ReturnedState::Finalized)?;
+            api.post(Some(returned.slate.id), &tx, true)?;
+            returned::set_state(config, slate_id, ReturnedState::Posted)?;
+            Ok("rolled_forward")
+        }
+        None => {
+            returned::set_state(config, slate_id, ReturnedState::Returned)?;
+            Ok("resume")
+        }
+    }
+}
+
+/// Unlock the outputs temporarily locked, by commitment. The ones spent since keep their
+/// status.
+fn recover_outputs_lock(config: &MobileWalletCfg, entry: &JournalEntry) -> Result<(), Error> {
+    let commits: Vec<String> = serde_json::from_value(entry.data["commits"].clone())
+        .map_err(|e| ErrorKind::GenericError(format!("invalid journal data: {}", e)))?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let (_, outputs) = upstream::api(wallet.clone()).outputs(true, false, None)?;
+    let locked: Vec<_> = outputs
+        .into_iter()
+        .map(|o| o.output)
+        .filter(|o| o.status == OutputStatus::Locked)
+        .filter(|o| {
+            o.commit
+                .as_ref()
+                .map(|c| commits.contains(c))
+                .unwrap_or(false)
+        })
+        .collect();
+    set_outputs_status(&wallet, &locked, OutputStatus::Unspent)
+}
+
+/// Roll forward or back the operations interrupted by a crash, of all the accounts. An
+/// operation failing to recover is kept in the journal for the next attempt.
+pub fn recover_pending(config: &MobileWalletCfg) -> Result<Vec<Recovery>, Error> {
+    let mut recoveries = vec![];
+    for entry in entries(config)? {
+        let mut account_config = config.clone();
+        account_config.account = entry.account.clone();
+        let res = match entry.op.as_str() {
+            OP_SEND => recover_send(&account_config, &entry),
+            OP_RETURNED_POST => recover_returned_post(&account_config, &entry),
+            OP_OUTPUTS_LOCK => recover_outputs_lock(&account_config, &entry).map(|_| "rolled_back"),
+            // A restore batch is idempotent, the app restarts it from its start index
+            OP_RESTORE => Ok("resume"),
+            // The seed file was replaced by a rename, it's whole with the old or the new
+            // password, only the temporary files are left
+            OP_PASSWORD_CHANGE => new_wallet_config(account_config.clone()).map(|c| {
+                password::clean_up(&c.data_file_dir);
+                "cleaned_up"
+            }),
+            _ => Ok("rolled_back"),
+        };
+        coalesce::invalidate();
+        let (action, detail) = match res {
+            Ok("resume") => ("resume", Some(entry.data.to_string())),
+            Ok(action) => (action, None),
+            Err(e) => ("failed", Some(e.to_string())),
+        };
+        if action != "failed" {
+            finish(config, &entry.id);
+        }
+        recoveries.push(Recovery {
+            op: entry.op,
+            phase: entry.phase,
+            account: entry.account,
+            slate_id: entry.slate_id,
+            action,
+            detail,
+        });
+    }
+    Ok(recoveries)
+}
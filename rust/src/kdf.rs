@@ -0,0 +1,332 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The key derivation of the seed file password. The seed file of the wallet libs is
+//! encrypted with a fast key derivation of the password, so with an Argon2 configuration the
+//! password is first stretched by Argon2 here, the seed file being encrypted with the result.
+//! An offline brute force of a weak password then costs the Argon2 memory and time per try.
+//!
+//! The parameters and the salt are kept next to the seed file. A re-wrap writes the new ones
+//! as pending, and they replace the current ones once the seed file is re-encrypted, the
+//! first open after an interrupted re-wrap picking the ones which open the seed file. The
+//! re-wrap holds the `dir_lock` of the data dir, so an open never runs in the middle of it,
+//! and only the re-wrap drops a pending key derivation.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use argon2::{Config, ThreadMode, Variant, Version};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind, WalletSeed};
+use grin_wallet_util::grin_util::blake2::blake2b::blake2b;
+use grin_wallet_util::grin_util::{to_hex, Mutex};
+
+use crate::store;
+
+const SEED_KDF_FILE: &str = "seed_kdf.json";
+const SEED_KDF_PENDING_FILE: &str = "seed_kdf.pending.json";
+
+/// Max number of cached derived passwords
+const MAX_CACHED_KEYS: usize = 4;
+
+/// The key derivation of the seed file password
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KdfAlgorithm {
+    /// The password as is, only the key derivation of the seed file
+    Legacy,
+    Argon2id,
+}
+
+impl Default for KdfAlgorithm {
+    fn default() -> Self {
+        KdfAlgorithm::Legacy
+    }
+}
+
+/// The key derivation parameters
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfParams {
+    #[serde(default)]
+    pub algorithm: KdfAlgorithm,
+    #[serde(default = "default_memory_kib")]
+    pub memory_kib: u32,
+    #[serde(default = "default_iterations")]
+    pub iterations: u32,
+    #[serde(default = "default_parallelism")]
+    pub parallelism: u32,
+}
+
+fn default_memory_kib() -> u32 {
+    64 * 1024
+}
+
+fn default_iterations() -> u32 {
+    3
+}
+
+fn default_parallelism() -> u32 {
+    1
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            algorithm: KdfAlgorithm::Legacy,
+            memory_kib: default_memory_kib(),
+            iterations: default_iterations(),
+            parallelism: default_parallelism(),
+        }
+    }
+}
+
+impl KdfParams {
+    /// Refuse the parameters too weak to matter or too heavy for a phone
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.algorithm == KdfAlgorithm::Legacy {
+            return Ok(());
+        }
+        let valid = (8 * 1024..=1024 * 1024).contains(&self.memory_kib)
+            && (1..=20).contains(&self.iterations)
+            && (1..=8).contains(&self.parallelism);
+        if !valid {
+            return Err(ErrorKind::ArgumentError(
+                "kdf parameters out of range, memory 8 MiB to 1 GiB, 1 to 20 iterations, \
+                 1 to 8 lanes"
+                    .to_owned(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// The key derivation of a seed file
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SeedKdf {
+    pub params: KdfParams,
+    /// Hex, empty for the legacy algorithm
+    pub salt: String,
+}
+
+lazy_static! {
+    /// The derived passwords, by salt and password hash, so a wallet open doesn't pay the
+    /// derivation each time
+    static ref CACHE: Mutex<HashMap<(String, String), String>> = Mutex::new(HashMap::new());
+    static ref DIR_LOCKS: Mutex<HashMap<String, Arc<Mutex<()>>>> = Mutex::new(HashMap::new());
+}
+
+/// The lock of the key derivation files of a data dir, held by a re-wrap from its start to
+/// the promotion of its key derivation, and by the seed password lookups
+pub fn dir_lock(data_file_dir: &str) -> Arc<Mutex<()>> {
+    DIR_LOCKS
+        .lock()
+        .entry(data_file_dir.to_owned())
+        .or_insert_with(|| Arc::new(Mutex::new(())))
+        .clone()
+}
+
+fn path(data_file_dir: &str, name: &str) -> PathBuf {
+    store::data_file(data_file_dir, name)
+}
+
+fn load(data_file_dir: &str, name: &str) -> Result<Option<SeedKdf>, Error> {
+    let path = path(data_file_dir, name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    store::load(&path).map(Some)
+}
+
+/// The key derivation of a seed file, the legacy one if none is configured
+pub fn current(data_file_dir: &str) -> Result<SeedKdf, Error> {
+    Ok(load(data_file_dir, SEED_KDF_FILE)?.unwrap_or_default())
+}
+
+/// Derive the seed file password of a password
+pub fn derive(kdf: &SeedKdf, password: &str) -> Result<String, Error> {
+    if kdf.params.algorithm == KdfAlgorithm::Legacy {
+        return Ok(password.to_owned());
+    }
+    let key = (
+        kdf.salt.clone(),
+        to_hex(blake2b(32, &[], password.as_bytes()).as_bytes().to_vec()),
+    );
+    if let Some(derived) = CACHE.lock().get(&key) {
+        return Ok(derived.clone());
+    }
+    let config = Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: kdf.params.memory_kib,
+        time_cost: kdf.params.iterations,
+        lanes: kdf.params.parallelism,
+        thread_mode: ThreadMode::Sequential,
+        hash_length: 32,
+        ..Config::default()
+    };
+    let hash = argon2::hash_raw(password.as_bytes(), kdf.salt.as_bytes(), &config)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to derive the key: {}", e)))?;
+    let derived = to_hex(hash);
+    let mut cache = CACHE.lock();
+    if cache.len() >= MAX_CACHED_KEYS {
+        cache.clear();
+    }
+    cache.insert(key, derived.clone());
+    Ok(derived)
+}
+
+/// The seed file password of a password. The pending key derivation of an interrupted
+/// re-wrap is adopted if it opens the seed file, otherwise it's left for the next re-wrap to
+/// replace.
+pub fn seed_password(data_file_dir: &str, password: &str) -> Result<String, Error> {
+    let lock = dir_lock(data_file_dir);
+    let _guard = lock.lock();
+    seed_password_locked(data_file_dir, password)
+}
+
+/// `seed_password` for a caller holding the `dir_lock` of the data dir
+pub fn seed_password_locked(data_file_dir: &str, password: &str) -> Result<String, Error> {
+    let current = current(data_file_dir)?;
+    let pending = match load(data_file_dir, SEED_KDF_PENDING_FILE)? {
+        Some(pending) => pending,
+        None => return derive(&current, password),
+    };
+    let pending_password = derive(&pending, password)?;
+    if WalletSeed::from_file(data_file_dir, &pending_password).is_ok() {
+        promote(data_file_dir)?;
+        return Ok(pending_password);
+    }
+    derive(&current, password)
+}
+
+/// A new key derivation with a random salt, saved as pending until `promote`
+pub fn new_pending(data_file_dir: &str, params: &KdfParams) -> Result<SeedKdf, Error> {
+    params.validate()?;
+    let salt = match params.algorithm {
+        KdfAlgorithm::Legacy => String::new(),
+        KdfAlgorithm::Argon2id => Uuid::new_v4().to_simple().to_string(),
+    };
+    let kdf = SeedKdf {
+        params: params.clone(),
+        salt,
+    };
+    fs::create_dir_all(data_file_dir)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to create the data dir: {}", e)))?;
+    store::save(&path(data_file_dir, SEED_KDF_PENDING_FILE), &kdf)?;
+    Ok(kdf)
+}
+
+/// Write a new seed with `write`, given the seed file password of a pending key derivation
+/// of `params`, the legacy one if None. The key derivation is promoted once the seed file is
+/// written, dropped otherwise, under the `dir_lock` so no re-wrap runs in between.
+pub fn with_new_seed<T, F>(
+    data_file_dir: &str,
+    params: Option<&KdfParams>,
+    password: &str,
+    write: F,
+) -> Result<T, Error>
+where
+    F: FnOnce(&str) -> Result<T, Error>,
+{
+    let lock = dir_lock(data_file_dir);
+    let _guard = lock.lock();
+    let pending = new_pending(data_file_dir, &params.cloned().unwrap_or_default())?;
+    match derive(&pending, password).and_then(|seed_password| write(&seed_password)) {
+        Ok(value) => {
+            promote(data_file_dir)?;
+            Ok(value)
+        }
+        Err(e) => {
+            discard(data_file_dir);
+            Err(e)
+        }
+    }
+}
+
+/// Make the pending key derivation the current one, once the seed file is encrypted with it
+pub fn promote(data_file_dir: &str) -> Result<(), Error> {
+    fs::rename(
+        path(data_file_dir, SEED_KDF_PENDING_FILE),
+        path(data_file_dir, SEED_KDF_FILE),
+    )
+    .map_err(|e| ErrorKind::GenericError(format!("fail to save the kdf: {}", e)).into())
+}
+
+/// Drop the pending key derivation of a failed re-wrap or new seed
+pub fn discard(data_file_dir: &str) {
+    let _ = fs::remove_file(path(data_file_dir, SEED_KDF_PENDING_FILE));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> String {
+        std::env::temp_dir()
+            .join(format!("grin_kdf_test_{}", Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn argon2() -> KdfParams {
+        KdfParams {
+            algorithm: KdfAlgorithm::Argon2id,
+            memory_kib: 8 * 1024,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn legacy_seed_password_is_the_password() {
+        let dir = temp_dir();
+        assert_eq!(seed_password(&dir, "secret").unwrap(), "secret");
+    }
+
+    #[test]
+    fn pending_kdf_not_opening_the_seed_is_ignored() {
+        let dir = temp_dir();
+        new_pending(&dir, &argon2()).unwrap();
+        assert_eq!(seed_password(&dir, "secret").unwrap(), "secret");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn argon2_derivation_is_salted() {
+        let dir = temp_dir();
+        let a = new_pending(&dir, &argon2()).unwrap();
+        let b = new_pending(&dir, &argon2()).unwrap();
+        let derived = derive(&a, "secret").unwrap();
+        assert_ne!(derived, "secret");
+        assert_eq!(derive(&a, "secret").unwrap(), derived);
+        assert_ne!(derive(&b, "secret").unwrap(), derived);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn out_of_range_params_are_refused() {
+        let params = KdfParams {
+            memory_kib: 1024,
+            ..argon2()
+        };
+        assert!(params.validate().is_err());
+        assert!(argon2().validate().is_ok());
+    }
+}
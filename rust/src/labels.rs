@@ -0,0 +1,102 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The labels of the txs, by slate id, set by the app or by the lib to link the txs of a
+//! flow, i.e. a refund and its refunded tx. The retrieved txs have the `label` of their slate.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use grin_wallet_impls::Error;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::{new_wallet_config, store, MobileWalletCfg};
+
+pub const TX_LABELS_FILE: &str = "tx_labels.json";
+
+/// Separates the labels added to an existing one
+const LABEL_SEPARATOR: &str = "; ";
+
+#[derive(Serialize, Deserialize, Default)]
+struct TxLabels {
+    labels: HashMap<String, String>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the labels file
+    static ref LABELS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        TX_LABELS_FILE,
+    ))
+}
+
+fn load(config: &MobileWalletCfg) -> Result<TxLabels, Error> {
+    let _guard = LABELS_LOCK.lock();
+    store::load(&data_path(config)?)
+}
+
+/// Set the label of a tx, removed if empty
+pub fn set(config: &MobileWalletCfg, slate_id: &str, label: &str) -> Result<(), Error> {
+    let _guard = LABELS_LOCK.lock();
+    let path = data_path(config)?;
+    let mut labels: TxLabels = store::load(&path)?;
+    let label = label.trim();
+    if label.is_empty() {
+        labels.labels.remove(slate_id);
+    } else {
+        labels.labels.insert(slate_id.to_owned(), label.to_owned());
+    }
+    store::save(&path, &labels)
+}
+
+/// Add to the label of a tx, after the existing one if any
+pub fn add(config: &MobileWalletCfg, slate_id: &str, label: &str) -> Result<(), Error> {
+    let _guard = LABELS_LOCK.lock();
+    let path = data_path(config)?;
+    let mut labels: TxLabels = store::load(&path)?;
+    let entry = labels.labels.entry(slate_id.to_owned()).or_default();
+    if !entry.is_empty() {
+        entry.push_str(LABEL_SEPARATOR);
+    }
+    entry.push_str(label);
+    store::save(&path, &labels)
+}
+
+/// Add the `label` of the labeled txs of a tx retrieval `[refreshed, [txs]]`
+pub fn annotate(config: &MobileWalletCfg, txs: &mut Value) -> Result<(), Error> {
+    let labels = load(config)?.labels;
+    if labels.is_empty() {
+        return Ok(());
+    }
+    if let Some(entries) = txs.get_mut(1).and_then(|e| e.as_array_mut()) {
+        for entry in entries {
+            let label = entry
+                .get("tx_slate_id")
+                .and_then(|id| id.as_str())
+                .and_then(|id| labels.get(id));
+            if let (Some(label), Some(obj)) = (label.cloned(), entry.as_object_mut()) {
+                obj.insert("label".to_owned(), Value::String(label));
+            }
+        }
+    }
+    Ok(())
+}
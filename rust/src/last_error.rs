@@ -0,0 +1,182 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The last error of the extern calls, per calling thread, with a stable error code, and a
+//! message key and params for its translation by `locale`
+
+use std::cell::RefCell;
+
+use failure::Fail;
+use serde_json::{json, Value};
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::ErrorKind as LibWalletErrorKind;
+
+use crate::errors::{self, LibErrorKind};
+use crate::locale;
+use crate::node::NODE_UNREACHABLE_ERROR;
+
+/// No error
+pub const CODE_NONE: u32 = 0;
+/// Any other error
+pub const CODE_GENERIC: u32 = 1;
+/// Invalid argument of the call
+pub const CODE_ARGUMENT: u32 = 2;
+/// Refused by the wallet logic, i.e. not enough funds or a tx not found
+pub const CODE_WALLET: u32 = 3;
+/// Seed or key error, i.e. a wrong password or an invalid mnemonic
+pub const CODE_KEYCHAIN: u32 = 4;
+/// Failed communication with the node or a peer
+pub const CODE_NETWORK: u32 = 5;
+/// Local storage error
+pub const CODE_IO: u32 = 6;
+/// A send refused as a likely duplicate of a recent one
+pub const CODE_DUPLICATE_PAYMENT: u32 = 7;
+/// A spending call refused by the read-only profile
+pub const CODE_READ_ONLY: u32 = 8;
+/// A password attempt refused during the delay of the last failed ones
+pub const CODE_LOCKED_OUT: u32 = 9;
+/// A call needing the node while it's unreachable, the local data calls returning their data
+/// marked stale instead
+pub const CODE_NODE_UNREACHABLE: u32 = 10;
+/// A network call past the `operation_timeout_ms` of the config
+pub const CODE_TIMEOUT: u32 = 11;
+/// A send cancelled by its cancellation token
+pub const CODE_CANCELLED: u32 = 12;
+/// A send refused for its change below the dust threshold, see `donate_dust` of the config
+pub const CODE_DUST_CHANGE: u32 = 13;
+
+struct LastError {
+    code: u32,
+    message: String,
+    key: &'static str,
+    params: Value,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = RefCell::new(None);
+}
+
+fn code_of(e: &Error) -> u32 {
+    match errors::kind_of(e) {
+        Some(LibErrorKind::DuplicatePayment(_)) => return CODE_DUPLICATE_PAYMENT,
+        Some(LibErrorKind::ReadOnly(_)) => return CODE_READ_ONLY,
+        Some(LibErrorKind::LockedOut(_)) => return CODE_LOCKED_OUT,
+        Some(LibErrorKind::NodeUnreachable(_)) => return CODE_NODE_UNREACHABLE,
+        Some(LibErrorKind::Timeout(_)) => return CODE_TIMEOUT,
+        Some(LibErrorKind::Cancelled) => return CODE_CANCELLED,
+        Some(LibErrorKind::DustChange(_)) => return CODE_DUST_CHANGE,
+        None => {}
+    }
+    match e.kind() {
+        ErrorKind::ArgumentError(_) => CODE_ARGUMENT,
+        ErrorKind::LibWallet(kind, _) if kind.to_string().contains(NODE_UNREACHABLE_ERROR) => {
+            CODE_NODE_UNREACHABLE
+        }
+        ErrorKind::LibWallet(_, _) => CODE_WALLET,
+        ErrorKind::Keychain(_) | ErrorKind::Encryption | ErrorKind::Mnemonic => CODE_KEYCHAIN,
+        ErrorKind::Hyper | ErrorKind::Uri => CODE_NETWORK,
+        ErrorKind::IO => CODE_IO,
+        _ => CODE_GENERIC,
+    }
+}
+
+/// A nanogrin amount in grin, without the trailing zeros
+fn grin(amount: u64) -> String {
+    let grin = format!("{}.{:09}", amount / 1_000_000_000, amount % 1_000_000_000);
+    grin.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+/// The message key of an error, with its params
+fn key_of(e: &Error, code: u32) -> (&'static str, Value) {
+    let detail = json!({ "detail": e.to_string() });
+    match e.kind() {
+        ErrorKind::LibWallet(
+            LibWalletErrorKind::NotEnoughFunds {
+                available, needed, ..
+            },
+            _,
+        ) => (
+            "not_enough_funds",
+            json!({ "available": grin(available), "needed": grin(needed) }),
+        ),
+        _ => match code {
+            CODE_ARGUMENT => ("invalid_argument", detail),
+            CODE_WALLET => ("wallet", detail),
+            CODE_KEYCHAIN => ("keychain", json!({})),
+            CODE_NETWORK => ("network", json!({})),
+            CODE_IO => ("storage", json!({})),
+            CODE_DUPLICATE_PAYMENT => ("duplicate_payment", json!({})),
+            CODE_READ_ONLY => ("read_only", json!({})),
+            CODE_LOCKED_OUT => ("locked_out", json!({})),
+            CODE_NODE_UNREACHABLE => ("node_unreachable", json!({})),
+            CODE_TIMEOUT => ("timeout", json!({})),
+            CODE_CANCELLED => ("cancelled", json!({})),
+            CODE_DUST_CHANGE => ("dust_change", json!({})),
+            _ => ("generic", detail),
+        },
+    }
+}
+
+/// Record the error of the current call on this thread, with the chain of its causes
+pub fn set(e: &Error) {
+    // The kind of a lib error repeats the message of its `GenericError`
+    let message = (e as &dyn Fail)
+        .iter_chain()
+        .filter(|f| f.downcast_ref::<LibErrorKind>().is_none())
+        .map(|f| f.to_string())
+        .collect::<Vec<_>>()
+        .join(": ");
+    let code = code_of(e);
+    let (key, params) = key_of(e, code);
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = Some(LastError {
+            code,
+            message,
+            key,
+            params,
+        })
+    });
+}
+
+/// Clear the last error, on a successful call
+pub fn clear() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+pub fn code() -> u32 {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(CODE_NONE, |last| last.code))
+}
+
+pub fn message() -> Option<String> {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map(|last| last.message.clone()))
+}
+
+/// The last error as `{"code", "key", "params", "message", "detail"}`, its `message`
+/// translated in the current locale, the raw one if the key has no translation, and its
+/// `detail` the raw message with its causes
+pub fn info() -> Option<Value> {
+    LAST_ERROR.with(|last| {
+        last.borrow().as_ref().map(|last| {
+            json!({
+                "code": last.code,
+                "key": last.key,
+                "params": last.params,
+                "message": locale::message(last.key, &last.params)
+                    .unwrap_or_else(|| last.message.clone()),
+                "detail": last.message,
+            })
+        })
+    })
+}
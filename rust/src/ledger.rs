@@ -0,0 +1,241 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ledger math over the tx log, for the charts and statistics of the app
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{TxLogEntry, TxLogEntryType};
+
+use crate::upstream::{self, WalletApi};
+use crate::{get_wallet_instance, netstats, store, MobileWalletCfg};
+
+const DAY_SECS: i64 = 86_400;
+
+/// The unix epoch was a Thursday, the weeks start on Monday
+const WEEK_START_OFFSET_SECS: i64 = 3 * DAY_SECS;
+
+/// Max number of points of a history, the oldest ones are dropped
+const MAX_HISTORY_POINTS: usize = 3660;
+
+/// The period of the balance history points
+#[derive(Clone, Copy)]
+pub enum Granularity {
+    Day,
+    Week,
+}
+
+impl Granularity {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        match s {
+            "day" | "daily" => Ok(Granularity::Day),
+            "week" | "weekly" => Ok(Granularity::Week),
+            _ => Err(ErrorKind::ArgumentError(format!("invalid granularity '{}'", s)).into()),
+        }
+    }
+
+    /// The start of the period of a timestamp, in UTC
+    fn period_start(self, ts: i64) -> i64 {
+        match self {
+            Granularity::Day => ts - ts.rem_euclid(DAY_SECS),
+            Granularity::Week => {
+                let week = 7 * DAY_SECS;
+                ts - (ts + WEEK_START_OFFSET_SECS).rem_euclid(week)
+            }
+        }
+    }
+
+    fn period_secs(self) -> i64 {
+        match self {
+            Granularity::Day => DAY_SECS,
+            Granularity::Week => 7 * DAY_SECS,
+        }
+    }
+}
+
+/// The balance at the end of a period
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BalancePoint {
+    /// Start of the period, unix timestamp
+    pub timestamp: i64,
+    pub balance: u64,
+    /// Net change over the period, negative for a decrease
+    pub change: i64,
+}
+
+/// The lifetime totals of an account
+#[derive(Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletStats {
+    /// The confirmed amounts, the sent one without the fees
+    pub total_received: u64,
+    pub total_mined: u64,
+    pub total_sent: u64,
+    pub total_fees: u64,
+    /// Number of txs by tx log type, including the unconfirmed and cancelled ones
+    pub tx_counts: BTreeMap<String, u64>,
+    pub unconfirmed_count: u64,
+}
+
+/// The lifetime totals of a tx log
+pub fn wallet_stats(txs: &[TxLogEntry]) -> WalletStats {
+    let mut stats = WalletStats::default();
+    for tx in txs {
+        *stats.tx_counts.entry(tx.tx_type.to_string()).or_insert(0) += 1;
+        if !tx.confirmed {
+            match tx.tx_type {
+                TxLogEntryType::TxSentCancelled | TxLogEntryType::TxReceivedCancelled => {}
+                _ => stats.unconfirmed_count += 1,
+            }
+            continue;
+        }
+        let fee = tx.fee.unwrap_or(0);
+        let credited = tx.amount_credited.saturating_sub(tx.amount_debited);
+        match tx.tx_type {
+            TxLogEntryType::TxReceived => stats.total_received += credited,
+            TxLogEntryType::ConfirmedCoinbase => stats.total_mined += credited,
+            TxLogEntryType::TxSent => {
+                let debited = tx.amount_debited.saturating_sub(tx.amount_credited);
+                stats.total_sent += debited.saturating_sub(fee);
+                stats.total_fees += fee;
+            }
+            _ => {}
+        }
+    }
+    stats
+}
+
+/// The lifetime totals of the configured account
+pub fn stats(config: MobileWalletCfg) -> Result<WalletStats, Error> {
+    let refresh_from_node = netstats::should_refresh(&config);
+    let api = upstream::api(get_wallet_instance(config)?);
+    let (_, txs) = api.txs(refresh_from_node, None, None)?;
+    Ok(wallet_stats(&txs))
+}
+
+/// Target block time, to date an output height back from the chain tip
+const BLOCK_TIME_SECS: i64 = 60;
+
+/// The chain tip, to date the txs by the height of their outputs. The confirmation timestamp
+/// of the tx log is the refresh time, which is the restore time for a restored wallet.
+pub struct ChainTip {
+    pub height: u64,
+    pub timestamp: i64,
+}
+
+impl ChainTip {
+    fn timestamp_at(&self, height: u64) -> Option<i64> {
+        if height == 0 || height > self.height {
+            return None;
+        }
+        Some(self.timestamp - (self.height - height) as i64 * BLOCK_TIME_SECS)
+    }
+}
+
+/// The net balance change of a confirmed tx and its timestamp, None if it didn't change the
+/// balance. The debited amount of a send includes its fee.
+fn balance_change(
+    tx: &TxLogEntry,
+    output_heights: &HashMap<u32, u64>,
+    tip: &ChainTip,
+) -> Option<(i64, i64)> {
+    match tx.tx_type {
+        TxLogEntryType::TxSentCancelled | TxLogEntryType::TxReceivedCancelled => return None,
+        _ => {}
+    }
+    if !tx.confirmed {
+        return None;
+    }
+    let ts = output_heights
+        .get(&tx.id)
+        .and_then(|height| tip.timestamp_at(*height))
+        .unwrap_or_else(|| tx.confirmation_ts.unwrap_or(tx.creation_ts).timestamp());
+    Some((ts, tx.amount_credited as i64 - tx.amount_debited as i64))
+}
+
+/// The balance at the end of each period, from the period of the first confirmed tx to the
+/// period of the chain tip, including the periods without any tx. `output_heights` is the
+/// height of the outputs created by a tx, by tx log id.
+pub fn balance_history(
+    txs: &[TxLogEntry],
+    output_heights: &HashMap<u32, u64>,
+    tip: &ChainTip,
+    granularity: Granularity,
+) -> Vec<BalancePoint> {
+    let mut changes: Vec<(i64, i64)> = txs
+        .iter()
+        .filter_map(|tx| balance_change(tx, output_heights, tip))
+        .collect();
+    changes.sort_by_key(|(ts, _)| *ts);
+    let (first, last) = match (changes.first(), changes.last()) {
+        (Some(first), Some(last)) => (
+            granularity.period_start(first.0),
+            granularity.period_start(tip.timestamp.max(last.0)),
+        ),
+        _ => return vec![],
+    };
+
+    let mut points = vec![];
+    let mut balance: i64 = 0;
+    let mut changes = changes.into_iter().peekable();
+    let mut period = first;
+    while period <= last {
+        let end = period + granularity.period_secs();
+        let mut change = 0;
+        while let Some((ts, amount)) = changes.peek() {
+            if *ts >= end {
+                break;
+            }
+            change += amount;
+            changes.next();
+        }
+        balance += change;
+        points.push(BalancePoint {
+            timestamp: period,
+            balance: balance.max(0) as u64,
+            change,
+        });
+        period = end;
+    }
+    let excess = points.len().saturating_sub(MAX_HISTORY_POINTS);
+    points.drain(..excess);
+    points
+}
+
+/// The balance history of the configured account
+pub fn history(
+    config: MobileWalletCfg,
+    granularity: Granularity,
+) -> Result<Vec<BalancePoint>, Error> {
+    let refresh_from_node = netstats::should_refresh(&config);
+    let api = upstream::api(get_wallet_instance(config)?);
+    let (_, txs) = api.txs(refresh_from_node, None, None)?;
+    let (_, outputs) = api.outputs(true, false, None)?;
+    let mut output_heights = HashMap::new();
+    for o in outputs.iter() {
+        if let Some(tx_id) = o.output.tx_log_entry {
+            let height = output_heights.entry(tx_id).or_insert(0);
+            *height = o.output.height.max(*height);
+        }
+    }
+    let tip = ChainTip {
+        height: api.node_height()?.0,
+        timestamp: store::now_secs() as i64,
+    };
+    Ok(balance_history(&txs, &output_heights, &tip, granularity))
+}
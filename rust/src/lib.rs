@@ -15,34 +15,178 @@
 
 //! Libs Wallet External API Definition
 
+#[macro_use]
+extern crate lazy_static;
+
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::sync::mpsc::{channel, TryRecvError};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
 use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
 
-use grin_wallet_api::{Foreign, Owner};
 use grin_wallet_config::{self, GrinRelayConfig, WalletConfig};
 use grin_wallet_controller::{grinrelay_address, grinrelay_listener};
 use grin_wallet_impls::{
     instantiate_wallet, Error, ErrorKind, FileWalletCommAdapter, GrinrelayWalletCommAdapter,
-    HTTPNodeClient, HTTPWalletCommAdapter, LMDBBackend, WalletSeed,
+    HTTPWalletCommAdapter, LMDBBackend, WalletSeed,
 };
 use grin_wallet_libwallet::api_impl::types::InitTxArgs;
-use grin_wallet_libwallet::{NodeClient, SlateVersion, VersionedSlate, WalletInst};
-use grin_wallet_util::grin_core::global::ChainTypes;
-use grin_wallet_util::grin_keychain::ExtKeychain;
-use grin_wallet_util::grin_util::{Mutex, ZeroingString};
+use grin_wallet_libwallet::{
+    NodeClient, OutputData, OutputStatus, Slate, TxLogEntry, TxLogEntryType, TxProof,
+    VersionedSlate, WalletInst,
+};
+use grin_wallet_util::grin_core::libtx::tx_fee;
+use grin_wallet_util::grin_core::ser;
+use grin_wallet_util::grin_keychain::{ExtKeychain, ExtKeychainPath};
+use grin_wallet_util::grin_util::{from_hex, to_hex, Mutex};
+
+use crate::node::MobileNodeClient;
+use crate::upstream::WalletApi;
+
+mod addr_cache;
+mod address;
+mod amounts;
+mod approval;
+mod attempts;
+mod audit;
+mod biometric;
+mod cancel;
+mod capability;
+mod check;
+mod coalesce;
+mod codec;
+mod contacts;
+mod deadline;
+mod diagnostics;
+mod doctor;
+mod dto;
+mod duplicate;
+mod errors;
+mod events;
+mod fees;
+mod iter;
+mod journal;
+mod kdf;
+mod labels;
+mod last_error;
+mod ledger;
+mod locale;
+mod message;
+mod metrics;
+mod migrate;
+#[cfg(feature = "mock-node")]
+mod mock;
+mod netstats;
+mod node;
+mod password;
+mod pos;
+mod progress;
+mod queue;
+mod receive;
+mod relay;
+mod returned;
+mod rotate;
+mod runtime;
+#[cfg(feature = "mock-node")]
+mod selftest;
+mod senders;
+mod servers;
+mod services;
+mod signer;
+mod slates;
+mod split;
+mod store;
+mod trace;
+mod upstream;
+mod webhook;
+
+/// Build information, generated by build.rs
+pub mod built_info {
+    include!(concat!(env!("OUT_DIR"), "/built.rs"));
+}
+
+/// Version of the extern functions surface, bumped on any incompatible change
+pub const API_VERSION: u32 = 1;
+
+/// Feature groups of the extern functions surface, for the app to gate its features
+const API_FEATURES: &[&str] = &[
+    "address_rotation",
+    "config_migration",
+    "grinrelay",
+    "http_send",
+    "file_exchange",
+    "network_info",
+    "wallet_doctor",
+    "background_services",
+    "seed_rotation",
+    "external_signer",
+    "address_validation",
+    "diagnostics_export",
+    "balance_history",
+    "wallet_stats",
+    "cancel_checks",
+    "duplicate_payment_check",
+    "contact_policies",
+    "account_attribution",
+    "read_only_profile",
+    "capability_tokens",
+    "owner_token",
+    "data_namespaces",
+    "dev_chains",
+    "address_reannouncement",
+    "call_coalescing",
+    "operation_journal",
+    "slate_version_matrix",
+    "slate_words",
+    "slate_nfc",
+    "relay_auto_post",
+    "audit_log",
+    "atomic_password_change",
+    "password_strength",
+    "seed_kdf",
+    "password_attempt_limit",
+    "biometric_unlock",
+    "server_list",
+    "sticky_node_selection",
+    "stale_local_data",
+    "operation_timeouts",
+    "cancelable_send",
+    "send_progress",
+    "fee_breakdown",
+    "dust_protection",
+    "split_send",
+    "receive_output_split",
+    "known_senders",
+    "payment_webhook",
+    "pos_charges",
+    "tx_labels",
+    "refund",
+    "output_on_chain",
+    "chain_explorer",
+    "relay_addr_cache",
+    "relay_addr_resolve",
+    "usage_metrics",
+    "amounts_as_strings",
+    "response_schema",
+    "wallet_migrate",
+    "relay_probe",
+    "wallet_close",
+    "send_tracing",
+    "message_validation",
+    "error_localization",
+    "seed_rotation_sweep",
+];
 
 /// Default balance minimum confirmation
 pub const MINIMUM_CONFIRMATIONS: u64 = 10;
 
+/// Default confirmations of a tx or output labeled "confirmed" by the app
+pub const DISPLAY_CONFIRMATIONS: u64 = 1;
+
 /// Default sending coins selection minimum confirmation
 pub const SENDING_MINIMUM_CONFIRMATIONS: u64 = 0;
 
@@ -50,6 +194,14 @@ fn cstr_to_str(s: *const c_char) -> String {
     unsafe { CStr::from_ptr(s).to_string_lossy().into_owned() }
 }
 
+/// An optional string argument, NULL or empty for none
+fn cstr_to_opt_str(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    Some(cstr_to_str(s)).filter(|s| !s.is_empty())
+}
+
 #[no_mangle]
 pub extern "C" fn cstr_free(s: *mut c_char) {
     unsafe {
@@ -61,22 +213,94 @@ pub extern "C" fn cstr_free(s: *mut c_char) {
     };
 }
 
-unsafe fn result_to_cstr(res: Result<String, Error>, error: *mut u8) -> *const c_char {
+/// A C string of a result, the NUL bytes which can't be in a C string are dropped
+fn string_into_raw(s: String) -> *const c_char {
+    let s = match CString::new(s) {
+        Ok(s) => s,
+        Err(e) => {
+            let mut bytes = e.into_vec();
+            bytes.retain(|b| *b != 0);
+            CString::new(bytes).unwrap()
+        }
+    };
+    s.into_raw()
+}
+
+/// A result as bytes with their length, for the large payloads and the ones which might
+/// contain NUL bytes, without the copy into a C string. To be freed with `grin_buffer_free`.
+#[repr(C)]
+pub struct GrinBuffer {
+    pub ptr: *mut u8,
+    pub len: usize,
+    pub cap: usize,
+}
+
+impl GrinBuffer {
+    fn from_string(s: String) -> Self {
+        let mut bytes = std::mem::ManuallyDrop::new(s.into_bytes());
+        GrinBuffer {
+            ptr: bytes.as_mut_ptr(),
+            len: bytes.len(),
+            cap: bytes.capacity(),
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_buffer_free(buffer: GrinBuffer) {
+    if buffer.ptr.is_null() {
+        return;
+    }
+    // Recover the Vec so rust can deallocate it
+    unsafe { Vec::from_raw_parts(buffer.ptr, buffer.len, buffer.cap) };
+}
+
+/// The result of an extern call, as returned to the app. The conversions take the metrics
+/// `Call` of the extern call, accounted once its result and last error are set, so no call
+/// returns its result unaccounted.
+unsafe fn result_to_buffer(
+    _call: metrics::Call,
+    res: Result<String, Error>,
+    error: *mut u8,
+) -> GrinBuffer {
+    match res {
+        Ok(res) => {
+            *error = 0;
+            last_error::clear();
+            GrinBuffer::from_string(res)
+        }
+        Err(e) => {
+            *error = 1;
+            last_error::set(&e);
+            GrinBuffer::from_string(serde_json::to_string(&format!("{}", e)).unwrap())
+        }
+    }
+}
+
+unsafe fn result_to_cstr(
+    _call: metrics::Call,
+    res: Result<String, Error>,
+    error: *mut u8,
+) -> *const c_char {
     match res {
         Ok(res) => {
             *error = 0;
-            CString::new(res).unwrap().into_raw()
+            last_error::clear();
+            string_into_raw(res)
         }
         Err(e) => {
             *error = 1;
-            CString::new(serde_json::to_string(&format!("{}", e)).unwrap())
-                .unwrap()
-                .into_raw()
+            last_error::set(&e);
+            string_into_raw(serde_json::to_string(&format!("{}", e)).unwrap())
         }
     }
 }
 
-unsafe fn result2_to_cstr(res: Result<(bool, String), Error>, error: *mut u8) -> *const c_char {
+unsafe fn result2_to_cstr(
+    _call: metrics::Call,
+    res: Result<(bool, String), Error>,
+    error: *mut u8,
+) -> *const c_char {
     match res {
         Ok((validated, res)) => {
             if validated {
@@ -84,46 +308,557 @@ unsafe fn result2_to_cstr(res: Result<(bool, String), Error>, error: *mut u8) ->
             } else {
                 *error = 2;
             }
-            CString::new(res).unwrap().into_raw()
+            last_error::clear();
+            string_into_raw(res)
         }
         Err(e) => {
             *error = 1;
-            CString::new(serde_json::to_string(&format!("{}", e)).unwrap())
-                .unwrap()
-                .into_raw()
+            last_error::set(&e);
+            string_into_raw(serde_json::to_string(&format!("{}", e)).unwrap())
         }
     }
 }
 
+/// The code of the last error on the calling thread, 0 if its last call succeeded:
+/// 1 generic, 2 invalid argument, 3 wallet, 4 keychain, 5 network, 6 storage,
+/// 7 duplicate payment, 8 read-only.
+/// The error out-parameters are still set, for compatibility.
+#[no_mangle]
+pub extern "C" fn grin_last_error_code() -> u32 {
+    last_error::code()
+}
+
+/// The message of the last error on the calling thread, with its causes, or NULL if its last
+/// call succeeded. To be freed with `cstr_free`.
+#[no_mangle]
+pub extern "C" fn grin_last_error_message() -> *const c_char {
+    match last_error::message() {
+        Some(message) => string_into_raw(message),
+        None => std::ptr::null(),
+    }
+}
+
+/// The last error on the calling thread as `{"code", "key", "params", "message", "detail"}`,
+/// or NULL if its last call succeeded. The `message` is translated in the locale set by
+/// `grin_set_locale` for the common errors, the `key` and `params` are for the app's own
+/// translations. To be freed with `cstr_free`.
+#[no_mangle]
+pub extern "C" fn grin_last_error_info() -> *const c_char {
+    match last_error::info() {
+        Some(info) => string_into_raw(info.to_string()),
+        None => std::ptr::null(),
+    }
+}
+
+fn set_locale(tag: &str) -> Result<String, Error> {
+    let locale = locale::set(tag);
+    Ok(json!({ "locale": locale, "supported": locale::SUPPORTED }).to_string())
+}
+
+/// Set the locale of the error messages of `grin_last_error_info`, i.e. `zh-CN` or the device
+/// locale tag. An unsupported locale falls back to English, returns `{"locale", "supported"}`
+/// with the locale set.
+#[no_mangle]
+pub extern "C" fn grin_set_locale(locale: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_set_locale");
+    let res = set_locale(&cstr_to_str(locale));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Current schema version of the MobileWalletCfg json
+pub const CFG_VERSION: u32 = 2;
+
+/// Default node api address, a cluster of public nodes selected by `select_nearest_node`
+pub const DEFAULT_NODE_API_ADDR: &str = "https://nodes.grin.icu";
+
+/// Default floonet node api address
+pub const DEFAULT_FLOONET_NODE_API_ADDR: &str = "https://nodes.grin.icu:13413";
+
+/// Default node api address of the dev chains, a local node
+pub const DEFAULT_DEV_NODE_API_ADDR: &str = "http://127.0.0.1:23413";
+
 #[derive(Serialize, Deserialize, Clone)]
 struct MobileWalletCfg {
+    #[serde(default = "default_cfg_version")]
+    cfg_version: u32,
+    #[serde(default = "default_account")]
     account: String,
+    #[serde(default = "default_chain_type")]
     chain_type: String,
     data_dir: String,
+    #[serde(default = "default_node_api_addr")]
     node_api_addr: String,
+    #[serde(default)]
     node_api_secret: String,
+    #[serde(default)]
     password: String,
+    /// Confirmations of the spendable amounts of the balance
+    #[serde(default = "default_minimum_confirmations")]
     minimum_confirmations: u64,
+    /// Confirmations of the amounts labeled "confirmed", usually lower than the spendable ones
+    #[serde(default = "default_display_confirmations")]
+    display_confirmations: u64,
+    #[serde(default)]
     grinrelay_config: Option<GrinRelayConfig>,
+    #[serde(default = "default_relay_connect_timeout_ms")]
+    relay_connect_timeout_ms: u64,
+    #[serde(default = "default_relay_response_timeout_ms")]
+    relay_response_timeout_ms: u64,
+    /// Park the incoming relay slates for the user approval, instead of receiving them
+    #[serde(default)]
+    receive_approval: bool,
+    /// Default message of the receiver on the slates received by the relay listener
+    #[serde(default)]
+    receive_message: Option<String>,
+    /// The limits of the automatic receives, enforced by the relay listener
+    #[serde(default)]
+    receive_policy: Option<receive::ReceivePolicy>,
+    /// Less frequent refreshes from the node and smaller batches, for metered networks
+    #[serde(default)]
+    low_data_mode: bool,
+    /// The throttling of the refreshes from the node, no limit if not set
+    #[serde(default)]
+    refresh_policy: Option<netstats::RefreshPolicy>,
+    /// Refuse a send of the same amount to the same receiver within this window, 0 to disable
+    #[serde(default)]
+    duplicate_send_window_secs: u64,
+    /// A token of `grin_owner_token_issue` standing for the password, which can then be empty
+    #[serde(default)]
+    owner_token: String,
+    #[serde(default = "default_owner_token_ttl_secs")]
+    owner_token_ttl_secs: u64,
+    /// Name of the wallet data subdirectory of `data_dir`
+    #[serde(default = "default_data_subdir")]
+    data_subdir: String,
+    /// Put the wallet data under a chain type directory, so the wallets of several networks
+    /// can share a `data_dir`
+    #[serde(default)]
+    namespace_by_chain_type: bool,
+    /// Put the wallet data under a directory of this name, for several wallets in a `data_dir`
+    #[serde(default)]
+    wallet_name: Option<String>,
+    /// How long the listeners are given to reconnect by themselves after a network change,
+    /// before they are restarted
+    #[serde(default = "default_reannounce_grace_secs")]
+    reannounce_grace_secs: u64,
+    /// Finalize and post the own sends coming back signed to a relay listener, instead of
+    /// leaving them to the app
+    #[serde(default)]
+    relay_auto_post: bool,
+    /// The min strength score of a new password, 0 to 4, see `grin_password_strength`
+    #[serde(default = "default_min_password_score")]
+    min_password_score: u8,
+    /// The key derivation of the seed file password of a new wallet, the legacy one if not
+    /// set. An existing wallet is migrated by `grin_seed_kdf_migrate`.
+    #[serde(default)]
+    seed_kdf: Option<kdf::KdfParams>,
+    /// Wipe the seed file after this number of failed password attempts in a row, 0 to never
+    /// wipe it. The wallet is then only recoverable from its mnemonic.
+    #[serde(default)]
+    wipe_after_failed_attempts: u32,
+    /// The deadline of the send, relay query, balance refresh and node status calls, the call
+    /// returning a timeout error then, and a send not posted yet being cancelled. 0 for no
+    /// deadline.
+    #[serde(default)]
+    operation_timeout_ms: u64,
+    /// A token of `grin_cancel_token_new`, so the send of this config can be aborted by
+    /// `grin_send_cancel` until its tx is posted
+    #[serde(default)]
+    cancel_token: Option<String>,
+    /// A send leaving a change below this amount is refused, 0 to disable
+    #[serde(default, deserialize_with = "amounts::deserialize")]
+    dust_threshold: u64,
+    /// Give a change below `dust_threshold` to the recipient, instead of refusing the send
+    #[serde(default)]
+    donate_dust: bool,
+    /// Receive the incoming amounts into this number of outputs, up to 10 and as much as the
+    /// fee of the slate pays for, so the funds can be spent by parallel txs. 0 or 1 for one.
+    #[serde(default)]
+    receive_outputs: u32,
+    /// The url notified of the payments accepted by the listener, by a signed json post
+    #[serde(default)]
+    webhook: Option<webhook::WebhookConfig>,
+    /// The schema version of the balance and tx responses, 0 for the upstream serde form of
+    /// the wallet types. The new configs take the current one.
+    #[serde(default)]
+    response_schema: u32,
+    /// Listen and send through the relay selected by `grin_probe_relays` for the chain type,
+    /// instead of the one of `grinrelay_config`
+    #[serde(default)]
+    auto_select_relay: bool,
+    /// Refuse the slate messages with control characters, instead of stripping them
+    #[serde(default)]
+    strict_messages: bool,
+}
+
+fn default_cfg_version() -> u32 {
+    CFG_VERSION
+}
+
+fn default_account() -> String {
+    "default".to_owned()
+}
+
+fn default_chain_type() -> String {
+    "mainnet".to_owned()
+}
+
+fn default_node_api_addr() -> String {
+    DEFAULT_NODE_API_ADDR.to_owned()
+}
+
+fn default_minimum_confirmations() -> u64 {
+    MINIMUM_CONFIRMATIONS
+}
+
+fn default_display_confirmations() -> u64 {
+    DISPLAY_CONFIRMATIONS
+}
+
+fn default_min_password_score() -> u8 {
+    password::MIN_PASSWORD_SCORE
+}
+
+fn default_relay_connect_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_relay_response_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_owner_token_ttl_secs() -> u64 {
+    300
+}
+
+fn default_data_subdir() -> String {
+    "wallet_data".to_owned()
+}
+
+fn default_reannounce_grace_secs() -> u64 {
+    30
+}
+
+/// The keys known by the current schema, anything else is reported as a warning
+const CFG_KNOWN_KEYS: &[&str] = &[
+    "cfg_version",
+    "account",
+    "chain_type",
+    "data_dir",
+    "node_api_addr",
+    "node_api_secret",
+    "password",
+    "minimum_confirmations",
+    "display_confirmations",
+    "grinrelay_config",
+    "relay_connect_timeout_ms",
+    "relay_response_timeout_ms",
+    "receive_approval",
+    "receive_message",
+    "receive_policy",
+    "low_data_mode",
+    "refresh_policy",
+    "duplicate_send_window_secs",
+    "owner_token",
+    "owner_token_ttl_secs",
+    "data_subdir",
+    "namespace_by_chain_type",
+    "wallet_name",
+    "reannounce_grace_secs",
+    "relay_auto_post",
+    "min_password_score",
+    "seed_kdf",
+    "wipe_after_failed_attempts",
+    "operation_timeout_ms",
+    "cancel_token",
+    "dust_threshold",
+    "donate_dust",
+    "receive_outputs",
+    "webhook",
+    "response_schema",
+    "auto_select_relay",
+    "strict_messages",
+];
+
+/// Upgrade a persisted config json of any older schema version to the current one.
+fn migrate_cfg(value: &mut serde_json::Value, warnings: &mut Vec<String>) -> Result<(), Error> {
+    let obj = value.as_object_mut().ok_or_else(|| {
+        Error::from(ErrorKind::GenericError(
+            "config is not a json object".to_owned(),
+        ))
+    })?;
+    let mut version = obj.get("cfg_version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > CFG_VERSION {
+        warnings.push(format!(
+            "config version {} is newer than supported {}",
+            version, CFG_VERSION
+        ));
+    }
+
+    while version < CFG_VERSION {
+        // v0 -> v1: unversioned config, where node address might use the wallet config naming
+        if version == 0 {
+            if let Some(addr) = obj.remove("check_node_api_http_addr") {
+                obj.entry("node_api_addr").or_insert(addr);
+                warnings.push("check_node_api_http_addr renamed to node_api_addr".to_owned());
+            }
+        }
+        // v1 -> v2: the owner token requirement is a setting of the wallet, not of a config
+        if version == 1 && obj.remove("require_owner_token").is_some() {
+            warnings.push(
+                "require_owner_token moved to the wallet, set it by grin_owner_token_require"
+                    .to_owned(),
+            );
+        }
+        version += 1;
+    }
+    obj.insert("cfg_version".to_owned(), json!(version));
+
+    for key in obj.keys() {
+        if !CFG_KNOWN_KEYS.contains(&key.as_str()) {
+            warnings.push(format!("unknown config key '{}' ignored", key));
+        }
+    }
+    Ok(())
 }
 
 impl MobileWalletCfg {
     pub fn from_str(json_cfg: &str) -> Result<Self, Error> {
-        serde_json::from_str::<MobileWalletCfg>(json_cfg)
-            .map_err(|e| Error::from(ErrorKind::GenericError(e.to_string())))
+        Self::from_str_with_warnings(json_cfg).map(|(config, _)| config)
+    }
+
+    pub fn from_str_with_warnings(json_cfg: &str) -> Result<(Self, Vec<String>), Error> {
+        let mut value: serde_json::Value = serde_json::from_str(json_cfg)
+            .map_err(|e| Error::from(ErrorKind::GenericError(e.to_string())))?;
+        let mut warnings = vec![];
+        migrate_cfg(&mut value, &mut warnings)?;
+        let config = serde_json::from_value::<MobileWalletCfg>(value)
+            .map_err(|e| Error::from(ErrorKind::GenericError(e.to_string())))?;
+        Ok((config, warnings))
+    }
+
+    /// Parse the config, with the account replaced by `account` if set
+    pub fn from_str_for_account(json_cfg: &str, account: Option<&str>) -> Result<Self, Error> {
+        let mut config = Self::from_str(json_cfg)?;
+        if let Some(account) = account {
+            config.account = account.to_owned();
+        }
+        Ok(config)
+    }
+
+    /// A fully populated config with the default settings of a chain type
+    fn default_for(chain_type: &str, data_dir: &str) -> Result<Self, Error> {
+        let node_api_addr = match chain_type {
+            "mainnet" => DEFAULT_NODE_API_ADDR,
+            "floonet" => DEFAULT_FLOONET_NODE_API_ADDR,
+            "usernet" | "automated_testing" => DEFAULT_DEV_NODE_API_ADDR,
+            _ => {
+                return Err(Error::from(ErrorKind::GenericError(
+                    "unsupported chain type".to_owned(),
+                )));
+            }
+        };
+        Ok(MobileWalletCfg {
+            cfg_version: CFG_VERSION,
+            account: default_account(),
+            chain_type: chain_type.to_owned(),
+            data_dir: data_dir.to_owned(),
+            node_api_addr: node_api_addr.to_owned(),
+            node_api_secret: String::new(),
+            password: String::new(),
+            minimum_confirmations: MINIMUM_CONFIRMATIONS,
+            display_confirmations: DISPLAY_CONFIRMATIONS,
+            grinrelay_config: Some(GrinRelayConfig::default()),
+            relay_connect_timeout_ms: default_relay_connect_timeout_ms(),
+            relay_response_timeout_ms: default_relay_response_timeout_ms(),
+            receive_approval: false,
+            receive_message: None,
+            receive_policy: None,
+            low_data_mode: false,
+            refresh_policy: None,
+            duplicate_send_window_secs: 0,
+            owner_token: String::new(),
+            owner_token_ttl_secs: default_owner_token_ttl_secs(),
+            data_subdir: default_data_subdir(),
+            namespace_by_chain_type: false,
+            wallet_name: None,
+            reannounce_grace_secs: default_reannounce_grace_secs(),
+            relay_auto_post: false,
+            min_password_score: default_min_password_score(),
+            seed_kdf: None,
+            wipe_after_failed_attempts: 0,
+            operation_timeout_ms: 0,
+            cancel_token: None,
+            dust_threshold: 0,
+            donate_dust: false,
+            receive_outputs: 1,
+            webhook: None,
+            response_schema: dto::SCHEMA_VERSION,
+            auto_select_relay: false,
+            strict_messages: false,
+        })
+    }
+
+    /// The wallet password, which an owner token doesn't stand for
+    fn wallet_password(&self) -> Result<String, Error> {
+        if !self.owner_token.is_empty() && self.password.is_empty() {
+            return Err(ErrorKind::ArgumentError(
+                "the wallet password is needed, an owner token doesn't hold it".to_owned(),
+            )
+            .into());
+        }
+        Ok(self.password.clone())
+    }
+
+    /// The password of the seed file, the wallet password through the key derivation, or the
+    /// one kept by the owner token if there is one
+    fn seed_password(&self) -> Result<String, Error> {
+        if !self.owner_token.is_empty() {
+            let (json_cfg, seed_password) = capability::resolve_owner(&self.owner_token)?;
+            check_same_wallet(&json_cfg, self)?;
+            if let Some(seed_password) = seed_password {
+                return Ok(seed_password);
+            }
+        }
+        let data_file_dir = new_wallet_config(self.clone())?.data_file_dir;
+        kdf::seed_password(&data_file_dir, &self.wallet_password()?)
     }
 }
 
-fn new_wallet_config(config: MobileWalletCfg) -> Result<WalletConfig, Error> {
-    let chain_type = match config.chain_type.as_str() {
-        "mainnet" => ChainTypes::Mainnet,
-        "floonet" => ChainTypes::Floonet,
-        _ => {
-            return Err(Error::from(ErrorKind::GenericError(
-                "unsupported chain type".to_owned(),
-            )));
+/// Run a network call of a config within its `operation_timeout_ms`. The call is cancelled
+/// at the deadline, by the `cancel_token` of the config or one issued for the call, so a send
+/// the caller saw timing out isn't finalized and posted after.
+fn within<T, F>(operation: &str, json_cfg: &str, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&str) -> Result<T, Error> + Send + 'static,
+{
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet_dir = wallet_data_dir(&config)?;
+    let mut json_cfg = json_cfg.to_owned();
+    let mut issued = None;
+    if config.operation_timeout_ms > 0 && config.cancel_token.is_none() {
+        let token = cancel::new_token();
+        let mut value: serde_json::Value = serde_json::from_str(&json_cfg)
+            .map_err(|e| Error::from(ErrorKind::GenericError(e.to_string())))?;
+        value["cancel_token"] = json!(token);
+        json_cfg = value.to_string();
+        issued = Some(token);
+    }
+    let cancel_token = config.cancel_token.clone().or_else(|| issued.clone());
+    // Also released if the call never runs
+    let issued = cancel::guard(issued.as_deref());
+    deadline::run(
+        operation,
+        &wallet_dir,
+        config.operation_timeout_ms,
+        cancel_token.as_deref(),
+        move || {
+            let _issued = issued;
+            f(&json_cfg)
+        },
+    )
+}
+
+/// The config json keying the coalescing of a call, without the `cancel_token` that `within`
+/// issues per call, so the calls within their deadline still coalesce
+fn coalesce_cfg(json_cfg: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(json_cfg) {
+        Ok(mut value) => {
+            if let Some(fields) = value.as_object_mut() {
+                fields.remove("cancel_token");
+            }
+            value.to_string()
         }
-    };
+        Err(_) => json_cfg.to_owned(),
+    }
+}
+
+/// Refuse a spending `operation` in the read-only profile, or without the owner token the
+/// wallet requires
+fn check_spending(config: &MobileWalletCfg, operation: &str) -> Result<(), Error> {
+    runtime::check_spending(operation)?;
+    let data_file_dir = new_wallet_config(config.clone())?.data_file_dir;
+    if capability::is_owner_token_required(&data_file_dir)? {
+        if config.owner_token.is_empty() {
+            return Err(
+                ErrorKind::ArgumentError(format!("{} requires an owner token", operation)).into(),
+            );
+        }
+        let (json_cfg, _) = capability::resolve_owner(&config.owner_token)?;
+        check_same_wallet(&json_cfg, config)?;
+    }
+    Ok(())
+}
+
+/// Refuse the owner token of another wallet, the config of the token being `json_cfg`
+fn check_same_wallet(json_cfg: &str, config: &MobileWalletCfg) -> Result<(), Error> {
+    if wallet_data_dir(&MobileWalletCfg::from_str(json_cfg)?)? != wallet_data_dir(config)? {
+        return Err(ErrorKind::ArgumentError("owner token of another wallet".to_owned()).into());
+    }
+    Ok(())
+}
+
+fn default_config(chain_type: &str, data_dir: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::default_for(chain_type, data_dir)?;
+    Ok(serde_json::to_string(&config).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_default_config(
+    chain_type: *const c_char,
+    data_dir: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_default_config");
+    let res = default_config(&cstr_to_str(chain_type), &cstr_to_str(data_dir));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn config_migrate(json_cfg: &str) -> Result<String, Error> {
+    let (config, warnings) = MobileWalletCfg::from_str_with_warnings(json_cfg)?;
+    Ok(json!({
+        "config": config,
+        "warnings": warnings,
+    })
+    .to_string())
+}
+
+/// Upgrade a persisted config json to the current schema, the app should persist the result.
+#[no_mangle]
+pub extern "C" fn grin_config_migrate(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_config_migrate");
+    let res = config_migrate(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// A single directory name of the data path, never escaping the `data_dir`
+fn check_dir_name(name: &str, what: &str) -> Result<(), Error> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains('\\') {
+        return Err(ErrorKind::ArgumentError(format!("invalid {} '{}'", what, name)).into());
+    }
+    Ok(())
+}
+
+/// The wallet data directory: `data_dir`, then the chain type and the wallet name if
+/// namespaced, then `data_subdir`
+fn wallet_data_dir(config: &MobileWalletCfg) -> Result<String, Error> {
+    let mut dir = config.data_dir.clone();
+    if config.namespace_by_chain_type {
+        dir = dir + "/" + &config.chain_type;
+    }
+    if let Some(wallet_name) = config.wallet_name.as_ref() {
+        check_dir_name(wallet_name, "wallet name")?;
+        dir = dir + "/" + wallet_name;
+    }
+    check_dir_name(&config.data_subdir, "data subdir")?;
+    Ok(dir + "/" + &config.data_subdir)
+}
+
+fn new_wallet_config(config: MobileWalletCfg) -> Result<WalletConfig, Error> {
+    let chain_type = runtime::parse_chain_type(&config.chain_type)?;
+    runtime::check_chain_type(&config.chain_type)?;
+    let data_file_dir = wallet_data_dir(&config)?;
 
     Ok(WalletConfig {
         chain_type: Some(chain_type),
@@ -134,7 +869,7 @@ fn new_wallet_config(config: MobileWalletCfg) -> Result<WalletConfig, Error> {
         node_api_secret: Some(config.node_api_secret),
         check_node_api_http_addr: config.node_api_addr,
         owner_api_include_foreign: Some(false),
-        data_file_dir: config.data_dir + "/wallet_data",
+        data_file_dir,
         no_commit_cache: Some(false),
         tls_certificate_file: None,
         tls_certificate_key: None,
@@ -144,11 +879,200 @@ fn new_wallet_config(config: MobileWalletCfg) -> Result<WalletConfig, Error> {
     })
 }
 
+/// The network a wallet data directory was created for
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct NetworkInfo {
+    chain_type: String,
+    created_at: u64,
+}
+
+const NETWORK_INFO_FILE: &str = "network.json";
+
+/// Record the chain type into wallet data. Legacy wallet data without this record is adopted
+/// by the configured chain type on first open.
+fn save_network_info(config: &MobileWalletCfg, data_file_dir: &str) -> Result<(), Error> {
+    let info = NetworkInfo {
+        chain_type: config.chain_type.clone(),
+        created_at: store::now_secs(),
+    };
+    store::save(&store::data_file(data_file_dir, NETWORK_INFO_FILE), &info)
+}
+
+/// Refuse to open the wallet data of the other network, to avoid the cross-network corruption.
+fn check_network(config: &MobileWalletCfg, data_file_dir: &str) -> Result<(), Error> {
+    let path = store::data_file(data_file_dir, NETWORK_INFO_FILE);
+    if !path.exists() {
+        if std::path::Path::new(data_file_dir).exists() {
+            save_network_info(config, data_file_dir)?;
+        }
+        return Ok(());
+    }
+    let info: NetworkInfo = store::load(&path)?;
+    if info.chain_type != config.chain_type {
+        return Err(ErrorKind::GenericError(format!(
+            "chain type mismatch, wallet data is for {} but {} is configured",
+            info.chain_type, config.chain_type
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Relay receiving addresses handed out by this wallet, indexed by the key derivation index
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct RelayAddressBook {
+    current_index: u32,
+    addresses: Vec<RelayAddressEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct RelayAddressEntry {
+    index: u32,
+    address: String,
+    created_at: u64,
+}
+
+const RELAY_ADDRESS_BOOK_FILE: &str = "relay_addresses.json";
+
+lazy_static! {
+    /// Serializes the read-modify-write of the relay address book
+    static ref RELAY_ADDRESS_BOOK_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn relay_address_book_path(config: &MobileWalletCfg) -> Result<std::path::PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        RELAY_ADDRESS_BOOK_FILE,
+    ))
+}
+
+/// The grinrelay config with the key derivation index of a specific receiving address, and
+/// the relay selected by `grin_probe_relays` if the config has `auto_select_relay`
+fn relay_config_at(config: &MobileWalletCfg, index: u32) -> GrinRelayConfig {
+    let mut relay_config = config.grinrelay_config.clone().unwrap_or_default();
+    relay_config.grinrelay_key_path_index = Some(index);
+    if config.auto_select_relay {
+        if let Some(relay) = servers::selected_relay(&config.chain_type) {
+            relay_config.grinrelay_domain = relay.domain;
+            relay_config.grinrelay_port = relay.port;
+        }
+    }
+    relay_config
+}
+
+/// The grinrelay config of the current (latest handed out) receiving address
+fn relay_config(config: &MobileWalletCfg) -> Result<GrinRelayConfig, Error> {
+    let book: RelayAddressBook = store::load(&relay_address_book_path(config)?)?;
+    Ok(relay_config_at(config, book.current_index))
+}
+
+fn init(global_cfg: &str) -> Result<String, Error> {
+    let global_cfg: runtime::GlobalCfg = serde_json::from_str(global_cfg)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid global config: {}", e)))?;
+    runtime::init(global_cfg)?;
+    Ok("OK".to_owned())
+}
+
+/// Set up the process wide state, `{"chain_type", "log_file_path", "log_level", "read_only",
+/// "genesis_hash"}`. The chain type is "mainnet", "floonet", or a local dev chain "usernet"
+/// or "automated_testing".
+/// To be called once on the app start, and again only after `grin_shutdown`.
+#[no_mangle]
+pub extern "C" fn grin_init(global_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_init");
+    let res = init(&cstr_to_str(global_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Stop all the background services and clear the process wide state
+#[no_mangle]
+pub extern "C" fn grin_shutdown() {
+    runtime::shutdown();
+}
+
+/// The version of a crate linked into this build, from the Cargo.lock recorded by build.rs
+fn dependency_version(name: &str) -> Option<&'static str> {
+    built_info::DEPENDENCIES
+        .iter()
+        .find(|(dependency, _)| *dependency == name)
+        .map(|(_, version)| *version)
+}
+
+/// The feature groups of this build, `API_FEATURES` and the ones of its optional cargo features
+fn api_features() -> Vec<&'static str> {
+    let mut features = API_FEATURES.to_vec();
+    if cfg!(feature = "mock-node") {
+        features.push("mock_node");
+    }
+    features
+}
+
+fn get_version() -> Result<String, Error> {
+    Ok(json!({
+        "version": built_info::PKG_VERSION,
+        "apiVersion": API_VERSION,
+        "apiFeatures": api_features(),
+        "buildFeatures": built_info::FEATURES_STR,
+        "readOnly": runtime::is_read_only(),
+        "slateVersions": slates::SUPPORTED,
+        "grinWalletVersion": dependency_version("grin_wallet_libwallet"),
+        "grinWalletCommit": env!("GRIN_WALLET_COMMIT"),
+        "gitVersion": built_info::GIT_VERSION,
+        "target": built_info::TARGET,
+        "builtTime": built_info::BUILT_TIME_UTC,
+    })
+    .to_string())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_get_version(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_get_version");
+    let res = get_version();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// The transports and workflows, with the upstream crate each is built on. The ones without
+/// a crate have no implementation in this lib. No invoice is issued by this lib, so there's
+/// no invoice to expire, cancel or list either; the expiry and cleanup come with the invoice
+/// workflow, once it's built on the invoice api of the grin-wallet libs.
+const CAPABILITY_CRATES: &[(&str, Option<&str>)] = &[
+    ("grinrelay", Some("grin_wallet_relay")),
+    ("http", Some("grin_wallet_impls")),
+    ("file", Some("grin_wallet_impls")),
+    ("tor", None),
+    ("keybase", None),
+    ("slatepack", None),
+    ("invoice", None),
+];
+
+/// The transports and workflows compiled into this lib, each one available if its crate is
+/// linked into this build, and the optional cargo features of the build
+fn capabilities() -> Result<String, Error> {
+    let mut capabilities = serde_json::Map::new();
+    for (capability, dependency) in CAPABILITY_CRATES {
+        let linked = dependency.map_or(false, |d| dependency_version(d).is_some());
+        capabilities.insert(capability.to_string(), json!(linked));
+    }
+    capabilities.insert("mockNode".to_owned(), json!(cfg!(feature = "mock-node")));
+    capabilities.insert("readOnly".to_owned(), json!(runtime::is_read_only()));
+    Ok(serde_json::Value::Object(capabilities).to_string())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_capabilities(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_capabilities");
+    let res = capabilities();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
 fn select_node_server(check_node_api_http_addr: &str) -> Result<String, Error> {
+    // Select the fastest of the recommended node servers, once a server list is fetched
+    if let Some(best) = servers::select_node(check_node_api_http_addr)? {
+        return Ok(best);
+    }
     // Select nearest node server
-    if check_node_api_http_addr
-        .starts_with("https://nodes.grin.icu")
-    {
+    if check_node_api_http_addr.starts_with("https://nodes.grin.icu") {
         match grin_wallet_config::select_node_server(check_node_api_http_addr) {
             Ok(best) => {
                 return Ok(best);
@@ -167,80 +1091,280 @@ pub extern "C" fn select_nearest_node(
     check_node_api_http_addr: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
+    let call = metrics::call("select_nearest_node");
     let res = select_node_server(&cstr_to_str(check_node_api_http_addr));
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn check_password(json_cfg: &str, password: &str) -> Result<String, Error> {
-    let wallet_config = new_wallet_config(MobileWalletCfg::from_str(json_cfg)?)?;
-    WalletSeed::from_file(&wallet_config.data_file_dir, password).map_err(|e| Error::from(e))?;
-    Ok("OK".to_owned())
+fn update_server_list(url: &str) -> Result<String, Error> {
+    Ok(serde_json::to_string(&servers::update(url)?).unwrap())
 }
 
+/// Fetch the signed list of the recommended node and relay servers at `url`, verify it
+/// against the embedded publisher key and cache it, for `select_nearest_node`
 #[no_mangle]
-pub extern "C" fn grin_check_password(
-    json_cfg: *const c_char,
-    password: *const c_char,
-    error: *mut u8,
-) -> *const c_char {
-    let res = check_password(&cstr_to_str(json_cfg), &cstr_to_str(password));
-    unsafe { result_to_cstr(res, error) }
+pub extern "C" fn grin_update_server_list(url: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_update_server_list");
+    let res = update_server_list(&cstr_to_str(url));
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn init_wallet_seed() -> Result<String, Error> {
-    WalletSeed::init_new(32).to_mnemonic()
+fn set_network(network: &str) -> Result<String, Error> {
+    servers::set_network(network);
+    Ok(json!({ "selection": servers::selection() }).to_string())
 }
 
+/// Tell the lib the current device network, i.e. "wifi:<ssid>" or "cellular", so
+/// `select_nearest_node` keeps the node selected on it before, without probing again. Returns
+/// the selected node of each known network.
 #[no_mangle]
-pub extern "C" fn grin_init_wallet_seed(error: *mut u8) -> *const c_char {
-    let res = init_wallet_seed();
-    unsafe { result_to_cstr(res, error) }
+pub extern "C" fn grin_set_network(network: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_set_network");
+    let res = set_network(&cstr_to_str(network));
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn wallet_init(json_cfg: &str, password: &str, is_12_phrases: bool) -> Result<String, Error> {
-    let wallet_config = new_wallet_config(MobileWalletCfg::from_str(json_cfg)?)?;
-    let node_api_secret = wallet_config.node_api_secret.clone();
-    let seed_length = if is_12_phrases { 16 } else { 32 };
-    let seed = WalletSeed::init_file(
-        &wallet_config.data_file_dir,
-        seed_length,
-        None,
-        password,
-        false,
+fn server_list() -> Result<String, Error> {
+    Ok(serde_json::to_string(&servers::cached()).unwrap())
+}
+
+/// The cached list of the recommended servers, null if none was fetched yet
+#[no_mangle]
+pub extern "C" fn grin_server_list(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_server_list");
+    let res = server_list();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[derive(Deserialize)]
+struct RelayCandidate {
+    domain: String,
+    port: u16,
+}
+
+fn probe_relays(json_cfg: &str, relays_json: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let candidates: Vec<RelayCandidate> = if relays_json.trim().is_empty() {
+        vec![]
+    } else {
+        serde_json::from_str(relays_json)
+            .map_err(|e| ErrorKind::ArgumentError(format!("invalid relays: {}", e)))?
+    };
+    let secure = !config
+        .grinrelay_config
+        .as_ref()
+        .map_or(false, |c| c.grinrelay_protocol_unsecure);
+    let (selected, probes) = servers::probe_relays(
+        &config.chain_type,
+        candidates.into_iter().map(|c| (c.domain, c.port)).collect(),
+        secure,
     )?;
-    let node_client = HTTPNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
-    let _: LMDBBackend<HTTPNodeClient, ExtKeychain> =
-        LMDBBackend::new(wallet_config, password, node_client)?;
-    seed.to_mnemonic()
+    Ok(json!({
+        "selected": selected,
+        "probes": probes,
+    })
+    .to_string())
 }
 
+/// Probe the relays `[{"domain", "port"}]`, the recommended ones of the chain type if empty,
+/// by their websocket connect and handshake time, over TLS unless the `grinrelay_config` has
+/// `grinrelay_protocol_unsecure`. The fastest responding one is selected for
+/// the configs with `auto_select_relay`. Returns `{"selected": {"domain", "port",
+/// "latencyMs", "selectedAt"}, "probes": [{"domain", "port", "connectMs", "handshakeMs",
+/// "error"}]}`, the fastest first.
 #[no_mangle]
-pub extern "C" fn grin_wallet_init(
+pub extern "C" fn grin_probe_relays(
     json_cfg: *const c_char,
-    password: *const c_char,
-    is_12_phrases: bool,
+    relays_json: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
+    let call = metrics::call("grin_probe_relays");
+    let res = probe_relays(&cstr_to_str(json_cfg), &cstr_to_str(relays_json));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn check_password(json_cfg: &str, password: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet_config = new_wallet_config(config.clone())?;
+    let res = attempts::guard(&config, || {
+        let seed_password = kdf::seed_password(&wallet_config.data_file_dir, password)?;
+        WalletSeed::from_file(&wallet_config.data_file_dir, &seed_password)
+    });
+    if let Err(e) = res {
+        audit::record(&config, "password_check_failed", json!({}));
+        return Err(e);
+    }
+    audit::record(&config, "wallet_unlocked", json!({}));
+    Ok("OK".to_owned())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_check_password(
+    json_cfg: *const c_char,
+    password: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_check_password");
+    let res = check_password(&cstr_to_str(json_cfg), &cstr_to_str(password));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn password_attempts(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet_config = new_wallet_config(config.clone())?;
+    let attempts = attempts::state(&wallet_config.data_file_dir)?;
+    let now = store::now_secs();
+    Ok(json!({
+        "failures": attempts.failures,
+        "lastFailureAt": attempts.last_failure_at,
+        "lockedUntil": attempts.locked_until,
+        "retryAfterSecs": attempts.locked_until.saturating_sub(now),
+        "wipeAfter": config.wipe_after_failed_attempts,
+    })
+    .to_string())
+}
+
+/// The failed password attempts in a row, with the delay before the next accepted one
+#[no_mangle]
+pub extern "C" fn grin_password_attempts(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_password_attempts");
+    let res = password_attempts(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn password_strength(password: &str) -> Result<String, Error> {
+    Ok(serde_json::to_string(&password::strength(password)).unwrap())
+}
+
+/// Estimate the strength of a password, as `{"entropyBits", "score", "classification",
+/// "meetsMinimum", "feedback"}`, the score from 0 "very_weak" to 4 "very_strong"
+#[no_mangle]
+pub extern "C" fn grin_password_strength(password: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_password_strength");
+    let res = password_strength(&cstr_to_str(password));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn seed_kdf_get(json_cfg: &str) -> Result<String, Error> {
+    let wallet_config = new_wallet_config(MobileWalletCfg::from_str(json_cfg)?)?;
+    let params = kdf::current(&wallet_config.data_file_dir)?.params;
+    Ok(serde_json::to_string(&params).unwrap())
+}
+
+/// The key derivation of the seed file password, `{"algorithm", "memoryKib", "iterations",
+/// "parallelism"}`, the algorithm "legacy" or "argon2id"
+#[no_mangle]
+pub extern "C" fn grin_seed_kdf_get(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_seed_kdf_get");
+    let res = seed_kdf_get(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn seed_kdf_migrate(json_cfg: &str, password: &str, params_json: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let params: kdf::KdfParams = serde_json::from_str(params_json)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid kdf params: {}", e)))?;
+    password::migrate_kdf(&config, password, &params)?;
+    audit::record(&config, "seed_kdf_migrated", json!({ "params": params }));
+    Ok("OK".to_owned())
+}
+
+/// Re-wrap the seed file of an existing wallet with another key derivation, i.e. Argon2 with
+/// `{"algorithm": "argon2id", "memoryKib", "iterations", "parallelism"}`. The password is
+/// kept, an interrupted migration leaves the wallet openable with the old or the new one.
+#[no_mangle]
+pub extern "C" fn grin_seed_kdf_migrate(
+    json_cfg: *const c_char,
+    password: *const c_char,
+    params_json: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_seed_kdf_migrate");
+    let res = seed_kdf_migrate(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(password),
+        &cstr_to_str(params_json),
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn init_wallet_seed() -> Result<String, Error> {
+    WalletSeed::init_new(32).to_mnemonic()
+}
+
+#[no_mangle]
+pub extern "C" fn grin_init_wallet_seed(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_init_wallet_seed");
+    let res = init_wallet_seed();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn wallet_init(json_cfg: &str, password: &str, is_12_phrases: bool) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    password::check_strength(password, config.min_password_score)?;
+    let wallet_config = new_wallet_config(config.clone())?;
+    let node_api_secret = wallet_config.node_api_secret.clone();
+    let seed_length = if is_12_phrases { 16 } else { 32 };
+    let (seed, seed_password) = kdf::with_new_seed(
+        &wallet_config.data_file_dir,
+        config.seed_kdf.as_ref(),
+        password,
+        |seed_password| {
+            let seed = WalletSeed::init_file(
+                &wallet_config.data_file_dir,
+                seed_length,
+                None,
+                seed_password,
+                false,
+            )?;
+            Ok((seed, seed_password.to_owned()))
+        },
+    )?;
+    save_network_info(&config, &wallet_config.data_file_dir)?;
+    let node_client =
+        MobileNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
+    let _: LMDBBackend<MobileNodeClient, ExtKeychain> =
+        LMDBBackend::new(wallet_config, &seed_password, node_client)?;
+    audit::record(&config, "wallet_created", json!({}));
+    seed.to_mnemonic()
+}
+
+#[no_mangle]
+pub extern "C" fn grin_wallet_init(
+    json_cfg: *const c_char,
+    password: *const c_char,
+    is_12_phrases: bool,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_init");
     let res = wallet_init(
         &cstr_to_str(json_cfg),
         &cstr_to_str(password),
         is_12_phrases,
     );
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
 }
 
 fn wallet_init_recover(json_cfg: &str, mnemonic: &str) -> Result<String, Error> {
     let config = MobileWalletCfg::from_str(json_cfg)?;
+    password::check_strength(&config.password, config.min_password_score)?;
     let wallet_config = new_wallet_config(config.clone())?;
-    WalletSeed::recover_from_phrase(
+    let seed_password = kdf::with_new_seed(
         &wallet_config.data_file_dir,
-        mnemonic,
-        config.password.as_str(),
+        config.seed_kdf.as_ref(),
+        &config.password,
+        |seed_password| {
+            WalletSeed::recover_from_phrase(&wallet_config.data_file_dir, mnemonic, seed_password)?;
+            Ok(seed_password.to_owned())
+        },
     )?;
+    save_network_info(&config, &wallet_config.data_file_dir)?;
     let node_api_secret = wallet_config.node_api_secret.clone();
-    let node_client = HTTPNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
-    let _: LMDBBackend<HTTPNodeClient, ExtKeychain> =
-        LMDBBackend::new(wallet_config, config.password.as_str(), node_client)?;
+    let node_client =
+        MobileNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
+    let _: LMDBBackend<MobileNodeClient, ExtKeychain> =
+        LMDBBackend::new(wallet_config, &seed_password, node_client)?;
+    audit::record(&config, "wallet_recovered", json!({}));
     Ok("OK".to_owned())
 }
 
@@ -250,21 +1374,25 @@ pub extern "C" fn grin_wallet_init_recover(
     mnemonic: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
+    let call = metrics::call("grin_wallet_init_recover");
     let res = wallet_init_recover(&cstr_to_str(json_cfg), &cstr_to_str(mnemonic));
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
 }
 
 fn wallet_change_password(
     json_cfg: &str,
     old_password: &str,
     new_password: &str,
-) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-
-    api.change_password(&Some(ZeroingString::from(old_password)), new_password)
-        .map_err(|e| Error::from(e))?;
-    Ok("OK".to_owned())
+) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let res = attempts::guard(&config, || {
+        password::change(&config, old_password, new_password)
+    });
+    match res {
+        Ok(_) => audit::record(&config, "password_changed", json!({})),
+        Err(_) => audit::record(&config, "password_change_failed", json!({})),
+    }
+    res
 }
 
 #[no_mangle]
@@ -274,34 +1402,49 @@ pub extern "C" fn grin_wallet_change_password(
     new_password: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
+    let call = metrics::call("grin_wallet_change_password");
     let res = wallet_change_password(
         &cstr_to_str(json_cfg),
         &cstr_to_str(old_password),
         &cstr_to_str(new_password),
-    );
-    unsafe { result_to_cstr(res, error) }
+    )
+    .map(|_| "OK".to_owned());
+    unsafe { result_to_cstr(call, res, error) }
 }
 
 fn wallet_restore(json_cfg: &str, start_index: u64, batch_size: u64) -> Result<String, Error> {
     let config = MobileWalletCfg::from_str(json_cfg)?;
     let wallet_config = new_wallet_config(config.clone())?;
+    check_network(&config, &wallet_config.data_file_dir)?;
     let node_api_secret = wallet_config.node_api_secret.clone();
-    let node_client = HTTPNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
+    let node_client =
+        MobileNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
     let wallet = instantiate_wallet(
         wallet_config,
         node_client,
-        config.password.as_str(),
+        config.seed_password()?.as_str(),
         &config.account,
     )?;
-    let api = Owner::new(wallet.clone());
+    let api = upstream::api(wallet.clone());
 
-    let (highest_index, last_retrieved_index, num_of_found) = api
-        .restore_batch(start_index, batch_size)
-        .map_err(|e| Error::from(e))?;
+    let before = check::snapshot(wallet.clone())?;
+    let batch_size = netstats::batch_size(&config, batch_size);
+    let journal_id = journal::begin(
+        &config,
+        journal::OP_RESTORE,
+        journal::PHASE_INITIATED,
+        None,
+        json!({ "startIndex": start_index, "batchSize": batch_size }),
+    )?;
+    let (highest_index, last_retrieved_index, num_of_found) =
+        api.restore_batch(start_index, batch_size)?;
+    journal::finish(&config, &journal_id);
+    let changes = check::diff(&before, &check::snapshot(wallet)?);
     Ok(json!({
         "highestIndex": highest_index,
         "lastRetrievedIndex": last_retrieved_index,
         "numberOfFound": num_of_found,
+        "changes": changes,
     })
     .to_string())
 }
@@ -313,8 +1456,9 @@ pub extern "C" fn grin_wallet_restore(
     batch_size: u64,
     error: *mut u8,
 ) -> *const c_char {
+    let call = metrics::call("grin_wallet_restore");
     let res = wallet_restore(&cstr_to_str(json_cfg), start_index, batch_size);
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
 }
 
 fn wallet_check(
@@ -323,15 +1467,19 @@ fn wallet_check(
     batch_size: u64,
     update_outputs: bool,
 ) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let (highest_index, last_retrieved_index) = api
-        .check_repair_batch(true, start_index, batch_size, update_outputs)
-        .map_err(|e| Error::from(e))?;
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let batch_size = netstats::batch_size(&config, batch_size);
+    let wallet = get_wallet_instance(config)?;
+    let api = upstream::api(wallet.clone());
+    let before = check::snapshot(wallet.clone())?;
+    let (highest_index, last_retrieved_index) =
+        api.check_repair_batch(true, start_index, batch_size, update_outputs)?;
+    let changes = check::diff(&before, &check::snapshot(wallet)?);
 
     Ok(json!({
         "highestIndex": highest_index,
         "lastRetrievedIndex": last_retrieved_index,
+        "changes": changes,
     })
     .to_string())
 }
@@ -344,19 +1492,96 @@ pub extern "C" fn grin_wallet_check(
     update_outputs: bool,
     error: *mut u8,
 ) -> *const c_char {
+    let call = metrics::call("grin_wallet_check");
     let res = wallet_check(
         &cstr_to_str(json_cfg),
         start_index,
         batch_size,
         update_outputs,
     );
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn wallet_check_full_start(json_cfg: &str, delete_unconfirmed: bool) -> Result<String, Error> {
+    check::start(MobileWalletCfg::from_str(json_cfg)?, delete_unconfirmed)
+}
+
+/// Start a full check_repair in background, returns the task id for the status and cancel.
+#[no_mangle]
+pub extern "C" fn grin_wallet_check_full_start(
+    json_cfg: *const c_char,
+    delete_unconfirmed: bool,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_check_full_start");
+    let res = wallet_check_full_start(&cstr_to_str(json_cfg), delete_unconfirmed);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_wallet_check_full_status(
+    task_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_check_full_status");
+    let res = check::status(&cstr_to_str(task_id)).map(|status| status.to_string());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_wallet_check_full_cancel(
+    task_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_check_full_cancel");
+    let res = check::cancel(&cstr_to_str(task_id)).map(|_| "OK".to_owned());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn wallet_doctor(json_cfg: &str, repair: bool) -> Result<String, Error> {
+    let report = doctor::wallet_doctor(MobileWalletCfg::from_str(json_cfg)?, repair)?;
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_wallet_doctor(
+    json_cfg: *const c_char,
+    repair: bool,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_doctor");
+    let res = wallet_doctor(&cstr_to_str(json_cfg), repair);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn export_diagnostics(json_cfg: &str) -> Result<String, Error> {
+    let bundle = diagnostics::export(&MobileWalletCfg::from_str(json_cfg)?);
+    Ok(bundle.to_string())
+}
+
+/// The support bundle for a bug report, `{"version", "config", "doctor", "node", "services",
+/// "listeners", "receiveFailures", "networkStats", "logs"}`, with the password and the node
+/// api secret redacted everywhere. The seed is never included.
+#[no_mangle]
+pub extern "C" fn grin_export_diagnostics(
+    json_cfg: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_export_diagnostics");
+    let res = export_diagnostics(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
 }
 
 fn get_wallet_mnemonic(json_cfg: &str) -> Result<String, Error> {
     let config = MobileWalletCfg::from_str(json_cfg)?;
     let wallet_config = new_wallet_config(config.clone())?;
-    let seed = WalletSeed::from_file(&wallet_config.data_file_dir, config.password.as_str())?;
+    let seed = attempts::guard(&config, || {
+        WalletSeed::from_file(
+            &wallet_config.data_file_dir,
+            config.seed_password()?.as_str(),
+        )
+    })?;
+    audit::record(&config, "seed_displayed", json!({}));
     seed.to_mnemonic()
 }
 
@@ -365,369 +1590,585 @@ pub extern "C" fn grin_get_wallet_mnemonic(
     json_cfg: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
+    let call = metrics::call("grin_get_wallet_mnemonic");
     let res = get_wallet_mnemonic(&cstr_to_str(json_cfg));
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn wallet_rotate_seed(
+    json_cfg: &str,
+    new_data_dir: &str,
+    is_12_phrases: bool,
+) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "seed rotation")?;
+    let rotation = rotate::rotate_seed(config.clone(), new_data_dir, is_12_phrases)?;
+    audit::record(
+        &config,
+        "seed_rotated",
+        json!({ "newDataDir": new_data_dir }),
+    );
+    Ok(serde_json::to_string(&rotation).unwrap())
+}
+
+/// Create a wallet with a new seed in `new_data_dir`, with the same password, accounts,
+/// contacts and tx labels. Returns `{"mnemonic", "config", "accounts", "migratedFiles"}`, the
+/// funds are swept by `grin_wallet_rotate_sweep` once the mnemonic is backed up.
+#[no_mangle]
+pub extern "C" fn grin_wallet_rotate_seed(
+    json_cfg: *const c_char,
+    new_data_dir: *const c_char,
+    is_12_phrases: bool,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_rotate_seed");
+    let res = wallet_rotate_seed(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(new_data_dir),
+        is_12_phrases,
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn wallet_rotate_sweep(
+    json_cfg: &str,
+    new_data_dir: &str,
+    mnemonic: &str,
+) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "seed rotation")?;
+    let sweeping = rotate::sweep(config.clone(), new_data_dir, mnemonic)?;
+    audit::record(
+        &config,
+        "seed_rotation_swept",
+        json!({ "newDataDir": new_data_dir, "txs": sweeping.sweeps.len() }),
+    );
+    Ok(serde_json::to_string(&sweeping).unwrap())
+}
+
+/// Sweep all the spendable funds to the new wallet of `grin_wallet_rotate_seed`, once its
+/// backup is confirmed by the `mnemonic` entered again. Returns `{"sweeps", "unsweptAmount"}`,
+/// the app switches to the new config then. The old wallet is kept for the funds not
+/// spendable yet, a later call sweeps them.
+#[no_mangle]
+pub extern "C" fn grin_wallet_rotate_sweep(
+    json_cfg: *const c_char,
+    new_data_dir: *const c_char,
+    mnemonic: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_rotate_sweep");
+    let res = wallet_rotate_sweep(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(new_data_dir),
+        &cstr_to_str(mnemonic),
+    );
+    unsafe { result_to_cstr(call, res, error) }
 }
 
 fn get_wallet_instance(
     config: MobileWalletCfg,
 ) -> Result<Arc<Mutex<dyn WalletInst<impl NodeClient, ExtKeychain>>>, Error> {
     let wallet_config = new_wallet_config(config.clone())?;
+    check_network(&config, &wallet_config.data_file_dir)?;
     let node_api_secret = wallet_config.node_api_secret.clone();
-    let node_client = HTTPNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
+    let node_client =
+        MobileNodeClient::new(&wallet_config.check_node_api_http_addr, node_api_secret);
 
     instantiate_wallet(
         wallet_config,
         node_client,
-        config.password.as_str(),
+        config.seed_password()?.as_str(),
         config.account.as_str(),
     )
 }
 
-fn get_balance(json_cfg: &str) -> Result<(bool, String), Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let (validated, wallet_info) = api.retrieve_summary_info(true, MINIMUM_CONFIRMATIONS)?;
-    Ok((validated, serde_json::to_string(&wallet_info).unwrap()))
+fn wallet_network_info(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet_config = new_wallet_config(config.clone())?;
+    let path = store::data_file(&wallet_config.data_file_dir, NETWORK_INFO_FILE);
+    let stored = if path.exists() {
+        Some(store::load::<NetworkInfo>(&path)?)
+    } else {
+        None
+    };
+    let matched = stored
+        .as_ref()
+        .map(|info| info.chain_type == config.chain_type)
+        .unwrap_or(true);
+    Ok(json!({
+        "configuredChainType": config.chain_type,
+        "storedChainType": stored.as_ref().map(|info| info.chain_type.clone()),
+        "createdAt": stored.as_ref().map(|info| info.created_at),
+        "dataDir": wallet_config.data_file_dir,
+        "matched": matched,
+    })
+    .to_string())
 }
 
 #[no_mangle]
-pub extern "C" fn grin_get_balance(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
-    let res = get_balance(&cstr_to_str(json_cfg));
-    unsafe { result2_to_cstr(res, error) }
+pub extern "C" fn grin_wallet_network_info(
+    json_cfg: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_network_info");
+    let res = wallet_network_info(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn tx_retrieve(json_cfg: &str, tx_slate_id: &str) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
-    let txs = api.retrieve_txs(true, None, Some(uuid))?;
-    Ok(serde_json::to_string(&txs).unwrap())
+fn wallet_migrate(data_dir: &str) -> Result<String, Error> {
+    Ok(serde_json::to_string(&migrate::run(data_dir)?).unwrap())
 }
 
+/// Upgrade the wallet data directories written by the earlier releases, found below a
+/// `data_dir`, before opening them. The wallet db is kept, no rescan is needed. Returns
+/// `[{"dataFileDir", "chainType", "backupDir", "changes", "notes"}]`, the changed files being
+/// copied into the backup dir first, the last 3 backups kept. Refused while a wallet is open,
+/// see `grin_wallet_close`.
 #[no_mangle]
-pub extern "C" fn grin_tx_retrieve(
+pub extern "C" fn grin_wallet_migrate(data_dir: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_wallet_migrate");
+    let res = wallet_migrate(&cstr_to_str(data_dir));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Read the local wallet data with a refresh from the node, and again without the refresh if
+/// the node is unreachable, so the local data is still returned, as not validated. The calls
+/// needing the node fail with the `CODE_NODE_UNREACHABLE` error code instead.
+fn local_read<T, F>(
+    config: &MobileWalletCfg,
+    refresh_from_node: bool,
+    read: F,
+) -> Result<(bool, T), Error>
+where
+    F: Fn(bool) -> Result<(bool, T), Error>,
+{
+    match read(refresh_from_node) {
+        Err(_) if refresh_from_node && node::is_unreachable(config) => read(false),
+        res => res,
+    }
+}
+
+/// The balance summary with the spendable amounts at `minimum_confirmations`, and the amount
+/// at `display_confirmations` as "amount_confirmed", with both thresholds
+fn balance_info<C>(
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    config: &MobileWalletCfg,
+    refresh_from_node: bool,
+) -> Result<(bool, serde_json::Value), Error>
+where
+    C: NodeClient + 'static,
+{
+    let api = upstream::api(wallet);
+    let (validated, wallet_info) = local_read(config, refresh_from_node, |refresh| {
+        api.summary_info(refresh, config.minimum_confirmations)
+    })?;
+    let (_, display_info) = api.summary_info(false, config.display_confirmations)?;
+    if config.response_schema >= 1 {
+        let balance = dto::Balance::new(
+            &wallet_info,
+            &display_info,
+            config.display_confirmations,
+            validated,
+            refresh_from_node && !validated,
+        );
+        return Ok((validated, serde_json::to_value(&balance).unwrap()));
+    }
+    let mut info = serde_json::to_value(&wallet_info).unwrap();
+    if let Some(obj) = info.as_object_mut() {
+        obj.insert("stale".to_owned(), json!(refresh_from_node && !validated));
+        obj.insert(
+            "amount_confirmed".to_owned(),
+            json!(display_info.amount_currently_spendable),
+        );
+        obj.insert(
+            "minimum_confirmations".to_owned(),
+            json!(config.minimum_confirmations),
+        );
+        obj.insert(
+            "display_confirmations".to_owned(),
+            json!(config.display_confirmations),
+        );
+    }
+    Ok((validated, info))
+}
+
+fn get_balance(json_cfg: &str, account: Option<&str>) -> Result<(bool, String), Error> {
+    let key = coalesce::key("get_balance", (coalesce_cfg(json_cfg), account));
+    coalesce::run(key, coalesce::COALESCE_WINDOW, || {
+        let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+        let refresh_from_node = netstats::should_refresh(&config);
+        let wallet = get_wallet_instance(config.clone())?;
+        let (validated, info) = balance_info(wallet, &config, refresh_from_node)?;
+        Ok((validated, info.to_string()))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn grin_get_balance(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_get_balance");
+    let res = within("get_balance", &cstr_to_str(json_cfg), |json_cfg| {
+        get_balance(json_cfg, None)
+    });
+    unsafe { result2_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_get_balance_for_account(
     json_cfg: *const c_char,
-    tx_slate_id: *const c_char,
+    account: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
-    let res = tx_retrieve(&cstr_to_str(json_cfg), &cstr_to_str(tx_slate_id));
-    unsafe { result_to_cstr(res, error) }
+    let call = metrics::call("grin_get_balance_for_account");
+    let account = cstr_to_opt_str(account);
+    let res = within("get_balance", &cstr_to_str(json_cfg), move |json_cfg| {
+        get_balance(json_cfg, account.as_deref())
+    });
+    unsafe { result2_to_cstr(call, res, error) }
 }
 
-fn txs_retrieve(json_cfg: &str) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-
-    match api.retrieve_txs(true, None, None) {
-        Ok(txs) => Ok(serde_json::to_string(&txs).unwrap()),
-        Err(e) => Err(Error::from(e)),
+fn account_balances(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let refresh_from_node = netstats::should_refresh(&config);
+    let accounts = upstream::api(get_wallet_instance(config.clone())?).accounts()?;
+    let listeners = relay::listeners();
+    let mut balances = vec![];
+    for account in accounts {
+        let mut account_config = config.clone();
+        account_config.account = account.label.clone();
+        let wallet = get_wallet_instance(account_config.clone())?;
+        let (validated, info) = balance_info(wallet, &account_config, refresh_from_node)?;
+        let listener_addresses: Vec<&str> = listeners
+            .iter()
+            .filter(|l| l.account == account.label)
+            .map(|l| l.address.as_str())
+            .collect();
+        balances.push(json!({
+            "account": account.label,
+            "validated": validated,
+            "listenerAddresses": listener_addresses,
+            "balance": info,
+        }));
     }
+    Ok(serde_json::to_string(&balances).unwrap())
 }
 
+/// The balances of all the accounts, with the relay addresses of their running listeners
 #[no_mangle]
-pub extern "C" fn grin_txs_retrieve(state_json: *const c_char, error: *mut u8) -> *const c_char {
-    let res = txs_retrieve(&cstr_to_str(state_json));
-    unsafe { result_to_cstr(res, error) }
+pub extern "C" fn grin_account_balances(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_account_balances");
+    let res = account_balances(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn outputs_retrieve(json_cfg: &str, tx_id: Option<u32>) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let outputs = api.retrieve_outputs(true, true, tx_id)?;
-    Ok(serde_json::to_string(&outputs).unwrap())
+fn refresh(json_cfg: &str, account: Option<&str>) -> Result<(bool, String), Error> {
+    let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+    netstats::refreshed(&config);
+    let wallet = get_wallet_instance(config.clone())?;
+    let (validated, info) = balance_info(wallet, &config, true)?;
+    Ok((validated, info.to_string()))
 }
 
+/// Refresh from the node whatever the refresh policy, i.e. on a pull-to-refresh, and return
+/// the balance of an optional account
 #[no_mangle]
-pub extern "C" fn grin_output_retrieve(
+pub extern "C" fn grin_refresh(
     json_cfg: *const c_char,
-    tx_id: u32,
+    account: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
-    let res = outputs_retrieve(&cstr_to_str(json_cfg), Some(tx_id));
-    unsafe { result_to_cstr(res, error) }
+    let call = metrics::call("grin_refresh");
+    let account = cstr_to_opt_str(account);
+    let res = within("refresh", &cstr_to_str(json_cfg), move |json_cfg| {
+        refresh(json_cfg, account.as_deref())
+    });
+    unsafe { result2_to_cstr(call, res, error) }
 }
 
-#[no_mangle]
-pub extern "C" fn grin_outputs_retrieve(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
-    let res = outputs_retrieve(&cstr_to_str(json_cfg), None);
-    unsafe { result_to_cstr(res, error) }
+/// The json of a tx retrieval, with the senders and labels of the txs, in the response schema
+/// of the config
+fn txs_json(config: &MobileWalletCfg, txs: &(bool, Vec<TxLogEntry>)) -> String {
+    let mut annotated = serde_json::to_value(txs).unwrap();
+    // A failure to read the senders or labels only loses the attribution
+    let _ = senders::annotate(config, &mut annotated);
+    let _ = labels::annotate(config, &mut annotated);
+    if config.response_schema >= 1 {
+        let list = dto::TxList::new(txs.0, &txs.1, &annotated);
+        return serde_json::to_string(&list).unwrap();
+    }
+    serde_json::to_string(&annotated).unwrap()
 }
 
-fn init_send_tx(
-    json_cfg: &str,
-    amount: u64,
-    selection_strategy: &str,
-    target_slate_version: Option<u16>,
-    message: &str,
-) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let tx_args = InitTxArgs {
-        src_acct_name: None,
-        amount,
-        minimum_confirmations: SENDING_MINIMUM_CONFIRMATIONS,
-        max_outputs: 500,
-        num_change_outputs: 1,
-        selection_strategy: selection_strategy.to_string(),
-        message: Some(message.to_string()),
-        target_slate_version,
-        estimate_only: None,
-        send_args: None,
-    };
-    let slate = api.init_send_tx(tx_args)?;
-    api.tx_lock_outputs(&slate, 0)?;
-    Ok(serde_json::to_string(&slate).expect("fail to serialize slate to json string"))
+fn tx_retrieve(json_cfg: &str, account: Option<&str>, tx_slate_id: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+    let refresh_from_node = netstats::should_refresh(&config);
+    let wallet = get_wallet_instance(config.clone())?;
+    let api = upstream::api(wallet);
+    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
+    let txs = local_read(&config, refresh_from_node, |refresh| {
+        api.txs(refresh, None, Some(uuid))
+    })?;
+    Ok(txs_json(&config, &txs))
 }
 
 #[no_mangle]
-pub extern "C" fn grin_init_tx(
+pub extern "C" fn grin_tx_retrieve(
     json_cfg: *const c_char,
-    amount: u64,
-    selection_strategy: *const c_char,
-    target_slate_version: i16,
-    message: *const c_char,
+    tx_slate_id: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
-    let mut slate_version: Option<u16> = None;
-    if target_slate_version >= 0 {
-        slate_version = Some(target_slate_version as u16);
-    }
+    let call = metrics::call("grin_tx_retrieve");
+    let res = tx_retrieve(&cstr_to_str(json_cfg), None, &cstr_to_str(tx_slate_id));
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-    let res = init_send_tx(
+#[no_mangle]
+pub extern "C" fn grin_tx_retrieve_for_account(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    tx_slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_retrieve_for_account");
+    let account = cstr_to_opt_str(account);
+    let res = tx_retrieve(
         &cstr_to_str(json_cfg),
-        amount,
-        &cstr_to_str(selection_strategy),
-        slate_version,
-        &cstr_to_str(message),
+        account.as_deref(),
+        &cstr_to_str(tx_slate_id),
     );
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn listen(json_cfg: &str) -> Result<String, Error> {
-    let config = MobileWalletCfg::from_str(json_cfg)?;
-    let wallet = get_wallet_instance(config.clone())?;
+fn txs_retrieve(json_cfg: &str, account: Option<&str>) -> Result<String, Error> {
+    let key = coalesce::key("txs_retrieve", (coalesce_cfg(json_cfg), account));
+    coalesce::run(key, coalesce::COALESCE_WINDOW, || {
+        let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+        let refresh_from_node = netstats::should_refresh(&config);
+        let wallet = get_wallet_instance(config.clone())?;
+        let api = upstream::api(wallet);
+        let txs = local_read(&config, refresh_from_node, |refresh| {
+            api.txs(refresh, None, None)
+        })?;
+        Ok(txs_json(&config, &txs))
+    })
+}
 
-    // The streaming channel between 'grinrelay_listener' and 'foreign_listener'
-    let (relay_tx_as_payee, relay_rx) = channel();
+#[no_mangle]
+pub extern "C" fn grin_txs_retrieve(state_json: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_txs_retrieve");
+    let res = txs_retrieve(&cstr_to_str(state_json), None);
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-    // Start a Grin Relay service firstly
-    let (grinrelay_key_path, grinrelay_listener) = grinrelay_listener(
-        wallet.clone(),
-        config.grinrelay_config.clone().unwrap_or_default(),
-        None,
-        Some(relay_tx_as_payee),
-        None,
-    )?;
+#[no_mangle]
+pub extern "C" fn grin_txs_retrieve_for_account(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_txs_retrieve_for_account");
+    let account = cstr_to_opt_str(account);
+    let res = txs_retrieve(&cstr_to_str(json_cfg), account.as_deref());
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-    let _handle = thread::spawn(move || {
-        let api = Foreign::new(wallet, None);
-        loop {
-            match relay_rx.try_recv() {
-                Ok((addr, slate)) => {
-                    let _slate_id = slate.id;
-                    if api.verify_slate_messages(&slate).is_ok() {
-                        let slate_rx = api.receive_tx(
-                            &slate,
-                            Some(&config.account),
-                            None,
-                            Some(grinrelay_key_path),
-                        );
-                        if let Ok(slate_rx) = slate_rx {
-                            let versioned_slate =
-                                VersionedSlate::into_version(slate_rx.clone(), SlateVersion::V2);
-                            let res =
-                                grinrelay_listener.publish(&versioned_slate, &addr.to_owned());
-                            match res {
-                                Ok(_) => {
-                                    //                                    info!(
-                                    //                                        "Slate [{}] sent back to {} successfully",
-                                    //                                        slate_id.to_string().bright_green(),
-                                    //                                        addr.bright_green(),
-                                    //                                    );
-                                }
-                                Err(_e) => {
-                                    //                                    error!(
-                                    //                                        "Slate [{}] fail to sent back to {} for {}",
-                                    //                                        slate_id.to_string().bright_green(),
-                                    //                                        addr.bright_green(),
-                                    //                                        e,
-                                    //                                    );
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(TryRecvError::Disconnected) => break,
-                Err(TryRecvError::Empty) => {}
-            }
-            thread::sleep(Duration::from_millis(100));
-        }
-    });
+/// Like `grin_txs_retrieve_for_account`, as a buffer for the wallets with many txs
+#[no_mangle]
+pub extern "C" fn grin_txs_retrieve_buffer(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    error: *mut u8,
+) -> GrinBuffer {
+    let call = metrics::call("grin_txs_retrieve_buffer");
+    let account = cstr_to_opt_str(account);
+    let res = txs_retrieve(&cstr_to_str(json_cfg), account.as_deref());
+    unsafe { result_to_buffer(call, res, error) }
+}
 
-    //    if handle.is_err() {
-    //        Err(ErrorKind::GenericError("Listen thread fail to start".to_string()).into())?
-    //    }
+fn tx_label_set(json_cfg: &str, tx_slate_id: &str, label: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::ArgumentError(e.to_string()))?;
+    labels::set(&config, tx_slate_id, label)?;
     Ok("OK".to_owned())
 }
 
+/// Set the label of a tx, removed if empty. The retrieved txs have the `label` of their slate.
 #[no_mangle]
-pub extern "C" fn grin_listen(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
-    let res = listen(&cstr_to_str(json_cfg));
-    unsafe { result_to_cstr(res, error) }
+pub extern "C" fn grin_tx_label_set(
+    json_cfg: *const c_char,
+    tx_slate_id: *const c_char,
+    label: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_label_set");
+    let res = tx_label_set(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(tx_slate_id),
+        &cstr_to_str(label),
+    );
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn my_relay_addr(json_cfg: &str) -> Result<String, Error> {
-    let config = MobileWalletCfg::from_str(json_cfg)?;
-    let wallet = get_wallet_instance(config.clone())?;
-    Ok(grinrelay_address(
-        wallet.clone(),
-        config.grinrelay_config.clone().unwrap_or_default(),
-    )?)
+fn balance_history(
+    json_cfg: &str,
+    account: Option<&str>,
+    granularity: &str,
+) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+    let points = ledger::history(config, ledger::Granularity::parse(granularity)?)?;
+    Ok(serde_json::to_string(&points).unwrap())
 }
 
+/// The balance of an optional account at the end of each "day" or "week" (UTC, from Monday),
+/// `[{"timestamp", "balance", "change"}]`, from the first confirmed tx to now. The txs are
+/// dated by the height of their outputs when known.
 #[no_mangle]
-pub extern "C" fn my_grin_relay_addr(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
-    let res = my_relay_addr(&cstr_to_str(json_cfg));
-    unsafe { result_to_cstr(res, error) }
+pub extern "C" fn grin_balance_history(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    granularity: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_balance_history");
+    let account = cstr_to_opt_str(account);
+    let res = balance_history(
+        &cstr_to_str(json_cfg),
+        account.as_deref(),
+        &cstr_to_str(granularity),
+    );
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn relay_addr_query(json_cfg: &str, six_code_suffix: &str) -> Result<String, Error> {
-    let mut is_valid_six_code = false;
-    if six_code_suffix.len() == 6 {
-        let re = Regex::new(r"[02-9ac-hj-np-z]{6}").unwrap();
-        let captures = re.captures(six_code_suffix);
-        if captures.is_some() {
-            is_valid_six_code = true;
-        }
-    }
-    if !is_valid_six_code {
-        return Err(ErrorKind::GenericError("invalid 6-code address".to_owned()).into());
-    }
+fn wallet_stats(json_cfg: &str, account: Option<&str>) -> Result<String, Error> {
+    let stats = ledger::stats(MobileWalletCfg::from_str_for_account(json_cfg, account)?)?;
+    Ok(serde_json::to_string(&stats).unwrap())
+}
 
-    let config = MobileWalletCfg::from_str(json_cfg)?;
-    let wallet = get_wallet_instance(config.clone())?;
+/// The lifetime totals of an optional account, `{"totalReceived", "totalMined", "totalSent",
+/// "totalFees", "txCounts", "unconfirmedCount"}`. The amounts are of the confirmed txs, the
+/// counts by tx log type of all of them.
+#[no_mangle]
+pub extern "C" fn grin_wallet_stats(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_wallet_stats");
+    let account = cstr_to_opt_str(account);
+    let res = wallet_stats(&cstr_to_str(json_cfg), account.as_deref());
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-    {
-        let (relay_addr_query_sender, relay_addr_query_rx) = channel();
+fn txs_iter_open(json_cfg: &str, account: Option<&str>) -> Result<String, Error> {
+    let handle = iter::open(MobileWalletCfg::from_str_for_account(json_cfg, account)?)?;
+    Ok(handle.to_string())
+}
 
-        // Start a Grin Relay service firstly
-        let (_key_path, listener) = grinrelay_listener(
-            wallet.clone(),
-            config.grinrelay_config.clone().unwrap_or_default(),
-            None,
-            None,
-            Some(relay_addr_query_sender),
-        )?;
-
-        // Wait for connecting with relay service
-        let mut wait_time = 0;
-        while !listener.is_connected() {
-            thread::sleep(Duration::from_millis(100));
-            wait_time += 1;
-            if wait_time > 50 {
-                return Err(ErrorKind::GenericError(
-                    "Fail to connect with grin relay service, 5s timeout. please try again later"
-                        .to_owned(),
-                )
-                .into());
-            }
-        }
+/// Open an iterator over a snapshot of the txs of an optional account, returns its handle
+/// for `grin_txs_iter_next`. To be released with `grin_txs_iter_close`.
+#[no_mangle]
+pub extern "C" fn grin_txs_iter_open(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_txs_iter_open");
+    let account = cstr_to_opt_str(account);
+    let res = txs_iter_open(&cstr_to_str(json_cfg), account.as_deref());
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-        // Conversion the 6-code abbreviation address to the full address
-        {
-            let abbr = six_code_suffix.clone();
-            if listener.retrieve_relay_addr(abbr.to_string()).is_err() {
-                return Err(ErrorKind::GenericError(
-                    "Fail to send query request for abbreviated relay addr!".to_owned(),
-                )
-                .into());
-            }
+fn txs_iter_next(handle: u64, page_size: u32) -> Result<String, Error> {
+    Ok(iter::next(handle, page_size as usize)?.to_string())
+}
 
-            const TTL: u16 = 10;
-            let mut addresses: Option<Vec<String>> = None;
-            let mut cnt = 0;
-            loop {
-                match relay_addr_query_rx.try_recv() {
-                    Ok((_abbr, addrs)) => {
-                        if !addrs.is_empty() {
-                            addresses = Some(addrs);
-                        }
-                        break;
-                    }
-                    Err(TryRecvError::Disconnected) => break,
-                    Err(TryRecvError::Empty) => {}
-                }
-                cnt += 1;
-                if cnt > TTL * 10 {
-                    //                    info!(
-                    //                        "{} from relay server for address query. {}s timeout",
-                    //                        "No response".bright_blue(),
-                    //                        TTL
-                    //                    );
-                    return Err(ErrorKind::GenericError(
-                        "relay server no response, please try again later".to_owned(),
-                    )
-                    .into());
-                }
-                thread::sleep(Duration::from_millis(100));
-            }
+/// The next page of txs, `{"txs", "done"}`, at most 500 per page
+#[no_mangle]
+pub extern "C" fn grin_txs_iter_next(handle: u64, page_size: u32, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_txs_iter_next");
+    let res = txs_iter_next(handle, page_size);
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-            if let Some(addresses) = addresses {
-                match addresses.len() {
-                    0 => {
-                        return Err(ErrorKind::ArgumentError(
-                            "wrong address, or destination is offline".to_owned(),
-                        )
-                        .into());
-                    }
-                    1 => {
-                        let dest = addresses.first().unwrap().clone();
-                        return Ok(dest);
-                    }
-                    _ => {
-                        //                        warn!(
-                        //                            "{} addresses matched the same abbreviation address: {:?}",
-                        //                            addresses.len(),
-                        //                            addresses,
-                        //                        );
-                        return Err(ErrorKind::ArgumentError(
-                            "address conflict, multiple matched addresses found".to_owned(),
-                        )
-                        .into());
-                    }
-                }
-            } else {
-                return Err(ErrorKind::ArgumentError(
-                    "wrong address, or destination is offline".to_owned(),
-                )
-                .into());
-            }
-        }
-    }
+#[no_mangle]
+pub extern "C" fn grin_txs_iter_close(handle: u64) {
+    iter::close(handle);
+}
+
+fn outputs_retrieve(
+    json_cfg: &str,
+    account: Option<&str>,
+    tx_id: Option<u32>,
+) -> Result<String, Error> {
+    let key = coalesce::key("outputs_retrieve", (coalesce_cfg(json_cfg), account, tx_id));
+    coalesce::run(key, coalesce::COALESCE_WINDOW, || {
+        let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+        let refresh_from_node = netstats::should_refresh(&config);
+        let wallet = get_wallet_instance(config.clone())?;
+        let api = upstream::api(wallet);
+        let outputs = local_read(&config, refresh_from_node, |refresh| {
+            api.outputs(true, refresh, tx_id)
+        })?;
+        Ok(serde_json::to_string(&outputs).unwrap())
+    })
 }
 
 #[no_mangle]
-pub extern "C" fn grin_relay_addr_query(
+pub extern "C" fn grin_output_retrieve(
     json_cfg: *const c_char,
-    six_code_suffix: *const c_char,
+    tx_id: u32,
     error: *mut u8,
 ) -> *const c_char {
-    let res = relay_addr_query(&cstr_to_str(json_cfg), &cstr_to_str(six_code_suffix));
-    unsafe { result_to_cstr(res, error) }
+    let call = metrics::call("grin_output_retrieve");
+    let res = outputs_retrieve(&cstr_to_str(json_cfg), None, Some(tx_id));
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn send_tx_by_http(
+#[no_mangle]
+pub extern "C" fn grin_outputs_retrieve(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_outputs_retrieve");
+    let res = outputs_retrieve(&cstr_to_str(json_cfg), None, None);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_outputs_retrieve_for_account(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_outputs_retrieve_for_account");
+    let account = cstr_to_opt_str(account);
+    let res = outputs_retrieve(&cstr_to_str(json_cfg), account.as_deref(), None);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Like `grin_outputs_retrieve_for_account`, as a buffer for the wallets with many outputs
+#[no_mangle]
+pub extern "C" fn grin_outputs_retrieve_buffer(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    error: *mut u8,
+) -> GrinBuffer {
+    let call = metrics::call("grin_outputs_retrieve_buffer");
+    let account = cstr_to_opt_str(account);
+    let res = outputs_retrieve(&cstr_to_str(json_cfg), account.as_deref(), None);
+    unsafe { result_to_buffer(call, res, error) }
+}
+
+fn init_send_tx(
     json_cfg: &str,
     amount: u64,
-    receiver_wallet_url: &str,
     selection_strategy: &str,
     target_slate_version: Option<u16>,
     message: &str,
 ) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let args = InitTxArgs {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "init tx")?;
+    slates::check_target(target_slate_version)?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let api = upstream::api(wallet.clone());
+    let tx_args = InitTxArgs {
         src_acct_name: None,
         amount,
         minimum_confirmations: SENDING_MINIMUM_CONFIRMATIONS,
@@ -739,279 +2180,3254 @@ fn send_tx_by_http(
         estimate_only: None,
         send_args: None,
     };
-    let slate_r1 = api.init_send_tx(args)?;
-
-    let adapter = HTTPWalletCommAdapter::new();
-    let (slate, _tx_proof) = adapter.send_tx_sync(receiver_wallet_url, &slate_r1)?;
-    api.verify_slate_messages(&slate)?;
-    api.tx_lock_outputs(&slate_r1, 0)?;
+    let slate = init_send_slate(&config, wallet, tx_args)?;
+    api.lock_outputs(&slate, 0)?;
+    audit::record(
+        &config,
+        "send_initiated",
+        json!({ "slateId": slate.id.to_string(), "amount": amount, "channel": "file" }),
+    );
+    Ok(slates::reply_json(slate))
+}
 
-    let finalized_slate = api.finalize_tx(&slate, None, None);
-    if finalized_slate.is_err() {
-        api.cancel_tx(None, Some(slate_r1.id))?;
+#[no_mangle]
+pub extern "C" fn grin_init_tx(
+    json_cfg: *const c_char,
+    amount: u64,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_init_tx");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
     }
-    let finalized_slate = finalized_slate?;
 
-    let res = api.post_tx(Some(finalized_slate.id), &finalized_slate.tx, true);
-    match res {
-        Ok(_) => {
-            //info!("Tx sent ok",);
-            return Ok(serde_json::to_string(&finalized_slate).expect("fail to serialize slate to json string"));
-        }
-        Err(e) => {
-            // re-post last unconfirmed txs and try again
-            if let Ok(true) = api.repost_last_txs(true, false) {
-                // iff one re-post success, post this transaction again
-                if let Ok(_) = api.post_tx(Some(finalized_slate.id), &finalized_slate.tx, true) {
-                    //info!("Tx sent ok (with last unconfirmed tx/s re-post)");
-                    return Ok(serde_json::to_string(&finalized_slate).expect("fail to serialize slate to json string"));
-                }
-            }
+    let res = init_send_tx(
+        &cstr_to_str(json_cfg),
+        amount,
+        &cstr_to_str(selection_strategy),
+        slate_version,
+        &cstr_to_str(message),
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-            //error!("Tx sent fail on post.");
-            let _ = api.cancel_tx(None, Some(finalized_slate.id));
-            return Err(ErrorKind::GenericError(e.to_string()).into());
-        }
+/// Receive a slate into the config account, split into the `receive_outputs` of the config.
+/// A split receive is done by the lib, without the `key_path` of the foreign api receive.
+fn receive_slate<C>(
+    config: &MobileWalletCfg,
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    slate: &Slate,
+    message: Option<String>,
+    key_path: Option<ExtKeychainPath>,
+) -> Result<Slate, Error>
+where
+    C: NodeClient + 'static,
+{
+    let message = message::clean(message.as_deref(), config.strict_messages)?;
+    let outputs = split::receive_outputs(
+        slate,
+        config.receive_outputs as usize,
+        config.dust_threshold,
+    );
+    let res = if outputs > 1 {
+        split::receive_into_outputs(wallet, slate, &config.account, message, outputs)
+    } else {
+        upstream::api(wallet).receive(slate, &config.account, message, key_path)
+    };
+    coalesce::invalidate();
+    res
+}
+
+/// Receive an incoming relay slate into the configured account, and send it back to the sender.
+/// The slate is remembered as received once sent back, the tx cancelled if it can't be.
+fn receive_relay_slate<C, F>(
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    config: &MobileWalletCfg,
+    slate: &Slate,
+    sender: &str,
+    listener_address: &str,
+    key_path: ExtKeychainPath,
+    publish: F,
+) -> Result<(), String>
+where
+    C: NodeClient + 'static,
+    F: Fn(&VersionedSlate, &str) -> Result<(), String>,
+{
+    let api = upstream::api(wallet.clone());
+    let slate_id = slate.id.to_string();
+    let message = receive::message_for(config, sender, slate.amount);
+    let slate_rx = api
+        .verify_messages(slate)
+        .and_then(|_| receive_slate(config, wallet.clone(), slate, message, Some(key_path)))
+        .map_err(|e| format!("fail to receive: {}", e))?;
+    netstats::record(
+        "relay.receive_slate",
+        slate_json_size(&slate_rx),
+        slate_json_size(slate),
+    );
+    let versioned_slate = slates::reply(slate_rx);
+    if let Err(e) = publish(&versioned_slate, sender) {
+        // Not sent back, the tx is cancelled so a new delivery of the slate is received again
+        let _ = upstream::api(wallet).cancel(None, Some(slate.id));
+        return Err(format!("fail to send back: {}", e));
     }
+    let _ = receive::mark_received(config, &slate_id);
+    receive::record_incoming(config, slate, sender, listener_address);
+    let _ = senders::record(config, &slate_id, sender, slate.amount);
+    let _ = webhook::notify_received(config, &slate_id, slate.amount, sender);
+    let _ = pos::match_incoming(config, slate, sender);
+    Ok(())
 }
 
-fn send_tx_by_relay(
-    json_cfg: &str,
-    amount: u64,
-    receiver_addr: &str,
-    selection_strategy: &str,
-    target_slate_version: Option<u16>,
-    message: &str,
-) -> Result<String, Error> {
-    let config = MobileWalletCfg::from_str(json_cfg)?;
+fn listen(json_cfg: &str, account: Option<&str>, key_index: Option<u32>) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
     let wallet = get_wallet_instance(config.clone())?;
-    let api = Owner::new(wallet.clone());
-    let args = InitTxArgs {
-        src_acct_name: None,
-        amount,
-        minimum_confirmations: SENDING_MINIMUM_CONFIRMATIONS,
-        max_outputs: 500,
-        num_change_outputs: 1,
-        selection_strategy: selection_strategy.to_string(),
-        message: Some(message.to_string()),
-        target_slate_version,
-        estimate_only: None,
-        send_args: None,
+    let relay_config = match key_index {
+        Some(index) => relay_config_at(&config, index),
+        None => relay_config(&config)?,
     };
-    let slate_r1 = api.init_send_tx(args)?;
+    let key_index = relay_config.grinrelay_key_path_index.unwrap_or_default();
 
-    // The streaming channel between 'grinrelay_listener' and 'GrinrelayWalletCommAdapter'
-    let (relay_tx_as_payer, relay_rx) = channel();
+    // One listener per relay address, the slates sent to it are received into its account
+    let relay_addr = grinrelay_address(wallet.clone(), relay_config.clone())?;
+    let wallet_dir = wallet_data_dir(&config)?;
+    let listener_id =
+        relay::register_listener(&relay_addr, &config.account, key_index, &wallet_dir)?;
+
+    // The streaming channel between 'grinrelay_listener' and 'foreign_listener'
+    let (relay_tx_as_payee, relay_rx) = channel();
+    let wake_tx = relay_tx_as_payee.clone();
+
+    // The address query channel, for the 6-code queries reusing this listener
+    let (relay_addr_query_sender, relay_addr_query_rx) = channel();
 
     // Start a Grin Relay service firstly
-    let (grinrelay_key_path, grinrelay_listener) = grinrelay_listener(
+    let (grinrelay_key_path, grinrelay_listener) = match grinrelay_listener(
         wallet.clone(),
-        config.grinrelay_config.clone().unwrap_or_default(),
-        Some(relay_tx_as_payer),
-        None,
+        relay_config,
         None,
-    )?;
-    // Wait for connecting with relay service
-    let mut wait_time = 0;
-    while !grinrelay_listener.is_connected() {
-        thread::sleep(Duration::from_millis(100));
-        wait_time += 1;
-        if wait_time > 50 {
-            return Err(ErrorKind::GenericError(
-                "Fail to connect with grin relay service, 5s timeout. please try again later"
-                    .to_owned(),
-            )
-                .into());
+        Some(relay_tx_as_payee),
+        Some(relay_addr_query_sender),
+    ) {
+        Ok(listener) => listener,
+        Err(e) => {
+            relay::unregister_listener_id(&relay_addr, &listener_id);
+            return Err(e.into());
         }
+    };
+    let grinrelay_listener = Arc::new(grinrelay_listener);
+    {
+        let retrieve_listener = grinrelay_listener.clone();
+        let connected_listener = grinrelay_listener.clone();
+        relay::set_query_handle(
+            &relay_addr,
+            &wallet_dir,
+            relay::RelayQueryHandle {
+                retrieve: Box::new(move |abbr| retrieve_listener.retrieve_relay_addr(abbr).is_ok()),
+                is_connected: Box::new(move || connected_listener.is_connected()),
+                responses: relay_addr_query_rx,
+                chain_type: config.chain_type.clone(),
+            },
+        );
     }
 
-    let adapter = GrinrelayWalletCommAdapter::new(grinrelay_listener, relay_rx);
-    let (slate, tx_proof) = adapter.send_tx_sync(receiver_addr, &slate_r1.clone())?;
-    api.verify_slate_messages(&slate)?;
-    api.tx_lock_outputs(&slate_r1, 0)?;
-
-    let finalized_slate = api.finalize_tx(&slate, tx_proof, Some(grinrelay_key_path));
-    if finalized_slate.is_err() {
-        api.cancel_tx(None, Some(slate_r1.id))?;
+    {
+        let publish_listener = grinrelay_listener.clone();
+        let connected_listener = grinrelay_listener.clone();
+        let stop_listener = grinrelay_listener.clone();
+        relay::set_listener_handle(
+            &relay_addr,
+            relay::ListenerHandle {
+                key_path: grinrelay_key_path,
+                publish: Box::new(move |slate, dest| {
+                    publish_listener
+                        .publish(slate, &dest.to_owned())
+                        .map_err(|e| e.to_string())
+                }),
+                is_connected: Box::new(move || connected_listener.is_connected()),
+                stop: Box::new(move || {
+                    let _ = stop_listener.stop();
+                    // No relay sender address, the wake-up of the listener thread
+                    let _ = wake_tx.send((String::new(), Slate::blank(2)));
+                }),
+            },
+        );
     }
-    let finalized_slate = finalized_slate?;
 
-    let res = api.post_tx(Some(finalized_slate.id), &finalized_slate.tx, true);
-    match res {
-        Ok(_) => {
-            //info!("Tx sent ok",);
-            return Ok(serde_json::to_string(&finalized_slate).expect("fail to serialize slate to json string"));
-        }
-        Err(e) => {
-            // re-post last unconfirmed txs and try again
-            if let Ok(true) = api.repost_last_txs(true, false) {
-                // iff one re-post success, post this transaction again
-                if let Ok(_) = api.post_tx(Some(finalized_slate.id), &finalized_slate.tx, true) {
-                    //info!("Tx sent ok (with last unconfirmed tx/s re-post)");
-                    return Ok(serde_json::to_string(&finalized_slate).expect("fail to serialize slate to json string"));
+    let thread_name = format!("listener {}", relay_addr);
+    let unregister_addr = relay_addr.clone();
+    let unregister_id = listener_id.clone();
+    let res = services::spawn_for(&wallet_dir, &thread_name, move || {
+        // Waits for the slates, and ends once the relay channel is disconnected, or the
+        // listener is stopped or replaced by a restarted one. A slate received meanwhile is
+        // still processed, replied through the listener now on the address.
+        while let Ok((addr, slate)) = relay_rx.recv() {
+            if addr.is_empty() {
+                if relay::is_current_listener(&relay_addr, &listener_id) {
+                    continue;
                 }
+                break;
+            }
+            let slate_id = slate.id.to_string();
+            let account = match relay::listener_account(&relay_addr) {
+                Some(account) => account,
+                None => {
+                    receive::record_failure(
+                        &config,
+                        &slate_id,
+                        &addr,
+                        "listener stopped before the slate was received",
+                    );
+                    continue;
+                }
+            };
+            let mut config = config.clone();
+            config.account = account;
+            // An own send signed by its recipient, to finalize rather than receive
+            if returned::is_own_send(wallet.clone(), &slate) {
+                if let Err(e) = returned::handle(wallet.clone(), &config, &slate, &addr) {
+                    receive::record_failure(&config, &slate_id, &addr, &e.to_string());
+                }
+                continue;
+            }
+            // A slate delivered twice is only received once
+            if receive::is_received(&config, &slate_id).unwrap_or(false) {
+                receive::duplicate_ignored(&slate_id, &addr);
+                continue;
+            }
+            let policy_reason = config
+                .receive_policy
+                .as_ref()
+                .and_then(|policy| policy.approval_reason(&addr, slate.amount));
+            let res = if config.receive_approval || policy_reason.is_some() {
+                upstream::api(wallet.clone())
+                    .verify_messages(&slate)
+                    .map_err(|e| format!("fail to receive: {}", e))
+                    .and_then(|_| {
+                        receive::park(&config, &slate, &addr, &relay_addr, policy_reason)
+                            .map_err(|e| format!("fail to park: {}", e))
+                    })
+            } else {
+                receive_relay_slate(
+                    wallet.clone(),
+                    &config,
+                    &slate,
+                    &addr,
+                    &relay_addr,
+                    grinrelay_key_path,
+                    |slate, dest| {
+                        relay::with_listener_handle(&relay_addr, |handle| {
+                            (handle.publish)(slate, dest)
+                        })
+                        .unwrap_or_else(|| Err("listener stopped".to_owned()))
+                    },
+                )
+            };
+            if let Err(e) = res {
+                receive::record_failure(&config, &slate_id, &addr, &e);
             }
-
-            //error!("Tx sent fail on post.");
-            let _ = api.cancel_tx(None, Some(finalized_slate.id));
-            return Err(ErrorKind::GenericError(e.to_string()).into());
         }
+        relay::unregister_listener_id(&relay_addr, &listener_id);
+    });
+    if let Err(e) = res {
+        relay::unregister_listener_id(&unregister_addr, &unregister_id);
+        return Err(e);
     }
+    Ok("OK".to_owned())
 }
 
 #[no_mangle]
-pub extern "C" fn grin_send_tx(
+pub extern "C" fn grin_listen(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_listen");
+    let res = listen(&cstr_to_str(json_cfg), None, None);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Listen on the relay address of a specific derivation index, i.e. a previously used address.
+#[no_mangle]
+pub extern "C" fn grin_listen_at(
     json_cfg: *const c_char,
-    amount: u64,
-    receiver_addr_or_url: *const c_char,
-    selection_strategy: *const c_char,
-    target_slate_version: i16,
-    message: *const c_char,
+    key_index: u32,
     error: *mut u8,
 ) -> *const c_char {
-    let mut slate_version: Option<u16> = None;
-    if target_slate_version >= 0 {
-        slate_version = Some(target_slate_version as u16);
-    }
+    let call = metrics::call("grin_listen_at");
+    let res = listen(&cstr_to_str(json_cfg), None, Some(key_index));
+    unsafe { result_to_cstr(call, res, error) }
+}
 
-    let receiver = &cstr_to_str(receiver_addr_or_url);
-    let res = if receiver.starts_with("http://") || receiver.starts_with("https://") {
-        send_tx_by_http(
-            &cstr_to_str(json_cfg),
-            amount,
-            receiver,
-            &cstr_to_str(selection_strategy),
-            slate_version,
-            &cstr_to_str(message),
-        )
+/// Listen for an account, on the relay address of a derivation index, or on the current
+/// address if `key_index` is negative. Listeners of several accounts can run simultaneously,
+/// on distinct relay addresses.
+#[no_mangle]
+pub extern "C" fn grin_listen_for_account(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    key_index: i64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_listen_for_account");
+    let key_index = if key_index >= 0 {
+        Some(key_index as u32)
     } else {
-        send_tx_by_relay(
-            &cstr_to_str(json_cfg),
-            amount,
-            receiver,
-            &cstr_to_str(selection_strategy),
-            slate_version,
-            &cstr_to_str(message),
-        )
+        None
     };
-    unsafe { result_to_cstr(res, error) }
+    let account = cstr_to_opt_str(account);
+    let res = listen(&cstr_to_str(json_cfg), account.as_deref(), key_index);
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn cancel_tx(json_cfg: &str, tx_slate_id: &str) -> Result<String, Error> {
-    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    api.cancel_tx(None, Some(uuid))?;
-    Ok("OK".to_owned())
+fn receive_failures(json_cfg: &str) -> Result<String, Error> {
+    let failures = receive::failures(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok(serde_json::to_string(&failures).unwrap())
 }
 
+/// The incoming slates which the listeners failed to receive or send back, the latest last
 #[no_mangle]
-pub extern "C" fn grin_cancel_tx(
+pub extern "C" fn grin_receive_failures(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_receive_failures");
+    let res = receive_failures(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn receive_failures_clear(json_cfg: &str) -> Result<String, Error> {
+    receive::clear_failures(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok("OK".to_owned())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_receive_failures_clear(
+    json_cfg: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_receive_failures_clear");
+    let res = receive_failures_clear(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn pending_receives(json_cfg: &str) -> Result<String, Error> {
+    let pending = receive::pending(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok(serde_json::to_string(&pending).unwrap())
+}
+
+/// The incoming slates parked for approval, when `receive_approval` is configured
+#[no_mangle]
+pub extern "C" fn grin_pending_receives(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_pending_receives");
+    let res = pending_receives(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn pending_receive_accept(json_cfg: &str, slate_id: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let pending = receive::take_pending(&config, slate_id)?;
+    let mut receive_config = config.clone();
+    receive_config.account = pending.account.clone();
+
+    // The reply goes through the listener which got the slate, so it must be still running
+    let res = get_wallet_instance(receive_config.clone())
+        .map_err(|e| e.to_string())
+        .and_then(|wallet| {
+            relay::with_listener_handle(&pending.listener_address, |handle| {
+                receive_relay_slate(
+                    wallet,
+                    &receive_config,
+                    &pending.slate,
+                    &pending.sender,
+                    &pending.listener_address,
+                    handle.key_path,
+                    |slate, dest| (handle.publish)(slate, dest),
+                )
+            })
+            .unwrap_or_else(|| {
+                Err(format!(
+                    "listener of {} is not running",
+                    pending.listener_address
+                ))
+            })
+        });
+    match res {
+        Ok(_) => Ok("OK".to_owned()),
+        Err(e) => {
+            if !receive::is_received(&config, slate_id).unwrap_or(false) {
+                receive::restore_pending(&config, pending)?;
+            }
+            Err(ErrorKind::GenericError(e).into())
+        }
+    }
+}
+
+/// Receive a parked incoming slate and send it back to the sender
+#[no_mangle]
+pub extern "C" fn grin_pending_receive_accept(
+    json_cfg: *const c_char,
+    slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_pending_receive_accept");
+    let res = pending_receive_accept(&cstr_to_str(json_cfg), &cstr_to_str(slate_id));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn pending_receive_reject(json_cfg: &str, slate_id: &str) -> Result<String, Error> {
+    let pending = receive::take_pending(&MobileWalletCfg::from_str(json_cfg)?, slate_id)?;
+    events::emit(
+        "receive_rejected",
+        json!({ "slateId": pending.slate_id, "sender": pending.sender }),
+    );
+    Ok("OK".to_owned())
+}
+
+/// Drop a parked incoming slate, nothing enters the wallet and the sender times out
+#[no_mangle]
+pub extern "C" fn grin_pending_receive_reject(
+    json_cfg: *const c_char,
+    slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_pending_receive_reject");
+    let res = pending_receive_reject(&cstr_to_str(json_cfg), &cstr_to_str(slate_id));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn returned_slates(json_cfg: &str) -> Result<String, Error> {
+    let returned = returned::list(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok(serde_json::to_string(&returned).unwrap())
+}
+
+/// The own sends which came back signed to a relay listener, with their finalize and post
+/// state
+#[no_mangle]
+pub extern "C" fn grin_returned_slates(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_returned_slates");
+    let res = returned_slates(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn returned_slate_post(json_cfg: &str, slate_id: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let returned = returned::post(wallet, &config, slate_id)?;
+    Ok(serde_json::to_string(&returned).unwrap())
+}
+
+/// Finalize a returned slate if not yet and post its tx, or retry a failed post. A posted
+/// slate is refused.
+#[no_mangle]
+pub extern "C" fn grin_returned_slate_post(
+    json_cfg: *const c_char,
+    slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_returned_slate_post");
+    let res = returned_slate_post(&cstr_to_str(json_cfg), &cstr_to_str(slate_id));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Set the callback computing the receive message per sender, or clear it with NULL
+#[no_mangle]
+pub extern "C" fn grin_set_receive_message_callback(callback: Option<receive::MessageCallback>) {
+    receive::set_message_callback(callback);
+}
+
+fn known_senders(json_cfg: &str) -> Result<String, Error> {
+    let senders = senders::list(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok(serde_json::to_string(&senders).unwrap())
+}
+
+/// The relay addresses the incoming txs were sent from, `[{"address", "receivedCount",
+/// "receivedAmount", "firstReceivedAt", "lastReceivedAt", "contact"}]`, the latest first, for
+/// the contact suggestions. The retrieved txs have the `sender_address` of their slate.
+#[no_mangle]
+pub extern "C" fn grin_known_senders(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_known_senders");
+    let res = known_senders(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn listeners_list() -> Result<String, Error> {
+    Ok(serde_json::to_string(&relay::listeners()).unwrap())
+}
+
+/// The running listeners, with their relay addresses and accounts
+#[no_mangle]
+pub extern "C" fn grin_listeners_list(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_listeners_list");
+    let res = listeners_list();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn incoming_slates(json_cfg: &str, account: Option<&str>) -> Result<String, Error> {
+    let incoming = receive::incoming(&MobileWalletCfg::from_str(json_cfg)?, account)?;
+    Ok(serde_json::to_string(&incoming).unwrap())
+}
+
+/// The slates received by the listeners, each attributed to the account of its listener, of
+/// an account or of all of them if NULL, the latest last
+#[no_mangle]
+pub extern "C" fn grin_incoming_slates(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_incoming_slates");
+    let account = cstr_to_opt_str(account);
+    let res = incoming_slates(&cstr_to_str(json_cfg), account.as_deref());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn network_changed(json_cfg: &str) -> Result<String, Error> {
+    services::reannounce(json_cfg)?;
+    Ok("OK".to_owned())
+}
+
+/// Tell the lib the device network changed, i.e. Wi-Fi to cellular, so the running listeners
+/// re-announce their relay addresses. The slates sent meanwhile are kept by the relay service
+/// and delivered on the reconnection. Returns at once, the outcome is notified by the
+/// `listener_reconnected` and `listener_restarted` events.
+#[no_mangle]
+pub extern "C" fn grin_network_changed(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_network_changed");
+    let res = network_changed(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn my_relay_addr(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet = get_wallet_instance(config.clone())?;
+    Ok(grinrelay_address(wallet.clone(), relay_config(&config)?)?)
+}
+
+#[no_mangle]
+pub extern "C" fn my_grin_relay_addr(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("my_grin_relay_addr");
+    let res = my_relay_addr(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn pos_create_charge(
+    json_cfg: &str,
+    amount: u64,
+    memo: &str,
+    expiry_secs: u64,
+) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let address = my_relay_addr(json_cfg)?;
+    let charge = pos::create(&config, &address, amount, memo, expiry_secs)?;
+    Ok(serde_json::to_string(&charge).unwrap())
+}
+
+/// Create a point-of-sale charge, a payment request `{"id", "address", "amount", "memo",
+/// "status", "createdAt", "expiresAt", "slateId", "sender", "receivedAt", "paidAt"}` to the
+/// relay address of the config, expiring after `expiry_secs` (15 minutes if 0). The payment is
+/// matched by the running listener with a `charge_received` event, the payer putting the
+/// charge id in the slate message if several charges of the same amount are pending, and paid
+/// with a `charge_paid` event once the tx is confirmed.
+#[no_mangle]
+pub extern "C" fn grin_pos_create_charge(
+    json_cfg: *const c_char,
+    amount: u64,
+    memo: *const c_char,
+    expiry_secs: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_pos_create_charge");
+    let res = pos_create_charge(
+        &cstr_to_str(json_cfg),
+        amount,
+        &cstr_to_str(memo),
+        expiry_secs,
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn pos_charge_status(json_cfg: &str, charge_id: &str) -> Result<String, Error> {
+    let charge = pos::get(&MobileWalletCfg::from_str(json_cfg)?, charge_id)?;
+    Ok(serde_json::to_string(&charge).unwrap())
+}
+
+/// A charge of `grin_pos_create_charge`, with its status "pending", "paid" or "expired"
+#[no_mangle]
+pub extern "C" fn grin_pos_charge_status(
+    json_cfg: *const c_char,
+    charge_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_pos_charge_status");
+    let res = pos_charge_status(&cstr_to_str(json_cfg), &cstr_to_str(charge_id));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn new_address(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let book_path = relay_address_book_path(&config)?;
+    let _lock = RELAY_ADDRESS_BOOK_LOCK.lock();
+    let mut book: RelayAddressBook = store::load(&book_path)?;
+
+    // The address at index 0 is the one used before any rotation, record it firstly
+    if book.addresses.is_empty() {
+        book.addresses.push(RelayAddressEntry {
+            index: 0,
+            address: grinrelay_address(wallet.clone(), relay_config_at(&config, 0))?,
+            created_at: store::now_secs(),
+        });
+    }
+
+    let index = book.addresses.iter().map(|a| a.index).max().unwrap_or(0) + 1;
+    let address = grinrelay_address(wallet.clone(), relay_config_at(&config, index))?;
+    book.addresses.push(RelayAddressEntry {
+        index,
+        address: address.clone(),
+        created_at: store::now_secs(),
+    });
+    book.current_index = index;
+    store::save(&book_path, &book)?;
+
+    Ok(json!({
+        "index": index,
+        "address": address,
+    })
+    .to_string())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_new_address(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_new_address");
+    let res = new_address(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn addresses_list(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let book: RelayAddressBook = store::load(&relay_address_book_path(&config)?)?;
+    Ok(serde_json::to_string(&book).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_addresses_list(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_addresses_list");
+    let res = addresses_list(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn relay_addresses_derive(json_cfg: &str, start_index: u32, count: u32) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let book: RelayAddressBook = store::load(&relay_address_book_path(&config)?)?;
+
+    let mut addresses = vec![];
+    for index in start_index..start_index.saturating_add(count) {
+        let address = grinrelay_address(wallet.clone(), relay_config_at(&config, index))?;
+        let used = index == 0 || book.addresses.iter().any(|a| a.index == index);
+        addresses.push(json!({
+            "index": index,
+            "address": address,
+            "used": used,
+        }));
+    }
+    Ok(serde_json::to_string(&addresses).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_relay_addresses_derive(
+    json_cfg: *const c_char,
+    start_index: u32,
+    count: u32,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_relay_addresses_derive");
+    let res = relay_addresses_derive(&cstr_to_str(json_cfg), start_index, count);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Send an abbreviated address query and wait for the matched full addresses
+fn query_relay_addr(
+    retrieve: &dyn Fn(String) -> bool,
+    responses: &Receiver<(String, Vec<String>)>,
+    six_code_suffix: &str,
+    timeout_ms: u64,
+) -> Result<Vec<String>, Error> {
+    // Drop any late response of an earlier timed out query
+    while responses.try_recv().is_ok() {}
+
+    if !retrieve(six_code_suffix.to_owned()) {
+        return Err(ErrorKind::GenericError(
+            "Fail to send query request for abbreviated relay addr!".to_owned(),
+        )
+        .into());
+    }
+
+    match responses.recv_timeout(Duration::from_millis(timeout_ms)) {
+        Ok((_abbr, addrs)) => Ok(addrs),
+        Err(RecvTimeoutError::Disconnected) => Ok(vec![]),
+        Err(RecvTimeoutError::Timeout) => {
+            //            info!(
+            //                "{} from relay server for address query. {}ms timeout",
+            //                "No response".bright_blue(),
+            //                timeout_ms
+            //            );
+            Err(ErrorKind::GenericError(
+                "relay server no response, please try again later".to_owned(),
+            )
+            .into())
+        }
+    }
+}
+
+fn relay_addr_query(json_cfg: &str, six_code_suffix: &str) -> Result<String, Error> {
+    if !address::is_six_code(six_code_suffix) {
+        return Err(ErrorKind::GenericError("invalid 6-code address".to_owned()).into());
+    }
+
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    if let Some(dest) = relay::cached_addr(&config.chain_type, six_code_suffix) {
+        return Ok(dest);
+    }
+    let stored = addr_cache::get(&config, six_code_suffix)?;
+    if let Some(stored) = stored.as_ref().filter(|stored| !stored.needs_verify()) {
+        relay::cache_addr(&config.chain_type, six_code_suffix, &stored.address);
+        return Ok(stored.address.clone());
+    }
+
+    let dest = match resolve_six_code(&config, six_code_suffix) {
+        Ok(dest) => dest,
+        // The relay can't verify it now, the stored address is still the one of this 6-code
+        Err(_) if stored.is_some() => return Ok(stored.unwrap().address),
+        Err(e) => return Err(e),
+    };
+    addr_cache::confirm(&config, six_code_suffix, &dest)?;
+    relay::cache_addr(&config.chain_type, six_code_suffix, &dest);
+    Ok(dest)
+}
+
+/// Ask the relay service for the full address of a 6-code
+fn resolve_six_code(config: &MobileWalletCfg, six_code_suffix: &str) -> Result<String, Error> {
+    let addresses = match_six_code(config, six_code_suffix)?;
+
+    // Conversion the 6-code abbreviation address to the full address
+    match addresses.len() {
+        0 => Err(
+            ErrorKind::ArgumentError("wrong address, or destination is offline".to_owned()).into(),
+        ),
+        1 => Ok(addresses.first().unwrap().clone()),
+        _ => {
+            //            warn!(
+            //                "{} addresses matched the same abbreviation address: {:?}",
+            //                addresses.len(),
+            //                addresses,
+            //            );
+            Err(ErrorKind::ArgumentError(
+                "address conflict, multiple matched addresses found".to_owned(),
+            )
+            .into())
+        }
+    }
+}
+
+/// Ask the relay service for all the full addresses matching a 6-code
+fn match_six_code(config: &MobileWalletCfg, six_code_suffix: &str) -> Result<Vec<String>, Error> {
+    // Reuse a running listener of the wallet if any, otherwise start a Grin Relay service for
+    // this query
+    let wallet_dir = wallet_data_dir(config)?;
+    let reused = relay::with_query_handle(&wallet_dir, &config.chain_type, |handle| {
+        query_relay_addr(
+            &handle.retrieve,
+            &handle.responses,
+            six_code_suffix,
+            config.relay_response_timeout_ms,
+        )
+    });
+    let addresses = match reused {
+        Some(addresses) => addresses?,
+        None => {
+            let wallet = get_wallet_instance(config.clone())?;
+            let (relay_addr_query_sender, relay_addr_query_rx) = channel();
+            let (_key_path, listener) = grinrelay_listener(
+                wallet.clone(),
+                relay_config(config)?,
+                None,
+                None,
+                Some(relay_addr_query_sender),
+            )?;
+
+            // Wait for connecting with relay service
+            let listener = Arc::new(listener);
+            let connected_listener = listener.clone();
+            relay::wait_connected(
+                Box::new(move || connected_listener.is_connected()),
+                config.relay_connect_timeout_ms,
+            )?;
+
+            query_relay_addr(
+                &|abbr| listener.retrieve_relay_addr(abbr).is_ok(),
+                &relay_addr_query_rx,
+                six_code_suffix,
+                config.relay_response_timeout_ms,
+            )?
+        }
+    };
+    Ok(addresses)
+}
+
+#[no_mangle]
+pub extern "C" fn grin_relay_addr_query(
+    json_cfg: *const c_char,
+    six_code_suffix: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_relay_addr_query");
+    let six_code_suffix = cstr_to_str(six_code_suffix);
+    let res = within(
+        "relay_addr_query",
+        &cstr_to_str(json_cfg),
+        move |json_cfg| relay_addr_query(json_cfg, &six_code_suffix),
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn relay_addr_resolve(json_cfg: &str, six_code_suffix: &str) -> Result<String, Error> {
+    if !address::is_six_code(six_code_suffix) {
+        return Err(ErrorKind::GenericError("invalid 6-code address".to_owned()).into());
+    }
+
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let matched = match_six_code(&config, six_code_suffix)?;
+    let resolution = addr_cache::resolve(&config, six_code_suffix, matched)?;
+    if let Some(dest) = resolution.address.as_ref() {
+        addr_cache::confirm(&config, six_code_suffix, dest)?;
+        relay::cache_addr(&config.chain_type, six_code_suffix, dest);
+    }
+    Ok(serde_json::to_string(&resolution).unwrap())
+}
+
+/// Look up a 6-code with all its matched addresses, `{"sixCode", "status", "address",
+/// "candidates": [{"address", "firstSeenAt", "contact", "stored"}]}`, the status being
+/// "resolved", "conflict" or "not_found". On a conflict, the user picks one of the candidates
+/// to send with `grin_send_to_resolved`.
+#[no_mangle]
+pub extern "C" fn grin_relay_addr_resolve(
+    json_cfg: *const c_char,
+    six_code_suffix: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_relay_addr_resolve");
+    let six_code_suffix = cstr_to_str(six_code_suffix);
+    let res = within(
+        "relay_addr_resolve",
+        &cstr_to_str(json_cfg),
+        move |json_cfg| relay_addr_resolve(json_cfg, &six_code_suffix),
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn relay_addr_cache_list(json_cfg: &str) -> Result<String, Error> {
+    let addresses = addr_cache::list(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok(serde_json::to_string(&addresses).unwrap())
+}
+
+/// The 6-code addresses resolved by the relay, `[{"sixCode", "address", "firstResolvedAt",
+/// "lastVerifiedAt"}]`, the latest verified first. A 6-code keeps its first resolved address,
+/// a different one resolved later is refused as an address conflict.
+#[no_mangle]
+pub extern "C" fn grin_relay_addr_cache_list(
+    json_cfg: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_relay_addr_cache_list");
+    let res = relay_addr_cache_list(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn address_validate(input: &str) -> Result<String, Error> {
+    Ok(serde_json::to_string(&address::parse(input)).unwrap())
+}
+
+/// Classify a send destination, `{"type", "normalized", "chainType", "supported", "error"}`,
+/// the type being "http", "onion", "grinrelay", "six_code" or "invalid". The send calls take
+/// the normalized address.
+#[no_mangle]
+pub extern "C" fn grin_address_validate(input: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_address_validate");
+    let res = address_validate(&cstr_to_str(input));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn slate_message_validate(json_cfg: &str, message: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let message = message::clean(Some(message), config.strict_messages)?;
+    Ok(json!({ "message": message }).to_string())
+}
+
+/// Check a slate message as the sends and receives do, for the app to report an invalid one
+/// as it's typed. Returns `{"message"}` with the message as it goes into the slate, null if
+/// empty.
+#[no_mangle]
+pub extern "C" fn grin_slate_message_validate(
+    json_cfg: *const c_char,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_slate_message_validate");
+    let res = slate_message_validate(&cstr_to_str(json_cfg), &cstr_to_str(message));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn send_tx_args(
+    amount: u64,
+    selection_strategy: &str,
+    target_slate_version: Option<u16>,
+    message: &str,
+) -> InitTxArgs {
+    InitTxArgs {
+        src_acct_name: None,
+        amount,
+        minimum_confirmations: SENDING_MINIMUM_CONFIRMATIONS,
+        max_outputs: 500,
+        num_change_outputs: 1,
+        selection_strategy: selection_strategy.to_string(),
+        message: Some(message.to_string()),
+        target_slate_version,
+        estimate_only: None,
+        send_args: None,
+    }
+}
+
+/// The result of the slate exchange with the recipient: the returned slate, the tx proof
+/// if the transport provides one, and the relay key path for the proof signature.
+type SlateExchange = (Slate, Option<TxProof>, Option<ExtKeychainPath>);
+
+/// The outcome of a sent tx
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SendResult {
+    slate_id: String,
+    amount: u64,
+    fee: u64,
+    /// The delivery channel, "http", "relay", "self", or "local" for an in-process recipient
+    channel: &'static str,
+    /// Duration of the slate exchange with the recipient
+    delivery_ms: u64,
+    /// "posted", or "reposted" if posted after a re-post of the last unconfirmed txs
+    post_status: &'static str,
+    repost_attempts: u32,
+    fee_breakdown: fees::FeeBreakdown,
+    slate: Slate,
+}
+
+/// Init a send tx with the cleaned message, and the dust threshold of the config
+fn init_send_slate<C>(
+    config: &MobileWalletCfg,
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    mut args: InitTxArgs,
+) -> Result<Slate, Error>
+where
+    C: NodeClient + 'static,
+{
+    args.message = message::clean(args.message.as_deref(), config.strict_messages)?;
+    let api = upstream::api(wallet);
+    fees::init_send(
+        args,
+        config.dust_threshold,
+        config.donate_dust,
+        |args| api.init_send(args),
+        |slate| {
+            let (_, outputs) = api.outputs(false, false, None)?;
+            Ok(outputs
+                .iter()
+                .filter(|o| slate.tx.inputs().iter().any(|i| i.commit == o.commit))
+                .map(|o| o.output.value)
+                .sum())
+        },
+    )
+}
+
+fn slate_json_size(slate: &Slate) -> u64 {
+    serde_json::to_string(slate).map(|s| s.len()).unwrap_or(0) as u64
+}
+
+/// Init a send tx, exchange the slate with the recipient, then finalize and post the tx.
+/// With a `lock_height`, the tx can't be confirmed before that block height. A `confirmation`
+/// reason requires the extra confirmation of the send approval callback.
+/// The tx is cancelled if it's not approved by the app, or can't be finalized or posted.
+/// The progress of the send is notified by the `send_progress` events.
+fn send_tx<C, F>(
+    config: &MobileWalletCfg,
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    args: InitTxArgs,
+    lock_height: Option<u64>,
+    confirmation: Option<&str>,
+    channel: &'static str,
+    exchange: F,
+) -> Result<SendResult, Error>
+where
+    C: NodeClient + 'static,
+    F: FnOnce(&Slate) -> Result<SlateExchange, Error>,
+{
+    progress::set(config, progress::INITIALIZING, None);
+    let mut trace = trace::start("send");
+    trace.enter(trace::INIT);
+    let res = send_tx_phases(
+        config,
+        &mut trace,
+        wallet,
+        args,
+        lock_height,
+        confirmation,
+        channel,
+        exchange,
+    );
+    let state = match &res {
+        Ok(_) => progress::POSTED,
+        Err(e) if cancel::is_cancelled_error(e) => progress::CANCELLED,
+        Err(_) => progress::FAILED,
+    };
+    trace.finish(&res);
+    progress::set(config, state, None);
+    coalesce::invalidate();
+    res
+}
+
+fn send_tx_phases<C, F>(
+    config: &MobileWalletCfg,
+    trace: &mut trace::Trace,
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    args: InitTxArgs,
+    lock_height: Option<u64>,
+    confirmation: Option<&str>,
+    channel: &'static str,
+    exchange: F,
+) -> Result<SendResult, Error>
+where
+    C: NodeClient + 'static,
+    F: FnOnce(&Slate) -> Result<SlateExchange, Error>,
+{
+    runtime::check_spending("send")?;
+    slates::check_target(args.target_slate_version)?;
+    let cancel_token = config.cancel_token.as_deref();
+    let _token = cancel::guard(cancel_token);
+    cancel::check(cancel_token)?;
+    let api = upstream::api(wallet.clone());
+    if let Some(lock_height) = lock_height {
+        if lock_height <= api.node_height()?.0 {
+            return Err(ErrorKind::ArgumentError(format!(
+                "lock height {} is not above the chain height",
+                lock_height
+            ))
+            .into());
+        }
+    }
+    let mut slate_r1 = init_send_slate(config, wallet, args)?;
+    trace.set_slate_id(&slate_r1.id.to_string());
+    queue::initiated(config, &slate_r1.id.to_string());
+    // The kernel is height locked, both parties sign its features before the finalization
+    if let Some(lock_height) = lock_height {
+        slate_r1.lock_height = lock_height;
+    }
+
+    // Each phase is journaled, the entry is kept if the send is interrupted or its cleanup
+    // fails, so the tx gets recovered on the next start. Every other error finishes it.
+    let journal_id = journal::begin(
+        config,
+        journal::OP_SEND,
+        journal::PHASE_INITIATED,
+        Some(&slate_r1.id.to_string()),
+        json!({ "channel": channel }),
+    )?;
+    audit::record(
+        config,
+        "send_initiated",
+        json!({ "slateId": slate_r1.id.to_string(), "amount": slate_r1.amount, "channel": channel }),
+    );
+    let finish = |res: Result<SendResult, Error>| {
+        journal::finish(config, &journal_id);
+        if let Err(e) = &res {
+            if cancel::is_cancelled_error(e) {
+                events::emit(
+                    "send_cancelled",
+                    json!({ "slateId": slate_r1.id.to_string() }),
+                );
+            }
+        }
+        res
+    };
+
+    progress::set(
+        config,
+        progress::CONTACTING_RECIPIENT,
+        Some(slate_r1.id.to_string()),
+    );
+    trace.enter(trace::EXCHANGE);
+    let delivery_start = Instant::now();
+    let (slate, tx_proof, key_path) = match exchange(&slate_r1) {
+        Ok(exchanged) => exchanged,
+        Err(e) => return finish(Err(e)),
+    };
+    progress::set(config, progress::FINALIZING, None);
+    let delivery_ms = delivery_start.elapsed().as_millis() as u64;
+    netstats::record(
+        &format!("{}.send_slate", channel),
+        slate_json_size(&slate_r1),
+        slate_json_size(&slate),
+    );
+    if let Err(e) = api.verify_messages(&slate) {
+        return finish(Err(e));
+    }
+    // Nothing is locked yet, a cancelled send is just dropped
+    if let Err(e) = cancel::check(cancel_token) {
+        return finish(Err(e));
+    }
+    trace.enter(trace::LOCK);
+    if let Err(e) = api.lock_outputs(&slate_r1, 0) {
+        return finish(Err(e));
+    }
+    journal::advance(config, &journal_id, journal::PHASE_LOCKED);
+
+    // The cancel also unlocks the outputs, so nothing stays locked on a rejection
+    trace.enter(trace::APPROVAL);
+    if !approval::approve(&slate, confirmation) {
+        if let Err(e) = api.cancel(None, Some(slate_r1.id)) {
+            return Err(e);
+        }
+        return finish(Err(ErrorKind::GenericError(
+            "tx rejected by the send approval".to_owned(),
+        )
+        .into()));
+    }
+
+    trace.enter(trace::FINALIZE);
+    let finalized_slate =
+        cancel::check(cancel_token).and_then(|_| api.finalize(&slate, tx_proof, key_path));
+    if finalized_slate.is_err() {
+        if let Err(e) = api.cancel(None, Some(slate_r1.id)) {
+            return Err(e);
+        }
+    }
+    let finalized_slate = match finalized_slate {
+        Ok(finalized_slate) => finalized_slate,
+        Err(e) => return finish(Err(e)),
+    };
+    journal::advance(config, &journal_id, journal::PHASE_FINALIZED);
+    // The last chance to cancel, the tx isn't posted yet
+    if let Err(e) = cancel::check(cancel_token) {
+        if let Err(e) = api.cancel(None, Some(finalized_slate.id)) {
+            return Err(e);
+        }
+        return finish(Err(e));
+    }
+    // The outputs of the sender slate, before the recipient added its ones
+    let change_outputs = slate_r1.tx.outputs().len();
+    let result = |post_status, repost_attempts, slate: Slate| SendResult {
+        slate_id: slate.id.to_string(),
+        amount: slate.amount,
+        fee: slate.fee,
+        channel,
+        delivery_ms,
+        post_status,
+        repost_attempts,
+        fee_breakdown: fees::FeeBreakdown::of(&slate, change_outputs),
+        slate,
+    };
+
+    progress::set(config, progress::POSTING, None);
+    trace.enter(trace::POST);
+    let res = api.post(Some(finalized_slate.id), &finalized_slate.tx, true);
+    match res {
+        Ok(_) => {
+            //info!("Tx sent ok",);
+            finish(Ok(result("posted", 0, finalized_slate)))
+        }
+        Err(e) => {
+            // re-post last unconfirmed txs and try again
+            if let Ok(true) = api.repost_last(true, false) {
+                // iff one re-post success, post this transaction again
+                if let Ok(_) = api.post(Some(finalized_slate.id), &finalized_slate.tx, true) {
+                    //info!("Tx sent ok (with last unconfirmed tx/s re-post)");
+                    return finish(Ok(result("reposted", 1, finalized_slate)));
+                }
+            }
+
+            //error!("Tx sent fail on post.");
+            let err: Error = ErrorKind::GenericError(e.to_string()).into();
+            match api.cancel(None, Some(finalized_slate.id)) {
+                Ok(_) => finish(Err(err)),
+                Err(_) => Err(err),
+            }
+        }
+    }
+}
+
+fn is_http_receiver(receiver: &str) -> bool {
+    receiver.starts_with("http://") || receiver.starts_with("https://")
+}
+
+fn send_tx_by_http(
+    json_cfg: &str,
+    account: Option<&str>,
+    receiver_wallet_url: &str,
+    mut args: InitTxArgs,
+    lock_height: Option<u64>,
+) -> Result<SendResult, Error> {
+    let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+    check_spending(&config, "send")?;
+    let confirmation = contacts::apply_policy(&config, receiver_wallet_url, &mut args)?;
+    duplicate::check(&config, receiver_wallet_url, args.amount)?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let adapter = HTTPWalletCommAdapter::new();
+    let result = send_tx(
+        &config,
+        wallet,
+        args,
+        lock_height,
+        confirmation.as_deref(),
+        "http",
+        |slate_r1| {
+            progress::set(&config, progress::AWAITING_SIGNATURE, None);
+            let url = receiver_wallet_url.to_owned();
+            let slate_r1 = slate_r1.clone();
+            let (slate, _tx_proof) = cancel::wait(
+                config.cancel_token.as_deref(),
+                &wallet_data_dir(&config)?,
+                move || Ok(adapter.send_tx_sync(&url, &slate_r1)?),
+            )?;
+            // An http recipient has no address key to sign a payment proof with, only the
+            // relay path gets one
+            Ok((slate, None, None))
+        },
+    )?;
+    duplicate::record(
+        &config,
+        receiver_wallet_url,
+        result.amount,
+        &result.slate_id,
+    );
+    Ok(result)
+}
+
+fn send_tx_by_relay(
+    json_cfg: &str,
+    account: Option<&str>,
+    receiver_addr: &str,
+    mut args: InitTxArgs,
+    lock_height: Option<u64>,
+) -> Result<SendResult, Error> {
+    let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+    check_spending(&config, "send")?;
+    let confirmation = contacts::apply_policy(&config, receiver_addr, &mut args)?;
+    duplicate::check(&config, receiver_addr, args.amount)?;
+    let wallet = get_wallet_instance(config.clone())?;
+
+    let relay_wallet = wallet.clone();
+    let result = send_tx(
+        &config,
+        wallet,
+        args,
+        lock_height,
+        confirmation.as_deref(),
+        "relay",
+        |slate_r1| {
+            // Connecting the relay service is the contact with the recipient
+            let (grinrelay_key_path, adapter) = relay_send_adapter(&config, relay_wallet)?;
+            progress::set(&config, progress::AWAITING_SIGNATURE, None);
+            let addr = receiver_addr.to_owned();
+            let slate_r1 = slate_r1.clone();
+            let (slate, tx_proof) = cancel::wait(
+                config.cancel_token.as_deref(),
+                &wallet_data_dir(&config)?,
+                move || Ok(adapter.send_tx_sync(&addr, &slate_r1)?),
+            )?;
+            Ok((slate, tx_proof, Some(grinrelay_key_path)))
+        },
+    )?;
+    duplicate::record(&config, receiver_addr, result.amount, &result.slate_id);
+    Ok(result)
+}
+
+/// Start a Grin Relay service as payer, and wait for it connected
+fn relay_send_adapter<C>(
+    config: &MobileWalletCfg,
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+) -> Result<(ExtKeychainPath, GrinrelayWalletCommAdapter), Error>
+where
+    C: NodeClient + 'static,
+{
+    // The streaming channel between 'grinrelay_listener' and 'GrinrelayWalletCommAdapter'
+    let (relay_tx_as_payer, relay_rx) = channel();
+
+    // Start a Grin Relay service firstly
+    let (grinrelay_key_path, grinrelay_listener) = grinrelay_listener(
+        wallet,
+        relay_config(config)?,
+        Some(relay_tx_as_payer),
+        None,
+        None,
+    )?;
+    // Wait for connecting with relay service
+    let grinrelay_listener = Arc::new(grinrelay_listener);
+    let connected_listener = grinrelay_listener.clone();
+    relay::wait_connected(
+        Box::new(move || connected_listener.is_connected()),
+        config.relay_connect_timeout_ms,
+    )?;
+    let grinrelay_listener = Arc::try_unwrap(grinrelay_listener).map_err(|_| {
+        ErrorKind::GenericError("relay listener is still shared after connected".to_owned())
+    })?;
+
+    Ok((
+        grinrelay_key_path,
+        GrinrelayWalletCommAdapter::new(grinrelay_listener, relay_rx),
+    ))
+}
+
+/// Send by http if the receiver is an url, otherwise by grinrelay
+fn send_to(
+    json_cfg: &str,
+    account: Option<&str>,
+    receiver: &str,
+    args: InitTxArgs,
+    lock_height: Option<u64>,
+) -> Result<SendResult, Error> {
+    if is_http_receiver(receiver) {
+        send_tx_by_http(json_cfg, account, receiver, args, lock_height)
+    } else {
+        send_tx_by_relay(json_cfg, account, receiver, args, lock_height)
+    }
+}
+
+/// `send_to` within the `operation_timeout_ms` of the config
+fn send_to_within(
+    json_cfg: &str,
+    account: Option<&str>,
+    receiver: &str,
+    args: InitTxArgs,
+    lock_height: Option<u64>,
+) -> Result<SendResult, Error> {
+    let account = account.map(|a| a.to_owned());
+    let receiver = receiver.to_owned();
+    within("send", json_cfg, move |json_cfg| {
+        send_to(json_cfg, account.as_deref(), &receiver, args, lock_height)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn grin_send_tx(
+    json_cfg: *const c_char,
+    amount: u64,
+    receiver_addr_or_url: *const c_char,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_tx");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let res = send_to_within(
+        &cstr_to_str(json_cfg),
+        None,
+        &cstr_to_str(receiver_addr_or_url),
+        send_tx_args(
+            amount,
+            &cstr_to_str(selection_strategy),
+            slate_version,
+            &cstr_to_str(message),
+        ),
+        None,
+    )
+    .map(|result| {
+        serde_json::to_string(&result.slate).expect("fail to serialize slate to json string")
+    });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Pin the full address picked for a 6-code, and send to it
+fn send_to_resolved(
+    json_cfg: &str,
+    six_code_suffix: &str,
+    full_addr: &str,
+    args: InitTxArgs,
+) -> Result<SendResult, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    if address::parse(full_addr).address_type != address::AddressType::Grinrelay {
+        return Err(
+            ErrorKind::ArgumentError(format!("{} is not a relay address", full_addr)).into(),
+        );
+    }
+    addr_cache::pin(&config, six_code_suffix, full_addr)?;
+    relay::cache_addr(&config.chain_type, six_code_suffix, full_addr);
+    send_to_within(json_cfg, None, full_addr, args, None)
+}
+
+/// Send to the full address picked among the candidates of an ambiguous 6-code, which becomes
+/// the stored address of the 6-code.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn grin_send_to_resolved(
+    json_cfg: *const c_char,
+    six_code_suffix: *const c_char,
+    full_addr: *const c_char,
+    amount: u64,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_to_resolved");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let res = send_to_resolved(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(six_code_suffix),
+        &cstr_to_str(full_addr),
+        send_tx_args(
+            amount,
+            &cstr_to_str(selection_strategy),
+            slate_version,
+            &cstr_to_str(message),
+        ),
+    )
+    .map(|result| {
+        serde_json::to_string(&result.slate).expect("fail to serialize slate to json string")
+    });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn grin_send_tx_for_account(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    amount: u64,
+    receiver_addr_or_url: *const c_char,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_tx_for_account");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let account = cstr_to_opt_str(account);
+    let res = send_to_within(
+        &cstr_to_str(json_cfg),
+        account.as_deref(),
+        &cstr_to_str(receiver_addr_or_url),
+        send_tx_args(
+            amount,
+            &cstr_to_str(selection_strategy),
+            slate_version,
+            &cstr_to_str(message),
+        ),
+        None,
+    )
+    .map(|result| {
+        serde_json::to_string(&result.slate).expect("fail to serialize slate to json string")
+    });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Send like `grin_send_tx` for an optional account, returning the structured result
+/// `{"slateId", "amount", "fee", "channel", "deliveryMs", "postStatus", "repostAttempts",
+/// "feeBreakdown", "slate"}`.
+/// A non-zero `lock_height` makes a tx which can't be confirmed before that block height.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn grin_send_tx_with_result(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    amount: u64,
+    receiver_addr_or_url: *const c_char,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    lock_height: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_tx_with_result");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let account = cstr_to_opt_str(account);
+    let res = send_to_within(
+        &cstr_to_str(json_cfg),
+        account.as_deref(),
+        &cstr_to_str(receiver_addr_or_url),
+        send_tx_args(
+            amount,
+            &cstr_to_str(selection_strategy),
+            slate_version,
+            &cstr_to_str(message),
+        ),
+        Some(lock_height).filter(|h| *h > 0),
+    )
+    .map(|result| serde_json::to_string(&result).unwrap());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn send_duplicate_allow(json_cfg: &str, receiver: &str, amount: u64) -> Result<String, Error> {
+    duplicate::allow(&MobileWalletCfg::from_str(json_cfg)?, receiver, amount);
+    Ok("OK".to_owned())
+}
+
+/// Allow the next send of `amount` to `receiver` once, after the user confirmed a duplicate
+/// payment warning, the error code 7 of a send within `duplicate_send_window_secs`
+#[no_mangle]
+pub extern "C" fn grin_send_duplicate_allow(
+    json_cfg: *const c_char,
+    receiver_addr_or_url: *const c_char,
+    amount: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_duplicate_allow");
+    let res = send_duplicate_allow(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(receiver_addr_or_url),
+        amount,
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn contacts_list(json_cfg: &str) -> Result<String, Error> {
+    let contacts = contacts::list(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok(serde_json::to_string(&contacts).unwrap())
+}
+
+/// The address book, a json list of `{"name", "url", "relayAddress", "policy"}`
+#[no_mangle]
+pub extern "C" fn grin_contacts_list(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_contacts_list");
+    let res = contacts_list(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn contact_save(json_cfg: &str, contact_json: &str) -> Result<String, Error> {
+    let contact: contacts::Contact = serde_json::from_str(contact_json)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid contact: {}", e)))?;
+    let contact = contacts::save(&MobileWalletCfg::from_str(json_cfg)?, contact)?;
+    Ok(serde_json::to_string(&contact).unwrap())
+}
+
+/// Add a contact or replace the one of the same name, returning the normalized contact. Its
+/// policy `{"maxAmountWithoutConfirmation", "preferredTransport", "defaultMessage"}` applies
+/// to all the sends to its addresses.
+#[no_mangle]
+pub extern "C" fn grin_contact_save(
+    json_cfg: *const c_char,
+    contact_json: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_contact_save");
+    let res = contact_save(&cstr_to_str(json_cfg), &cstr_to_str(contact_json));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn contact_delete(json_cfg: &str, name: &str) -> Result<String, Error> {
+    contacts::delete(&MobileWalletCfg::from_str(json_cfg)?, name)?;
+    Ok("OK".to_owned())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_contact_delete(
+    json_cfg: *const c_char,
+    name: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_contact_delete");
+    let res = contact_delete(&cstr_to_str(json_cfg), &cstr_to_str(name));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Send to a contact of the address book, by its preferred transport
+#[no_mangle]
+pub extern "C" fn grin_send_to_contact(
+    json_cfg: *const c_char,
+    contact_name: *const c_char,
+    amount: u64,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_to_contact");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let json_cfg = cstr_to_str(json_cfg);
+    let res = MobileWalletCfg::from_str(&json_cfg)
+        .and_then(|config| contacts::receiver_of(&config, &cstr_to_str(contact_name)))
+        .and_then(|receiver| {
+            send_to_within(
+                &json_cfg,
+                None,
+                &receiver,
+                send_tx_args(
+                    amount,
+                    &cstr_to_str(selection_strategy),
+                    slate_version,
+                    &cstr_to_str(message),
+                ),
+                None,
+            )
+        })
+        .map(|result| {
+            serde_json::to_string(&result.slate).expect("fail to serialize slate to json string")
+        });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// One recipient of a batch send
+#[derive(Deserialize)]
+struct BatchRecipient {
+    address: String,
+    #[serde(deserialize_with = "amounts::deserialize")]
+    amount: u64,
+    #[serde(default)]
+    message: String,
+}
+
+fn send_batch(
+    json_cfg: &str,
+    recipients_json: &str,
+    selection_strategy: &str,
+    target_slate_version: Option<u16>,
+) -> Result<String, Error> {
+    let recipients: Vec<BatchRecipient> = serde_json::from_str(recipients_json)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid recipients: {}", e)))?;
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "send")?;
+    let wallet = get_wallet_instance(config.clone())?;
+
+    // The relay adapter is shared by all the recipients, only started if needed
+    let relay_adapter = if recipients.iter().any(|r| !is_http_receiver(&r.address)) {
+        Some(
+            relay_send_adapter(&config, wallet.clone())
+                .map(|adapter| Arc::new(Mutex::new(adapter)))
+                .map_err(|e| e.to_string()),
+        )
+    } else {
+        None
+    };
+
+    let mut results = vec![];
+    for recipient in recipients {
+        let mut args = send_tx_args(
+            recipient.amount,
+            selection_strategy,
+            target_slate_version,
+            &recipient.message,
+        );
+        let checked = contacts::apply_policy(&config, &recipient.address, &mut args).and_then(
+            |confirmation| {
+                duplicate::check(&config, &recipient.address, recipient.amount)?;
+                Ok(confirmation)
+            },
+        );
+        let res = checked.and_then(|confirmation| {
+            // Each send within its own deadline
+            let wallet = wallet.clone();
+            let relay_adapter = relay_adapter.clone();
+            let address = recipient.address.clone();
+            within("send", json_cfg, move |json_cfg| {
+                let config = MobileWalletCfg::from_str(json_cfg)?;
+                if is_http_receiver(&address) {
+                    return send_tx(
+                        &config,
+                        wallet,
+                        args,
+                        None,
+                        confirmation.as_deref(),
+                        "http",
+                        |slate_r1| {
+                            let (slate, _tx_proof) =
+                                HTTPWalletCommAdapter::new().send_tx_sync(&address, slate_r1)?;
+                            Ok((slate, None, None))
+                        },
+                    );
+                }
+                match relay_adapter {
+                    Some(Ok(relay_adapter)) => send_tx(
+                        &config,
+                        wallet,
+                        args,
+                        None,
+                        confirmation.as_deref(),
+                        "relay",
+                        |slate_r1| {
+                            let relay_adapter = relay_adapter.lock();
+                            let (grinrelay_key_path, adapter) = &*relay_adapter;
+                            let (slate, tx_proof) = adapter.send_tx_sync(&address, slate_r1)?;
+                            Ok((slate, tx_proof, Some(grinrelay_key_path.clone())))
+                        },
+                    ),
+                    Some(Err(e)) => Err(ErrorKind::GenericError(e).into()),
+                    None => Err(ErrorKind::GenericError(
+                        "no relay adapter for a relay recipient".to_owned(),
+                    )
+                    .into()),
+                }
+            })
+        });
+        if let Ok(result) = res.as_ref() {
+            duplicate::record(
+                &config,
+                &recipient.address,
+                recipient.amount,
+                &result.slate_id,
+            );
+        }
+        results.push(match res {
+            Ok(result) => json!({
+                "address": recipient.address,
+                "amount": recipient.amount,
+                "success": true,
+                "feeBreakdown": result.fee_breakdown,
+                "slate": result.slate,
+            }),
+            Err(e) => json!({
+                "address": recipient.address,
+                "amount": recipient.amount,
+                "success": false,
+                "error": e.to_string(),
+            }),
+        });
+    }
+    Ok(serde_json::to_string(&results).unwrap())
+}
+
+/// Send to a json list of recipients, `[{"address", "amount", "message"}]`, sequentially.
+/// Returns the result per recipient, a failed one doesn't stop the others.
+#[no_mangle]
+pub extern "C" fn grin_send_batch(
+    json_cfg: *const c_char,
+    recipients_json: *const c_char,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_batch");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let res = send_batch(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(recipients_json),
+        &cstr_to_str(selection_strategy),
+        slate_version,
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// The outcome of a payment split into several txs
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SplitSendResult {
+    amount: u64,
+    sent: u64,
+    fee: u64,
+    /// False if a tx failed, `sent` being what the previous txs sent
+    complete: bool,
+    txs: Vec<SendResult>,
+    error: Option<String>,
+}
+
+fn send_tx_split(
+    json_cfg: &str,
+    account: Option<&str>,
+    amount: u64,
+    receiver: &str,
+    selection_strategy: &str,
+    target_slate_version: Option<u16>,
+    message: &str,
+) -> Result<SplitSendResult, Error> {
+    let config = MobileWalletCfg::from_str_for_account(json_cfg, account)?;
+    check_spending(&config, "send")?;
+    let api = upstream::api(get_wallet_instance(config.clone())?);
+    let mut split = SplitSendResult {
+        amount,
+        sent: 0,
+        fee: 0,
+        complete: false,
+        txs: vec![],
+        error: None,
+    };
+
+    while split.sent < amount {
+        // The outputs of the previous txs are locked, no refresh needed to skip them
+        let height = api.node_height()?.0;
+        let (_, outputs) = api.outputs(false, split.txs.is_empty(), None)?;
+        let spendable: Vec<OutputData> = outputs
+            .into_iter()
+            .map(|o| o.output)
+            .filter(|o| {
+                o.status == OutputStatus::Unspent
+                    && o.eligible_to_spend(height, SENDING_MINIMUM_CONFIRMATIONS)
+            })
+            .collect();
+        let remaining = amount - split.sent;
+        let capacity = split::capacity(&spendable);
+        let res = if capacity == 0 {
+            Err(ErrorKind::GenericError("not enough funds for the next tx".to_owned()).into())
+        } else if split.txs.len() == split::MAX_SPLIT_TXS {
+            Err(
+                ErrorKind::GenericError(format!("more than {} txs needed", split::MAX_SPLIT_TXS))
+                    .into(),
+            )
+        } else if capacity >= remaining {
+            send_to_within(
+                json_cfg,
+                account,
+                receiver,
+                send_tx_args(remaining, selection_strategy, target_slate_version, message),
+                None,
+            )
+        } else {
+            // A full tx, spending exactly the largest outputs, which the selection falls
+            // back to once the smallest ones don't cover the amount
+            send_to_within(
+                json_cfg,
+                account,
+                receiver,
+                send_tx_args(capacity, "smallest", target_slate_version, message),
+                None,
+            )
+        };
+        match res {
+            Ok(result) => {
+                split.sent += result.amount;
+                split.fee += result.fee;
+                split.txs.push(result);
+            }
+            // Nothing sent, the payment just failed
+            Err(e) if split.txs.is_empty() => return Err(e),
+            Err(e) => {
+                split.error = Some(e.to_string());
+                return Ok(split);
+            }
+        }
+    }
+    split.complete = true;
+    Ok(split)
+}
+
+/// Send like `grin_send_tx_with_result`, splitting a payment needing more than 500 inputs
+/// into sequential txs to the same receiver, up to 20. Returns the aggregate result
+/// `{"amount", "sent", "fee", "complete", "txs", "error"}`, `txs` the result of each tx as
+/// `grin_send_tx_with_result` and `complete` false if a tx failed after the first one.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn grin_send_tx_split(
+    json_cfg: *const c_char,
+    account: *const c_char,
+    amount: u64,
+    receiver_addr_or_url: *const c_char,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_send_tx_split");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let account = cstr_to_opt_str(account);
+    let res = send_tx_split(
+        &cstr_to_str(json_cfg),
+        account.as_deref(),
+        amount,
+        &cstr_to_str(receiver_addr_or_url),
+        &cstr_to_str(selection_strategy),
+        slate_version,
+        &cstr_to_str(message),
+    )
+    .map(|split| serde_json::to_string(&split).unwrap());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn refund(json_cfg: &str, tx_slate_id: &str, amount: u64) -> Result<SendResult, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::ArgumentError(e.to_string()))?;
+    let api = upstream::api(get_wallet_instance(config.clone())?);
+    // Refreshed from the node, a reorg or a tx which never made it on chain must not be refunded
+    let (validated, txs) = api.txs(true, None, Some(uuid))?;
+    if !validated {
+        return Err(ErrorKind::GenericError("fail to check the tx on chain".to_owned()).into());
+    }
+    let received = txs
+        .into_iter()
+        .find(|t| t.tx_type == TxLogEntryType::TxReceived)
+        .ok_or_else(|| ErrorKind::ArgumentError("not an incoming tx".to_owned()))?;
+    if !received.confirmed {
+        return Err(ErrorKind::ArgumentError("incoming tx not confirmed yet".to_owned()).into());
+    }
+    let sender = senders::sender_of(&config, tx_slate_id)?
+        .ok_or_else(|| ErrorKind::ArgumentError("sender address of the tx unknown".to_owned()))?;
+    let amount = if amount == 0 {
+        received.amount_credited
+    } else {
+        amount
+    };
+    if amount > received.amount_credited {
+        return Err(ErrorKind::ArgumentError(format!(
+            "refund amount {} above the received {}",
+            amount, received.amount_credited
+        ))
+        .into());
+    }
+
+    let result = send_to_within(
+        json_cfg,
+        None,
+        &sender,
+        send_tx_args(
+            amount,
+            "smallest",
+            None,
+            &format!("refund of {}", tx_slate_id),
+        ),
+        None,
+    )?;
+    // The refund is sent, a failure to label it only loses the linkage
+    let _ = labels::add(
+        &config,
+        tx_slate_id,
+        &format!("refunded by {}", result.slate_id),
+    );
+    let _ = labels::add(
+        &config,
+        &result.slate_id,
+        &format!("refund of {}", tx_slate_id),
+    );
+    Ok(result)
+}
+
+/// Refund a confirmed incoming tx to the relay address it was sent from, the whole received
+/// amount if `amount` is 0. The tx is checked on chain with a refresh from the node first. Returns the result of the send as `grin_send_tx_with_result`, the labels of
+/// both txs linking them.
+#[no_mangle]
+pub extern "C" fn grin_refund(
+    json_cfg: *const c_char,
+    tx_slate_id: *const c_char,
+    amount: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_refund");
+    let res = refund(&cstr_to_str(json_cfg), &cstr_to_str(tx_slate_id), amount)
+        .map(|result| serde_json::to_string(&result).unwrap());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Set the status of the outputs, by commitment, for the recovery of the output locks
+/// journaled by the earlier releases
+fn set_outputs_status<C>(
+    wallet: &Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    outputs: &[OutputData],
+    status: OutputStatus,
+) -> Result<(), Error>
+where
+    C: NodeClient + 'static,
+{
+    let mut w = wallet.lock();
+    let mut batch = w.batch()?;
+    for output in outputs {
+        let mut output = output.clone();
+        output.status = status.clone();
+        batch.save(output)?;
+    }
+    batch.commit()?;
+    Ok(())
+}
+
+fn self_spend(json_cfg: &str, output_commitments: &str) -> Result<String, Error> {
+    let commits: Vec<String> = serde_json::from_str(output_commitments)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid commitments: {}", e)))?;
+    if commits.is_empty() {
+        return Err(ErrorKind::ArgumentError("no output selected".to_owned()).into());
+    }
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "send")?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let api = upstream::api(wallet.clone());
+
+    let height = api.node_height()?.0;
+    let (_, outputs) = api.outputs(false, true, None)?;
+    let eligible: Vec<OutputData> = outputs
+        .into_iter()
+        .map(|o| o.output)
+        .filter(|o| {
+            o.status == OutputStatus::Unspent
+                && o.eligible_to_spend(height, SENDING_MINIMUM_CONFIRMATIONS)
+        })
+        .collect();
+    let selected: Vec<&OutputData> = eligible
+        .iter()
+        .filter(|o| {
+            o.commit
+                .as_ref()
+                .map(|c| commits.contains(c))
+                .unwrap_or(false)
+        })
+        .collect();
+    if selected.len() != commits.len() {
+        return Err(
+            ErrorKind::ArgumentError("output not found, or not spendable".to_owned()).into(),
+        );
+    }
+    // The wallet libs have no input selection by the caller, the tx is built by the "all"
+    // selection, which spends all the spendable outputs up to the max inputs of a tx
+    if selected.len() != eligible.len() || selected.len() > split::MAX_TX_INPUTS {
+        return Err(ErrorKind::ArgumentError(format!(
+            "a self spend spends all the {} spendable outputs, up to {}",
+            eligible.len(),
+            split::MAX_TX_INPUTS
+        ))
+        .into());
+    }
+
+    // The fee of a tx without change output, as the selection computes it, so the change is
+    // exactly 0
+    let total: u64 = selected.iter().map(|o| o.value).sum();
+    let fee = tx_fee(selected.len(), 1, 1, None);
+    if total <= fee {
+        return Err(ErrorKind::ArgumentError(format!(
+            "selected amount {} can't cover the fee {}",
+            total, fee
+        ))
+        .into());
+    }
+    let mut args = send_tx_args(total - fee, "all", None, "self spend");
+    args.num_change_outputs = 0;
+
+    let foreign = upstream::api(wallet.clone());
+    let res = send_tx(&config, wallet, args, None, None, "self", |slate_r1| {
+        // Nothing is locked before the exchange, a tx of other inputs is just dropped
+        let spends_selected = slate_r1.tx.inputs().len() == commits.len()
+            && slate_r1
+                .tx
+                .inputs()
+                .iter()
+                .all(|i| commits.contains(&to_hex(i.commit.0.to_vec())));
+        if !spends_selected {
+            return Err(ErrorKind::GenericError(
+                "other outputs selected, retry once the wallet is refreshed".to_owned(),
+            )
+            .into());
+        }
+        let slate = foreign.receive(slate_r1, &config.account, None, None)?;
+        Ok((slate, None, None))
+    });
+
+    Ok(serde_json::to_string(&res?.slate).expect("fail to serialize slate to json string"))
+}
+
+/// Send the selected outputs, a json list of commitments in hex, back to this wallet. The
+/// selection must be all the spendable outputs, up to the max inputs of a tx.
+#[no_mangle]
+pub extern "C" fn grin_self_spend(
+    json_cfg: *const c_char,
+    output_commitments: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_self_spend");
+    let res = self_spend(&cstr_to_str(json_cfg), &cstr_to_str(output_commitments));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn recover_pending(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let recoveries = journal::recover_pending(&config)?;
+    Ok(serde_json::to_string(&recoveries).unwrap())
+}
+
+/// Roll forward or back the sends, output locks and restores interrupted by an app crash, to
+/// be called on start before any other wallet operation. Returns the recovery of each.
+#[no_mangle]
+pub extern "C" fn grin_recover_pending(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_recover_pending");
+    let res = recover_pending(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn tx_schedule(json_cfg: &str, args_json: &str, not_before: u64) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "send")?;
+    let args: queue::ScheduledTxArgs = serde_json::from_str(args_json)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid tx args: {}", e)))?;
+    let tx = queue::schedule(&config, args, not_before)?;
+    Ok(serde_json::to_string(&tx).unwrap())
+}
+
+/// Schedule a send, `{"receiver", "amount", "selection_strategy", "target_slate_version",
+/// "message"}`, not before the unix timestamp `not_before`. The queued sends are executed by
+/// `grin_tx_queue_process` or the queue service.
+#[no_mangle]
+pub extern "C" fn grin_tx_schedule(
+    json_cfg: *const c_char,
+    args_json: *const c_char,
+    not_before: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_schedule");
+    let res = tx_schedule(&cstr_to_str(json_cfg), &cstr_to_str(args_json), not_before);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn tx_queue_list(json_cfg: &str) -> Result<String, Error> {
+    let txs = queue::list(&MobileWalletCfg::from_str(json_cfg)?)?;
+    Ok(serde_json::to_string(&txs).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_tx_queue_list(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_tx_queue_list");
+    let res = tx_queue_list(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn tx_queue_cancel(json_cfg: &str, id: &str) -> Result<String, Error> {
+    queue::cancel(&MobileWalletCfg::from_str(json_cfg)?, id)?;
+    Ok("OK".to_owned())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_tx_queue_cancel(
+    json_cfg: *const c_char,
+    id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_queue_cancel");
+    let res = tx_queue_cancel(&cstr_to_str(json_cfg), &cstr_to_str(id));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_tx_queue_process(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_tx_queue_process");
+    let res = queue::process(&cstr_to_str(json_cfg)).map(|sent| sent.to_string());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_tx_queue_start(
+    json_cfg: *const c_char,
+    interval_secs: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_queue_start");
+    let res = queue::start_service(&cstr_to_str(json_cfg), interval_secs).map(|_| "OK".to_owned());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_tx_queue_stop(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_tx_queue_stop");
+    queue::stop_service();
+    unsafe { result_to_cstr(call, Ok("OK".to_owned()), error) }
+}
+
+fn start_services(json_cfg: &str, options_json: &str) -> Result<String, Error> {
+    services::start(json_cfg, options_json)?;
+    Ok("OK".to_owned())
+}
+
+/// Start the background services of a wallet: the balance refresher, the tx monitor, the relay
+/// listener and the post retry queue, restarted by a watchdog if they die. `options` is
+/// `{"refresh_interval_secs", "tx_monitor_interval_secs", "queue_interval_secs", "listen"}`,
+/// all optional, a zero interval disables its worker. The app is notified by the
+/// `balance_updated`, `tx_confirmed` and `service_restarted` events.
+#[no_mangle]
+pub extern "C" fn grin_start_services(
+    json_cfg: *const c_char,
+    options: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_start_services");
+    let res = start_services(&cstr_to_str(json_cfg), &cstr_to_str(options));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_stop_services(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_stop_services");
+    services::stop();
+    unsafe { result_to_cstr(call, Ok("OK".to_owned()), error) }
+}
+
+/// How long `grin_wallet_close` waits for the threads of the wallet
+const WALLET_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn wallet_close(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let (listeners, running) =
+        services::close_wallet(&wallet_data_dir(&config)?, WALLET_CLOSE_TIMEOUT);
+    Ok(json!({
+        "stoppedListeners": listeners,
+        "runningThreads": running,
+    })
+    .to_string())
+}
+
+/// Close the wallet of the config: stop its services and listeners, and join its background
+/// threads, so its files and memory are released. The calls still waiting on the network are
+/// left to complete, and returned as `runningThreads`, with the `stoppedListeners` addresses.
+#[no_mangle]
+pub extern "C" fn grin_wallet_close(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_wallet_close");
+    let res = wallet_close(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// The background threads of the lib, with their last run and error
+#[no_mangle]
+pub extern "C" fn grin_services_status(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_services_status");
+    let res = Ok(serde_json::to_string(&services::tasks()).unwrap());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn send_progress(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    Ok(serde_json::to_string(&progress::get(&config)?).unwrap())
+}
+
+/// The progress of the current or last send of the config account, `{"account", "state",
+/// "slateId", "startedAt", "updatedAt"}` or null, as notified by the `send_progress` events
+#[no_mangle]
+pub extern "C" fn grin_send_progress(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_send_progress");
+    let res = send_progress(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn cancel_token_new() -> Result<String, Error> {
+    Ok(cancel::new_token())
+}
+
+/// Issue a cancellation token, to set as the `cancel_token` of a send config
+#[no_mangle]
+pub extern "C" fn grin_cancel_token_new(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_cancel_token_new");
+    let res = cancel_token_new();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn send_cancel(token: &str) -> Result<String, Error> {
+    Ok(json!({ "requested": cancel::request(token) }).to_string())
+}
+
+/// Cancel the in-flight send of a token, i.e. from a Cancel button. The send returns the
+/// "cancelled by user" error with its outputs unlocked, unless its tx is already posted.
+#[no_mangle]
+pub extern "C" fn grin_send_cancel(token: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_send_cancel");
+    let res = send_cancel(&cstr_to_str(token));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn cancel_tx(
+    json_cfg: &str,
+    tx_slate_id: &str,
+    force: bool,
+    dry_run: bool,
+) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    if !dry_run {
+        check_spending(&config, "cancel")?;
+    }
+    let report = cancel::cancel(config.clone(), tx_slate_id, force, dry_run)?;
+    if report.cancelled {
+        audit::record(
+            &config,
+            "tx_cancelled",
+            json!({ "slateId": tx_slate_id, "forced": force }),
+        );
+    }
+    Ok(serde_json::to_string(&report).unwrap())
+}
+
+/// Cancel an unconfirmed tx and unlock its inputs. Refused while the tx might still be
+/// confirmed, see `grin_cancel_tx_with_options`.
+#[no_mangle]
+pub extern "C" fn grin_cancel_tx(
+    json_cfg: *const c_char,
+    tx_slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_cancel_tx");
+    let res = cancel_tx(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(tx_slate_id),
+        false,
+        false,
+    )
+    .map(|_| "OK".to_owned());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Cancel an unconfirmed tx, `force` to cancel it even if its outputs or kernel are on chain.
+/// With `dry_run` nothing is cancelled. Returns `{"txId", "txSlateId", "txType", "risks",
+/// "warnings", "outputsUnlocked", "outputsDeleted", "cancelled"}`.
+#[no_mangle]
+pub extern "C" fn grin_cancel_tx_with_options(
+    json_cfg: *const c_char,
+    tx_slate_id: *const c_char,
+    force: bool,
+    dry_run: bool,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_cancel_tx_with_options");
+    let res = cancel_tx(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(tx_slate_id),
+        force,
+        dry_run,
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn post_tx(json_cfg: &str, tx_slate_id: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "post")?;
+    let wallet = get_wallet_instance(config)?;
+    let api = upstream::api(wallet);
+    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
+    let (validated, txs) = api.txs(true, None, Some(uuid))?;
+    if txs[0].confirmed {
+        return Err(Error::from(ErrorKind::GenericError(format!(
+            "Transaction already confirmed"
+        ))));
+    } else if !validated {
+        return Err(Error::from(ErrorKind::GenericError(format!(
+            "api.retrieve_txs not validated"
+        ))));
+    }
+
+    let stored_tx = api.stored_tx(&txs[0])?;
+    match stored_tx {
+        Some(stored_tx) => {
+            api.post(Some(uuid), &stored_tx, true)?;
+            Ok("OK".to_owned())
+        }
+        None => Err(Error::from(ErrorKind::GenericError(format!(
+            "transaction data not found"
+        )))),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_post_tx(
+    json_cfg: *const c_char,
+    tx_slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_post_tx");
+    let res = post_tx(&cstr_to_str(json_cfg), &cstr_to_str(tx_slate_id));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn tx_export_raw(json_cfg: &str, tx_slate_id: &str) -> Result<String, Error> {
+    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
+    let api = upstream::api(wallet);
+    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
+    let (_, txs) = api.txs(true, None, Some(uuid))?;
+    let tx_entry = txs
+        .first()
+        .ok_or_else(|| Error::from(ErrorKind::ArgumentError("tx not found".to_owned())))?;
+    let stored_tx = api.stored_tx(tx_entry)?.ok_or_else(|| {
+        Error::from(ErrorKind::GenericError(
+            "transaction data not found".to_owned(),
+        ))
+    })?;
+    let tx_hex =
+        to_hex(ser::ser_vec(&stored_tx).map_err(|e| ErrorKind::GenericError(e.to_string()))?);
+    Ok(json!({
+        "txSlateId": tx_slate_id,
+        "confirmed": tx_entry.confirmed,
+        "txHex": tx_hex,
+        "tx": stored_tx,
+    })
+    .to_string())
+}
+
+/// The stored tx of a slate id, as `{"txHex", "tx"}`, to be broadcast through any node or
+/// explorer push api, i.e. when the configured node refuses the post
+#[no_mangle]
+pub extern "C" fn grin_tx_export_raw(
+    json_cfg: *const c_char,
+    tx_slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_export_raw");
+    let res = tx_export_raw(&cstr_to_str(json_cfg), &cstr_to_str(tx_slate_id));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn tx_file_receive(json_cfg: &str, slate_file_path: &str, message: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet = get_wallet_instance(config.clone())?;
+    let api = upstream::api(wallet.clone());
+    let adapter = FileWalletCommAdapter::new();
+    slates::check_file_readable(slate_file_path)?;
+    let mut slate = adapter.receive_tx_async(&slate_file_path)?;
+    api.verify_messages(&slate)?;
+    slate = receive_slate(&config, wallet, &slate, Some(message.to_string()), None)?;
+    Ok(slates::reply_json(slate))
+}
+
+#[no_mangle]
+pub extern "C" fn grin_tx_file_receive(
+    json_cfg: *const c_char,
+    slate_file_path: *const c_char,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_file_receive");
+    let res = tx_file_receive(
+        &cstr_to_str(json_cfg),
+        &cstr_to_str(slate_file_path),
+        &cstr_to_str(message),
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn tx_file_finalize(json_cfg: &str, slate_file_path: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    check_spending(&config, "finalize")?;
+    let wallet = get_wallet_instance(config)?;
+    let api = upstream::api(wallet);
+    let adapter = FileWalletCommAdapter::new();
+    slates::check_file_readable(slate_file_path)?;
+    let mut slate = adapter.receive_tx_async(slate_file_path)?;
+    api.verify_messages(&slate)?;
+    slate = api.finalize(&slate, None, None)?;
+    coalesce::invalidate();
+    Ok(serde_json::to_string(&slate).expect("fail to serialize slate to json string"))
+}
+
+#[no_mangle]
+pub extern "C" fn grin_tx_file_finalize(
+    json_cfg: *const c_char,
+    slate_file_path: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_tx_file_finalize");
+    let res = tx_file_finalize(&cstr_to_str(json_cfg), &cstr_to_str(slate_file_path));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn capability_issue(json_cfg: &str, scope: &str, ttl_secs: u64) -> Result<String, Error> {
+    let scope = capability::Scope::parse(scope)?;
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    if scope == capability::Scope::Owner {
+        return issue_owner_token(config, ttl_secs);
+    }
+    // Opening the wallet checks the password, a token is never issued for a wrong one
+    get_wallet_instance(config)?;
+    Ok(capability::issue(json_cfg, scope, ttl_secs, None))
+}
+
+/// Issue an owner token for the password of a config, keeping its seed password only
+fn issue_owner_token(mut config: MobileWalletCfg, ttl_secs: u64) -> Result<String, Error> {
+    config.owner_token = String::new();
+    // Opening the wallet checks the password, a token is never issued for a wrong one
+    get_wallet_instance(config.clone())?;
+    let seed_password = config.seed_password()?;
+    audit::record(&config, "owner_token_issued", json!({}));
+    config.password = String::new();
+    Ok(capability::issue(
+        &serde_json::to_string(&config).unwrap(),
+        capability::Scope::Owner,
+        ttl_secs,
+        Some(seed_password),
+    ))
+}
+
+/// Issue a capability token of the "owner" or "foreign" scope for a wallet config, valid for
+/// `ttl_secs` or until revoked if 0. The `grin_owner_*` calls need an owner token, and the
+/// `grin_foreign_*` calls a foreign or owner one.
+#[no_mangle]
+pub extern "C" fn grin_capability_issue(
+    json_cfg: *const c_char,
+    scope: *const c_char,
+    ttl_secs: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_capability_issue");
+    let res = capability_issue(&cstr_to_str(json_cfg), &cstr_to_str(scope), ttl_secs);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_capability_revoke(token: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_capability_revoke");
+    let res = if capability::revoke(&cstr_to_str(token)) {
+        Ok("OK".to_owned())
+    } else {
+        Err(ErrorKind::ArgumentError("unknown token".to_owned()).into())
+    };
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn owner_token_issue(json_cfg: &str, password: &str) -> Result<String, Error> {
+    let mut config = MobileWalletCfg::from_str(json_cfg)?;
+    config.password = password.to_owned();
+    let ttl_secs = config.owner_token_ttl_secs;
+    issue_owner_token(config, ttl_secs)
+}
+
+fn owner_token_require(json_cfg: &str, required: bool) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    // Dropping the requirement is a spending operation itself
+    check_spending(&config, "owner_token_require")?;
+    // Opening the wallet checks the password or the owner token
+    get_wallet_instance(config.clone())?;
+    let data_file_dir = new_wallet_config(config.clone())?.data_file_dir;
+    capability::set_owner_token_required(&data_file_dir, required)?;
+    audit::record(
+        &config,
+        "owner_token_required",
+        json!({ "required": required }),
+    );
+    Ok("OK".to_owned())
+}
+
+fn biometric_enroll(json_cfg: &str, password: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet_config = new_wallet_config(config.clone())?;
+    attempts::guard(&config, || {
+        let seed_password = kdf::seed_password(&wallet_config.data_file_dir, password)?;
+        WalletSeed::from_file(&wallet_config.data_file_dir, &seed_password)
+    })?;
+    let blob = biometric::enroll(&wallet_config.data_file_dir, password)?;
+    audit::record(&config, "biometric_enrolled", json!({}));
+    Ok(blob)
+}
+
+/// Enroll the biometric unlock with the wallet password. The returned opaque blob is to be
+/// stored behind LocalAuthentication, and given back to `grin_unlock_with_blob` after a
+/// biometric success. A new enrollment or a password change revokes the previous blob.
+#[no_mangle]
+pub extern "C" fn grin_biometric_enroll(
+    json_cfg: *const c_char,
+    password: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_biometric_enroll");
+    let res = biometric_enroll(&cstr_to_str(json_cfg), &cstr_to_str(password));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn unlock_with_blob(json_cfg: &str, blob: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet_config = new_wallet_config(config.clone())?;
+    let password = attempts::guard(&config, || {
+        biometric::unwrap(&wallet_config.data_file_dir, blob)
+    })?;
+    let token = owner_token_issue(json_cfg, &password)?;
+    audit::record(&config, "biometric_unlocked", json!({}));
+    Ok(token)
+}
+
+/// Unlock the wallet with the blob of `grin_biometric_enroll`, returning an owner token, as
+/// `grin_owner_token_issue` does for the password
+#[no_mangle]
+pub extern "C" fn grin_unlock_with_blob(
+    json_cfg: *const c_char,
+    blob: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_unlock_with_blob");
+    let res = unlock_with_blob(&cstr_to_str(json_cfg), &cstr_to_str(blob));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn biometric_revoke(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet_config = new_wallet_config(config.clone())?;
+    let revoked = biometric::revoke(&wallet_config.data_file_dir)?;
+    if revoked {
+        audit::record(&config, "biometric_revoked", json!({}));
+    }
+    Ok(json!({ "revoked": revoked }).to_string())
+}
+
+/// Revoke the biometric enrollment, its blob no longer unlocking the wallet
+#[no_mangle]
+pub extern "C" fn grin_biometric_revoke(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_biometric_revoke");
+    let res = biometric_revoke(&cstr_to_str(json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Issue a short-lived owner token for the wallet password, valid for `owner_token_ttl_secs`.
+/// Set as the `owner_token` of a config, it stands for the password, so the password doesn't
+/// need to be in every config json, and the spending calls accept it when the wallet requires
+/// it, see `grin_owner_token_require`. The token keeps the derived seed password only.
+#[no_mangle]
+pub extern "C" fn grin_owner_token_issue(
+    json_cfg: *const c_char,
+    password: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_owner_token_issue");
+    let res = owner_token_issue(&cstr_to_str(json_cfg), &cstr_to_str(password));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Require an owner token, or not, for the spending calls of the wallet, a setting kept in the
+/// wallet data dir whatever the config of a call. Dropping the requirement needs an owner token.
+#[no_mangle]
+pub extern "C" fn grin_owner_token_require(
     json_cfg: *const c_char,
+    required: bool,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_owner_token_require");
+    let res = owner_token_require(&cstr_to_str(json_cfg), required);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// The config of an owner token, with the token standing for its password
+fn owner_cfg(token: *const c_char) -> Result<String, Error> {
+    let token = cstr_to_str(token);
+    let (json_cfg, _) = capability::resolve_owner(&token)?;
+    let mut config = MobileWalletCfg::from_str(&json_cfg)?;
+    config.owner_token = token;
+    Ok(serde_json::to_string(&config).unwrap())
+}
+
+fn foreign_cfg(token: *const c_char) -> Result<String, Error> {
+    capability::resolve(&cstr_to_str(token), capability::Scope::Foreign)
+}
+
+#[no_mangle]
+pub extern "C" fn grin_owner_get_balance(token: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_owner_get_balance");
+    let res = owner_cfg(token).and_then(|json_cfg| {
+        within("get_balance", &json_cfg, |json_cfg| {
+            get_balance(json_cfg, None)
+        })
+    });
+    unsafe { result2_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_owner_txs_retrieve(token: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_owner_txs_retrieve");
+    let res = owner_cfg(token).and_then(|json_cfg| txs_retrieve(&json_cfg, None));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_owner_outputs_retrieve(
+    token: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_owner_outputs_retrieve");
+    let res = owner_cfg(token).and_then(|json_cfg| outputs_retrieve(&json_cfg, None, None));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Send as `grin_send_tx_with_result` does
+#[no_mangle]
+pub extern "C" fn grin_owner_send_tx(
+    token: *const c_char,
+    amount: u64,
+    receiver_addr_or_url: *const c_char,
+    selection_strategy: *const c_char,
+    target_slate_version: i16,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_owner_send_tx");
+    let mut slate_version: Option<u16> = None;
+    if target_slate_version >= 0 {
+        slate_version = Some(target_slate_version as u16);
+    }
+
+    let res = owner_cfg(token)
+        .and_then(|json_cfg| {
+            send_to_within(
+                &json_cfg,
+                None,
+                &cstr_to_str(receiver_addr_or_url),
+                send_tx_args(
+                    amount,
+                    &cstr_to_str(selection_strategy),
+                    slate_version,
+                    &cstr_to_str(message),
+                ),
+                None,
+            )
+        })
+        .map(|result| serde_json::to_string(&result).unwrap());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_owner_cancel_tx(
+    token: *const c_char,
     tx_slate_id: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
-    let res = cancel_tx(&cstr_to_str(json_cfg), &cstr_to_str(tx_slate_id));
-    unsafe { result_to_cstr(res, error) }
+    let call = metrics::call("grin_owner_cancel_tx");
+    let res = owner_cfg(token)
+        .and_then(|json_cfg| cancel_tx(&json_cfg, &cstr_to_str(tx_slate_id), false, false))
+        .map(|_| "OK".to_owned());
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn post_tx(json_cfg: &str, tx_slate_id: &str) -> Result<String, Error> {
+#[no_mangle]
+pub extern "C" fn grin_owner_post_tx(
+    token: *const c_char,
+    tx_slate_id: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_owner_post_tx");
+    let res = owner_cfg(token).and_then(|json_cfg| post_tx(&json_cfg, &cstr_to_str(tx_slate_id)));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn foreign_check_version(json_cfg: &str) -> Result<String, Error> {
     let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let uuid = Uuid::parse_str(tx_slate_id).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
-    let (validated, txs) = api.retrieve_txs(true, None, Some(uuid))?;
-    if txs[0].confirmed {
-        return Err(Error::from(ErrorKind::GenericError(format!(
-            "Transaction already confirmed"
-        ))));
-    } else if !validated {
-        return Err(Error::from(ErrorKind::GenericError(format!(
-            "api.retrieve_txs not validated"
-        ))));
-    }
+    let version = upstream::api(wallet).version()?;
+    Ok(serde_json::to_string(&version).unwrap())
+}
 
-    let stored_tx = api.get_stored_tx(&txs[0])?;
-    match stored_tx {
-        Some(stored_tx) => {
-            api.post_tx(Some(uuid), &stored_tx, true)?;
-            Ok("OK".to_owned())
-        }
-        None => Err(Error::from(ErrorKind::GenericError(format!(
-            "transaction data not found"
-        )))),
+/// The foreign api version and the supported slate versions
+#[no_mangle]
+pub extern "C" fn grin_foreign_check_version(
+    token: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_foreign_check_version");
+    let res = foreign_cfg(token).and_then(|json_cfg| foreign_check_version(&json_cfg));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn foreign_verify_slate_messages(json_cfg: &str, slate_json: &str) -> Result<String, Error> {
+    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
+    slates::check_readable(slate_json)?;
+    let slate = Slate::deserialize_upgrade(slate_json)?;
+    upstream::api(wallet).verify_messages(&slate)?;
+    Ok("OK".to_owned())
+}
+
+#[no_mangle]
+pub extern "C" fn grin_foreign_verify_slate_messages(
+    token: *const c_char,
+    slate_json: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_foreign_verify_slate_messages");
+    let res = foreign_cfg(token)
+        .and_then(|json_cfg| foreign_verify_slate_messages(&json_cfg, &cstr_to_str(slate_json)));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn foreign_receive_tx(json_cfg: &str, slate_json: &str, message: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    slates::check_readable(slate_json)?;
+    let slate = Slate::deserialize_upgrade(slate_json)?;
+    let slate_id = slate.id.to_string();
+    if receive::is_received(&config, &slate_id)? {
+        return Err(ErrorKind::ArgumentError("slate already received".to_owned()).into());
     }
+    let wallet = get_wallet_instance(config.clone())?;
+    let api = upstream::api(wallet.clone());
+    api.verify_messages(&slate)?;
+    let slate = receive_slate(&config, wallet, &slate, Some(message.to_string()), None)?;
+    receive::mark_received(&config, &slate_id)?;
+    Ok(slates::reply_json(slate))
 }
 
+/// Receive a slate json into the account of the token config, returning the slate to send
+/// back to the sender
 #[no_mangle]
-pub extern "C" fn grin_post_tx(
+pub extern "C" fn grin_foreign_receive_tx(
+    token: *const c_char,
+    slate_json: *const c_char,
+    message: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_foreign_receive_tx");
+    let res = foreign_cfg(token).and_then(|json_cfg| {
+        foreign_receive_tx(&json_cfg, &cstr_to_str(slate_json), &cstr_to_str(message))
+    });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn fee_breakdown(json_cfg: &str, slate_json: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let slate = Slate::deserialize_upgrade(slate_json)?;
+    // The change outputs are the outputs of the tx log entry of the sender
+    let api = upstream::api(get_wallet_instance(config)?);
+    let (_, txs) = api.txs(false, None, Some(slate.id))?;
+    let change_outputs = txs
+        .iter()
+        .find(|t| t.tx_type == TxLogEntryType::TxSent)
+        .map(|t| t.num_outputs)
+        .ok_or_else(|| Error::from(ErrorKind::ArgumentError("sent tx not found".to_owned())))?;
+    Ok(serde_json::to_string(&fees::FeeBreakdown::of(&slate, change_outputs)).unwrap())
+}
+
+/// The fee of a slate json sent by the config wallet, i.e. of `grin_send_tx` or
+/// `grin_tx_file_finalize`, broken down as `{"fee", "inputs", "outputs", "changeOutputs",
+/// "kernels", "weight"}`. The send results include it as `feeBreakdown`.
+#[no_mangle]
+pub extern "C" fn grin_fee_breakdown(
     json_cfg: *const c_char,
-    tx_slate_id: *const c_char,
+    slate_json: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
-    let res = post_tx(&cstr_to_str(json_cfg), &cstr_to_str(tx_slate_id));
-    unsafe { result_to_cstr(res, error) }
+    let call = metrics::call("grin_fee_breakdown");
+    let res = fee_breakdown(&cstr_to_str(json_cfg), &cstr_to_str(slate_json));
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn tx_file_receive(json_cfg: &str, slate_file_path: &str, message: &str) -> Result<String, Error> {
+fn slate_to_words(slate_json: &str, format: &str) -> Result<String, Error> {
+    codec::slate_to_text(slate_json, codec::TextFormat::parse(format)?)
+}
+
+/// Encode a slate json as compressed words, or emojis with the "emoji" format, for the chat
+/// apps mangling the json
+#[no_mangle]
+pub extern "C" fn grin_slate_to_words(
+    slate_json: *const c_char,
+    format: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_slate_to_words");
+    let res = slate_to_words(&cstr_to_str(slate_json), &cstr_to_str(format));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Decode the slate json of `grin_slate_to_words`, the words or emojis being detected
+#[no_mangle]
+pub extern "C" fn grin_slate_from_words(words: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_slate_from_words");
+    let res = codec::slate_from_text(&cstr_to_str(words));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn slate_to_nfc(slate_json: &str) -> Result<String, Error> {
+    Ok(to_hex(codec::slate_to_nfc(slate_json)?))
+}
+
+/// The NFC frame of a slate json, in hex, to write as the payload of an NDEF record
+#[no_mangle]
+pub extern "C" fn grin_slate_to_nfc(slate_json: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_slate_to_nfc");
+    let res = slate_to_nfc(&cstr_to_str(slate_json));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn slate_from_nfc(frame_hex: &str) -> Result<String, Error> {
+    let frame = from_hex(frame_hex.trim().to_owned())
+        .map_err(|_| ErrorKind::ArgumentError("invalid nfc frame hex".to_owned()))?;
+    codec::slate_from_nfc(&frame)
+}
+
+/// The slate json of an NFC frame in hex, as read from an NDEF record
+#[no_mangle]
+pub extern "C" fn grin_slate_from_nfc(frame_hex: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_slate_from_nfc");
+    let res = slate_from_nfc(&cstr_to_str(frame_hex));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn audit_log_read(json_cfg: &str, since: u64, limit: u32) -> Result<String, Error> {
+    let entries = audit::read(&MobileWalletCfg::from_str(json_cfg)?, since, limit as usize)?;
+    Ok(serde_json::to_string(&entries).unwrap())
+}
+
+/// The audit log entries since a unix timestamp, the latest `limit` ones or all if 0, the
+/// oldest first
+#[no_mangle]
+pub extern "C" fn grin_audit_log_read(
+    json_cfg: *const c_char,
+    since: u64,
+    limit: u32,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_audit_log_read");
+    let res = audit_log_read(&cstr_to_str(json_cfg), since, limit);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn signer_challenge(
+    slate_json: &str,
+    participant_id: u32,
+    frame_size: u32,
+) -> Result<String, Error> {
+    slates::check_readable(slate_json)?;
+    let slate = Slate::deserialize_upgrade(slate_json)?;
+    let challenge = signer::challenge(&slate, participant_id as usize)?;
+    let payload = serde_json::to_string(&challenge).unwrap();
+    Ok(json!({
+        "challenge": challenge,
+        "frames": signer::encode_frames(&payload, frame_size as usize),
+    })
+    .to_string())
+}
+
+/// Build the partial signature challenge of a slate participant for an external signer, and
+/// its frames to show as QR codes or to write over BLE, at most `frame_size` bytes of data
+/// each, or a default size if 0.
+#[no_mangle]
+pub extern "C" fn grin_signer_challenge(
+    slate_json: *const c_char,
+    participant_id: u32,
+    frame_size: u32,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_signer_challenge");
+    let res = signer_challenge(&cstr_to_str(slate_json), participant_id, frame_size);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn signer_response(frames_json: &str) -> Result<String, Error> {
+    let frames: Vec<String> = serde_json::from_str(frames_json)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid frames: {}", e)))?;
+    let payload = signer::decode_frames(&frames)?;
+    let response: signer::SignResponse = serde_json::from_str(&payload)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid signer response: {}", e)))?;
+    let challenge = signer::take_response(&response)?;
+    Ok(json!({
+        "challenge": challenge,
+        "publicBlindExcess": response.public_blind_excess,
+        "partSig": response.part_sig,
+    })
+    .to_string())
+}
+
+/// Verify the response frames of an external signer, as a json array in any order. Returns
+/// the answered challenge with the verified partial signature.
+#[no_mangle]
+pub extern "C" fn grin_signer_response(
+    frames_json: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_signer_response");
+    let res = signer_response(&cstr_to_str(frames_json));
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn events_poll() -> Result<String, Error> {
+    Ok(serde_json::to_string(&events::drain()).unwrap())
+}
+
+/// Take the queued wallet events
+#[no_mangle]
+pub extern "C" fn grin_events_poll(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_events_poll");
+    let res = events_poll();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Set the callback receiving the wallet events as they happen, or NULL to clear it.
+/// The callback is called on the background thread emitting the event.
+#[no_mangle]
+pub extern "C" fn grin_set_event_callback(callback: Option<events::EventCallback>) {
+    events::set_callback(callback);
+}
+
+/// Set the callback receiving the log lines json of the lib, or NULL to clear it. The steps of
+/// the sends are logged as `span_start` and `span_end` lines, with their slate id and trace id.
+#[no_mangle]
+pub extern "C" fn grin_set_log_callback(callback: Option<trace::LogCallback>) {
+    trace::set_callback(callback);
+}
+
+/// The timeline of the steps of the sends of a slate id, or of a trace id, with the open step
+/// of a send still running last
+#[no_mangle]
+pub extern "C" fn grin_trace_timeline(id: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_trace_timeline");
+    let res = Ok(serde_json::to_string(&trace::timeline(&cstr_to_str(id))).unwrap());
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// Set the callback confirming the outgoing txs before they are finalized, or NULL to clear it.
+/// A tx not approved is cancelled and its outputs unlocked.
+#[no_mangle]
+pub extern "C" fn grin_set_send_approval_callback(
+    callback: Option<approval::SendApprovalCallback>,
+) {
+    approval::set_callback(callback);
+}
+
+fn node_peers(json_cfg: &str) -> Result<String, Error> {
+    Ok(node::peers(&MobileWalletCfg::from_str(json_cfg)?)?.to_string())
+}
+
+/// The peers connected with the configured node, needs the node api secret
+#[no_mangle]
+pub extern "C" fn grin_node_peers(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_node_peers");
+    let res = within("node_peers", &cstr_to_str(json_cfg), node_peers);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn node_mempool_stats(json_cfg: &str) -> Result<String, Error> {
+    Ok(node::mempool_stats(&MobileWalletCfg::from_str(json_cfg)?)?.to_string())
+}
+
+fn node_check_genesis(json_cfg: &str) -> Result<String, Error> {
     let config = MobileWalletCfg::from_str(json_cfg)?;
-    let wallet = get_wallet_instance(config.clone())?;
-    let api = Foreign::new(wallet, None);
-    let adapter = FileWalletCommAdapter::new();
-    let mut slate = adapter.receive_tx_async(&slate_file_path)?;
-    api.verify_slate_messages(&slate)?;
-    slate = api.receive_tx(
-        &slate,
-        Some(&config.account),
-        Some(message.to_string()),
-        None,
-    )?;
-    Ok(serde_json::to_string(&slate).expect("fail to serialize slate to json string"))
+    Ok(node::check_genesis(&config, runtime::genesis_hash())?.to_string())
 }
 
+/// The genesis block hash of the configured node, and whether it's the `genesis_hash` of
+/// `grin_init`, i.e. a dev chain which was reset since the wallet was created
 #[no_mangle]
-pub extern "C" fn grin_tx_file_receive(
+pub extern "C" fn grin_node_check_genesis(
     json_cfg: *const c_char,
-    slate_file_path: *const c_char,
-    message: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
-    let res = tx_file_receive(
+    let call = metrics::call("grin_node_check_genesis");
+    let res = within(
+        "node_check_genesis",
         &cstr_to_str(json_cfg),
-        &cstr_to_str(slate_file_path),
-        &cstr_to_str(message),
+        node_check_genesis,
     );
-    unsafe { result_to_cstr(res, error) }
+    unsafe { result_to_cstr(call, res, error) }
 }
 
-fn tx_file_finalize(json_cfg: &str, slate_file_path: &str) -> Result<String, Error> {
-    let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let adapter = FileWalletCommAdapter::new();
-    let mut slate = adapter.receive_tx_async(slate_file_path)?;
-    api.verify_slate_messages(&slate)?;
-    slate = api.finalize_tx(&slate, None, None)?;
-    Ok(serde_json::to_string(&slate).expect("fail to serialize slate to json string"))
+fn output_on_chain(json_cfg: &str, commitment: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let commitment = commitment.trim().to_lowercase();
+    let mut output = node::output_by_commitment(&config, &commitment)?;
+    // The wallet side of the output, without a refresh
+    let api = upstream::api(get_wallet_instance(config)?);
+    let (_, outputs) = api.outputs(true, false, None)?;
+    output["walletStatus"] = json!(outputs
+        .into_iter()
+        .find(|o| to_hex(o.commit.0.to_vec()) == commitment)
+        .map(|o| o.output.status.to_string()));
+    Ok(output.to_string())
 }
 
+/// Whether an output is unspent on the chain of the configured node, by its commitment in
+/// hex, `{"commitment", "unspent", "height", "mmrIndex", "walletStatus"}`, the wallet status
+/// null for an output not of this wallet. Nothing is refreshed from the node.
 #[no_mangle]
-pub extern "C" fn grin_tx_file_finalize(
+pub extern "C" fn grin_output_on_chain(
     json_cfg: *const c_char,
-    slate_file_path: *const c_char,
+    commitment: *const c_char,
     error: *mut u8,
 ) -> *const c_char {
-    let res = tx_file_finalize(&cstr_to_str(json_cfg), &cstr_to_str(slate_file_path));
-    unsafe { result_to_cstr(res, error) }
+    let call = metrics::call("grin_output_on_chain");
+    let commitment = cstr_to_str(commitment);
+    let res = within("output_on_chain", &cstr_to_str(json_cfg), move |json_cfg| {
+        output_on_chain(json_cfg, &commitment)
+    });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// A block header of the configured node by height or hash, as the node api lists it, null
+/// if the node doesn't have it
+#[no_mangle]
+pub extern "C" fn grin_get_header(
+    json_cfg: *const c_char,
+    height_or_hash: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_get_header");
+    let height_or_hash = cstr_to_str(height_or_hash);
+    let res = within("get_header", &cstr_to_str(json_cfg), move |json_cfg| {
+        let config = MobileWalletCfg::from_str(json_cfg)?;
+        Ok(node::header(&config, &height_or_hash)?.to_string())
+    });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// A tx kernel of the configured node by its excess in hex, `{"tx_kernel", "height",
+/// "mmr_index"}` as the node api lists it, null if it's not on the chain (yet)
+#[no_mangle]
+pub extern "C" fn grin_get_kernel(
+    json_cfg: *const c_char,
+    excess: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_get_kernel");
+    let excess = cstr_to_str(excess).trim().to_lowercase();
+    let res = within("get_kernel", &cstr_to_str(json_cfg), move |json_cfg| {
+        let config = MobileWalletCfg::from_str(json_cfg)?;
+        Ok(node::kernel(&config, &excess)?.to_string())
+    });
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+/// The transaction pool size and chain tip of the configured node
+#[no_mangle]
+pub extern "C" fn grin_node_mempool_stats(
+    json_cfg: *const c_char,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_node_mempool_stats");
+    let res = within(
+        "node_mempool_stats",
+        &cstr_to_str(json_cfg),
+        node_mempool_stats,
+    );
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[cfg(feature = "mock-node")]
+fn testing_enable(enabled: bool) -> Result<String, Error> {
+    if !cfg!(debug_assertions) {
+        return Err(
+            ErrorKind::GenericError("testing mode is for debug builds only".to_owned()).into(),
+        );
+    }
+    mock::set_enabled(enabled);
+    node::clear_cache();
+    Ok("OK".to_owned())
+}
+
+#[cfg(not(feature = "mock-node"))]
+fn testing_enable(_enabled: bool) -> Result<String, Error> {
+    Err(ErrorKind::GenericError("built without the mock-node feature".to_owned()).into())
+}
+
+/// Switch all the wallets to the in-memory mock node, with an empty chain, for the UI tests.
+/// Only in the debug builds with the `mock-node` feature.
+#[no_mangle]
+pub extern "C" fn grin_testing_enable(enabled: bool, error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_testing_enable");
+    let res = testing_enable(enabled);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[cfg(feature = "mock-node")]
+fn testing_fixture(chain_type: &str, data_dir: &str, blocks: u64) -> Result<String, Error> {
+    mock::fixture(chain_type, data_dir, blocks)
+}
+
+#[cfg(not(feature = "mock-node"))]
+fn testing_fixture(_chain_type: &str, _data_dir: &str, _blocks: u64) -> Result<String, Error> {
+    Err(ErrorKind::GenericError("built without the mock-node feature".to_owned()).into())
+}
+
+/// Create the deterministic fixture wallet in `data_dir`, funded with `blocks` coinbases on
+/// the mock node, and return its config json. The testing mode must be enabled.
+#[no_mangle]
+pub extern "C" fn grin_testing_fixture(
+    chain_type: *const c_char,
+    data_dir: *const c_char,
+    blocks: u64,
+    error: *mut u8,
+) -> *const c_char {
+    let call = metrics::call("grin_testing_fixture");
+    let res = testing_fixture(&cstr_to_str(chain_type), &cstr_to_str(data_dir), blocks);
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[cfg(feature = "mock-node")]
+fn run_selftest() -> Result<String, Error> {
+    selftest::run()
+}
+
+#[cfg(not(feature = "mock-node"))]
+fn run_selftest() -> Result<String, Error> {
+    Err(ErrorKind::GenericError("built without the mock-node feature".to_owned()).into())
+}
+
+/// Run the end to end self test with two wallets on a fresh mock chain, for the diagnostics.
+/// Returns `{"passed", "steps": [{"name", "passed", "error", "durationMs"}]}`.
+/// Only in the debug builds with the `mock-node` feature.
+#[no_mangle]
+pub extern "C" fn grin_selftest(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_selftest");
+    let res = run_selftest();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+fn network_stats() -> Result<String, Error> {
+    Ok(netstats::snapshot().to_string())
+}
+
+/// The bytes sent and received per network operation since the start or the last reset,
+/// as the json payload sizes
+#[no_mangle]
+pub extern "C" fn grin_network_stats(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_network_stats");
+    let res = network_stats();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_network_stats_reset() {
+    netstats::reset();
+}
+
+/// Return the amounts of the response schema results, the balance and the txs, as decimal
+/// strings, i.e. `"amountCredited": "1500000000"`, instead of integers, since a double can't
+/// hold every u64 amount. Off by default, the amounts of the json inputs take both forms
+/// anyway.
+#[no_mangle]
+pub extern "C" fn grin_set_amounts_as_strings(as_strings: bool) {
+    amounts::set_as_strings(as_strings);
+}
+
+fn metrics_snapshot() -> Result<String, Error> {
+    Ok(metrics::snapshot().to_string())
+}
+
+/// Turn the usage metrics of the extern calls on or off, off by default
+#[no_mangle]
+pub extern "C" fn grin_metrics_enable(enabled: bool) {
+    metrics::set_enabled(enabled);
+}
+
+/// The usage metrics since they were enabled or reset, `{"enabled", "functions": [{"function",
+/// "calls", "failures", "avgMs", "maxMs"}], "failuresByCode": {code: count}}`, the slowest
+/// functions on average first. The codes are the ones of `grin_last_error_code`.
+#[no_mangle]
+pub extern "C" fn grin_metrics_snapshot(error: *mut u8) -> *const c_char {
+    let call = metrics::call("grin_metrics_snapshot");
+    let res = metrics_snapshot();
+    unsafe { result_to_cstr(call, res, error) }
+}
+
+#[no_mangle]
+pub extern "C" fn grin_metrics_reset() {
+    metrics::reset();
 }
 
 fn chain_height(json_cfg: &str) -> Result<String, Error> {
     let wallet = get_wallet_instance(MobileWalletCfg::from_str(json_cfg)?)?;
-    let api = Owner::new(wallet);
-    let height = api.node_height()?;
-    Ok(serde_json::to_string(&height).unwrap())
+    let (height, updated_from_node) = upstream::api(wallet).node_height()?;
+    Ok(json!({
+        "height": height,
+        "updated_from_node": updated_from_node,
+    })
+    .to_string())
 }
 
 #[no_mangle]
 pub extern "C" fn grin_chain_height(json_cfg: *const c_char, error: *mut u8) -> *const c_char {
-    let res = chain_height(&cstr_to_str(json_cfg));
-    unsafe { result_to_cstr(res, error) }
+    let call = metrics::call("grin_chain_height");
+    let res = within("chain_height", &cstr_to_str(json_cfg), chain_height);
+    unsafe { result_to_cstr(call, res, error) }
 }
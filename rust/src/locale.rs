@@ -0,0 +1,133 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The built-in translations of the common errors, by the message key and params of the last
+//! error, so each platform doesn't translate the raw error strings. The locale is set by
+//! `grin_set_locale`, English by default and for an unsupported locale.
+
+use serde_json::Value;
+
+use grin_wallet_util::grin_util::Mutex;
+
+pub const EN: &str = "en";
+pub const ZH_CN: &str = "zh-CN";
+
+/// The supported locales
+pub const SUPPORTED: &[&str] = &[EN, ZH_CN];
+
+lazy_static! {
+    static ref LOCALE: Mutex<&'static str> = Mutex::new(EN);
+}
+
+/// The supported locale of a locale tag, i.e. `zh_CN`, `zh-Hans` or `en-US`
+fn supported(tag: &str) -> Option<&'static str> {
+    let tag = tag.trim().replace('_', "-").to_lowercase();
+    let language = tag.split('-').next().unwrap_or("");
+    match language {
+        "en" => Some(EN),
+        "zh" if tag == "zh" || tag == "zh-cn" || tag == "zh-sg" || tag.starts_with("zh-hans") => {
+            Some(ZH_CN)
+        }
+        _ => None,
+    }
+}
+
+/// Set the locale of the error messages, English for an unsupported one. Returns the locale
+/// set.
+pub fn set(tag: &str) -> &'static str {
+    let locale = supported(tag).unwrap_or(EN);
+    *LOCALE.lock() = locale;
+    locale
+}
+
+pub fn get() -> &'static str {
+    *LOCALE.lock()
+}
+
+fn template(locale: &str, key: &str) -> Option<&'static str> {
+    let template = match (locale, key) {
+        (EN, "generic") => "Something went wrong: {detail}",
+        (EN, "invalid_argument") => "Invalid input: {detail}",
+        (EN, "wallet") => "The wallet refused the operation: {detail}",
+        (EN, "not_enough_funds") => {
+            "Not enough funds: {available} grin available, {needed} grin needed"
+        }
+        (EN, "keychain") => "Wrong password or invalid recovery phrase",
+        (EN, "network") => "Network error, please check the connection",
+        (EN, "storage") => "Failed to read or write the wallet data",
+        (EN, "duplicate_payment") => "This looks like a duplicate of a recent payment",
+        (EN, "read_only") => "This wallet is read-only",
+        (EN, "locked_out") => "Too many failed password attempts, please try again later",
+        (EN, "node_unreachable") => "The node is unreachable, please try again later",
+        (EN, "timeout") => "The operation timed out",
+        (EN, "cancelled") => "The send was cancelled",
+        (EN, "dust_change") => {
+            "The change of this send is too small, send another amount or donate it to the fee"
+        }
+        (ZH_CN, "generic") => "出错了：{detail}",
+        (ZH_CN, "invalid_argument") => "输入无效：{detail}",
+        (ZH_CN, "wallet") => "钱包拒绝了该操作：{detail}",
+        (ZH_CN, "not_enough_funds") => "余额不足：可用 {available} grin，需要 {needed} grin",
+        (ZH_CN, "keychain") => "密码错误或助记词无效",
+        (ZH_CN, "network") => "网络错误，请检查网络连接",
+        (ZH_CN, "storage") => "钱包数据读写失败",
+        (ZH_CN, "duplicate_payment") => "这笔付款疑似与最近的一笔重复",
+        (ZH_CN, "read_only") => "该钱包为只读钱包",
+        (ZH_CN, "locked_out") => "密码错误次数过多，请稍后再试",
+        (ZH_CN, "node_unreachable") => "无法连接节点，请稍后再试",
+        (ZH_CN, "timeout") => "操作超时",
+        (ZH_CN, "cancelled") => "发送已取消",
+        (ZH_CN, "dust_change") => "本次发送的找零过小，请调整金额或将找零计入手续费",
+        _ => return None,
+    };
+    Some(template)
+}
+
+/// The message of a key in the current locale, with its `{param}`s replaced, or none for a
+/// key without a translation
+pub fn message(key: &str, params: &Value) -> Option<String> {
+    let mut message = template(get(), key)?.to_owned();
+    if let Value::Object(params) = params {
+        for (name, value) in params {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            };
+            message = message.replace(&format!("{{{}}}", name), &value);
+        }
+    }
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_locales() {
+        assert_eq!(supported("en-US"), Some(EN));
+        assert_eq!(supported(" EN "), Some(EN));
+        assert_eq!(supported("zh_CN"), Some(ZH_CN));
+        assert_eq!(supported("zh-Hans-CN"), Some(ZH_CN));
+        assert_eq!(supported("zh-TW"), None);
+        assert_eq!(supported("fr"), None);
+    }
+
+    #[test]
+    fn template_of_a_key() {
+        let message = template(EN, "generic").unwrap();
+        assert!(message.contains("{detail}"));
+        assert!(template(EN, "unknown").is_none());
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The validation of the user messages of the slates, sent or received, before they reach the
+//! slate: NFC normalized, trimmed, without the control and bidi format characters, and below
+//! a max size, so a malformed message gets a clear error instead of failing in the slate
+//! serialization, or a spoofed text in the counterparty's tx list.
+
+use unicode_normalization::UnicodeNormalization;
+
+use grin_wallet_impls::{Error, ErrorKind};
+
+/// Max size of a slate message, in bytes of its normalized utf-8
+pub const MAX_MESSAGE_BYTES: usize = 256;
+
+/// The control characters, and the zero width and bidi override format characters
+fn is_control(c: char) -> bool {
+    c.is_control()
+        || ('\u{200B}'..='\u{200F}').contains(&c)
+        || ('\u{202A}'..='\u{202E}').contains(&c)
+        || ('\u{2066}'..='\u{2069}').contains(&c)
+        || c == '\u{FEFF}'
+}
+
+/// Clean a slate message, none if empty. A control character is stripped, or a whitespace one
+/// replaced by a space, unless `strict` where it's refused. A message above the max size is
+/// always refused, it's not cut silently.
+pub fn clean(message: Option<&str>, strict: bool) -> Result<Option<String>, Error> {
+    let message = match message {
+        Some(message) => message,
+        None => return Ok(None),
+    };
+    let mut cleaned = String::with_capacity(message.len());
+    for (i, c) in message.nfc().enumerate() {
+        if !is_control(c) {
+            cleaned.push(c);
+        } else if strict {
+            return Err(ErrorKind::ArgumentError(format!(
+                "message has the control character U+{:04X} at position {}",
+                c as u32, i
+            ))
+            .into());
+        } else if c.is_whitespace() {
+            cleaned.push(' ');
+        }
+    }
+    let cleaned = cleaned.trim();
+    if cleaned.len() > MAX_MESSAGE_BYTES {
+        return Err(ErrorKind::ArgumentError(format!(
+            "message of {} bytes is above the max of {} bytes",
+            cleaned.len(),
+            MAX_MESSAGE_BYTES
+        ))
+        .into());
+    }
+    Ok(Some(cleaned.to_owned()).filter(|m| !m.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn none_stays_none() {
+        assert_eq!(clean(None, true).unwrap(), None);
+    }
+
+    #[test]
+    fn control_characters_are_dropped() {
+        let cleaned = clean(Some(" pay\u{202E}ment\tfor\u{0}\u{200B} tea "), false).unwrap();
+        assert_eq!(cleaned.as_deref(), Some("payment for tea"));
+    }
+
+    #[test]
+    fn strict_refuses_control_characters() {
+        assert!(clean(Some("pay\u{202E}ment"), true).is_err());
+        assert_eq!(
+            clean(Some("payment"), true).unwrap().as_deref(),
+            Some("payment")
+        );
+    }
+
+    #[test]
+    fn message_is_nfc_and_bounded() {
+        let cleaned = clean(Some("cafe\u{301}"), true).unwrap();
+        assert_eq!(cleaned.as_deref(), Some("caf\u{e9}"));
+        assert!(clean(Some(&"a".repeat(MAX_MESSAGE_BYTES + 1)), false).is_err());
+    }
+}
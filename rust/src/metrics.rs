@@ -0,0 +1,137 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Opt-in usage metrics of the extern calls: the calls, failures and durations per function,
+//! and the failures per error code, for the app developers to find the hot and slow calls in
+//! the field. Off by default, a disabled call costs an atomic load.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use serde_json::json;
+
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::last_error;
+
+#[derive(Default)]
+struct FunctionMetrics {
+    calls: u64,
+    failures: u64,
+    avg_ms: f64,
+    max_ms: f64,
+    total_ms: f64,
+}
+
+#[derive(Default)]
+struct Metrics {
+    functions: BTreeMap<&'static str, FunctionMetrics>,
+    failures_by_code: BTreeMap<u32, u64>,
+}
+
+lazy_static! {
+    static ref ENABLED: AtomicBool = AtomicBool::new(false);
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// A running extern call, accounted when dropped by the conversion of its result, failed if
+/// it set the last error of the calling thread
+pub struct Call {
+    function: &'static str,
+    started: Option<Instant>,
+}
+
+/// Start accounting an extern call, if enabled
+pub fn call(function: &'static str) -> Call {
+    Call {
+        function,
+        started: if is_enabled() {
+            Some(Instant::now())
+        } else {
+            None
+        },
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        let started = match self.started {
+            Some(started) => started,
+            None => return,
+        };
+        let elapsed_ms = started.elapsed().as_micros() as f64 / 1000.0;
+        let code = last_error::code();
+
+        let mut metrics = METRICS.lock();
+        let function = metrics.functions.entry(self.function).or_default();
+        function.calls += 1;
+        function.total_ms += elapsed_ms;
+        function.avg_ms = function.total_ms / function.calls as f64;
+        if elapsed_ms > function.max_ms {
+            function.max_ms = elapsed_ms;
+        }
+        if code != last_error::CODE_NONE {
+            function.failures += 1;
+            *metrics.failures_by_code.entry(code).or_default() += 1;
+        }
+    }
+}
+
+/// The metrics per function, the slowest on average first, with the failures per error code
+pub fn snapshot() -> serde_json::Value {
+    let metrics = METRICS.lock();
+    let mut functions: Vec<(&str, &FunctionMetrics)> =
+        metrics.functions.iter().map(|(k, v)| (*k, v)).collect();
+    functions.sort_by(|a, b| {
+        b.1.avg_ms
+            .partial_cmp(&a.1.avg_ms)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let functions: Vec<serde_json::Value> = functions
+        .into_iter()
+        .map(|(name, f)| {
+            json!({
+                "function": name,
+                "calls": f.calls,
+                "failures": f.failures,
+                "avgMs": f.avg_ms,
+                "maxMs": f.max_ms,
+            })
+        })
+        .collect();
+    let failures_by_code: BTreeMap<String, u64> = metrics
+        .failures_by_code
+        .iter()
+        .map(|(code, count)| (code.to_string(), *count))
+        .collect();
+    json!({
+        "enabled": is_enabled(),
+        "functions": functions,
+        "failuresByCode": failures_by_code,
+    })
+}
+
+/// Restart the accounting
+pub fn reset() {
+    *METRICS.lock() = Metrics::default();
+}
@@ -0,0 +1,245 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The upgrade of the wallet data directories written by the earlier releases of this lib.
+//! The wallet db and the seed encryption are the upstream ones and stay as they are, so no
+//! rescan is needed: the upgrade adds the lib records the earlier releases didn't write, and
+//! removes the leftovers of their interrupted operations. The changed files are copied into
+//! a backup directory first, the last `MAX_BACKUPS` ones being kept. An open wallet, one with
+//! running services, listeners or threads, is refused.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use grin_wallet_impls::{Error, ErrorKind};
+
+use crate::kdf;
+use crate::password::{self, SEED_FILE, SEED_ROLLBACK_FILE};
+use crate::{runtime, services};
+use crate::{store, NetworkInfo, NETWORK_INFO_FILE};
+
+/// The prefix of the backup directories, inside the wallet data directory
+const BACKUP_DIR_PREFIX: &str = "migration_backup_";
+
+/// The kept backup directories of a wallet data directory, the oldest ones are removed
+const MAX_BACKUPS: usize = 3;
+
+/// The age of a leftover of an interrupted json save, a save in progress has a younger one
+const TMP_FILE_MIN_AGE: Duration = Duration::from_secs(60);
+
+/// How deep the wallet data directories are searched for below the `data_dir`, i.e.
+/// `<data_dir>/<chain type>/<wallet name>/wallet_data`
+const MAX_SEARCH_DEPTH: usize = 3;
+
+/// The upgrade of a wallet data directory
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Migration {
+    pub data_file_dir: String,
+    /// The chain type of the wallet data, if known
+    pub chain_type: Option<String>,
+    /// The backup of the changed files, if anything changed
+    pub backup_dir: Option<String>,
+    pub changes: Vec<String>,
+    /// What was found but left as is
+    pub notes: Vec<String>,
+}
+
+fn io_error(what: &str, e: std::io::Error) -> Error {
+    ErrorKind::GenericError(format!("fail to {}: {}", what, e)).into()
+}
+
+/// The wallet data directories below `dir`, the ones with a seed file
+fn find_wallet_dirs(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    if dir.join(SEED_FILE).is_file() {
+        found.push(dir.to_path_buf());
+        return;
+    }
+    if depth == 0 {
+        return;
+    }
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                find_wallet_dirs(&path, depth - 1, found);
+            }
+        }
+    }
+}
+
+/// The chain type of a namespaced wallet data directory, from its path
+fn chain_type_of(dir: &Path) -> Option<String> {
+    dir.ancestors()
+        .filter_map(|a| a.file_name())
+        .filter_map(|name| name.to_str())
+        .find(|name| runtime::parse_chain_type(name).is_ok())
+        .map(|name| name.to_owned())
+}
+
+/// Whether a file was last modified at least `age` ago
+fn is_older_than(path: &Path, age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .map_or(false, |elapsed| elapsed >= age)
+}
+
+/// The leftovers of the interrupted json saves
+fn tmp_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && p.extension().map_or(false, |ext| ext == "tmp"))
+                .filter(|p| is_older_than(p, TMP_FILE_MIN_AGE))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Remove the oldest backup directories, keeping the last `MAX_BACKUPS`
+fn prune_backups(dir: &Path) -> Result<(), Error> {
+    let entries = fs::read_dir(dir).map_err(|e| io_error("read the wallet data dir", e))?;
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name()
+                    .and_then(|name| name.to_str())
+                    .map_or(false, |name| name.starts_with(BACKUP_DIR_PREFIX))
+        })
+        .collect();
+    // The names end with their creation time, the same number of digits for centuries
+    backups.sort();
+    let excess = backups.len().saturating_sub(MAX_BACKUPS);
+    for backup_dir in backups.into_iter().take(excess) {
+        fs::remove_dir_all(&backup_dir).map_err(|e| io_error("remove an old backup dir", e))?;
+    }
+    Ok(())
+}
+
+/// Copy the files of the wallet data directory, not its db, into a new backup directory
+fn backup(dir: &Path) -> Result<PathBuf, Error> {
+    let backup_dir = dir.join(format!("{}{}", BACKUP_DIR_PREFIX, store::now_secs()));
+    fs::create_dir_all(&backup_dir).map_err(|e| io_error("create the backup dir", e))?;
+    let entries = fs::read_dir(dir).map_err(|e| io_error("read the wallet data dir", e))?;
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if let (true, Some(name)) = (path.is_file(), path.file_name()) {
+            // The rollback copy of the seed would keep the old password valid
+            if name == SEED_ROLLBACK_FILE {
+                continue;
+            }
+            fs::copy(&path, backup_dir.join(name)).map_err(|e| io_error("back up a file", e))?;
+        }
+    }
+    Ok(backup_dir)
+}
+
+fn migrate_dir(dir: &Path) -> Result<Migration, Error> {
+    let data_file_dir = dir.to_string_lossy().into_owned();
+    if services::is_wallet_open(dir) {
+        return Err(ErrorKind::GenericError(format!(
+            "wallet {} is open, close it before the migration",
+            data_file_dir
+        ))
+        .into());
+    }
+    // No password change or seed lookup runs in between
+    let lock = kdf::dir_lock(&data_file_dir);
+    let _lock = lock.lock();
+    let network_file = store::data_file(&data_file_dir, NETWORK_INFO_FILE);
+    let mut chain_type = if network_file.exists() {
+        Some(store::load::<NetworkInfo>(&network_file)?.chain_type)
+    } else {
+        None
+    };
+    let mut migration = Migration {
+        data_file_dir: data_file_dir.clone(),
+        chain_type: chain_type.clone(),
+        backup_dir: None,
+        changes: vec![],
+        notes: vec![],
+    };
+
+    let missing_network = chain_type.is_none();
+    if missing_network {
+        chain_type = chain_type_of(dir);
+        if chain_type.is_none() {
+            migration.notes.push(
+                "no chain type record, the configured chain type adopts it on the first open"
+                    .to_owned(),
+            );
+        }
+    }
+    let tmp_files = tmp_files(dir);
+    let interrupted_change = password::interrupted(&data_file_dir);
+
+    let changed =
+        (missing_network && chain_type.is_some()) || !tmp_files.is_empty() || interrupted_change;
+    if changed {
+        migration.backup_dir = Some(backup(dir)?.to_string_lossy().into_owned());
+        prune_backups(dir)?;
+    }
+
+    if let (true, Some(chain_type)) = (missing_network, chain_type.as_ref()) {
+        let info = NetworkInfo {
+            chain_type: chain_type.clone(),
+            created_at: store::now_secs(),
+        };
+        store::save(&network_file, &info)?;
+        migration.chain_type = Some(chain_type.clone());
+        migration
+            .changes
+            .push(format!("recorded the chain type {}", chain_type));
+    }
+    for tmp_file in tmp_files {
+        fs::remove_file(&tmp_file).map_err(|e| io_error("remove a leftover file", e))?;
+        migration.changes.push(format!(
+            "removed the leftover of an interrupted save {:?}",
+            tmp_file
+        ));
+    }
+    if interrupted_change {
+        password::clean_up(&data_file_dir);
+        migration
+            .changes
+            .push("removed the leftovers of an interrupted password change".to_owned());
+    }
+
+    if kdf::current(&data_file_dir)?.params.algorithm == kdf::KdfAlgorithm::Legacy {
+        migration.notes.push(
+            "the seed password has the legacy key derivation, see grin_seed_kdf_migrate".to_owned(),
+        );
+    }
+    Ok(migration)
+}
+
+/// Upgrade the wallet data directories below `data_dir`, to be done before opening them
+pub fn run(data_dir: &str) -> Result<Vec<Migration>, Error> {
+    let root = Path::new(data_dir);
+    if !root.is_dir() {
+        return Err(ErrorKind::ArgumentError(format!("{} is not a directory", data_dir)).into());
+    }
+    let mut dirs = vec![];
+    find_wallet_dirs(root, MAX_SEARCH_DEPTH, &mut dirs);
+    dirs.sort();
+    dirs.iter().map(|dir| migrate_dir(dir)).collect()
+}
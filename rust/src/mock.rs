@@ -0,0 +1,270 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory node for the app UI tests, built with the `mock-node` feature only.
+//! The chain is a list of outputs, a posted tx is mined right away in a new block.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use grin_wallet_api::{Foreign, Owner};
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{self as libwallet, BlockFees, NodeClient, NodeVersionInfo, TxWrapper};
+use grin_wallet_util::grin_core::core::Transaction;
+use grin_wallet_util::grin_core::{global, ser};
+use grin_wallet_util::grin_util::secp::pedersen;
+use grin_wallet_util::grin_util::{from_hex, to_hex, Mutex};
+
+use crate::{get_wallet_instance, node, wallet_init_recover, MobileWalletCfg};
+
+/// The mnemonic of the fixture wallet, the same wallet data on every run
+pub const FIXTURE_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon \
+                                    abandon abandon abandon abandon about";
+
+/// The password of the fixture wallet
+pub const FIXTURE_PASSWORD: &str = "mock";
+
+const MOCK_NODE_URL: &str = "mock://node";
+
+struct MockOutput {
+    commit: pedersen::Commitment,
+    proof: pedersen::RangeProof,
+    is_coinbase: bool,
+    height: u64,
+    spent: bool,
+}
+
+#[derive(Default)]
+struct MockChain {
+    height: u64,
+    /// The outputs by insertion order, the mmr index is the position + 1
+    outputs: Vec<MockOutput>,
+}
+
+impl MockChain {
+    fn add_output(&mut self, output: MockOutput) {
+        self.outputs.push(output);
+    }
+
+    fn find(&self, commit: &pedersen::Commitment) -> Option<(usize, &MockOutput)> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .find(|(_, o)| !o.spent && o.commit == *commit)
+    }
+}
+
+lazy_static! {
+    static ref ENABLED: AtomicBool = AtomicBool::new(false);
+    static ref CHAIN: Mutex<MockChain> = Mutex::new(MockChain::default());
+}
+
+/// Switch the wallets to the mock node, with an empty chain if it wasn't enabled. The chain
+/// of an enabled mock node is kept.
+pub fn set_enabled(enabled: bool) {
+    let mut chain = CHAIN.lock();
+    if !ENABLED.swap(enabled, Ordering::SeqCst) {
+        *chain = MockChain::default();
+    }
+}
+
+/// Run `f` on the mock node with an empty chain, the chain and the enabled state before are
+/// restored afterwards. The wallets of the app on the mock node meanwhile see the scratch
+/// chain.
+pub fn with_scratch_chain<T, F: FnOnce() -> T>(f: F) -> T {
+    let saved = std::mem::take(&mut *CHAIN.lock());
+    let was_enabled = ENABLED.swap(true, Ordering::SeqCst);
+    node::clear_cache();
+    let res = f();
+    *CHAIN.lock() = saved;
+    ENABLED.store(was_enabled, Ordering::SeqCst);
+    node::clear_cache();
+    res
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// The in-memory node client
+#[derive(Clone)]
+pub struct MockNodeClient {
+    node_url: String,
+}
+
+impl MockNodeClient {
+    pub fn new() -> Self {
+        MockNodeClient {
+            node_url: MOCK_NODE_URL.to_owned(),
+        }
+    }
+}
+
+impl Default for MockNodeClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NodeClient for MockNodeClient {
+    fn node_url(&self) -> &str {
+        &self.node_url
+    }
+
+    fn set_node_url(&mut self, node_url: &str) {
+        self.node_url = node_url.to_owned();
+    }
+
+    fn node_api_secret(&self) -> Option<String> {
+        None
+    }
+
+    fn set_node_api_secret(&mut self, _node_api_secret: Option<String>) {}
+
+    fn post_tx(&self, tx: &TxWrapper, _fluff: bool) -> Result<(), libwallet::Error> {
+        let bytes = from_hex(tx.tx_hex.clone())
+            .map_err(|e| libwallet::ErrorKind::ClientCallback(format!("mock post: {}", e)))?;
+        let tx: Transaction = ser::deserialize(&mut &bytes[..])
+            .map_err(|e| libwallet::ErrorKind::ClientCallback(format!("mock post: {}", e)))?;
+
+        let mut chain = CHAIN.lock();
+        for input in tx.inputs() {
+            let index = chain
+                .find(&input.commitment())
+                .map(|(i, _)| i)
+                .ok_or_else(|| {
+                    libwallet::ErrorKind::ClientCallback("mock post: input not found".to_owned())
+                })?;
+            chain.outputs[index].spent = true;
+        }
+        chain.height += 1;
+        let height = chain.height;
+        for output in tx.outputs() {
+            chain.add_output(MockOutput {
+                commit: output.commitment(),
+                proof: output.proof,
+                is_coinbase: false,
+                height,
+                spent: false,
+            });
+        }
+        Ok(())
+    }
+
+    fn get_version_info(&mut self) -> Option<NodeVersionInfo> {
+        Some(NodeVersionInfo {
+            node_version: "mock".to_owned(),
+            block_header_version: 2,
+            verified: Some(true),
+        })
+    }
+
+    fn get_chain_height(&self) -> Result<u64, libwallet::Error> {
+        Ok(CHAIN.lock().height)
+    }
+
+    fn get_outputs_from_node(
+        &self,
+        wallet_outputs: Vec<pedersen::Commitment>,
+    ) -> Result<HashMap<pedersen::Commitment, (String, u64, u64)>, libwallet::Error> {
+        let chain = CHAIN.lock();
+        Ok(wallet_outputs
+            .into_iter()
+            .filter_map(|commit| {
+                chain
+                    .find(&commit)
+                    .map(|(i, o)| (commit, (to_hex(commit.0.to_vec()), o.height, i as u64 + 1)))
+            })
+            .collect())
+    }
+
+    fn get_outputs_by_pmmr_index(
+        &self,
+        start_height: u64,
+        max_outputs: u64,
+    ) -> Result<
+        (
+            u64,
+            u64,
+            Vec<(pedersen::Commitment, pedersen::RangeProof, bool, u64, u64)>,
+        ),
+        libwallet::Error,
+    > {
+        let chain = CHAIN.lock();
+        let highest_index = chain.outputs.len() as u64;
+        let start = start_height.max(1);
+        let outputs: Vec<_> = (start..=highest_index)
+            .take(max_outputs as usize)
+            .map(|index| (index, &chain.outputs[index as usize - 1]))
+            .filter(|(_, o)| !o.spent)
+            .map(|(index, o)| (o.commit, o.proof, o.is_coinbase, o.height, index))
+            .collect();
+        let last_retrieved_index = (start + max_outputs).saturating_sub(1).min(highest_index);
+        Ok((highest_index, last_retrieved_index, outputs))
+    }
+}
+
+/// Mine `blocks` coinbase outputs to the wallet, then enough empty blocks for them to mature
+pub fn fund(config: &MobileWalletCfg, blocks: u64) -> Result<(), Error> {
+    let wallet = get_wallet_instance(config.clone())?;
+    let foreign = Foreign::new(wallet.clone(), None);
+    for _ in 0..blocks {
+        let height = CHAIN.lock().height + 1;
+        let cb_data = foreign.build_coinbase(&BlockFees {
+            fees: 0,
+            height,
+            key_id: None,
+        })?;
+        let mut chain = CHAIN.lock();
+        chain.height = height;
+        chain.add_output(MockOutput {
+            commit: cb_data.output.commitment(),
+            proof: cb_data.output.proof,
+            is_coinbase: true,
+            height,
+            spent: false,
+        });
+    }
+    CHAIN.lock().height += global::coinbase_maturity();
+    // The chain changed behind the node client, so its cached responses are stale
+    node::clear_cache();
+    Owner::new(wallet).retrieve_summary_info(true, 1)?;
+    Ok(())
+}
+
+/// Create the fixture wallet in `data_dir` on the mock node, funded with `blocks` coinbases.
+/// Returns its config json.
+pub fn fixture(chain_type: &str, data_dir: &str, blocks: u64) -> Result<String, Error> {
+    fixture_with(chain_type, data_dir, FIXTURE_MNEMONIC, blocks)
+}
+
+/// Create a wallet of a given mnemonic on the mock node, like `fixture`
+pub fn fixture_with(
+    chain_type: &str,
+    data_dir: &str,
+    mnemonic: &str,
+    blocks: u64,
+) -> Result<String, Error> {
+    if !is_enabled() {
+        return Err(ErrorKind::GenericError("mock node is not enabled".to_owned()).into());
+    }
+    let mut config = MobileWalletCfg::default_for(chain_type, data_dir)?;
+    config.password = FIXTURE_PASSWORD.to_owned();
+    config.min_password_score = 0;
+    config.node_api_addr = MOCK_NODE_URL.to_owned();
+    let json_cfg = serde_json::to_string(&config).unwrap();
+    wallet_init_recover(&json_cfg, mnemonic)?;
+    fund(&config, blocks)?;
+    Ok(json_cfg)
+}
@@ -0,0 +1,142 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bandwidth accounting of the node queries and slate exchanges, the refresh throttling and the
+//! low-data mode policy.
+//! The byte counts are the json payload sizes, without the http and tls overhead.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::MobileWalletCfg;
+
+/// Min interval between two refreshes from the node in low-data mode
+pub const LOW_DATA_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Max batch size of the restore and check in low-data mode
+pub const LOW_DATA_BATCH_SIZE: u64 = 200;
+
+/// Slow down factor of the periodic background services in low-data mode
+pub const LOW_DATA_INTERVAL_FACTOR: u64 = 4;
+
+/// When the wallet queries refresh from the node, so the queries of several ui components
+/// don't each trigger a full outputs refresh
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RefreshPolicy {
+    /// Min interval between two refreshes from the node, no limit if 0
+    #[serde(default)]
+    pub min_interval_secs: u64,
+    /// Refresh only on an explicit `grin_refresh`, i.e. a pull-to-refresh
+    #[serde(default)]
+    pub manual_only: bool,
+}
+
+#[derive(Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationStats {
+    pub requests: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+lazy_static! {
+    static ref STATS: Mutex<BTreeMap<String, OperationStats>> = Mutex::new(BTreeMap::new());
+    /// The last refresh from the node, per wallet data dir
+    static ref LAST_REFRESH: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Account a request of an operation, i.e. "node.get_outputs" or "relay.send_slate"
+pub fn record(operation: &str, bytes_sent: u64, bytes_received: u64) {
+    let mut stats = STATS.lock();
+    let op = stats.entry(operation.to_owned()).or_default();
+    op.requests += 1;
+    op.bytes_sent += bytes_sent;
+    op.bytes_received += bytes_received;
+}
+
+/// The stats per operation, with the totals
+pub fn snapshot() -> serde_json::Value {
+    let stats = STATS.lock();
+    let total = stats.values().fold(OperationStats::default(), |mut t, op| {
+        t.requests += op.requests;
+        t.bytes_sent += op.bytes_sent;
+        t.bytes_received += op.bytes_received;
+        t
+    });
+    json!({
+        "total": total,
+        "operations": *stats,
+    })
+}
+
+/// Restart the accounting
+pub fn reset() {
+    STATS.lock().clear();
+}
+
+/// Whether the wallet should refresh from the node before answering a query. Never with a
+/// manual only policy, otherwise at most once per the policy interval, and per
+/// `LOW_DATA_REFRESH_INTERVAL` in low-data mode. Always if there's no limit.
+pub fn should_refresh(config: &MobileWalletCfg) -> bool {
+    let policy = config.refresh_policy.clone().unwrap_or_default();
+    if policy.manual_only {
+        return false;
+    }
+    let mut min_interval = Duration::from_secs(policy.min_interval_secs);
+    if config.low_data_mode {
+        min_interval = min_interval.max(LOW_DATA_REFRESH_INTERVAL);
+    }
+    if min_interval == Duration::from_secs(0) {
+        return true;
+    }
+    // The refresh is accounted at the decision, so the concurrent queries don't all refresh
+    let mut last_refresh = LAST_REFRESH.lock();
+    match last_refresh.get(&config.data_dir) {
+        Some(at) if at.elapsed() < min_interval => false,
+        _ => {
+            last_refresh.insert(config.data_dir.clone(), Instant::now());
+            true
+        }
+    }
+}
+
+/// Account an explicit refresh, which restarts the throttling interval
+pub fn refreshed(config: &MobileWalletCfg) {
+    LAST_REFRESH
+        .lock()
+        .insert(config.data_dir.clone(), Instant::now());
+}
+
+/// The batch size of a restore or check, capped in low-data mode
+pub fn batch_size(config: &MobileWalletCfg, batch_size: u64) -> u64 {
+    if config.low_data_mode {
+        batch_size.min(LOW_DATA_BATCH_SIZE)
+    } else {
+        batch_size
+    }
+}
+
+/// The interval of a periodic background service, slowed down in low-data mode
+pub fn service_interval(config: &MobileWalletCfg, interval_secs: u64) -> u64 {
+    if config.low_data_mode {
+        interval_secs.saturating_mul(LOW_DATA_INTERVAL_FACTOR)
+    } else {
+        interval_secs
+    }
+}
@@ -0,0 +1,442 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The node client of the wallets, and the direct queries of the node api for the network
+//! information not covered by the wallet api
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use grin_wallet_impls::{Error, ErrorKind, HTTPNodeClient};
+use grin_wallet_libwallet::{self as libwallet, NodeClient, NodeVersionInfo, TxWrapper};
+use grin_wallet_util::grin_api;
+use grin_wallet_util::grin_util::secp::pedersen;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::errors::LibErrorKind;
+use crate::{netstats, new_wallet_config, MobileWalletCfg};
+
+/// Estimated json size of a commitment in a request or response
+const COMMIT_JSON_SIZE: u64 = 70;
+
+/// Estimated json size of an output with its range proof, as listed by pmmr index
+const OUTPUT_WITH_PROOF_JSON_SIZE: u64 = 1_500;
+
+/// Estimated json size of a small node response, i.e. the chain tip or the version
+const SMALL_RESPONSE_JSON_SIZE: u64 = 200;
+
+/// How long a node response is reused, long enough to serve a burst of ui driven calls
+const NODE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Max number of cached responses, the cache is cleared when it's full
+const NODE_CACHE_MAX_ENTRIES: usize = 100;
+
+type NodeOutputs = HashMap<pedersen::Commitment, (String, u64, u64)>;
+type PmmrOutputs = (
+    u64,
+    u64,
+    Vec<(pedersen::Commitment, pedersen::RangeProof, bool, u64, u64)>,
+);
+
+#[derive(Clone)]
+enum CachedResponse {
+    ChainHeight(u64),
+    Outputs(NodeOutputs),
+    PmmrOutputs(PmmrOutputs),
+    Json(Value),
+}
+
+/// Prefix of the error of a node call of the wallet libs failed on the node. Such an error
+/// reaches the lib as an upstream `LibWallet` error keeping only its message, so it can't
+/// carry a `LibErrorKind::NodeUnreachable`.
+pub const NODE_UNREACHABLE_ERROR: &str = "node unreachable";
+
+lazy_static! {
+    /// Node responses by node url and request, shared by all the wallet instances
+    static ref NODE_CACHE: Mutex<HashMap<String, (Instant, CachedResponse)>> =
+        Mutex::new(HashMap::new());
+    /// The node urls whose last call failed
+    static ref UNREACHABLE: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+fn set_reachable(node_url: &str, reachable: bool) {
+    let mut unreachable = UNREACHABLE.lock();
+    if reachable {
+        unreachable.remove(node_url);
+    } else {
+        unreachable.insert(node_url.to_owned());
+    }
+}
+
+/// Whether the last call to the configured node failed
+pub fn is_unreachable(config: &MobileWalletCfg) -> bool {
+    new_wallet_config(config.clone())
+        .map(|c| UNREACHABLE.lock().contains(&c.check_node_api_http_addr))
+        .unwrap_or(false)
+}
+
+/// Track the reachability of a node by the result of a call, a failure being turned into a
+/// `NODE_UNREACHABLE_ERROR`
+fn track<T>(node_url: &str, res: Result<T, libwallet::Error>) -> Result<T, libwallet::Error> {
+    set_reachable(node_url, res.is_ok());
+    res.map_err(|e| {
+        libwallet::ErrorKind::ClientCallback(format!(
+            "{} {}: {}",
+            NODE_UNREACHABLE_ERROR, node_url, e
+        ))
+        .into()
+    })
+}
+
+fn cache_get(key: &str) -> Option<CachedResponse> {
+    let cache = NODE_CACHE.lock();
+    match cache.get(key) {
+        Some((at, response)) if at.elapsed() < NODE_CACHE_TTL => Some(response.clone()),
+        _ => None,
+    }
+}
+
+fn cache_put(key: String, response: CachedResponse) {
+    let mut cache = NODE_CACHE.lock();
+    if cache.len() >= NODE_CACHE_MAX_ENTRIES {
+        cache.retain(|_, (at, _)| at.elapsed() < NODE_CACHE_TTL);
+        if cache.len() >= NODE_CACHE_MAX_ENTRIES {
+            cache.clear();
+        }
+    }
+    cache.insert(key, (Instant::now(), response));
+}
+
+/// Drop the cached responses of a node, i.e. once a tx is posted to it
+fn cache_invalidate(node_url: &str) {
+    let prefix = format!("{}|", node_url);
+    NODE_CACHE.lock().retain(|key, _| !key.starts_with(&prefix));
+}
+
+/// Drop all the cached node responses
+pub fn clear_cache() {
+    NODE_CACHE.lock().clear();
+    UNREACHABLE.lock().clear();
+}
+
+#[cfg(not(feature = "mock-node"))]
+type InnerNodeClient = HTTPNodeClient;
+
+/// The http node, or the in-memory one once the testing mode is enabled
+#[cfg(feature = "mock-node")]
+#[derive(Clone)]
+enum InnerNodeClient {
+    Http(HTTPNodeClient),
+    Mock(crate::mock::MockNodeClient),
+}
+
+#[cfg(feature = "mock-node")]
+macro_rules! inner {
+    ($client:expr, $inner:ident => $e:expr) => {
+        match $client {
+            InnerNodeClient::Http($inner) => $e,
+            InnerNodeClient::Mock($inner) => $e,
+        }
+    };
+}
+
+#[cfg(not(feature = "mock-node"))]
+macro_rules! inner {
+    ($client:expr, $inner:ident => $e:expr) => {{
+        let $inner = $client;
+        $e
+    }};
+}
+
+/// The http node client, with the bandwidth accounting and the short-lived response cache.
+/// Only the read queries are cached, a posted tx invalidates the cache of its node.
+#[derive(Clone)]
+pub struct MobileNodeClient {
+    inner: InnerNodeClient,
+}
+
+impl MobileNodeClient {
+    #[cfg(not(feature = "mock-node"))]
+    pub fn new(node_url: &str, node_api_secret: Option<String>) -> Self {
+        MobileNodeClient {
+            inner: HTTPNodeClient::new(node_url, node_api_secret),
+        }
+    }
+
+    #[cfg(feature = "mock-node")]
+    pub fn new(node_url: &str, node_api_secret: Option<String>) -> Self {
+        let inner = if crate::mock::is_enabled() {
+            InnerNodeClient::Mock(crate::mock::MockNodeClient::new())
+        } else {
+            InnerNodeClient::Http(HTTPNodeClient::new(node_url, node_api_secret))
+        };
+        MobileNodeClient { inner }
+    }
+
+    fn cache_key(&self, request: &str) -> String {
+        format!("{}|{}", self.node_url(), request)
+    }
+}
+
+impl NodeClient for MobileNodeClient {
+    fn node_url(&self) -> &str {
+        inner!(&self.inner, c => c.node_url())
+    }
+
+    fn set_node_url(&mut self, node_url: &str) {
+        inner!(&mut self.inner, c => c.set_node_url(node_url))
+    }
+
+    fn node_api_secret(&self) -> Option<String> {
+        inner!(&self.inner, c => c.node_api_secret())
+    }
+
+    fn set_node_api_secret(&mut self, node_api_secret: Option<String>) {
+        inner!(&mut self.inner, c => c.set_node_api_secret(node_api_secret))
+    }
+
+    fn post_tx(&self, tx: &TxWrapper, fluff: bool) -> Result<(), libwallet::Error> {
+        let sent = serde_json::to_string(tx).map(|s| s.len()).unwrap_or(0) as u64;
+        netstats::record("node.post_tx", sent, 0);
+        let res = inner!(&self.inner, c => c.post_tx(tx, fluff));
+        cache_invalidate(self.node_url());
+        res
+    }
+
+    fn get_version_info(&mut self) -> Option<NodeVersionInfo> {
+        netstats::record("node.get_version_info", 0, SMALL_RESPONSE_JSON_SIZE);
+        inner!(&mut self.inner, c => c.get_version_info())
+    }
+
+    fn get_chain_height(&self) -> Result<u64, libwallet::Error> {
+        let key = self.cache_key("chain_height");
+        if let Some(CachedResponse::ChainHeight(height)) = cache_get(&key) {
+            return Ok(height);
+        }
+        netstats::record("node.get_chain_height", 0, SMALL_RESPONSE_JSON_SIZE);
+        let height = track(
+            self.node_url(),
+            inner!(&self.inner, c => c.get_chain_height()),
+        )?;
+        cache_put(key, CachedResponse::ChainHeight(height));
+        Ok(height)
+    }
+
+    fn get_outputs_from_node(
+        &self,
+        wallet_outputs: Vec<pedersen::Commitment>,
+    ) -> Result<NodeOutputs, libwallet::Error> {
+        let mut hasher = DefaultHasher::new();
+        for commit in &wallet_outputs {
+            commit.0.hash(&mut hasher);
+        }
+        let key = self.cache_key(&format!("outputs:{:x}", hasher.finish()));
+        if let Some(CachedResponse::Outputs(outputs)) = cache_get(&key) {
+            return Ok(outputs);
+        }
+
+        let sent = wallet_outputs.len() as u64 * COMMIT_JSON_SIZE;
+        let outputs = track(
+            self.node_url(),
+            inner!(&self.inner, c => c.get_outputs_from_node(wallet_outputs)),
+        )?;
+        netstats::record(
+            "node.get_outputs",
+            sent,
+            outputs.len() as u64 * COMMIT_JSON_SIZE * 2,
+        );
+        cache_put(key, CachedResponse::Outputs(outputs.clone()));
+        Ok(outputs)
+    }
+
+    fn get_outputs_by_pmmr_index(
+        &self,
+        start_height: u64,
+        max_outputs: u64,
+    ) -> Result<PmmrOutputs, libwallet::Error> {
+        let key = self.cache_key(&format!("pmmr:{}:{}", start_height, max_outputs));
+        if let Some(CachedResponse::PmmrOutputs(outputs)) = cache_get(&key) {
+            return Ok(outputs);
+        }
+
+        let outputs = track(
+            self.node_url(),
+            inner!(&self.inner, c => {
+                c.get_outputs_by_pmmr_index(start_height, max_outputs)
+            }),
+        )?;
+        netstats::record(
+            "node.get_outputs_by_pmmr_index",
+            0,
+            outputs.2.len() as u64 * OUTPUT_WITH_PROOF_JSON_SIZE,
+        );
+        cache_put(key, CachedResponse::PmmrOutputs(outputs.clone()));
+        Ok(outputs)
+    }
+}
+
+/// The HTTP status of a failed node api request. The api client reports a response status
+/// as a `RequestError` "Wrong response code: <status> ...", without a status field.
+fn response_status(e: &grin_api::Error) -> Option<u16> {
+    const WRONG_CODE: &str = "Wrong response code: ";
+    match e.kind() {
+        grin_api::ErrorKind::NotFound => Some(404),
+        grin_api::ErrorKind::RequestError(msg) | grin_api::ErrorKind::ResponseError(msg)
+            if msg.starts_with(WRONG_CODE) =>
+        {
+            msg[WRONG_CODE.len()..]
+                .split_whitespace()
+                .next()
+                .and_then(|status| status.parse().ok())
+        }
+        _ => None,
+    }
+}
+
+/// GET a node api path, i.e. "/v1/chain", with the configured node address and secret.
+/// None if the node has nothing at this path, a 404 response.
+fn get_opt<T: DeserializeOwned>(config: &MobileWalletCfg, path: &str) -> Result<Option<T>, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    let url = format!(
+        "{}{}",
+        wallet_config.check_node_api_http_addr.trim_end_matches('/'),
+        path
+    );
+    let key = format!("{}|get {}", wallet_config.check_node_api_http_addr, path);
+    let value = match cache_get(&key) {
+        Some(CachedResponse::Json(value)) => value,
+        _ => {
+            let res = grin_api::client::get::<Value>(&url, wallet_config.node_api_secret);
+            let not_found = res
+                .as_ref()
+                .err()
+                .map_or(false, |e| response_status(e) == Some(404));
+            set_reachable(
+                &wallet_config.check_node_api_http_addr,
+                res.is_ok() || not_found,
+            );
+            if not_found {
+                return Ok(None);
+            }
+            let value = res.map_err(|e| {
+                LibErrorKind::NodeUnreachable(format!("node api {} request failed: {}", path, e))
+            })?;
+            netstats::record(
+                &format!("node.get {}", path),
+                0,
+                value.to_string().len() as u64,
+            );
+            cache_put(key, CachedResponse::Json(value.clone()));
+            value
+        }
+    };
+    serde_json::from_value(value).map(Some).map_err(|e| {
+        ErrorKind::GenericError(format!("node api {} invalid response: {}", path, e)).into()
+    })
+}
+
+/// GET a node api path, which must exist
+fn get<T: DeserializeOwned>(config: &MobileWalletCfg, path: &str) -> Result<T, Error> {
+    get_opt(config, path)?
+        .ok_or_else(|| ErrorKind::GenericError(format!("node api {} not found", path)).into())
+}
+
+/// The peers connected with the node. It's an owner endpoint, which public nodes might refuse.
+pub fn peers(config: &MobileWalletCfg) -> Result<Value, Error> {
+    let peers: Vec<Value> = get(config, "/v1/peers/connected")?;
+    Ok(json!({
+        "count": peers.len(),
+        "peers": peers,
+    }))
+}
+
+/// The genesis block hash of the node chain, compared with the configured one if any
+pub fn check_genesis(config: &MobileWalletCfg, expected: Option<String>) -> Result<Value, Error> {
+    let header: Value = get(config, "/v1/headers/0")?;
+    let hash = header["hash"].as_str().unwrap_or_default().to_owned();
+    let matches = expected
+        .as_ref()
+        .map(|expected| expected.eq_ignore_ascii_case(&hash));
+    Ok(json!({
+        "chainType": config.chain_type,
+        "genesisHash": hash,
+        "expectedGenesisHash": expected,
+        "matches": matches,
+    }))
+}
+
+/// Check a commitment, or a kernel excess, is 33 bytes of hex
+fn check_commitment(commitment: &str) -> Result<(), Error> {
+    if commitment.len() != 66 || !commitment.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(
+            ErrorKind::ArgumentError(format!("invalid commitment '{}'", commitment)).into(),
+        );
+    }
+    Ok(())
+}
+
+/// An output of the node utxo set by commitment, with `unspent` false if it's not there, i.e.
+/// spent or never confirmed
+pub fn output_by_commitment(config: &MobileWalletCfg, commitment: &str) -> Result<Value, Error> {
+    check_commitment(commitment)?;
+    let outputs: Vec<Value> = get(
+        config,
+        &format!("/v1/chain/outputs/byids?id={}", commitment),
+    )?;
+    let output = outputs.into_iter().next();
+    Ok(json!({
+        "commitment": commitment,
+        "unspent": output.is_some(),
+        "height": output.as_ref().map(|o| o["height"].clone()),
+        "mmrIndex": output.as_ref().map(|o| o["mmr_index"].clone()),
+    }))
+}
+
+/// A block header of the node chain, by height or hash, null if the node doesn't have it
+pub fn header(config: &MobileWalletCfg, height_or_hash: &str) -> Result<Value, Error> {
+    let id = height_or_hash.trim().to_lowercase();
+    let is_hash = id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit());
+    if id.parse::<u64>().is_err() && !is_hash {
+        return Err(ErrorKind::ArgumentError(format!(
+            "invalid block height or hash '{}'",
+            height_or_hash
+        ))
+        .into());
+    }
+    Ok(get_opt(config, &format!("/v1/headers/{}", id))?.unwrap_or(Value::Null))
+}
+
+/// A tx kernel of the node chain by its excess, with the height of its block, null if not
+/// found, i.e. the tx isn't confirmed yet
+pub fn kernel(config: &MobileWalletCfg, excess: &str) -> Result<Value, Error> {
+    check_commitment(excess)?;
+    Ok(get_opt(config, &format!("/v1/chain/kernels/{}", excess))?.unwrap_or(Value::Null))
+}
+
+/// The transaction pool size of the node, with its chain tip
+pub fn mempool_stats(config: &MobileWalletCfg) -> Result<Value, Error> {
+    let pool: Value = get(config, "/v1/pool")?;
+    let tip: Value = get(config, "/v1/chain")?;
+    Ok(json!({
+        "poolSize": pool["pool_size"],
+        "tipHeight": tip["height"],
+        "tipHash": tip["last_block_pushed"],
+        "totalDifficulty": tip["total_difficulty"],
+    }))
+}
@@ -0,0 +1,382 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The wallet password: its change and its strength.
+//!
+//! On a change, the seed is re-encrypted into a temporary directory, checked, then renamed
+//! over the seed file, so an interruption leaves either the old or the new seed file whole,
+//! never a wallet which can't be opened.
+//!
+//! The strength estimate is done here rather than in the app, so all the platforms apply the
+//! same policy to a new password.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::json;
+
+use grin_wallet_impls::{Error, ErrorKind, WalletSeed};
+
+use crate::kdf::{self, KdfParams};
+use crate::{biometric, journal, new_wallet_config, MobileWalletCfg};
+
+/// The seed file of a wallet data dir
+pub const SEED_FILE: &str = "wallet.seed";
+
+/// The directory of the re-encrypted seed, before it replaces the seed file
+const PASSWORD_CHANGE_DIR: &str = "password_change";
+
+/// The copy of the seed file kept for a rollback, until the new one is checked
+pub const SEED_ROLLBACK_FILE: &str = "wallet.seed.rollback";
+
+fn io_error(what: &str, e: std::io::Error) -> Error {
+    ErrorKind::GenericError(format!("fail to {}: {}", what, e)).into()
+}
+
+fn paths(data_file_dir: &str) -> (PathBuf, PathBuf, PathBuf) {
+    let dir = Path::new(data_file_dir);
+    (
+        dir.join(SEED_FILE),
+        dir.join(PASSWORD_CHANGE_DIR),
+        dir.join(SEED_ROLLBACK_FILE),
+    )
+}
+
+/// Whether a change was interrupted, leaving its temporary dir or rollback copy
+pub fn interrupted(data_file_dir: &str) -> bool {
+    let (_, tmp_dir, rollback) = paths(data_file_dir);
+    tmp_dir.exists() || rollback.exists()
+}
+
+/// Remove the leftovers of an interrupted change. The rollback copy goes too, it would keep
+/// the old password valid.
+pub fn clean_up(data_file_dir: &str) {
+    let (_, tmp_dir, rollback) = paths(data_file_dir);
+    let _ = fs::remove_dir_all(tmp_dir);
+    let _ = fs::remove_file(rollback);
+}
+
+/// The re-encryption steps, the seed file being restored from the rollback copy if the new
+/// one can't be opened
+fn reencrypt(data_file_dir: &str, old_password: &str, new_password: &str) -> Result<(), Error> {
+    let (seed_file, tmp_dir, rollback) = paths(data_file_dir);
+    let seed = WalletSeed::from_file(data_file_dir, old_password)?;
+    let mnemonic = seed.to_mnemonic()?;
+
+    fs::create_dir_all(&tmp_dir).map_err(|e| io_error("create the temporary dir", e))?;
+    let tmp_dir_str = tmp_dir.to_string_lossy().into_owned();
+    WalletSeed::recover_from_phrase(&tmp_dir_str, &mnemonic, new_password)?;
+    if WalletSeed::from_file(&tmp_dir_str, new_password)?.to_mnemonic()? != mnemonic {
+        return Err(ErrorKind::GenericError("re-encrypted seed mismatch".to_owned()).into());
+    }
+
+    fs::copy(&seed_file, &rollback).map_err(|e| io_error("copy the seed file", e))?;
+    fs::rename(tmp_dir.join(SEED_FILE), &seed_file)
+        .map_err(|e| io_error("replace the seed file", e))?;
+    let reopened = WalletSeed::from_file(data_file_dir, new_password)
+        .and_then(|seed| seed.to_mnemonic())
+        .map(|m| m == mnemonic)
+        .unwrap_or(false);
+    if !reopened {
+        fs::rename(&rollback, &seed_file).map_err(|e| io_error("roll back the seed file", e))?;
+        return Err(ErrorKind::GenericError(
+            "new seed file can't be opened, rolled back".to_owned(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Re-encrypt the seed file for a new password and key derivation, the current key
+/// derivation with a new salt if `params` is None
+fn rewrap(
+    config: &MobileWalletCfg,
+    old_password: &str,
+    new_password: &str,
+    params: Option<&KdfParams>,
+) -> Result<(), Error> {
+    let data_file_dir = new_wallet_config(config.clone())?.data_file_dir;
+    // No open runs until the seed file and the key derivation are replaced
+    let lock = kdf::dir_lock(&data_file_dir);
+    let _guard = lock.lock();
+    let old_seed_password = kdf::seed_password_locked(&data_file_dir, old_password)?;
+    let params = match params {
+        Some(params) => params.clone(),
+        None => kdf::current(&data_file_dir)?.params,
+    };
+    clean_up(&data_file_dir);
+    let journal_id = journal::begin(
+        config,
+        journal::OP_PASSWORD_CHANGE,
+        journal::PHASE_INITIATED,
+        None,
+        json!({}),
+    )?;
+    let res = kdf::new_pending(&data_file_dir, &params)
+        .and_then(|pending| kdf::derive(&pending, new_password))
+        .and_then(|new_seed_password| {
+            reencrypt(&data_file_dir, &old_seed_password, &new_seed_password)
+        })
+        .and_then(|_| kdf::promote(&data_file_dir));
+    if res.is_err() {
+        kdf::discard(&data_file_dir);
+    }
+    clean_up(&data_file_dir);
+    journal::finish(config, &journal_id);
+    res
+}
+
+/// Re-wrap the seed file with another key derivation, the password being kept
+pub fn migrate_kdf(
+    config: &MobileWalletCfg,
+    password: &str,
+    params: &KdfParams,
+) -> Result<(), Error> {
+    rewrap(config, password, password, Some(params))
+}
+
+/// Change the wallet password, re-encrypting the seed. The wallet db of this version isn't
+/// encrypted, so there's no db key to rotate with it.
+pub fn change(
+    config: &MobileWalletCfg,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(), Error> {
+    check_strength(new_password, config.min_password_score)?;
+    rewrap(config, old_password, new_password, None)?;
+    // The biometric escrow holds the old password
+    biometric::revoke(&new_wallet_config(config.clone())?.data_file_dir)?;
+    Ok(())
+}
+
+/// The minimum strength score of a new password, see `strength`
+pub const MIN_PASSWORD_SCORE: u8 = 2;
+
+/// The most common passwords, the most common first
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "password",
+    "123456789",
+    "12345678",
+    "12345",
+    "qwerty",
+    "1234567",
+    "111111",
+    "1234567890",
+    "123123",
+    "abc123",
+    "1234",
+    "password1",
+    "iloveyou",
+    "1q2w3e4r",
+    "000000",
+    "qwerty123",
+    "zaq12wsx",
+    "dragon",
+    "sunshine",
+    "princess",
+    "letmein",
+    "654321",
+    "monkey",
+    "27653",
+    "1qaz2wsx",
+    "123321",
+    "qwertyuiop",
+    "superman",
+    "asdfghjkl",
+    "trustno1",
+    "welcome",
+    "admin",
+    "login",
+    "master",
+    "football",
+    "baseball",
+    "shadow",
+    "michael",
+    "passw0rd",
+    "starwars",
+    "whatever",
+    "freedom",
+    "hello",
+    "secret",
+    "bitcoin",
+    "grin",
+    "mimblewimble",
+    "wallet",
+    "crypto",
+];
+
+/// The keyboard rows, for the patterns like "qwerty" or "asdf"
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// The min length of a repeat, sequence or keyboard pattern
+const MIN_PATTERN_LEN: usize = 3;
+
+/// The estimated strength of a password
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordStrength {
+    /// Estimated guesses to find it, as bits
+    pub entropy_bits: f64,
+    /// 0 to 4, from "very_weak" to "very_strong"
+    pub score: u8,
+    pub classification: &'static str,
+    pub meets_minimum: bool,
+    /// Hints to make it stronger
+    pub feedback: Vec<&'static str>,
+}
+
+/// The size of the character set of a char
+fn pool_size(c: char) -> f64 {
+    if c.is_ascii_lowercase() || c.is_ascii_uppercase() {
+        26.0
+    } else if c.is_ascii_digit() {
+        10.0
+    } else if c.is_ascii() {
+        33.0
+    } else {
+        100.0
+    }
+}
+
+/// The length of the repeat, sequence or keyboard pattern at the start of some chars
+fn pattern_len(chars: &[char]) -> (usize, Option<&'static str>) {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let repeat = lower.iter().take_while(|c| **c == lower[0]).count();
+    let mut sequence = 1;
+    if lower.len() > 1 {
+        let step = lower[1] as i64 - lower[0] as i64;
+        if step == 1 || step == -1 {
+            sequence = 2 + lower
+                .windows(2)
+                .skip(1)
+                .take_while(|w| w[1] as i64 - w[0] as i64 == step)
+                .count();
+        }
+    }
+    let mut keyboard = 0;
+    for row in KEYBOARD_ROWS {
+        for row in &[row.to_string(), row.chars().rev().collect::<String>()] {
+            let len = (MIN_PATTERN_LEN..=lower.len())
+                .rev()
+                .find(|len| row.contains(&lower[..*len].iter().collect::<String>()))
+                .unwrap_or(0);
+            keyboard = keyboard.max(len);
+        }
+    }
+    let best = repeat.max(sequence).max(keyboard);
+    if best < MIN_PATTERN_LEN {
+        return (1, None);
+    }
+    let hint = if best == repeat {
+        "avoid repeated characters"
+    } else if best == sequence {
+        "avoid sequences like abc or 123"
+    } else {
+        "avoid keyboard patterns like qwerty"
+    };
+    (best, Some(hint))
+}
+
+/// The guesses of a password as bits, and the hints of the patterns found
+fn entropy(password: &str) -> (f64, Vec<&'static str>) {
+    let lower = password.to_lowercase();
+    let mut feedback = vec![];
+    if let Some(rank) = COMMON_PASSWORDS.iter().position(|p| *p == lower) {
+        feedback.push("this is a very common password");
+        return (((rank + 1) as f64).log2() + 1.0, feedback);
+    }
+
+    let chars: Vec<char> = password.chars().collect();
+    let pool: f64 = [
+        chars.iter().any(|c| c.is_ascii_lowercase()),
+        chars.iter().any(|c| c.is_ascii_uppercase()),
+        chars.iter().any(|c| c.is_ascii_digit()),
+        chars
+            .iter()
+            .any(|c| c.is_ascii() && !c.is_ascii_alphanumeric()),
+        chars.iter().any(|c| !c.is_ascii()),
+    ]
+    .iter()
+    .zip(&[26.0, 26.0, 10.0, 33.0, 100.0])
+    .filter(|(present, _)| **present)
+    .map(|(_, size)| size)
+    .sum();
+
+    // A common password with a few extra chars is only as strong as its extra chars
+    let mut bits = 0.0;
+    let mut start = 0;
+    if let Some(rank) = COMMON_PASSWORDS
+        .iter()
+        .position(|p| lower.starts_with(p) && lower.len() - p.len() <= 4)
+    {
+        feedback.push("avoid a common password with a few extra characters");
+        bits += ((rank + 1) as f64).log2() + 1.0;
+        start = COMMON_PASSWORDS[rank].chars().count();
+    }
+
+    let mut i = start;
+    while i < chars.len() {
+        let (len, hint) = pattern_len(&chars[i..]);
+        match hint {
+            Some(hint) => {
+                if !feedback.contains(&hint) {
+                    feedback.push(hint);
+                }
+                bits += pool_size(chars[i]).log2() + (len as f64).log2();
+            }
+            None => bits += pool.log2(),
+        }
+        i += len;
+    }
+    (bits, feedback)
+}
+
+/// Estimate the strength of a password, from the guesses of an attacker knowing the common
+/// passwords and patterns
+pub fn strength(password: &str) -> PasswordStrength {
+    let (bits, mut feedback) = entropy(password);
+    let (score, classification) = match bits {
+        b if b < 28.0 => (0, "very_weak"),
+        b if b < 36.0 => (1, "weak"),
+        b if b < 60.0 => (2, "fair"),
+        b if b < 80.0 => (3, "strong"),
+        _ => (4, "very_strong"),
+    };
+    if score < 3 && password.chars().count() < 12 {
+        feedback.push("use a longer password, or a few random words");
+    }
+    PasswordStrength {
+        entropy_bits: (bits * 10.0).round() / 10.0,
+        score,
+        classification,
+        meets_minimum: score >= MIN_PASSWORD_SCORE,
+        feedback,
+    }
+}
+
+/// Refuse a new password below a minimum strength score, no check if 0
+pub fn check_strength(password: &str, min_score: u8) -> Result<(), Error> {
+    let strength = strength(password);
+    if strength.score < min_score {
+        return Err(ErrorKind::ArgumentError(format!(
+            "password too weak, {}: {}",
+            strength.classification,
+            strength.feedback.join(", ")
+        ))
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,213 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The point-of-sale charges: a payment request of an amount to the relay address of the
+//! wallet, matched with the incoming payment received by the listener. A payment is matched
+//! to the pending charge of its amount whose id is in the sender message, or else to the only
+//! pending charge of its amount. The charge is received then, and paid once the tx monitor
+//! sees the tx confirmed.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::Slate;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::{events, new_wallet_config, store, MobileWalletCfg};
+
+const POS_CHARGES_FILE: &str = "pos_charges.json";
+
+/// Max number of kept charges, the oldest ones are dropped
+const MAX_CHARGES: usize = 1000;
+
+/// The expiry of a charge created without one
+const DEFAULT_EXPIRY_SECS: u64 = 900;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ChargeStatus {
+    Pending,
+    /// The payment is received, not confirmed yet
+    Received,
+    Paid,
+    Expired,
+}
+
+/// A payment request of the point of sale
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Charge {
+    pub id: String,
+    /// The relay address to pay to
+    pub address: String,
+    pub amount: u64,
+    pub memo: String,
+    pub status: ChargeStatus,
+    pub created_at: u64,
+    pub expires_at: u64,
+    /// The paying slate and its sender, once received
+    pub slate_id: Option<String>,
+    pub sender: Option<String>,
+    pub received_at: Option<u64>,
+    pub paid_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Charges {
+    charges: Vec<Charge>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the charges file
+    static ref POS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        POS_CHARGES_FILE,
+    ))
+}
+
+/// Update the charges, the lapsed pending ones expired firstly with a `charge_expired` event
+fn update<T, F: FnOnce(&mut Charges) -> T>(config: &MobileWalletCfg, f: F) -> Result<T, Error> {
+    let _guard = POS_LOCK.lock();
+    let path = data_path(config)?;
+    let mut charges: Charges = store::load(&path)?;
+    let now = store::now_secs();
+    for charge in charges.charges.iter_mut() {
+        if charge.status == ChargeStatus::Pending && charge.expires_at <= now {
+            charge.status = ChargeStatus::Expired;
+            events::emit("charge_expired", json!({ "id": charge.id }));
+        }
+    }
+    let res = f(&mut charges);
+    store::save(&path, &charges)?;
+    Ok(res)
+}
+
+/// Create a charge of `amount` to `address`, expiring after `expiry_secs`, the default if 0
+pub fn create(
+    config: &MobileWalletCfg,
+    address: &str,
+    amount: u64,
+    memo: &str,
+    expiry_secs: u64,
+) -> Result<Charge, Error> {
+    if amount == 0 {
+        return Err(ErrorKind::ArgumentError("zero charge amount".to_owned()).into());
+    }
+    let now = store::now_secs();
+    let expiry_secs = if expiry_secs == 0 {
+        DEFAULT_EXPIRY_SECS
+    } else {
+        expiry_secs
+    };
+    let charge = Charge {
+        id: Uuid::new_v4().to_string(),
+        address: address.to_owned(),
+        amount,
+        memo: memo.to_owned(),
+        status: ChargeStatus::Pending,
+        created_at: now,
+        expires_at: now + expiry_secs,
+        slate_id: None,
+        sender: None,
+        received_at: None,
+        paid_at: None,
+    };
+    update(config, |charges| {
+        charges.charges.push(charge.clone());
+        let excess = charges.charges.len().saturating_sub(MAX_CHARGES);
+        charges.charges.drain(..excess);
+    })?;
+    Ok(charge)
+}
+
+/// A charge, with its current status
+pub fn get(config: &MobileWalletCfg, id: &str) -> Result<Charge, Error> {
+    update(config, |charges| {
+        charges.charges.iter().find(|c| c.id == id).cloned()
+    })?
+    .ok_or_else(|| ErrorKind::ArgumentError(format!("charge {} not found", id)).into())
+}
+
+/// Match a received slate with a pending charge, which is then received with a
+/// `charge_received` event. Several pending charges of the amount need the charge id in the
+/// sender message.
+pub fn match_incoming(config: &MobileWalletCfg, slate: &Slate, sender: &str) -> Result<(), Error> {
+    let messages: Vec<&str> = slate
+        .participant_data
+        .iter()
+        .filter_map(|p| p.message.as_deref())
+        .collect();
+    let received = update(config, |charges| {
+        let is_pending = |c: &Charge| c.status == ChargeStatus::Pending && c.amount == slate.amount;
+        let index = charges
+            .charges
+            .iter()
+            .position(|c| is_pending(c) && messages.iter().any(|m| m.contains(&c.id)))
+            .or_else(|| {
+                let mut pending = charges
+                    .charges
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| is_pending(c));
+                match (pending.next(), pending.next()) {
+                    (Some((index, _)), None) => Some(index),
+                    _ => None,
+                }
+            })?;
+        let charge = &mut charges.charges[index];
+        charge.status = ChargeStatus::Received;
+        charge.slate_id = Some(slate.id.to_string());
+        charge.sender = Some(sender.to_owned());
+        charge.received_at = Some(store::now_secs());
+        Some(charge.clone())
+    })?;
+    if let Some(charge) = received {
+        events::emit("charge_received", serde_json::to_value(&charge).unwrap());
+    }
+    Ok(())
+}
+
+/// Pay the received charges whose slate is in the confirmed ones, with a `charge_paid` event
+pub fn confirm(config: &MobileWalletCfg, confirmed: &HashSet<String>) -> Result<(), Error> {
+    let paid = update(config, |charges| {
+        let now = store::now_secs();
+        let mut paid = vec![];
+        for charge in charges.charges.iter_mut() {
+            let is_confirmed = match charge.slate_id.as_ref() {
+                Some(slate_id) => confirmed.contains(slate_id),
+                None => false,
+            };
+            if charge.status == ChargeStatus::Received && is_confirmed {
+                charge.status = ChargeStatus::Paid;
+                charge.paid_at = Some(now);
+                paid.push(charge.clone());
+            }
+        }
+        paid
+    })?;
+    for charge in paid {
+        events::emit("charge_paid", serde_json::to_value(&charge).unwrap());
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The progress of the current send of each account of each wallet, notified by a `send_progress` event on
+//! each state change and pollable by `grin_send_progress`, so the app can show where a send
+//! of 10+ seconds is.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use grin_wallet_util::grin_util::Mutex;
+
+use grin_wallet_impls::Error;
+
+use crate::{events, store, wallet_data_dir, MobileWalletCfg};
+
+pub const INITIALIZING: &str = "initializing";
+pub const CONTACTING_RECIPIENT: &str = "contacting_recipient";
+pub const AWAITING_SIGNATURE: &str = "awaiting_signature";
+pub const FINALIZING: &str = "finalizing";
+pub const POSTING: &str = "posting";
+/// The final states
+pub const POSTED: &str = "posted";
+pub const CANCELLED: &str = "cancelled";
+pub const FAILED: &str = "failed";
+
+/// The progress of a send
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SendProgress {
+    pub account: String,
+    pub state: &'static str,
+    /// Known once the send is initialized
+    pub slate_id: Option<String>,
+    pub started_at: u64,
+    pub updated_at: u64,
+}
+
+lazy_static! {
+    /// The last send of each account, by wallet data dir and account
+    static ref PROGRESS: Mutex<HashMap<(String, String), SendProgress>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Set the state of the current send of the config account, a new send if `INITIALIZING`
+pub fn set(config: &MobileWalletCfg, state: &'static str, slate_id: Option<String>) {
+    let now = store::now_secs();
+    // The config of a send is already checked, its data dir is valid
+    let wallet = wallet_data_dir(config).unwrap_or_else(|_| config.data_dir.clone());
+    let progress = {
+        let mut all = PROGRESS.lock();
+        let progress = all
+            .entry((wallet, config.account.clone()))
+            .or_insert_with(|| SendProgress {
+                account: config.account.clone(),
+                state,
+                slate_id: None,
+                started_at: now,
+                updated_at: now,
+            });
+        if state == INITIALIZING {
+            progress.started_at = now;
+            progress.slate_id = None;
+        }
+        progress.state = state;
+        progress.slate_id = slate_id.or_else(|| progress.slate_id.take());
+        progress.updated_at = now;
+        progress.clone()
+    };
+    events::emit("send_progress", serde_json::to_value(&progress).unwrap());
+}
+
+/// The progress of the last send of the config account
+pub fn get(config: &MobileWalletCfg) -> Result<Option<SendProgress>, Error> {
+    let key = (wallet_data_dir(config)?, config.account.clone());
+    Ok(PROGRESS.lock().get(&key).cloned())
+}
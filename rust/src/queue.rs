@@ -0,0 +1,379 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Persistent queue of the scheduled outgoing transactions. The slate of a tx is recorded as
+//! soon as it's initiated, so a tx left in `Sending` by an interrupted process is recovered
+//! from the tx log on the next start of the queue.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::TxLogEntryType;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::upstream::{self, WalletApi};
+use crate::{
+    amounts, events, get_wallet_instance, netstats, new_wallet_config, send_to, send_tx_args,
+    services, store, MobileWalletCfg,
+};
+
+const TX_QUEUE_FILE: &str = "tx_queue.json";
+
+/// Max sending attempts of a scheduled tx before it's marked as failed
+const MAX_ATTEMPTS: u32 = 5;
+
+/// The send arguments of a scheduled tx
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScheduledTxArgs {
+    pub receiver: String,
+    #[serde(deserialize_with = "amounts::deserialize")]
+    pub amount: u64,
+    #[serde(default = "default_selection_strategy")]
+    pub selection_strategy: String,
+    #[serde(default)]
+    pub target_slate_version: Option<u16>,
+    #[serde(default)]
+    pub message: String,
+    /// The block height before which the tx can't be confirmed
+    #[serde(default)]
+    pub lock_height: Option<u64>,
+}
+
+fn default_selection_strategy() -> String {
+    "smallest".to_owned()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduledTxStatus {
+    Queued,
+    Sending,
+    Sent,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScheduledTx {
+    pub id: String,
+    pub args: ScheduledTxArgs,
+    pub not_before: u64,
+    pub status: ScheduledTxStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub tx_slate_id: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct TxQueue {
+    txs: Vec<ScheduledTx>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the queue file
+    static ref QUEUE_LOCK: Mutex<()> = Mutex::new(());
+    /// Bumped by each start and stop of the service, the thread of an older one exits
+    static ref SERVICE_GENERATION: AtomicU64 = AtomicU64::new(0);
+    static ref SERVICE_RUNNING: Mutex<bool> = Mutex::new(false);
+    /// The scheduled txs being sent by this process
+    static ref IN_FLIGHT: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+thread_local! {
+    /// The scheduled tx being sent on this thread, to record its slate once initiated
+    static SENDING: RefCell<Option<String>> = RefCell::new(None);
+}
+
+fn queue_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        TX_QUEUE_FILE,
+    ))
+}
+
+fn update<T, F: FnOnce(&mut TxQueue) -> Result<T, Error>>(
+    config: &MobileWalletCfg,
+    f: F,
+) -> Result<T, Error> {
+    let _guard = QUEUE_LOCK.lock();
+    let path = queue_path(config)?;
+    let mut queue: TxQueue = store::load(&path)?;
+    let res = f(&mut queue)?;
+    store::save(&path, &queue)?;
+    Ok(res)
+}
+
+fn set_status(tx: &mut ScheduledTx, status: ScheduledTxStatus) {
+    tx.status = status;
+    tx.updated_at = store::now_secs();
+}
+
+/// Notify the status of a tx, out of the queue lock
+fn emit_status(tx: &ScheduledTx) {
+    events::emit(
+        "scheduled_tx_status",
+        json!({
+            "id": tx.id,
+            "status": tx.status,
+            "txSlateId": tx.tx_slate_id,
+            "error": tx.last_error,
+        }),
+    );
+}
+
+/// Add a tx to the queue, to be sent not before the unix timestamp `not_before`
+pub fn schedule(
+    config: &MobileWalletCfg,
+    args: ScheduledTxArgs,
+    not_before: u64,
+) -> Result<ScheduledTx, Error> {
+    if args.amount == 0 {
+        return Err(ErrorKind::ArgumentError("zero amount".to_owned()).into());
+    }
+    let now = store::now_secs();
+    let tx = ScheduledTx {
+        id: Uuid::new_v4().to_string(),
+        args,
+        not_before,
+        status: ScheduledTxStatus::Queued,
+        attempts: 0,
+        last_error: None,
+        tx_slate_id: None,
+        created_at: now,
+        updated_at: now,
+    };
+    update(config, |queue| {
+        queue.txs.push(tx.clone());
+        Ok(())
+    })?;
+    events::emit(
+        "scheduled_tx_status",
+        json!({ "id": tx.id, "status": tx.status }),
+    );
+    Ok(tx)
+}
+
+/// All the scheduled txs, in any status
+pub fn list(config: &MobileWalletCfg) -> Result<Vec<ScheduledTx>, Error> {
+    let _guard = QUEUE_LOCK.lock();
+    let queue: TxQueue = store::load(&queue_path(config)?)?;
+    Ok(queue.txs)
+}
+
+/// Cancel a scheduled tx which is not sent yet
+pub fn cancel(config: &MobileWalletCfg, id: &str) -> Result<(), Error> {
+    let cancelled = update(config, |queue| {
+        match queue.txs.iter_mut().find(|t| t.id == id) {
+            Some(tx) if tx.status == ScheduledTxStatus::Queued => {
+                set_status(tx, ScheduledTxStatus::Cancelled);
+                Ok(tx.clone())
+            }
+            Some(tx) => Err(ErrorKind::GenericError(format!(
+                "scheduled tx is already {:?}",
+                tx.status
+            ))
+            .into()),
+            None => Err(ErrorKind::ArgumentError("scheduled tx not found".to_owned()).into()),
+        }
+    })?;
+    emit_status(&cancelled);
+    Ok(())
+}
+
+/// Record the slate of the scheduled tx being sent on this thread, once it's initiated
+pub fn initiated(config: &MobileWalletCfg, slate_id: &str) {
+    if let Some(id) = SENDING.with(|sending| sending.borrow().clone()) {
+        let _ = update(config, |queue| {
+            if let Some(t) = queue.txs.iter_mut().find(|t| t.id == id) {
+                t.tx_slate_id = Some(slate_id.to_owned());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Recover the txs left in `Sending` by an interrupted process: sent if the tx log has their
+/// slate, the tx being then recovered by the journal, otherwise queued again
+pub fn recover(config: &MobileWalletCfg) -> Result<(), Error> {
+    let in_flight = IN_FLIGHT.lock().clone();
+    let interrupted: Vec<ScheduledTx> = list(config)?
+        .into_iter()
+        .filter(|t| t.status == ScheduledTxStatus::Sending && !in_flight.contains(&t.id))
+        .collect();
+    if interrupted.is_empty() {
+        return Ok(());
+    }
+    let api = upstream::api(get_wallet_instance(config.clone())?);
+    let mut sent = HashSet::new();
+    for tx in interrupted.iter() {
+        let uuid = match tx
+            .tx_slate_id
+            .as_ref()
+            .and_then(|id| Uuid::parse_str(id).ok())
+        {
+            Some(uuid) => uuid,
+            None => continue,
+        };
+        let (_, txs) = api.txs(false, None, Some(uuid))?;
+        if txs.iter().any(|t| t.tx_type == TxLogEntryType::TxSent) {
+            sent.insert(tx.id.clone());
+        }
+    }
+    let recovered = update(config, |queue| {
+        let mut recovered = vec![];
+        for t in queue.txs.iter_mut() {
+            if t.status != ScheduledTxStatus::Sending || in_flight.contains(&t.id) {
+                continue;
+            }
+            if sent.contains(&t.id) {
+                set_status(t, ScheduledTxStatus::Sent);
+            } else if interrupted.iter().any(|i| i.id == t.id) {
+                t.tx_slate_id = None;
+                set_status(t, ScheduledTxStatus::Queued);
+            } else {
+                continue;
+            }
+            recovered.push(t.clone());
+        }
+        Ok(recovered)
+    })?;
+    for tx in recovered.iter() {
+        emit_status(tx);
+    }
+    Ok(())
+}
+
+/// Send the due txs of the queue, if the node is reachable. Returns the number of sent txs.
+pub fn process(json_cfg: &str) -> Result<usize, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let now = store::now_secs();
+    let due: Vec<ScheduledTx> = list(&config)?
+        .into_iter()
+        .filter(|t| t.status == ScheduledTxStatus::Queued && t.not_before <= now)
+        .collect();
+    if due.is_empty() {
+        return Ok(0);
+    }
+
+    // Don't spend the attempts while the node is unreachable
+    let api = upstream::api(get_wallet_instance(config.clone())?);
+    api.node_height()?;
+
+    let mut sent = 0;
+    for tx in due {
+        // Claimed by one worker only, the tx may be already sent or cancelled by another
+        let claimed = update(&config, |queue| {
+            match queue.txs.iter_mut().find(|t| t.id == tx.id) {
+                Some(t) if t.status == ScheduledTxStatus::Queued => {
+                    t.attempts += 1;
+                    set_status(t, ScheduledTxStatus::Sending);
+                    IN_FLIGHT.lock().insert(t.id.clone());
+                    Ok(Some(t.clone()))
+                }
+                _ => Ok(None),
+            }
+        })?;
+        match claimed {
+            Some(claimed) => emit_status(&claimed),
+            None => continue,
+        }
+
+        SENDING.with(|sending| *sending.borrow_mut() = Some(tx.id.clone()));
+        let res = send_to(
+            json_cfg,
+            None,
+            &tx.args.receiver,
+            send_tx_args(
+                tx.args.amount,
+                &tx.args.selection_strategy,
+                tx.args.target_slate_version,
+                &tx.args.message,
+            ),
+            tx.args.lock_height,
+        );
+        SENDING.with(|sending| *sending.borrow_mut() = None);
+        IN_FLIGHT.lock().remove(&tx.id);
+
+        let done = update(&config, |queue| {
+            let t = match queue.txs.iter_mut().find(|t| t.id == tx.id) {
+                Some(t) => t,
+                None => return Ok(None),
+            };
+            match &res {
+                Ok(result) => {
+                    t.tx_slate_id = Some(result.slate_id.clone());
+                    t.last_error = None;
+                    set_status(t, ScheduledTxStatus::Sent);
+                    sent += 1;
+                }
+                Err(e) => {
+                    t.last_error = Some(e.to_string());
+                    t.tx_slate_id = None;
+                    if t.attempts >= MAX_ATTEMPTS {
+                        set_status(t, ScheduledTxStatus::Failed);
+                    } else {
+                        set_status(t, ScheduledTxStatus::Queued);
+                    }
+                }
+            }
+            Ok(Some(t.clone()))
+        })?;
+        if let Some(done) = done {
+            emit_status(&done);
+        }
+    }
+    Ok(sent)
+}
+
+/// Start the queue service thread, processing the queue every `interval_secs`, or less often
+/// in low-data mode. The txs of an interrupted process are recovered firstly.
+pub fn start_service(json_cfg: &str, interval_secs: u64) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let mut running = SERVICE_RUNNING.lock();
+    if *running {
+        return Err(ErrorKind::GenericError("tx queue service already running".to_owned()).into());
+    }
+    recover(&config)?;
+    let generation = SERVICE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let json_cfg = json_cfg.to_owned();
+    let interval = Duration::from_secs(netstats::service_interval(&config, interval_secs.max(1)));
+    services::spawn("tx_queue", move || {
+        // A stopped service exits after its current round, even if a new one is started
+        while SERVICE_GENERATION.load(Ordering::SeqCst) == generation {
+            let _ = process(&json_cfg);
+            thread::sleep(interval);
+        }
+    })?;
+    *running = true;
+    Ok(())
+}
+
+/// Stop the queue service thread, after its current round
+pub fn stop_service() {
+    let mut running = SERVICE_RUNNING.lock();
+    SERVICE_GENERATION.fetch_add(1, Ordering::SeqCst);
+    *running = false;
+}
@@ -0,0 +1,374 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! State of the incoming slates of the relay listeners
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::Slate;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::{events, new_wallet_config, store, MobileWalletCfg};
+
+const RECEIVE_FAILURES_FILE: &str = "receive_failures.json";
+const RECEIVED_SLATES_FILE: &str = "received_slates.json";
+const PENDING_RECEIVES_FILE: &str = "pending_receives.json";
+const INCOMING_SLATES_FILE: &str = "incoming_slates.json";
+
+/// Max number of kept receive failures, the oldest ones are dropped
+const MAX_RECEIVE_FAILURES: usize = 100;
+
+/// Max number of remembered received slate ids, the oldest ones are dropped.
+/// An older replayed slate is still refused by the wallet, but not silently.
+const MAX_RECEIVED_SLATES: usize = 1000;
+
+/// Max number of kept received slates, the oldest ones are dropped
+const MAX_INCOMING_SLATES: usize = 1000;
+
+/// An incoming slate which failed to be received or sent back
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiveFailure {
+    pub slate_id: String,
+    pub sender: String,
+    pub account: String,
+    pub error: String,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ReceiveFailures {
+    failures: Vec<ReceiveFailure>,
+}
+
+/// An incoming slate received by a listener, attributed to the account of the listener
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IncomingSlate {
+    pub slate_id: String,
+    pub sender: String,
+    pub account: String,
+    pub listener_address: String,
+    pub amount: u64,
+    pub received_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct IncomingSlates {
+    slates: Vec<IncomingSlate>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ReceivedSlates {
+    /// (slate id, received timestamp), the latest last
+    slates: Vec<(String, u64)>,
+}
+
+/// The limits of the automatic receives, the slates out of them are parked for approval
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ReceivePolicy {
+    /// Max amount received without approval
+    #[serde(default)]
+    pub max_auto_receive_amount: Option<u64>,
+    /// Sender relay addresses received without approval, any sender if empty
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+}
+
+impl ReceivePolicy {
+    /// Why an incoming slate needs the user approval, None if it can be received automatically
+    pub fn approval_reason(&self, sender: &str, amount: u64) -> Option<String> {
+        if let Some(max) = self.max_auto_receive_amount {
+            if amount > max {
+                return Some(format!(
+                    "amount {} exceeds the auto receive limit {}",
+                    amount, max
+                ));
+            }
+        }
+        if !self.allowed_senders.is_empty() && !self.allowed_senders.iter().any(|s| s == sender) {
+            return Some(format!("sender {} is not allowed", sender));
+        }
+        None
+    }
+}
+
+/// An incoming slate parked for the user approval
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingReceive {
+    pub slate_id: String,
+    pub sender: String,
+    /// The relay address of the listener which got the slate, to reply through it
+    pub listener_address: String,
+    pub account: String,
+    pub amount: u64,
+    pub fee: u64,
+    pub messages: Vec<String>,
+    /// Why the slate is parked, None when all the slates need approval
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub received_at: u64,
+    pub slate: Slate,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct PendingReceives {
+    pending: Vec<PendingReceive>,
+}
+
+/// Compute the receive message for an incoming slate, from the sender address and the amount.
+/// The returned string is owned by the app and must stay valid until the next call, a NULL or
+/// empty string falls back to the configured `receive_message`.
+pub type MessageCallback = extern "C" fn(sender: *const c_char, amount: u64) -> *const c_char;
+
+lazy_static! {
+    /// Serializes the read-modify-write of the receive state files
+    static ref RECEIVE_LOCK: Mutex<()> = Mutex::new(());
+    static ref MESSAGE_CALLBACK: Mutex<Option<MessageCallback>> = Mutex::new(None);
+}
+
+fn data_path(config: &MobileWalletCfg, name: &str) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(&wallet_config.data_file_dir, name))
+}
+
+/// Record a failed incoming slate, and notify the app with a `receive_failed` event
+pub fn record_failure(config: &MobileWalletCfg, slate_id: &str, sender: &str, error: &str) {
+    let failure = ReceiveFailure {
+        slate_id: slate_id.to_owned(),
+        sender: sender.to_owned(),
+        account: config.account.clone(),
+        error: error.to_owned(),
+        timestamp: store::now_secs(),
+    };
+    events::emit(
+        "receive_failed",
+        serde_json::to_value(&failure).unwrap_or_else(|_| json!({ "slateId": slate_id })),
+    );
+
+    // The event is already out, a failure to persist it is not worth breaking the listener
+    let _guard = RECEIVE_LOCK.lock();
+    if let Ok(path) = data_path(config, RECEIVE_FAILURES_FILE) {
+        if let Ok(mut list) = store::load::<ReceiveFailures>(&path) {
+            list.failures.push(failure);
+            let excess = list.failures.len().saturating_sub(MAX_RECEIVE_FAILURES);
+            list.failures.drain(..excess);
+            let _ = store::save(&path, &list);
+        }
+    }
+}
+
+/// The recorded receive failures, the latest last
+pub fn failures(config: &MobileWalletCfg) -> Result<Vec<ReceiveFailure>, Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    let list: ReceiveFailures = store::load(&data_path(config, RECEIVE_FAILURES_FILE)?)?;
+    Ok(list.failures)
+}
+
+/// Forget the recorded receive failures
+pub fn clear_failures(config: &MobileWalletCfg) -> Result<(), Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    store::save(
+        &data_path(config, RECEIVE_FAILURES_FILE)?,
+        &ReceiveFailures::default(),
+    )
+}
+
+/// Whether a slate id was already received by a listener of this wallet
+pub fn is_received(config: &MobileWalletCfg, slate_id: &str) -> Result<bool, Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    let received: ReceivedSlates = store::load(&data_path(config, RECEIVED_SLATES_FILE)?)?;
+    Ok(received.slates.iter().any(|(id, _)| id == slate_id))
+}
+
+/// Remember a received slate id, so a double delivery or a replay is not received again
+pub fn mark_received(config: &MobileWalletCfg, slate_id: &str) -> Result<(), Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    let path = data_path(config, RECEIVED_SLATES_FILE)?;
+    let mut received: ReceivedSlates = store::load(&path)?;
+    received
+        .slates
+        .push((slate_id.to_owned(), store::now_secs()));
+    let excess = received.slates.len().saturating_sub(MAX_RECEIVED_SLATES);
+    received.slates.drain(..excess);
+    store::save(&path, &received)
+}
+
+/// Record a slate received into the configured account, and notify the app with a
+/// `slate_received` event
+pub fn record_incoming(
+    config: &MobileWalletCfg,
+    slate: &Slate,
+    sender: &str,
+    listener_address: &str,
+) {
+    let incoming = IncomingSlate {
+        slate_id: slate.id.to_string(),
+        sender: sender.to_owned(),
+        account: config.account.clone(),
+        listener_address: listener_address.to_owned(),
+        amount: slate.amount,
+        received_at: store::now_secs(),
+    };
+    events::emit(
+        "slate_received",
+        serde_json::to_value(&incoming).unwrap_or_else(|_| json!({ "slateId": incoming.slate_id })),
+    );
+
+    // The slate is already received, a failure to record it only loses the attribution
+    let _guard = RECEIVE_LOCK.lock();
+    if let Ok(path) = data_path(config, INCOMING_SLATES_FILE) {
+        if let Ok(mut list) = store::load::<IncomingSlates>(&path) {
+            list.slates.push(incoming);
+            let excess = list.slates.len().saturating_sub(MAX_INCOMING_SLATES);
+            list.slates.drain(..excess);
+            let _ = store::save(&path, &list);
+        }
+    }
+}
+
+/// The slates received by the listeners, of an account or all of them, the latest last
+pub fn incoming(
+    config: &MobileWalletCfg,
+    account: Option<&str>,
+) -> Result<Vec<IncomingSlate>, Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    let list: IncomingSlates = store::load(&data_path(config, INCOMING_SLATES_FILE)?)?;
+    Ok(list
+        .slates
+        .into_iter()
+        .filter(|s| account.map(|account| s.account == account).unwrap_or(true))
+        .collect())
+}
+
+/// Notify the app of an ignored duplicate slate
+pub fn duplicate_ignored(slate_id: &str, sender: &str) {
+    events::emit(
+        "receive_duplicate",
+        json!({ "slateId": slate_id, "sender": sender }),
+    );
+}
+
+/// Park an incoming slate until the user accepts or rejects it, and notify the app with a
+/// `receive_pending` event
+pub fn park(
+    config: &MobileWalletCfg,
+    slate: &Slate,
+    sender: &str,
+    listener_address: &str,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    let pending = PendingReceive {
+        slate_id: slate.id.to_string(),
+        sender: sender.to_owned(),
+        listener_address: listener_address.to_owned(),
+        account: config.account.clone(),
+        amount: slate.amount,
+        fee: slate.fee,
+        messages: slate
+            .participant_data
+            .iter()
+            .filter_map(|p| p.message.clone())
+            .collect(),
+        reason,
+        received_at: store::now_secs(),
+        slate: slate.clone(),
+    };
+    {
+        let _guard = RECEIVE_LOCK.lock();
+        let path = data_path(config, PENDING_RECEIVES_FILE)?;
+        let mut list: PendingReceives = store::load(&path)?;
+        if list.pending.iter().any(|p| p.slate_id == pending.slate_id) {
+            return Ok(());
+        }
+        list.pending.push(pending.clone());
+        store::save(&path, &list)?;
+    }
+    events::emit(
+        "receive_pending",
+        json!({
+            "slateId": pending.slate_id,
+            "sender": pending.sender,
+            "account": pending.account,
+            "amount": pending.amount,
+            "messages": pending.messages,
+            "reason": pending.reason,
+        }),
+    );
+    Ok(())
+}
+
+/// The incoming slates waiting for the user approval
+pub fn pending(config: &MobileWalletCfg) -> Result<Vec<PendingReceive>, Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    let list: PendingReceives = store::load(&data_path(config, PENDING_RECEIVES_FILE)?)?;
+    Ok(list.pending)
+}
+
+/// Remove a parked slate, returns it
+pub fn take_pending(config: &MobileWalletCfg, slate_id: &str) -> Result<PendingReceive, Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    let path = data_path(config, PENDING_RECEIVES_FILE)?;
+    let mut list: PendingReceives = store::load(&path)?;
+    let index = list
+        .pending
+        .iter()
+        .position(|p| p.slate_id == slate_id)
+        .ok_or_else(|| {
+            Error::from(ErrorKind::ArgumentError(
+                "pending receive not found".to_owned(),
+            ))
+        })?;
+    let pending = list.pending.remove(index);
+    store::save(&path, &list)?;
+    Ok(pending)
+}
+
+/// Put back a parked slate, i.e. after a failed accept
+pub fn restore_pending(config: &MobileWalletCfg, pending: PendingReceive) -> Result<(), Error> {
+    let _guard = RECEIVE_LOCK.lock();
+    let path = data_path(config, PENDING_RECEIVES_FILE)?;
+    let mut list: PendingReceives = store::load(&path)?;
+    list.pending.push(pending);
+    store::save(&path, &list)
+}
+
+/// Set or clear the receive message callback
+pub fn set_message_callback(callback: Option<MessageCallback>) {
+    *MESSAGE_CALLBACK.lock() = callback;
+}
+
+/// The message attached to the receiver participant data of an incoming slate
+pub fn message_for(config: &MobileWalletCfg, sender: &str, amount: u64) -> Option<String> {
+    let callback = *MESSAGE_CALLBACK.lock();
+    let from_callback = callback.and_then(|callback| {
+        let sender = CString::new(sender).ok()?;
+        let message = callback(sender.as_ptr(), amount);
+        if message.is_null() {
+            return None;
+        }
+        let message = unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned();
+        Some(message).filter(|m| !m.is_empty())
+    });
+    from_callback.or_else(|| config.receive_message.clone().filter(|m| !m.is_empty()))
+}
@@ -0,0 +1,346 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shared grinrelay state across the extern calls: the running listeners with their accounts,
+//! the listener for address queries, and the cache of resolved 6-code addresses.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::VersionedSlate;
+use grin_wallet_util::grin_keychain::ExtKeychainPath;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::store::now_secs;
+
+/// How long a resolved 6-code address is trusted without a new query
+pub const ADDR_CACHE_TTL: Duration = Duration::from_secs(600);
+
+/// How often the connect watcher checks the listeners waited on
+const CONNECT_WATCH_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Address query access to a running relay listener
+pub struct RelayQueryHandle {
+    /// Send a query request for an abbreviated address, false if the request fails
+    pub retrieve: Box<dyn Fn(String) -> bool + Send>,
+    /// Whether the listener is connected with the relay service
+    pub is_connected: Box<dyn Fn() -> bool + Send>,
+    /// The query responses, as (abbreviated address, matched full addresses)
+    pub responses: Receiver<(String, Vec<String>)>,
+    /// The chain type of the wallet of the listener
+    pub chain_type: String,
+}
+
+/// Publish a slate to a relay address
+pub type Publisher = Box<dyn Fn(&VersionedSlate, &str) -> Result<(), String> + Send>;
+
+/// Reply access to a running listener, for the slates received out of its loop
+pub struct ListenerHandle {
+    /// The relay key path of the listener address
+    pub key_path: ExtKeychainPath,
+    pub publish: Publisher,
+    /// Whether the listener is connected with the relay service
+    pub is_connected: Box<dyn Fn() -> bool + Send>,
+    /// Close the relay connection, and wake the listener thread so it ends
+    pub stop: Box<dyn Fn() + Send>,
+}
+
+/// A running listener, receiving the slates sent to its relay address into its account
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenerInfo {
+    pub address: String,
+    pub account: String,
+    pub key_index: u32,
+    pub started_at: u64,
+    pub connected: bool,
+    /// The wallet data dir of the listener
+    #[serde(skip)]
+    pub wallet: String,
+    /// Tells a restarted listener from the replaced one, whose thread must exit
+    #[serde(skip)]
+    pub id: String,
+}
+
+/// A caller waiting for a listener getting connected with the relay service
+struct ConnectWaiter {
+    id: u64,
+    is_connected: Box<dyn Fn() -> bool + Send>,
+    wake: Sender<()>,
+}
+
+lazy_static! {
+    static ref CONNECT_WAITERS: Mutex<Vec<ConnectWaiter>> = Mutex::new(vec![]);
+    static ref NEXT_WAITER_ID: AtomicU64 = AtomicU64::new(0);
+    /// The query handles by listener address, with the wallet data dir of the listener. A
+    /// handle is locked by its query only, not the whole map.
+    static ref QUERY_HANDLES: Mutex<HashMap<String, (String, Arc<Mutex<RelayQueryHandle>>)>> =
+        Mutex::new(HashMap::new());
+    static ref ADDR_CACHE: Mutex<HashMap<String, (String, Instant)>> = Mutex::new(HashMap::new());
+    static ref LISTENERS: Mutex<HashMap<String, ListenerInfo>> = Mutex::new(HashMap::new());
+    static ref LISTENER_HANDLES: Mutex<HashMap<String, ListenerHandle>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Wait for a listener getting connected with the relay service, up to `timeout_ms`. The
+/// upstream listener has no connect notification, so one watcher thread checks the listeners
+/// of all the waiting callers, and wakes a caller through its channel once connected. The
+/// `is_connected` of a woken caller is dropped before the wake-up, so whatever it shares is
+/// released when this returns.
+pub fn wait_connected(
+    is_connected: Box<dyn Fn() -> bool + Send>,
+    timeout_ms: u64,
+) -> Result<(), Error> {
+    if is_connected() {
+        return Ok(());
+    }
+    let (wake, woken) = channel();
+    let id = NEXT_WAITER_ID.fetch_add(1, Ordering::SeqCst);
+    let start_watcher = {
+        let mut waiters = CONNECT_WAITERS.lock();
+        waiters.push(ConnectWaiter {
+            id,
+            is_connected,
+            wake,
+        });
+        waiters.len() == 1
+    };
+    if start_watcher {
+        if let Err(e) = thread::Builder::new()
+            .name("relay-connect-watch".to_owned())
+            .spawn(watch_connects)
+        {
+            CONNECT_WAITERS.lock().retain(|w| w.id != id);
+            return Err(ErrorKind::GenericError(format!(
+                "fail to start the relay connect watcher: {}",
+                e
+            ))
+            .into());
+        }
+    }
+    if woken
+        .recv_timeout(Duration::from_millis(timeout_ms))
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    let mut waiters = CONNECT_WAITERS.lock();
+    match waiters.iter().position(|w| w.id == id) {
+        Some(index) => {
+            waiters.remove(index);
+            Err(ErrorKind::GenericError(format!(
+                "Fail to connect with grin relay service, {}ms timeout. please try again later",
+                timeout_ms
+            ))
+            .into())
+        }
+        None => {
+            // Connected right at the timeout, the watcher took the waiter out to wake it
+            drop(waiters);
+            let _ = woken.recv();
+            Ok(())
+        }
+    }
+}
+
+/// Check the waited listeners until no caller is waiting, waking the connected ones
+fn watch_connects() {
+    loop {
+        let connected: Vec<Sender<()>> = {
+            let mut waiters = CONNECT_WAITERS.lock();
+            if waiters.is_empty() {
+                return;
+            }
+            let (connected, pending): (Vec<_>, Vec<_>) =
+                waiters.drain(..).partition(|w| (w.is_connected)());
+            *waiters = pending;
+            connected.into_iter().map(|w| w.wake).collect()
+        };
+        for wake in connected {
+            let _ = wake.send(());
+        }
+        thread::sleep(CONNECT_WATCH_INTERVAL);
+    }
+}
+
+/// Register a listener on a relay address, returning its id. Only one listener per address is
+/// allowed, so a slate is never received into two accounts.
+pub fn register_listener(
+    address: &str,
+    account: &str,
+    key_index: u32,
+    wallet: &str,
+) -> Result<String, Error> {
+    let mut listeners = LISTENERS.lock();
+    if let Some(listener) = listeners.get(address) {
+        return Err(ErrorKind::GenericError(format!(
+            "relay address {} is already listened for account {}",
+            address, listener.account
+        ))
+        .into());
+    }
+    let id = Uuid::new_v4().to_string();
+    listeners.insert(
+        address.to_owned(),
+        ListenerInfo {
+            address: address.to_owned(),
+            account: account.to_owned(),
+            key_index,
+            started_at: now_secs(),
+            connected: false,
+            wallet: wallet.to_owned(),
+            id: id.clone(),
+        },
+    );
+    Ok(id)
+}
+
+/// Remove a listener, closing its relay connection. Its thread ends once the slates already
+/// received are processed.
+pub fn unregister_listener(address: &str) {
+    LISTENERS.lock().remove(address);
+    QUERY_HANDLES.lock().remove(address);
+    let handle = LISTENER_HANDLES.lock().remove(address);
+    if let Some(handle) = handle {
+        (handle.stop)();
+    }
+}
+
+/// Remove a listener, unless it was already replaced by a restarted one
+pub fn unregister_listener_id(address: &str, id: &str) {
+    if is_current_listener(address, id) {
+        unregister_listener(address);
+    }
+}
+
+/// Whether the listener of an id is still the one registered on its address
+pub fn is_current_listener(address: &str, id: &str) -> bool {
+    LISTENERS
+        .lock()
+        .get(address)
+        .map(|l| l.id == id)
+        .unwrap_or(false)
+}
+
+/// Whether the listener on `address` is connected with the relay service
+pub fn is_listener_connected(address: &str) -> bool {
+    LISTENER_HANDLES
+        .lock()
+        .get(address)
+        .map(|handle| (handle.is_connected)())
+        .unwrap_or(false)
+}
+
+/// Set the reply handle of a registered listener
+pub fn set_listener_handle(address: &str, handle: ListenerHandle) {
+    LISTENER_HANDLES.lock().insert(address.to_owned(), handle);
+}
+
+/// Run `f` with the reply handle of the listener on `address`, if it's running
+pub fn with_listener_handle<T, F: FnOnce(&ListenerHandle) -> T>(address: &str, f: F) -> Option<T> {
+    LISTENER_HANDLES.lock().get(address).map(f)
+}
+
+/// The account receiving the slates sent to a relay address, if it's listened
+pub fn listener_account(address: &str) -> Option<String> {
+    LISTENERS.lock().get(address).map(|l| l.account.clone())
+}
+
+/// All the running listeners
+pub fn listeners() -> Vec<ListenerInfo> {
+    let mut listeners: Vec<ListenerInfo> = LISTENERS.lock().values().cloned().collect();
+    for listener in listeners.iter_mut() {
+        listener.connected = is_listener_connected(&listener.address);
+    }
+    listeners.sort_by_key(|l| l.started_at);
+    listeners
+}
+
+/// Register the address query handle of a running listener of a wallet, to be reused by the
+/// address queries
+pub fn set_query_handle(address: &str, wallet: &str, handle: RelayQueryHandle) {
+    QUERY_HANDLES.lock().insert(
+        address.to_owned(),
+        (wallet.to_owned(), Arc::new(Mutex::new(handle))),
+    );
+}
+
+/// Drop the address query handles of the listeners of a wallet
+pub fn clear_query_handle(wallet: &str) {
+    QUERY_HANDLES.lock().retain(|_, (w, _)| w != wallet);
+}
+
+/// Drop the address query handles, of any wallet
+pub fn clear_query_handles() {
+    QUERY_HANDLES.lock().clear();
+}
+
+/// Run `f` with a connected query handle of a wallet on a chain, not busy with another
+/// query, if there is one. The handle is locked meanwhile, so the queries on it are
+/// serialized, the other handles and listeners are not.
+pub fn with_query_handle<T, F: FnOnce(&RelayQueryHandle) -> T>(
+    wallet: &str,
+    chain_type: &str,
+    f: F,
+) -> Option<T> {
+    let handles: Vec<Arc<Mutex<RelayQueryHandle>>> = QUERY_HANDLES
+        .lock()
+        .values()
+        .filter(|(w, _)| w == wallet)
+        .map(|(_, handle)| handle.clone())
+        .collect();
+    for handle in handles {
+        if let Some(handle) = handle.try_lock() {
+            if handle.chain_type == chain_type && (handle.is_connected)() {
+                return Some(f(&handle));
+            }
+        }
+    }
+    None
+}
+
+fn cache_key(chain_type: &str, six_code: &str) -> String {
+    format!("{}:{}", chain_type, six_code)
+}
+
+/// The cached full address of a 6-code, if not expired
+pub fn cached_addr(chain_type: &str, six_code: &str) -> Option<String> {
+    let mut cache = ADDR_CACHE.lock();
+    let key = cache_key(chain_type, six_code);
+    match cache.get(&key) {
+        Some((addr, at)) if at.elapsed() < ADDR_CACHE_TTL => Some(addr.clone()),
+        Some(_) => {
+            cache.remove(&key);
+            None
+        }
+        None => None,
+    }
+}
+
+/// Cache a resolved full address of a 6-code
+pub fn cache_addr(chain_type: &str, six_code: &str, addr: &str) {
+    ADDR_CACHE.lock().insert(
+        cache_key(chain_type, six_code),
+        (addr.to_owned(), Instant::now()),
+    );
+}
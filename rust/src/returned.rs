@@ -0,0 +1,271 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The slates of the own sends coming back signed to a relay listener, i.e. when the
+//! recipient answered after the send gave up waiting. With `relay_auto_post` the listener
+//! finalizes and posts them itself, otherwise they wait for `grin_returned_slate_post`.
+//!
+//! Their state is tracked, so a slate delivered twice is never finalized or posted twice. The
+//! finalize and the post are journaled, a crash in between being rolled forward by the crash
+//! recovery, or by the next post finding the tx already finalized.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{NodeClient, Slate, TxLogEntryType, WalletInst};
+use grin_wallet_util::grin_core::core::Transaction;
+use grin_wallet_util::grin_keychain::ExtKeychain;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::upstream::{self, WalletApi};
+use crate::{
+    check_spending, coalesce, events, journal, new_wallet_config, receive, store, MobileWalletCfg,
+};
+
+const RETURNED_SLATES_FILE: &str = "returned_slates.json";
+
+/// Max number of kept returned slates, the oldest ones are dropped
+const MAX_RETURNED_SLATES: usize = 200;
+
+/// Where a returned slate is, each state is only entered once
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnedState {
+    /// Waiting to be finalized
+    Returned,
+    /// Finalized, the tx is stored but not posted yet
+    Finalized,
+    Posted,
+}
+
+/// A returned slate of an own send
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReturnedSlate {
+    pub slate_id: String,
+    pub sender: String,
+    pub account: String,
+    pub state: ReturnedState,
+    /// The error of the last finalize or post attempt
+    pub error: Option<String>,
+    pub updated_at: u64,
+    pub slate: Slate,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ReturnedSlates {
+    slates: Vec<ReturnedSlate>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the returned slates file
+    static ref RETURNED_LOCK: Mutex<()> = Mutex::new(());
+    /// Held over a whole finalize and post, so two deliveries can't both post
+    static ref POST_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        RETURNED_SLATES_FILE,
+    ))
+}
+
+fn find(config: &MobileWalletCfg, slate_id: &str) -> Result<Option<ReturnedSlate>, Error> {
+    let _guard = RETURNED_LOCK.lock();
+    let list: ReturnedSlates = store::load(&data_path(config)?)?;
+    Ok(list.slates.into_iter().find(|s| s.slate_id == slate_id))
+}
+
+fn save(config: &MobileWalletCfg, returned: &ReturnedSlate) -> Result<(), Error> {
+    let _guard = RETURNED_LOCK.lock();
+    let path = data_path(config)?;
+    let mut list: ReturnedSlates = store::load(&path)?;
+    list.slates.retain(|s| s.slate_id != returned.slate_id);
+    list.slates.push(returned.clone());
+    let excess = list.slates.len().saturating_sub(MAX_RETURNED_SLATES);
+    list.slates.drain(..excess);
+    store::save(&path, &list)
+}
+
+/// Set the state of a returned slate, if tracked
+pub fn set_state(
+    config: &MobileWalletCfg,
+    slate_id: &str,
+    state: ReturnedState,
+) -> Result<(), Error> {
+    if let Some(mut returned) = find(config, slate_id)? {
+        returned.state = state;
+        returned.error = None;
+        returned.updated_at = store::now_secs();
+        save(config, &returned)?;
+    }
+    Ok(())
+}
+
+/// The stored tx of an own send once finalized, none before
+pub fn finalized_tx<A: WalletApi>(api: &A, slate: &Slate) -> Result<Option<Transaction>, Error> {
+    let (_, txs) = api.txs(false, None, Some(slate.id))?;
+    match txs.iter().find(|tx| tx.tx_type == TxLogEntryType::TxSent) {
+        Some(tx) => Ok(api.stored_tx(tx).unwrap_or(None)),
+        None => Ok(None),
+    }
+}
+
+/// The returned slates, the latest last
+pub fn list(config: &MobileWalletCfg) -> Result<Vec<ReturnedSlate>, Error> {
+    let _guard = RETURNED_LOCK.lock();
+    let list: ReturnedSlates = store::load(&data_path(config)?)?;
+    Ok(list.slates)
+}
+
+/// Whether an incoming slate is one of the own sends, rather than a slate to receive
+pub fn is_own_send<C>(wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>, slate: &Slate) -> bool
+where
+    C: NodeClient + 'static,
+{
+    upstream::api(wallet)
+        .txs(false, None, Some(slate.id))
+        .map(|(_, txs)| txs.iter().any(|tx| tx.tx_type == TxLogEntryType::TxSent))
+        .unwrap_or(false)
+}
+
+/// Track a returned slate, notify the app with a `slate_returned` event, and post it if
+/// `relay_auto_post` is configured. A slate already tracked is ignored.
+pub fn handle<C>(
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    config: &MobileWalletCfg,
+    slate: &Slate,
+    sender: &str,
+) -> Result<(), Error>
+where
+    C: NodeClient + 'static,
+{
+    let slate_id = slate.id.to_string();
+    {
+        let _guard = POST_LOCK.lock();
+        if find(config, &slate_id)?.is_some() {
+            receive::duplicate_ignored(&slate_id, sender);
+            return Ok(());
+        }
+        save(
+            config,
+            &ReturnedSlate {
+                slate_id: slate_id.clone(),
+                sender: sender.to_owned(),
+                account: config.account.clone(),
+                state: ReturnedState::Returned,
+                error: None,
+                updated_at: store::now_secs(),
+                slate: slate.clone(),
+            },
+        )?;
+    }
+    events::emit(
+        "slate_returned",
+        json!({ "slateId": slate_id, "sender": sender, "amount": slate.amount }),
+    );
+    if config.relay_auto_post {
+        post(wallet, config, &slate_id)?;
+    }
+    Ok(())
+}
+
+/// Finalize a returned slate if not yet, then post its tx. The finalize and the post are
+/// journaled until posted, so an interrupted one is resumed by the crash recovery.
+pub fn post<C>(
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    config: &MobileWalletCfg,
+    slate_id: &str,
+) -> Result<ReturnedSlate, Error>
+where
+    C: NodeClient + 'static,
+{
+    check_spending(config, "post")?;
+    let _guard = POST_LOCK.lock();
+    let mut returned = find(config, slate_id)?.ok_or_else(|| {
+        Error::from(ErrorKind::ArgumentError(
+            "returned slate not found".to_owned(),
+        ))
+    })?;
+    let api = upstream::api(wallet);
+    if returned.state == ReturnedState::Posted {
+        return Err(ErrorKind::ArgumentError("tx already posted".to_owned()).into());
+    }
+    let journal_id = journal::begin(
+        config,
+        journal::OP_RETURNED_POST,
+        journal::PHASE_LOCKED,
+        Some(slate_id),
+        json!({ "channel": "relay" }),
+    )?;
+    // Finalized before a crash, the state saved or not
+    let tx = match finalized_tx(&api, &returned.slate)? {
+        Some(tx) => tx,
+        None if returned.state == ReturnedState::Finalized => {
+            journal::finish(config, &journal_id);
+            return Err(ErrorKind::GenericError("finalized tx not found".to_owned()).into());
+        }
+        None => {
+            let finalized = api
+                .verify_messages(&returned.slate)
+                .and_then(|_| api.finalize(&returned.slate, None, None));
+            coalesce::invalidate();
+            match finalized {
+                Ok(finalized) => finalized.tx,
+                Err(e) => {
+                    journal::finish(config, &journal_id);
+                    returned.error = Some(e.to_string());
+                    returned.updated_at = store::now_secs();
+                    save(config, &returned)?;
+                    return Err(e);
+                }
+            }
+        }
+    };
+    journal::advance(config, &journal_id, journal::PHASE_FINALIZED);
+    returned.state = ReturnedState::Finalized;
+    returned.error = None;
+    returned.updated_at = store::now_secs();
+    save(config, &returned)?;
+
+    match api.post(Some(returned.slate.id), &tx, true) {
+        Ok(_) => {
+            journal::finish(config, &journal_id);
+            returned.state = ReturnedState::Posted;
+            returned.error = None;
+            returned.updated_at = store::now_secs();
+            save(config, &returned)?;
+            events::emit("slate_posted", json!({ "slateId": slate_id }));
+            Ok(returned)
+        }
+        Err(e) => {
+            // The tx stays finalized, the post is retried by the app
+            journal::finish(config, &journal_id);
+            returned.error = Some(e.to_string());
+            returned.updated_at = store::now_secs();
+            save(config, &returned)?;
+            events::emit(
+                "slate_post_failed",
+                json!({ "slateId": slate_id, "error": e.to_string() }),
+            );
+            Err(e)
+        }
+    }
+}
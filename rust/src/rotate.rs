@@ -0,0 +1,281 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Seed rotation: a new wallet with a new seed, for a user whose mnemonic might be exposed,
+//! and all the spendable funds swept to it once its mnemonic is backed up.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use grin_wallet_impls::{Error, ErrorKind, WalletSeed};
+use grin_wallet_libwallet::{OutputData, OutputStatus};
+use grin_wallet_util::grin_core::libtx::tx_fee;
+
+use crate::password::SEED_FILE;
+use crate::upstream::{self, WalletApi};
+use crate::{
+    addr_cache, contacts, get_wallet_instance, labels, new_wallet_config, send_tx, send_tx_args,
+    senders, store, wallet_init, MobileWalletCfg, SENDING_MINIMUM_CONFIRMATIONS,
+};
+
+/// Max inputs of a sweep tx, more outputs are swept in several txs
+const MAX_SWEEP_INPUTS: usize = 500;
+
+/// The files of the lib carried over to the new wallet
+const MIGRATED_FILES: &[&str] = &[
+    contacts::CONTACTS_FILE,
+    labels::TX_LABELS_FILE,
+    senders::KNOWN_SENDERS_FILE,
+    addr_cache::ADDR_CACHE_FILE,
+];
+
+/// A sweep tx of an account
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sweep {
+    pub account: String,
+    pub slate_id: String,
+    pub inputs: usize,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+/// The new wallet of a seed rotation
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rotation {
+    /// The mnemonic of the new seed, to be backed up by the user
+    pub mnemonic: String,
+    /// The config of the new wallet
+    pub config: MobileWalletCfg,
+    /// The account labels created in the new wallet
+    pub accounts: Vec<String>,
+    /// The contacts, tx labels and address book files copied to the new wallet
+    pub migrated_files: Vec<String>,
+}
+
+/// The sweep of a seed rotation
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sweeping {
+    pub sweeps: Vec<Sweep>,
+    /// The funds not swept yet, i.e. immature coinbase, unconfirmed outputs, or the change of
+    /// a sweep of more than `MAX_SWEEP_INPUTS` outputs
+    pub unswept_amount: u64,
+}
+
+/// The config of the new wallet of a rotation, with the password of the old one
+fn new_config_of(config: &MobileWalletCfg, new_data_dir: &str) -> Result<MobileWalletCfg, Error> {
+    if new_data_dir.is_empty() || new_data_dir == config.data_dir {
+        return Err(
+            ErrorKind::ArgumentError("the new wallet needs another data dir".to_owned()).into(),
+        );
+    }
+    // The owner token is bound to the old wallet, the new one gets the password
+    let mut new_config = config.clone();
+    new_config.data_dir = new_data_dir.to_owned();
+    new_config.account = "default".to_owned();
+    new_config.password = config.wallet_password()?;
+    new_config.owner_token = String::new();
+    // The password is kept, not a new one to check
+    new_config.min_password_score = 0;
+    Ok(new_config)
+}
+
+fn spendable(outputs: Vec<OutputData>, height: u64) -> (Vec<OutputData>, Vec<OutputData>) {
+    outputs
+        .into_iter()
+        .filter(|o| o.status == OutputStatus::Unspent)
+        .partition(|o| o.eligible_to_spend(height, SENDING_MINIMUM_CONFIRMATIONS))
+}
+
+/// Sweep the spendable outputs of an account of the old wallet, into the same account of
+/// the new wallet. The inputs are selected by the wallet libs: up to `MAX_SWEEP_INPUTS`
+/// outputs are all spent by one tx without change, more are swept by txs of at most
+/// `MAX_SWEEP_INPUTS` inputs, whose change is left unswept.
+fn sweep_account(
+    old_config: &MobileWalletCfg,
+    new_config: &MobileWalletCfg,
+    sweeps: &mut Vec<Sweep>,
+) -> Result<u64, Error> {
+    let wallet = get_wallet_instance(old_config.clone())?;
+    let api = upstream::api(wallet.clone());
+    let height = api.node_height()?.0;
+    let receiver = upstream::api(get_wallet_instance(new_config.clone())?);
+    let mut refresh = true;
+    loop {
+        let (_, outputs) = api.outputs(false, refresh, None)?;
+        refresh = false;
+        let (eligible, _) = spendable(outputs.into_iter().map(|o| o.output).collect(), height);
+        if eligible.is_empty() {
+            break;
+        }
+        let mut values: Vec<u64> = eligible.iter().map(|o| o.value).collect();
+        // The fee the selection computes: all the outputs spent without change, or the largest
+        // ones with a change output
+        let (total, fee, change_outputs) = if values.len() <= MAX_SWEEP_INPUTS {
+            let total: u64 = values.iter().sum();
+            (total, tx_fee(values.len(), 1, 1, None), 0)
+        } else {
+            values.sort_unstable_by(|a, b| b.cmp(a));
+            let total: u64 = values[..MAX_SWEEP_INPUTS].iter().sum();
+            (total, tx_fee(MAX_SWEEP_INPUTS, 2, 1, None), 1)
+        };
+        if total <= fee {
+            break;
+        }
+        let mut args = send_tx_args(total - fee, "all", None, "seed rotation");
+        args.num_change_outputs = change_outputs;
+        let result = send_tx(
+            old_config,
+            wallet.clone(),
+            args,
+            None,
+            None,
+            "local",
+            |slate_r1| {
+                let slate = receiver.receive(slate_r1, &new_config.account, None, None)?;
+                Ok((slate, None, None))
+            },
+        )?;
+        sweeps.push(Sweep {
+            account: old_config.account.clone(),
+            slate_id: result.slate_id,
+            inputs: result.slate.tx.inputs().len(),
+            amount: result.amount,
+            fee: result.fee,
+        });
+    }
+
+    // The spent outputs are locked, the change and the outputs not eligible are left
+    let (_, outputs) = api.outputs(false, false, None)?;
+    Ok(outputs
+        .into_iter()
+        .map(|o| o.output)
+        .filter(|o| o.status == OutputStatus::Unspent || o.status == OutputStatus::Unconfirmed)
+        .map(|o| o.value)
+        .sum())
+}
+
+/// Copy the lib files of the old wallet which the new one doesn't have
+fn migrate_files(old_dir: &str, new_dir: &str) -> Result<Vec<String>, Error> {
+    let mut migrated = vec![];
+    for name in MIGRATED_FILES {
+        let from = store::data_file(old_dir, name);
+        let to = store::data_file(new_dir, name);
+        if from.exists() && !to.exists() {
+            fs::copy(&from, &to)
+                .map_err(|e| ErrorKind::GenericError(format!("fail to migrate {}: {}", name, e)))?;
+            migrated.push(name.to_string());
+        }
+    }
+    Ok(migrated)
+}
+
+/// Create a wallet with a new seed in `new_data_dir`, with the same accounts, config,
+/// contacts and tx labels. The funds are swept by `sweep` once the new mnemonic is backed up.
+pub fn rotate_seed(
+    config: MobileWalletCfg,
+    new_data_dir: &str,
+    is_12_phrases: bool,
+) -> Result<Rotation, Error> {
+    let mut new_config = new_config_of(&config, new_data_dir)?;
+    let new_wallet_config = new_wallet_config(new_config.clone())?;
+    if Path::new(&new_wallet_config.data_file_dir)
+        .join(SEED_FILE)
+        .exists()
+    {
+        return Err(ErrorKind::ArgumentError(
+            "a wallet already exists in the new data dir".to_owned(),
+        )
+        .into());
+    }
+
+    let accounts = upstream::api(get_wallet_instance(config.clone())?).accounts()?;
+    let mnemonic = wallet_init(
+        &serde_json::to_string(&new_config).unwrap(),
+        &new_config.password,
+        is_12_phrases,
+    )?;
+
+    let new_api = upstream::api(get_wallet_instance(new_config.clone())?);
+    let mut labels = vec![];
+    for account in accounts {
+        if account.label != "default" {
+            new_api.create_account(&account.label)?;
+        }
+        labels.push(account.label);
+    }
+    let migrated_files = migrate_files(
+        &new_wallet_config(config.clone())?.data_file_dir,
+        &new_wallet_config.data_file_dir,
+    )?;
+
+    new_config.account = config.account;
+    // A token config is not given the password back, a new token is issued for the new wallet
+    if !config.owner_token.is_empty() {
+        new_config.password = String::new();
+    }
+    Ok(Rotation {
+        mnemonic,
+        config: new_config,
+        accounts: labels,
+        migrated_files,
+    })
+}
+
+fn words(mnemonic: &str) -> Vec<&str> {
+    mnemonic.split_whitespace().collect()
+}
+
+/// Sweep all the spendable funds of each account to the new wallet of `rotate_seed`, once its
+/// backup is confirmed by the mnemonic entered again. A failed sweep can simply be retried.
+pub fn sweep(
+    config: MobileWalletCfg,
+    new_data_dir: &str,
+    mnemonic: &str,
+) -> Result<Sweeping, Error> {
+    let new_config = new_config_of(&config, new_data_dir)?;
+    let new_wallet_config = new_wallet_config(new_config.clone())?;
+    let seed = WalletSeed::from_file(
+        &new_wallet_config.data_file_dir,
+        &new_config.seed_password()?,
+    )?;
+    if words(&seed.to_mnemonic()?) != words(&mnemonic.to_lowercase()) {
+        return Err(ErrorKind::ArgumentError(
+            "the mnemonic doesn't match the new wallet, back it up again".to_owned(),
+        )
+        .into());
+    }
+
+    let labels = upstream::api(get_wallet_instance(new_config.clone())?)
+        .accounts()?
+        .into_iter()
+        .map(|a| a.label);
+    let mut sweeps = vec![];
+    let mut unswept_amount = 0;
+    for label in labels {
+        let mut old_account = config.clone();
+        old_account.account = label.clone();
+        let mut new_account = new_config.clone();
+        new_account.account = label;
+        unswept_amount += sweep_account(&old_account, &new_account, &mut sweeps)?;
+    }
+    Ok(Sweeping {
+        sweeps,
+        unswept_amount,
+    })
+}
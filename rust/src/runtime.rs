@@ -0,0 +1,178 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The process wide state of the lib: chain type and logging, set up once by `grin_init`
+//! and torn down by `grin_shutdown`.
+
+use serde::Deserialize;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_core::global::{self, ChainTypes};
+use grin_wallet_util::grin_util::{init_logger, LoggingConfig, Mutex};
+
+use crate::errors::LibErrorKind;
+use crate::{approval, capability, events, node, queue, receive, relay, services, signer};
+
+/// The global config of `grin_init`
+#[derive(Deserialize, Clone)]
+pub struct GlobalCfg {
+    pub chain_type: String,
+    /// Log file path, no logging if not set
+    #[serde(default)]
+    pub log_file_path: Option<String>,
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Refuse all the spending entry points, as the `read-only` build feature does
+    #[serde(default)]
+    pub read_only: bool,
+    /// The genesis block hash of the chain, hex, checked against the node by
+    /// `grin_node_check_genesis`. Mostly for the dev chains, which get reset.
+    #[serde(default)]
+    pub genesis_hash: Option<String>,
+    /// A directory for the process wide caches, i.e. the recommended server list
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+}
+
+fn default_log_level() -> String {
+    "Info".to_owned()
+}
+
+lazy_static! {
+    static ref GLOBAL_CFG: Mutex<Option<GlobalCfg>> = Mutex::new(None);
+}
+
+pub fn parse_chain_type(chain_type: &str) -> Result<ChainTypes, Error> {
+    match chain_type {
+        "mainnet" => Ok(ChainTypes::Mainnet),
+        "floonet" => Ok(ChainTypes::Floonet),
+        // The local dev chains, for the integration tests against a local node
+        "usernet" => Ok(ChainTypes::UserTesting),
+        "automated_testing" => Ok(ChainTypes::AutomatedTesting),
+        _ => Err(ErrorKind::GenericError("unsupported chain type".to_owned()).into()),
+    }
+}
+
+/// Set up the global state. Calling it again with another chain type is refused, the app
+/// must call `shutdown` firstly to switch the network.
+pub fn init(global_cfg: GlobalCfg) -> Result<(), Error> {
+    let mut current = GLOBAL_CFG.lock();
+    if let Some(cfg) = current.as_ref() {
+        if cfg.chain_type != global_cfg.chain_type {
+            return Err(ErrorKind::GenericError(format!(
+                "already initialized for {}, shutdown firstly",
+                cfg.chain_type
+            ))
+            .into());
+        }
+        return Ok(());
+    }
+
+    let chain_type = parse_chain_type(&global_cfg.chain_type)?;
+    global::set_mining_mode(chain_type);
+
+    if let Some(log_file_path) = global_cfg.log_file_path.clone() {
+        let level = global_cfg
+            .log_level
+            .parse()
+            .map_err(|_| ErrorKind::ArgumentError("invalid log level".to_owned()))?;
+        init_logger(Some(LoggingConfig {
+            log_to_stdout: false,
+            log_to_file: true,
+            file_log_level: level,
+            log_file_path,
+            log_file_append: true,
+            ..LoggingConfig::default()
+        }));
+    }
+
+    *current = Some(global_cfg);
+    Ok(())
+}
+
+/// Stop the background services and clear the global state
+pub fn shutdown() {
+    services::stop();
+    queue::stop_service();
+    relay::clear_query_handles();
+    events::set_callback(None);
+    receive::set_message_callback(None);
+    approval::set_callback(None);
+    node::clear_cache();
+    signer::clear();
+    capability::clear();
+    *GLOBAL_CFG.lock() = None;
+}
+
+/// The initialized chain type, if `init` was called
+pub fn chain_type() -> Option<String> {
+    GLOBAL_CFG.lock().as_ref().map(|cfg| cfg.chain_type.clone())
+}
+
+/// The configured genesis block hash of the chain, if any
+pub fn genesis_hash() -> Option<String> {
+    GLOBAL_CFG
+        .lock()
+        .as_ref()
+        .and_then(|cfg| cfg.genesis_hash.clone())
+}
+
+/// The cache directory of the initialized lib, if set
+pub fn cache_dir() -> Option<String> {
+    GLOBAL_CFG
+        .lock()
+        .as_ref()
+        .and_then(|cfg| cfg.cache_dir.clone())
+}
+
+/// The log file path of the initialized lib, if logging is enabled
+pub fn log_file_path() -> Option<String> {
+    GLOBAL_CFG
+        .lock()
+        .as_ref()
+        .and_then(|cfg| cfg.log_file_path.clone())
+}
+
+/// Whether the spending entry points are disabled, by the `read-only` build feature or by the
+/// `read_only` flag of `init`. The flag can only be changed by a `shutdown` and a new `init`.
+pub fn is_read_only() -> bool {
+    cfg!(feature = "read-only")
+        || GLOBAL_CFG
+            .lock()
+            .as_ref()
+            .map(|cfg| cfg.read_only)
+            .unwrap_or(false)
+}
+
+/// Refuse a spending `operation` in the read-only profile. The balance, tx and receive calls
+/// are never refused.
+pub fn check_spending(operation: &str) -> Result<(), Error> {
+    if is_read_only() {
+        return Err(LibErrorKind::ReadOnly(operation.to_owned()).into());
+    }
+    Ok(())
+}
+
+/// Refuse a wallet config of another chain type than the initialized one, since the chain
+/// type is a process wide state.
+pub fn check_chain_type(chain_type: &str) -> Result<(), Error> {
+    match GLOBAL_CFG.lock().as_ref() {
+        Some(cfg) if cfg.chain_type != chain_type => Err(ErrorKind::GenericError(format!(
+            "lib initialized for {}, but {} is configured",
+            cfg.chain_type, chain_type
+        ))
+        .into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! End to end self test of the extern functions, with two fixture wallets on the mock node.
+//! Built with the `mock-node` feature only.
+
+use std::fs;
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+
+use crate::upstream::{self, WalletApi};
+use crate::{
+    check_password, get_balance, get_wallet_instance, mock, outputs_retrieve, runtime, send_tx,
+    send_tx_args, tx_export_raw, txs_retrieve, wallet_check, MobileWalletCfg,
+};
+
+/// The mnemonic of the receiving wallet, distinct from the fixture one
+const RECEIVER_MNEMONIC: &str = "legal winner thank year wave sausage worth useful legal \
+                                 winner thank yellow";
+
+/// The amount sent from the funded wallet to the receiving one, 1 grin
+const SEND_AMOUNT: u64 = 1_000_000_000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StepReport {
+    name: &'static str,
+    passed: bool,
+    error: Option<String>,
+    duration_ms: u64,
+}
+
+struct SelfTest {
+    steps: Vec<StepReport>,
+    failed: bool,
+}
+
+impl SelfTest {
+    /// Run a step, skipped once a previous step failed since the steps build on each other
+    fn step<T, F: FnOnce() -> Result<T, Error>>(&mut self, name: &'static str, f: F) -> Option<T> {
+        if self.failed {
+            return None;
+        }
+        let start = Instant::now();
+        let res = f();
+        let duration_ms = start.elapsed().as_millis() as u64;
+        self.failed = res.is_err();
+        self.steps.push(StepReport {
+            name,
+            passed: res.is_ok(),
+            error: res.as_ref().err().map(|e| e.to_string()),
+            duration_ms,
+        });
+        res.ok()
+    }
+}
+
+fn check(condition: bool, msg: &str) -> Result<(), Error> {
+    if condition {
+        Ok(())
+    } else {
+        Err(ErrorKind::GenericError(msg.to_owned()).into())
+    }
+}
+
+/// A wallet info amount, serialized as a string or a number
+fn amount(info: &Value, field: &str) -> u64 {
+    match &info[field] {
+        Value::String(s) => s.parse().unwrap_or(0),
+        v => v.as_u64().unwrap_or(0),
+    }
+}
+
+fn balance(json_cfg: &str) -> Result<Value, Error> {
+    let (_, info) = get_balance(json_cfg, None)?;
+    serde_json::from_str(&info).map_err(|e| ErrorKind::GenericError(e.to_string()).into())
+}
+
+fn list_len(json: &str) -> usize {
+    // The wallet api lists are (validated, entries)
+    serde_json::from_str::<Value>(json)
+        .ok()
+        .and_then(|v| v[1].as_array().map(|a| a.len()))
+        .unwrap_or(0)
+}
+
+/// Run the self test on a scratch mock chain, in a temporary directory removed afterwards.
+/// The mock node and its chain are back to their state before once done.
+pub fn run() -> Result<String, Error> {
+    if !cfg!(debug_assertions) {
+        return Err(
+            ErrorKind::GenericError("self test is for debug builds only".to_owned()).into(),
+        );
+    }
+    let chain_type = runtime::chain_type().unwrap_or_else(|| "floonet".to_owned());
+    let dir = std::env::temp_dir().join(format!("grin_selftest_{}", Uuid::new_v4()));
+    let sender_dir = dir.join("sender").to_string_lossy().into_owned();
+    let receiver_dir = dir.join("receiver").to_string_lossy().into_owned();
+    let test = mock::with_scratch_chain(|| steps(&chain_type, &sender_dir, &receiver_dir));
+    let _ = fs::remove_dir_all(&dir);
+    Ok(json!({
+        "passed": !test.failed,
+        "steps": test.steps,
+    })
+    .to_string())
+}
+
+fn steps(chain_type: &str, sender_dir: &str, receiver_dir: &str) -> SelfTest {
+    let mut test = SelfTest {
+        steps: vec![],
+        failed: false,
+    };
+    let sender = test.step("sender_fixture", || {
+        mock::fixture(chain_type, sender_dir, 2)
+    });
+    let receiver = test.step("receiver_fixture", || {
+        mock::fixture_with(chain_type, receiver_dir, RECEIVER_MNEMONIC, 0)
+    });
+    if let (Some(sender), Some(receiver)) = (sender, receiver) {
+        test.step("check_password", || {
+            check_password(&sender, mock::FIXTURE_PASSWORD).map(|_| ())
+        });
+        test.step("sender_balance", || {
+            let info = balance(&sender)?;
+            check(
+                amount(&info, "amount_currently_spendable") > SEND_AMOUNT,
+                "sender not funded",
+            )
+        });
+        let slate_id = test.step("send", || {
+            let sender_config = MobileWalletCfg::from_str(&sender)?;
+            let sender_wallet = get_wallet_instance(sender_config.clone())?;
+            let receiver_wallet = get_wallet_instance(MobileWalletCfg::from_str(&receiver)?)?;
+            let foreign = upstream::api(receiver_wallet);
+            let args = send_tx_args(SEND_AMOUNT, "smallest", None, "self test");
+            let result = send_tx(
+                &sender_config,
+                sender_wallet,
+                args,
+                None,
+                None,
+                "local",
+                |slate_r1| {
+                    let slate = foreign.receive(slate_r1, "default", None, None)?;
+                    Ok((slate, None, None))
+                },
+            )?;
+            Ok(result.slate_id)
+        });
+        test.step("receiver_balance", || {
+            let info = balance(&receiver)?;
+            check(
+                amount(&info, "total") == SEND_AMOUNT,
+                "received amount mismatch",
+            )
+        });
+        test.step("txs_retrieve", || {
+            check(
+                list_len(&txs_retrieve(&sender, None)?) >= 3,
+                "missing sender txs",
+            )
+        });
+        test.step("outputs_retrieve", || {
+            check(
+                list_len(&outputs_retrieve(&receiver, None, None)?) == 1,
+                "missing receiver output",
+            )
+        });
+        if let Some(slate_id) = slate_id {
+            test.step("tx_export_raw", || {
+                tx_export_raw(&sender, &slate_id).map(|_| ())
+            });
+        }
+        test.step("wallet_check", || {
+            wallet_check(&receiver, 1, 1000, true).map(|_| ())
+        });
+    }
+    test
+}
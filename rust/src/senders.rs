@@ -0,0 +1,158 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The known senders: the relay address each incoming tx was sent from, as told by the relay
+//! envelope, kept by slate id for the tx retrieval ("received from gri1...") and aggregated by
+//! address for the contact suggestions.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use grin_wallet_impls::Error;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::{contacts, new_wallet_config, store, MobileWalletCfg};
+
+pub const KNOWN_SENDERS_FILE: &str = "known_senders.json";
+
+/// Max number of remembered slate senders, the oldest ones are dropped
+const MAX_SENDER_SLATES: usize = 10_000;
+
+/// A sender of incoming txs
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownSender {
+    pub address: String,
+    pub received_count: u64,
+    pub received_amount: u64,
+    pub first_received_at: u64,
+    pub last_received_at: u64,
+    /// The name of the contact with this address, if any
+    #[serde(default, skip_deserializing)]
+    pub contact: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct KnownSenders {
+    /// (slate id, sender address), the latest last
+    slates: Vec<(String, String)>,
+    senders: Vec<KnownSender>,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the known senders file
+    static ref SENDERS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn data_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        KNOWN_SENDERS_FILE,
+    ))
+}
+
+/// Record the sender of a received slate
+pub fn record(
+    config: &MobileWalletCfg,
+    slate_id: &str,
+    sender: &str,
+    amount: u64,
+) -> Result<(), Error> {
+    if sender.is_empty() {
+        return Ok(());
+    }
+    let _guard = SENDERS_LOCK.lock();
+    let path = data_path(config)?;
+    let mut known: KnownSenders = store::load(&path)?;
+    let now = store::now_secs();
+    known.slates.push((slate_id.to_owned(), sender.to_owned()));
+    let excess = known.slates.len().saturating_sub(MAX_SENDER_SLATES);
+    known.slates.drain(..excess);
+    match known.senders.iter_mut().find(|s| s.address == sender) {
+        Some(known_sender) => {
+            known_sender.received_count += 1;
+            known_sender.received_amount += amount;
+            known_sender.last_received_at = now;
+        }
+        None => known.senders.push(KnownSender {
+            address: sender.to_owned(),
+            received_count: 1,
+            received_amount: amount,
+            first_received_at: now,
+            last_received_at: now,
+            contact: None,
+        }),
+    }
+    store::save(&path, &known)
+}
+
+/// The sender of a received slate, if known
+pub fn sender_of(config: &MobileWalletCfg, slate_id: &str) -> Result<Option<String>, Error> {
+    let known: KnownSenders = {
+        let _guard = SENDERS_LOCK.lock();
+        store::load(&data_path(config)?)?
+    };
+    Ok(known
+        .slates
+        .into_iter()
+        .rev()
+        .find(|(id, _)| id == slate_id)
+        .map(|(_, sender)| sender))
+}
+
+/// The known senders, with their contact name if any, the latest first
+pub fn list(config: &MobileWalletCfg) -> Result<Vec<KnownSender>, Error> {
+    let known: KnownSenders = {
+        let _guard = SENDERS_LOCK.lock();
+        store::load(&data_path(config)?)?
+    };
+    let contacts = contacts::list(config)?;
+    let mut senders = known.senders;
+    for sender in senders.iter_mut() {
+        sender.contact = contacts
+            .iter()
+            .find(|c| c.relay_address.as_deref() == Some(sender.address.as_str()))
+            .map(|c| c.name.clone());
+    }
+    senders.sort_by(|a, b| b.last_received_at.cmp(&a.last_received_at));
+    Ok(senders)
+}
+
+/// Add the `sender_address` of the received txs of a tx retrieval `[refreshed, [txs]]`
+pub fn annotate(config: &MobileWalletCfg, txs: &mut Value) -> Result<(), Error> {
+    let known: KnownSenders = {
+        let _guard = SENDERS_LOCK.lock();
+        store::load(&data_path(config)?)?
+    };
+    if known.slates.is_empty() {
+        return Ok(());
+    }
+    let by_slate: HashMap<String, String> = known.slates.into_iter().collect();
+    if let Some(entries) = txs.get_mut(1).and_then(|e| e.as_array_mut()) {
+        for entry in entries {
+            let sender = entry
+                .get("tx_slate_id")
+                .and_then(|id| id.as_str())
+                .and_then(|id| by_slate.get(id));
+            if let (Some(sender), Some(obj)) = (sender.cloned(), entry.as_object_mut()) {
+                obj.insert("sender_address".to_owned(), Value::String(sender));
+            }
+        }
+    }
+    Ok(())
+}
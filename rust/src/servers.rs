@@ -0,0 +1,528 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The recommended node and relay servers, a signed json list fetched from a remote url, so
+//! the servers can change without an app release. The list is verified against the embedded
+//! publisher key, and kept in the `cache_dir` of `grin_init` for the next launches.
+//!
+//! The node selected on a device network is sticky: it's kept for the next launches on the same
+//! network, without probing all the nodes again, and only replaced by the background re-probe
+//! of the services once it degrades.
+//!
+//! The relays are probed on demand, by a websocket handshake over TLS unless the config has
+//! `grinrelay_protocol_unsecure`, the fastest one being kept as the relay of its chain type
+//! for the configs with `auto_select_relay`.
+//!
+//! The signed list is `{"payload": "<list json>", "signature": "<hex>"}`, the signature a DER
+//! ECDSA signature of the blake2b-256 hash of the payload string.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustls::{ClientConfig, ClientSession, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_util::grin_api;
+use grin_wallet_util::grin_util::blake2::blake2b::blake2b;
+use grin_wallet_util::grin_util::secp::key::PublicKey;
+use grin_wallet_util::grin_util::secp::{Message, Signature};
+use grin_wallet_util::grin_util::{from_hex, static_secp_instance, Mutex};
+
+use crate::{events, runtime, store, DEFAULT_FLOONET_NODE_API_ADDR, DEFAULT_NODE_API_ADDR};
+
+/// The public key of the server list publisher, compressed secp256k1
+const SERVER_LIST_PUBLIC_KEY: &str =
+    "022239af9daa686b2fd3cd57d480a77d35ddad2b1d77ea38391eca95dcd56f4cd0";
+
+const SERVER_LIST_FILE: &str = "server_list.json";
+
+const NODE_SELECTION_FILE: &str = "node_selection.json";
+
+const RELAY_SELECTION_FILE: &str = "relay_selection.json";
+
+/// The connect and handshake timeout of a relay probe, each
+const RELAY_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The websocket key of the probe handshakes, the sample nonce of RFC 6455
+const PROBE_WEBSOCKET_KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+/// The `Sec-WebSocket-Accept` of `PROBE_WEBSOCKET_KEY`, the base64 sha1 of the key and the
+/// websocket guid, as in RFC 6455
+const PROBE_WEBSOCKET_ACCEPT: &str = "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=";
+
+/// Max size of a probe handshake response
+const MAX_HANDSHAKE_RESPONSE: usize = 4096;
+
+/// The network of a device not told its network yet
+const DEFAULT_NETWORK: &str = "default";
+
+/// A selected node degrades when its latency exceeds this multiple of its selection latency,
+/// and at least `MIN_DEGRADATION_MS` more
+const DEGRADATION_FACTOR: u128 = 3;
+const MIN_DEGRADATION_MS: u128 = 300;
+
+/// The signed list, as published
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignedServerList {
+    pub payload: String,
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeServer {
+    pub url: String,
+    pub chain_type: String,
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayServer {
+    pub domain: String,
+    pub port: u16,
+    pub chain_type: String,
+}
+
+/// The list of the recommended servers
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerList {
+    /// Increased on each publication, an older list than the cached one is refused
+    pub version: u64,
+    pub issued_at: u64,
+    #[serde(default)]
+    pub nodes: Vec<NodeServer>,
+    #[serde(default)]
+    pub relays: Vec<RelayServer>,
+}
+
+/// The node selected on a device network
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StickyNode {
+    pub url: String,
+    pub latency_ms: u64,
+    pub selected_at: u64,
+    pub probed_at: u64,
+}
+
+/// The outcome of a relay probe
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayProbe {
+    pub domain: String,
+    pub port: u16,
+    /// The tcp connect time
+    pub connect_ms: Option<u64>,
+    /// The connect, TLS and websocket handshake time, if the handshake succeeded
+    pub handshake_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// The relay selected for a chain type
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectedRelay {
+    pub domain: String,
+    pub port: u16,
+    pub latency_ms: u64,
+    pub selected_at: u64,
+}
+
+lazy_static! {
+    /// The verified list, loaded from the cache dir on the first use
+    static ref SERVER_LIST: Mutex<Option<ServerList>> = Mutex::new(None);
+    /// The selected node of each device network, loaded from the cache dir on the first use
+    static ref SELECTION: Mutex<Option<HashMap<String, StickyNode>>> = Mutex::new(None);
+    /// The selected relay of each chain type, loaded from the cache dir on the first use
+    static ref RELAY_SELECTION: Mutex<Option<HashMap<String, SelectedRelay>>> = Mutex::new(None);
+    /// The current device network, i.e. "wifi:<ssid>" or "cellular", as told by the app
+    static ref NETWORK: Mutex<String> = Mutex::new(DEFAULT_NETWORK.to_owned());
+}
+
+fn invalid(msg: &str) -> Error {
+    ErrorKind::GenericError(format!("invalid server list: {}", msg)).into()
+}
+
+/// Verify the signature of a signed list against the embedded key, and parse its payload
+pub fn verify(signed: &SignedServerList) -> Result<ServerList, Error> {
+    {
+        let secp = static_secp_instance();
+        let secp = secp.lock();
+        let key_bytes = from_hex(SERVER_LIST_PUBLIC_KEY.to_owned())
+            .map_err(|_| invalid("invalid publisher key"))?;
+        let public_key = PublicKey::from_slice(&secp, &key_bytes)
+            .map_err(|_| invalid("invalid publisher key"))?;
+        let hash = blake2b(32, &[], signed.payload.as_bytes());
+        let msg = Message::from_slice(hash.as_bytes()).map_err(|_| invalid("invalid hash"))?;
+        let sig_bytes =
+            from_hex(signed.signature.clone()).map_err(|_| invalid("invalid signature hex"))?;
+        let sig =
+            Signature::from_der(&secp, &sig_bytes).map_err(|_| invalid("invalid signature"))?;
+        secp.verify(&msg, &sig, &public_key)
+            .map_err(|_| invalid("signature verification failed"))?;
+    }
+    serde_json::from_str(&signed.payload).map_err(|e| invalid(&e.to_string()))
+}
+
+fn cache_path(name: &str) -> Option<std::path::PathBuf> {
+    runtime::cache_dir().map(|dir| store::data_file(&dir, name))
+}
+
+/// The cached list, verified again when loaded from the cache dir
+pub fn cached() -> Option<ServerList> {
+    let mut current = SERVER_LIST.lock();
+    if current.is_none() {
+        let path = cache_path(SERVER_LIST_FILE).filter(|path| path.exists())?;
+        let signed: SignedServerList = store::load::<Option<_>>(&path).ok()??;
+        *current = verify(&signed).ok();
+    }
+    current.clone()
+}
+
+/// Fetch the signed list at `url`, verify it, and cache it
+pub fn update(url: &str) -> Result<ServerList, Error> {
+    let signed = grin_api::client::get::<SignedServerList>(url, None)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to fetch the server list: {}", e)))?;
+    let list = verify(&signed)?;
+    if let Some(current) = cached() {
+        if list.version < current.version {
+            return Err(invalid(&format!(
+                "version {} older than the cached {}",
+                list.version, current.version
+            )));
+        }
+    }
+    if let Some(path) = cache_path(SERVER_LIST_FILE) {
+        store::save(&path, &Some(signed))?;
+    }
+    *SERVER_LIST.lock() = Some(list.clone());
+    Ok(list)
+}
+
+/// The recommended nodes of the initialized chain type
+fn recommended_nodes(list: &ServerList) -> Vec<String> {
+    let chain_type = runtime::chain_type();
+    list.nodes
+        .iter()
+        .filter(|node| chain_type.as_ref().map_or(true, |c| *c == node.chain_type))
+        .map(|node| node.url.trim_end_matches('/').to_owned())
+        .collect()
+}
+
+/// Set the current device network, i.e. "wifi:<ssid>" or "cellular"
+pub fn set_network(network: &str) {
+    let network = network.trim();
+    *NETWORK.lock() = if network.is_empty() {
+        DEFAULT_NETWORK.to_owned()
+    } else {
+        network.to_owned()
+    };
+}
+
+fn network() -> String {
+    NETWORK.lock().clone()
+}
+
+/// The selected node of each device network
+pub fn selection() -> HashMap<String, StickyNode> {
+    let mut selection = SELECTION.lock();
+    if selection.is_none() {
+        *selection = Some(
+            cache_path(NODE_SELECTION_FILE)
+                .and_then(|path| store::load(&path).ok())
+                .unwrap_or_default(),
+        );
+    }
+    selection.clone().unwrap_or_default()
+}
+
+fn set_selection(network: &str, node: StickyNode) -> Result<(), Error> {
+    let mut all = selection();
+    all.insert(network.to_owned(), node);
+    if let Some(path) = cache_path(NODE_SELECTION_FILE) {
+        store::save(&path, &all)?;
+    }
+    *SELECTION.lock() = Some(all);
+    Ok(())
+}
+
+/// The latency of a node in milliseconds, None if it doesn't respond
+fn probe(url: &str) -> Option<u128> {
+    let started = Instant::now();
+    grin_api::client::get::<Value>(&format!("{}/v1/chain", url), None).ok()?;
+    Some(started.elapsed().as_millis())
+}
+
+/// Probe all the candidates and keep the fastest one as the node of the current network
+fn probe_all(candidates: Vec<String>) -> Result<StickyNode, Error> {
+    let mut best: Option<(u128, String)> = None;
+    for url in candidates {
+        if let Some(elapsed) = probe(&url) {
+            if best.as_ref().map_or(true, |(ms, _)| elapsed < *ms) {
+                best = Some((elapsed, url));
+            }
+        }
+    }
+    let (latency_ms, url) =
+        best.ok_or_else(|| ErrorKind::GenericError("no recommended node responding".to_owned()))?;
+    let now = store::now_secs();
+    let node = StickyNode {
+        url,
+        latency_ms: latency_ms as u64,
+        selected_at: now,
+        probed_at: now,
+    };
+    set_selection(&network(), node.clone())?;
+    Ok(node)
+}
+
+/// The node of the current network if one was selected and is still recommended, otherwise
+/// the fastest responding recommended node, if `node_api_addr` is a default or a recommended
+/// one and a list is cached. A node of the user's own is kept, None is returned.
+pub fn select_node(node_api_addr: &str) -> Result<Option<String>, Error> {
+    let list = match cached() {
+        Some(list) => list,
+        None => return Ok(None),
+    };
+    let candidates = recommended_nodes(&list);
+    let addr = node_api_addr.trim_end_matches('/');
+    let recommended = addr == DEFAULT_NODE_API_ADDR
+        || addr == DEFAULT_FLOONET_NODE_API_ADDR
+        || candidates.iter().any(|url| url == addr);
+    if !recommended || candidates.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(sticky) = selection().remove(&network()) {
+        if candidates.contains(&sticky.url) {
+            return Ok(Some(sticky.url));
+        }
+    }
+    Ok(Some(probe_all(candidates)?.url))
+}
+
+/// Probe the node of the current network again, and switch to the fastest recommended one if
+/// it degraded, with a `node_switched` event. Run by the background services.
+pub fn reprobe() -> Result<(), Error> {
+    let network = network();
+    let sticky = match selection().remove(&network) {
+        Some(sticky) => sticky,
+        None => return Ok(()),
+    };
+    let degraded = match probe(&sticky.url) {
+        Some(ms) => {
+            ms > sticky.latency_ms as u128 * DEGRADATION_FACTOR
+                && ms > sticky.latency_ms as u128 + MIN_DEGRADATION_MS
+        }
+        None => true,
+    };
+    if !degraded {
+        return set_selection(
+            &network,
+            StickyNode {
+                probed_at: store::now_secs(),
+                ..sticky
+            },
+        );
+    }
+
+    let candidates = cached().map(|list| recommended_nodes(&list));
+    let best = probe_all(candidates.unwrap_or_default())?;
+    if best.url != sticky.url {
+        events::emit(
+            "node_switched",
+            json!({ "network": network, "from": sticky.url, "to": best.url }),
+        );
+    }
+    Ok(())
+}
+
+/// Connect a relay and do the websocket opening handshake, which the relay accepts with a
+/// "101 Switching Protocols"
+fn probe_relay(domain: &str, port: u16, secure: bool) -> RelayProbe {
+    let mut probe = RelayProbe {
+        domain: domain.to_owned(),
+        port,
+        connect_ms: None,
+        handshake_ms: None,
+        error: None,
+    };
+    let started = Instant::now();
+    let res = (|| -> Result<(), String> {
+        let addr = (domain, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("fail to resolve: {}", e))?
+            .next()
+            .ok_or_else(|| "fail to resolve".to_owned())?;
+        let mut stream = TcpStream::connect_timeout(&addr, RELAY_PROBE_TIMEOUT)
+            .map_err(|e| format!("fail to connect: {}", e))?;
+        probe.connect_ms = Some(started.elapsed().as_millis() as u64);
+
+        stream
+            .set_read_timeout(Some(RELAY_PROBE_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(RELAY_PROBE_TIMEOUT)))
+            .map_err(|e| e.to_string())?;
+        if !secure {
+            return websocket_handshake(stream, domain, port);
+        }
+        // The TLS handshake runs with the first write of the websocket handshake
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str(domain)
+            .map_err(|_| "invalid relay domain".to_owned())?;
+        let mut config = ClientConfig::new();
+        config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        let mut session = ClientSession::new(&Arc::new(config), dns_name);
+        websocket_handshake(Stream::new(&mut session, &mut stream), domain, port)
+    })();
+    match res {
+        Ok(()) => probe.handshake_ms = Some(started.elapsed().as_millis() as u64),
+        Err(e) => probe.error = Some(e),
+    }
+    probe
+}
+
+/// A websocket opening handshake, checked by its status and its `Sec-WebSocket-Accept`
+fn websocket_handshake<S: Read + Write>(
+    mut stream: S,
+    domain: &str,
+    port: u16,
+) -> Result<(), String> {
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: {}:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        domain, port, PROBE_WEBSOCKET_KEY
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("fail to send the handshake: {}", e))?;
+
+    // The headers end with an empty line, the relay then waits for the websocket frames
+    let mut response = vec![];
+    let mut buf = [0u8; 512];
+    while !response.windows(4).any(|w| w == b"\r\n\r\n") {
+        if response.len() > MAX_HANDSHAKE_RESPONSE {
+            return Err("handshake response too large".to_owned());
+        }
+        let len = stream
+            .read(&mut buf)
+            .map_err(|e| format!("no handshake response: {}", e))?;
+        if len == 0 {
+            return Err("handshake response truncated".to_owned());
+        }
+        response.extend_from_slice(&buf[..len]);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let mut lines = response.lines();
+    let status = lines.next().unwrap_or_default();
+    if !status.starts_with("HTTP/1.1 101") {
+        return Err(format!("handshake refused: {}", status));
+    }
+    let accept = lines
+        .filter_map(|line| {
+            let mut header = line.splitn(2, ':');
+            let name = header.next()?.trim();
+            let value = header.next()?.trim();
+            Some((name, value))
+        })
+        .find(|(name, _)| name.eq_ignore_ascii_case("sec-websocket-accept"))
+        .map(|(_, value)| value);
+    if accept != Some(PROBE_WEBSOCKET_ACCEPT) {
+        return Err("handshake with a wrong Sec-WebSocket-Accept".to_owned());
+    }
+    Ok(())
+}
+
+/// The recommended relays of a chain type
+fn recommended_relays(list: &ServerList, chain_type: &str) -> Vec<RelayServer> {
+    list.relays
+        .iter()
+        .filter(|relay| relay.chain_type == chain_type)
+        .cloned()
+        .collect()
+}
+
+fn relay_selection() -> HashMap<String, SelectedRelay> {
+    let mut selection = RELAY_SELECTION.lock();
+    if selection.is_none() {
+        *selection = Some(
+            cache_path(RELAY_SELECTION_FILE)
+                .and_then(|path| store::load(&path).ok())
+                .unwrap_or_default(),
+        );
+    }
+    selection.clone().unwrap_or_default()
+}
+
+/// The selected relay of a chain type, if any
+pub fn selected_relay(chain_type: &str) -> Option<SelectedRelay> {
+    relay_selection().remove(chain_type)
+}
+
+/// Probe the candidate relays of a chain type in parallel, the recommended ones if none, and
+/// keep the fastest one with a successful handshake as the relay of the chain type. Returns
+/// the new selection, and the probes the fastest first.
+pub fn probe_relays(
+    chain_type: &str,
+    candidates: Vec<(String, u16)>,
+    secure: bool,
+) -> Result<(Option<SelectedRelay>, Vec<RelayProbe>), Error> {
+    let candidates = if candidates.is_empty() {
+        cached()
+            .map(|list| recommended_relays(&list, chain_type))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|relay| (relay.domain, relay.port))
+            .collect()
+    } else {
+        candidates
+    };
+    if candidates.is_empty() {
+        return Err(ErrorKind::ArgumentError("no relay to probe".to_owned()).into());
+    }
+
+    let handles: Vec<_> = candidates
+        .into_iter()
+        .map(|(domain, port)| thread::spawn(move || probe_relay(&domain, port, secure)))
+        .collect();
+    let mut probes: Vec<RelayProbe> = handles.into_iter().filter_map(|h| h.join().ok()).collect();
+    probes.sort_by_key(|p| (p.handshake_ms.is_none(), p.handshake_ms, p.connect_ms));
+
+    let selected = probes.first().and_then(|best| {
+        best.handshake_ms.map(|latency_ms| SelectedRelay {
+            domain: best.domain.clone(),
+            port: best.port,
+            latency_ms,
+            selected_at: store::now_secs(),
+        })
+    });
+    if let Some(selected) = selected.as_ref() {
+        let mut all = relay_selection();
+        all.insert(chain_type.to_owned(), selected.clone());
+        if let Some(path) = cache_path(RELAY_SELECTION_FILE) {
+            store::save(&path, &all)?;
+        }
+        *RELAY_SELECTION.lock() = Some(all);
+    }
+    Ok((selected, probes))
+}
@@ -0,0 +1,700 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The background threads of the lib, and the supervised set of workers started by
+//! `grin_start_services`: a balance refresher, a tx monitor, the relay listener, the post
+//! retry queue and the webhook retries, all watched by one watchdog which restarts a dead
+//! worker.
+//!
+//! The threads holding a wallet, i.e. its listeners and the calls waiting on the network, are
+//! registered with the wallet data dir, for `close_wallet` to stop and join them.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex as StdMutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::TxLogEntryType;
+use grin_wallet_util::grin_util::Mutex;
+
+use crate::upstream::{self, WalletApi};
+use crate::{
+    balance_info, events, get_wallet_instance, listen, netstats, pos, queue, relay, servers, store,
+    wallet_data_dir, webhook, MobileWalletCfg,
+};
+
+/// How often the watchdog checks the workers
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The options of `grin_start_services`, a zero interval disables its worker
+#[derive(Deserialize, Clone)]
+pub struct ServicesOptions {
+    #[serde(default = "default_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+    #[serde(default = "default_tx_monitor_interval_secs")]
+    pub tx_monitor_interval_secs: u64,
+    #[serde(default = "default_queue_interval_secs")]
+    pub queue_interval_secs: u64,
+    /// Re-probe the selected node, see `grin_set_network`
+    #[serde(default = "default_node_probe_interval_secs")]
+    pub node_probe_interval_secs: u64,
+    /// Retry the webhook notifications not delivered yet, see `webhook` of the config
+    #[serde(default = "default_webhook_interval_secs")]
+    pub webhook_interval_secs: u64,
+    /// Run the relay listener of the configured account
+    #[serde(default = "default_listen")]
+    pub listen: bool,
+}
+
+fn default_refresh_interval_secs() -> u64 {
+    60
+}
+
+fn default_tx_monitor_interval_secs() -> u64 {
+    30
+}
+
+fn default_queue_interval_secs() -> u64 {
+    30
+}
+
+fn default_node_probe_interval_secs() -> u64 {
+    300
+}
+
+fn default_webhook_interval_secs() -> u64 {
+    30
+}
+
+fn default_listen() -> bool {
+    true
+}
+
+/// A background thread, as listed by `grin_services_status`
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskInfo {
+    pub name: String,
+    pub alive: bool,
+    pub started_at: u64,
+    pub restarts: u32,
+    pub last_run: Option<u64>,
+    pub last_error: Option<String>,
+    /// Tells the thread of a task from the earlier ones of the same name
+    #[serde(skip)]
+    id: u64,
+}
+
+/// The running services, started by `start`
+struct Services {
+    json_cfg: String,
+    options: ServicesOptions,
+    generation: u64,
+    /// The relay address of the services listener, to stop it with the services
+    listener_address: Option<String>,
+}
+
+lazy_static! {
+    static ref SERVICES: Mutex<Option<Services>> = Mutex::new(None);
+    /// Bumped by each start and stop, the workers of an older generation exit
+    static ref GENERATION: AtomicU64 = AtomicU64::new(0);
+    static ref TASK_ID: AtomicU64 = AtomicU64::new(0);
+    static ref TASKS: Mutex<HashMap<String, TaskInfo>> = Mutex::new(HashMap::new());
+    /// The threads of each wallet data dir, not joined yet
+    static ref WALLET_THREADS: Mutex<HashMap<String, Vec<WalletThread>>> =
+        Mutex::new(HashMap::new());
+    /// Notified on each generation change, waking the sleeping workers
+    static ref GENERATION_CHANGED: (StdMutex<()>, Condvar) = (StdMutex::new(()), Condvar::new());
+    /// Notified on the end of each wallet thread, waking `close_wallet`
+    static ref THREAD_DONE: (StdMutex<()>, Condvar) = (StdMutex::new(()), Condvar::new());
+}
+
+/// A thread holding a wallet
+struct WalletThread {
+    name: String,
+    handle: JoinHandle<()>,
+    done: Arc<AtomicBool>,
+}
+
+/// Marks its wallet thread as done once the thread ends, also on a panic
+struct DoneGuard(Arc<AtomicBool>);
+
+impl Drop for DoneGuard {
+    fn drop(&mut self) {
+        let (lock, done) = &*THREAD_DONE;
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.0.store(true, Ordering::SeqCst);
+        done.notify_all();
+    }
+}
+
+/// Marks its task as dead once the thread ends, also on a panic, unless the task was
+/// restarted meanwhile by another thread
+struct AliveGuard(String, u64);
+
+impl Drop for AliveGuard {
+    fn drop(&mut self) {
+        if let Some(task) = TASKS.lock().get_mut(&self.0) {
+            if task.id == self.1 {
+                task.alive = false;
+            }
+        }
+    }
+}
+
+/// Spawn a named background thread, tracked in the task list. Used for all the long running
+/// threads of the lib instead of detached `thread::spawn`s.
+pub fn spawn<F: FnOnce() + Send + 'static>(name: &str, f: F) -> Result<(), Error> {
+    start_thread(name, f).map(|_| ())
+}
+
+/// Spawn a named background thread holding the wallet of a data dir, to be joined by
+/// `close_wallet`
+pub fn spawn_for<F: FnOnce() + Send + 'static>(
+    wallet: &str,
+    name: &str,
+    f: F,
+) -> Result<(), Error> {
+    let done = Arc::new(AtomicBool::new(false));
+    let guard = DoneGuard(done.clone());
+    let handle = start_thread(name, move || {
+        let _guard = guard;
+        f()
+    })?;
+    let mut threads = WALLET_THREADS.lock();
+    let threads = threads.entry(wallet.to_owned()).or_insert_with(Vec::new);
+    // The ended threads are joined as they are replaced
+    threads.retain(|t| !t.done.load(Ordering::SeqCst));
+    threads.push(WalletThread {
+        name: name.to_owned(),
+        handle,
+        done,
+    });
+    Ok(())
+}
+
+fn start_thread<F: FnOnce() + Send + 'static>(name: &str, f: F) -> Result<JoinHandle<()>, Error> {
+    let id = TASK_ID.fetch_add(1, Ordering::SeqCst) + 1;
+    {
+        let mut tasks = TASKS.lock();
+        let restarts = tasks.get(name).map(|t| t.restarts).unwrap_or(0);
+        tasks.insert(
+            name.to_owned(),
+            TaskInfo {
+                name: name.to_owned(),
+                alive: true,
+                started_at: store::now_secs(),
+                restarts,
+                last_run: None,
+                last_error: None,
+                id,
+            },
+        );
+    }
+    let guard = AliveGuard(name.to_owned(), id);
+    thread::Builder::new()
+        .name(format!("grin-{}", name))
+        .spawn(move || {
+            let _guard = guard;
+            f()
+        })
+        .map_err(|e| {
+            ErrorKind::GenericError(format!("fail to start thread {}: {}", name, e)).into()
+        })
+}
+
+fn record_run(name: &str, res: Result<(), Error>) {
+    if let Some(task) = TASKS.lock().get_mut(name) {
+        task.last_run = Some(store::now_secs());
+        task.last_error = res.err().map(|e| e.to_string());
+    }
+}
+
+fn is_alive(name: &str) -> bool {
+    TASKS.lock().get(name).map(|t| t.alive).unwrap_or(false)
+}
+
+fn is_current(generation: u64) -> bool {
+    GENERATION.load(Ordering::SeqCst) == generation
+}
+
+/// Start a new generation of the services, waking the workers of the older one
+fn next_generation() -> u64 {
+    let (lock, changed) = &*GENERATION_CHANGED;
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    changed.notify_all();
+    generation
+}
+
+/// Sleep for `duration`, returns false once the services of `generation` are stopped
+fn sleep_while_current(generation: u64, duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    let (lock, changed) = &*GENERATION_CHANGED;
+    let mut guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    while is_current(generation) {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        guard = changed
+            .wait_timeout(guard, deadline - now)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+    false
+}
+
+/// Run `step` every `interval_secs` until the services are stopped, as a thread of the
+/// wallet of `json_cfg`
+fn start_worker<F>(
+    name: &str,
+    json_cfg: &str,
+    generation: u64,
+    interval: Duration,
+    mut step: F,
+) -> Result<(), Error>
+where
+    F: FnMut() -> Result<(), Error> + Send + 'static,
+{
+    let wallet = wallet_data_dir(&MobileWalletCfg::from_str(json_cfg)?)?;
+    let task = name.to_owned();
+    spawn_for(&wallet, name, move || loop {
+        let res = step();
+        // A worker stopped meanwhile doesn't report on its restarted one
+        if !is_current(generation) {
+            break;
+        }
+        record_run(&task, res);
+        if !sleep_while_current(generation, interval) {
+            break;
+        }
+    })
+}
+
+/// Refresh the outputs from the node as allowed by the refresh policy, and notify the app of
+/// the new balance
+fn refresh(config: &MobileWalletCfg) -> Result<(), Error> {
+    if !netstats::should_refresh(config) {
+        return Ok(());
+    }
+    let (validated, info) = balance_info(get_wallet_instance(config.clone())?, config, true)?;
+    events::emit(
+        "balance_updated",
+        json!({ "account": config.account, "validated": validated, "info": info }),
+    );
+    Ok(())
+}
+
+/// Notify the app of the txs confirmed since the last round, with a `tx_confirmed` event, and
+/// pay the point-of-sale charges received by a confirmed tx
+fn monitor_txs(
+    config: &MobileWalletCfg,
+    unconfirmed: &mut Option<HashSet<u32>>,
+) -> Result<(), Error> {
+    let api = upstream::api(get_wallet_instance(config.clone())?);
+    let (_, txs) = api.txs(false, None, None)?;
+    let pending: HashSet<u32> = txs.iter().filter(|t| !t.confirmed).map(|t| t.id).collect();
+    if let Some(previous) = unconfirmed.as_ref() {
+        for tx in txs
+            .iter()
+            .filter(|t| t.confirmed && previous.contains(&t.id))
+        {
+            events::emit(
+                "tx_confirmed",
+                json!({
+                    "account": config.account,
+                    "id": tx.id,
+                    "slateId": tx.tx_slate_id.map(|id| id.to_string()),
+                }),
+            );
+        }
+    }
+    *unconfirmed = Some(pending);
+    let confirmed: HashSet<String> = txs
+        .iter()
+        .filter(|t| t.confirmed && t.tx_type == TxLogEntryType::TxReceived)
+        .filter_map(|t| t.tx_slate_id.map(|id| id.to_string()))
+        .collect();
+    pos::confirm(config, &confirmed)?;
+    Ok(())
+}
+
+fn start_refresher(
+    json_cfg: &str,
+    options: &ServicesOptions,
+    generation: u64,
+) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let interval = netstats::service_interval(&config, options.refresh_interval_secs);
+    start_worker(
+        "refresher",
+        json_cfg,
+        generation,
+        Duration::from_secs(interval),
+        move || refresh(&config),
+    )
+}
+
+fn start_tx_monitor(
+    json_cfg: &str,
+    options: &ServicesOptions,
+    generation: u64,
+) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let interval = netstats::service_interval(&config, options.tx_monitor_interval_secs);
+    let mut unconfirmed = None;
+    start_worker(
+        "tx_monitor",
+        json_cfg,
+        generation,
+        Duration::from_secs(interval),
+        move || monitor_txs(&config, &mut unconfirmed),
+    )
+}
+
+fn start_post_queue(
+    json_cfg: &str,
+    options: &ServicesOptions,
+    generation: u64,
+) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    queue::recover(&config)?;
+    let interval = netstats::service_interval(&config, options.queue_interval_secs);
+    let queue_cfg = json_cfg.to_owned();
+    start_worker(
+        "post_queue",
+        json_cfg,
+        generation,
+        Duration::from_secs(interval),
+        move || queue::process(&queue_cfg).map(|_| ()),
+    )
+}
+
+fn start_node_prober(
+    json_cfg: &str,
+    options: &ServicesOptions,
+    generation: u64,
+) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let interval = netstats::service_interval(&config, options.node_probe_interval_secs);
+    start_worker(
+        "node_prober",
+        json_cfg,
+        generation,
+        Duration::from_secs(interval),
+        servers::reprobe,
+    )
+}
+
+fn start_webhook_retry(
+    json_cfg: &str,
+    options: &ServicesOptions,
+    generation: u64,
+) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let interval = netstats::service_interval(&config, options.webhook_interval_secs);
+    start_worker(
+        "webhook_retry",
+        json_cfg,
+        generation,
+        Duration::from_secs(interval),
+        move || webhook::deliver(&config).map(|_| ()),
+    )
+}
+
+/// Start the relay listener of the configured wallet and account, if not running yet.
+/// Returns its relay address.
+fn ensure_listener(json_cfg: &str) -> Result<String, Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet = wallet_data_dir(&config)?;
+    let running = relay::listeners()
+        .into_iter()
+        .find(|l| l.wallet == wallet && l.account == config.account);
+    if let Some(listener) = running {
+        return Ok(listener.address);
+    }
+    listen(json_cfg, None, None)?;
+    relay::listeners()
+        .into_iter()
+        .find(|l| l.wallet == wallet && l.account == config.account)
+        .map(|l| l.address)
+        .ok_or_else(|| ErrorKind::GenericError("relay listener not registered".to_owned()).into())
+}
+
+type WorkerStart = fn(&str, &ServicesOptions, u64) -> Result<(), Error>;
+
+fn workers(options: &ServicesOptions) -> Vec<(&'static str, WorkerStart)> {
+    let mut workers: Vec<(&'static str, WorkerStart)> = vec![];
+    if options.refresh_interval_secs > 0 {
+        workers.push(("refresher", start_refresher));
+    }
+    if options.tx_monitor_interval_secs > 0 {
+        workers.push(("tx_monitor", start_tx_monitor));
+    }
+    if options.queue_interval_secs > 0 {
+        workers.push(("post_queue", start_post_queue));
+    }
+    if options.node_probe_interval_secs > 0 {
+        workers.push(("node_prober", start_node_prober));
+    }
+    if options.webhook_interval_secs > 0 {
+        workers.push(("webhook_retry", start_webhook_retry));
+    }
+    workers
+}
+
+fn restarted(name: &str, res: Result<(), Error>) {
+    if let Some(task) = TASKS.lock().get_mut(name) {
+        task.restarts += 1;
+    }
+    events::emit(
+        "service_restarted",
+        json!({ "name": name, "error": res.err().map(|e| e.to_string()) }),
+    );
+}
+
+/// Restart the dead workers and the relay listener, until the services are stopped
+fn watchdog(generation: u64) {
+    while sleep_while_current(generation, WATCHDOG_INTERVAL) {
+        let (json_cfg, options) = match SERVICES.lock().as_ref() {
+            Some(s) if s.generation == generation => (s.json_cfg.clone(), s.options.clone()),
+            _ => break,
+        };
+        for (name, start) in workers(&options) {
+            if !is_alive(name) {
+                restarted(name, start(&json_cfg, &options, generation));
+            }
+        }
+        if options.listen {
+            let listener_address = SERVICES
+                .lock()
+                .as_ref()
+                .and_then(|s| s.listener_address.clone());
+            let running = listener_address
+                .map(|address| relay::listener_account(&address).is_some())
+                .unwrap_or(false);
+            if !running {
+                let res = ensure_listener(&json_cfg);
+                if let Ok(address) = &res {
+                    match SERVICES.lock().as_mut() {
+                        Some(s) if s.generation == generation => {
+                            s.listener_address = Some(address.clone())
+                        }
+                        // The services were stopped meanwhile, so is the listener
+                        _ => relay::unregister_listener(address),
+                    }
+                }
+                restarted("relay_listener", res.map(|_| ()));
+            }
+        }
+    }
+}
+
+/// Start the background services of a wallet, `options_json` being the `ServicesOptions`.
+/// Any services already running are stopped firstly.
+pub fn start(json_cfg: &str, options_json: &str) -> Result<(), Error> {
+    MobileWalletCfg::from_str(json_cfg)?;
+    let options: ServicesOptions = if options_json.trim().is_empty() {
+        serde_json::from_str("{}").unwrap()
+    } else {
+        serde_json::from_str(options_json)
+            .map_err(|e| ErrorKind::ArgumentError(format!("invalid services options: {}", e)))?
+    };
+    stop();
+
+    let wallet = wallet_data_dir(&MobileWalletCfg::from_str(json_cfg)?)?;
+    let generation = next_generation();
+    // The listener is started firstly, its relay connection failure is a start failure
+    let listener_address = if options.listen {
+        Some(ensure_listener(json_cfg)?)
+    } else {
+        None
+    };
+    *SERVICES.lock() = Some(Services {
+        json_cfg: json_cfg.to_owned(),
+        options: options.clone(),
+        generation,
+        listener_address,
+    });
+    for (_, start) in workers(&options) {
+        if let Err(e) = start(json_cfg, &options, generation) {
+            stop();
+            return Err(e);
+        }
+    }
+    if let Err(e) = spawn_for(&wallet, "watchdog", move || watchdog(generation)) {
+        stop();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Stop the background services, the workers exit after their current round
+pub fn stop() {
+    next_generation();
+    if let Some(services) = SERVICES.lock().take() {
+        if let Some(address) = services.listener_address {
+            relay::unregister_listener(&address);
+        }
+    }
+}
+
+/// Stop the services, listeners and threads of the wallet of a data dir, waiting up to
+/// `timeout` for its threads to end, so its files and memory are released. The service
+/// workers are woken by the stop, the listener threads once their relay connection is closed,
+/// the network calls end once they complete. Returns the stopped listener addresses, and the
+/// names of the threads still running.
+pub fn close_wallet(wallet: &str, timeout: Duration) -> (Vec<String>, Vec<String>) {
+    let services_wallet = SERVICES.lock().as_ref().and_then(|s| {
+        MobileWalletCfg::from_str(&s.json_cfg)
+            .and_then(|config| wallet_data_dir(&config))
+            .ok()
+    });
+    if services_wallet.as_deref() == Some(wallet) {
+        stop();
+    }
+    let listeners: Vec<String> = relay::listeners()
+        .into_iter()
+        .filter(|l| l.wallet == wallet)
+        .map(|l| l.address)
+        .collect();
+    for address in listeners.iter() {
+        relay::unregister_listener(address);
+    }
+    relay::clear_query_handle(wallet);
+
+    let mut threads = WALLET_THREADS.lock().remove(wallet).unwrap_or_default();
+    let deadline = Instant::now() + timeout;
+    let (lock, done) = &*THREAD_DONE;
+    let mut guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        let (ended, running): (Vec<_>, Vec<_>) = threads
+            .into_iter()
+            .partition(|t| t.done.load(Ordering::SeqCst));
+        for t in ended {
+            let _ = t.handle.join();
+        }
+        threads = running;
+        let now = Instant::now();
+        if threads.is_empty() || now >= deadline {
+            break;
+        }
+        guard = done
+            .wait_timeout(guard, deadline - now)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+    drop(guard);
+    let running = threads.iter().map(|t| t.name.clone()).collect();
+    if !threads.is_empty() {
+        // Still tracked, for a later close
+        WALLET_THREADS
+            .lock()
+            .entry(wallet.to_owned())
+            .or_insert_with(Vec::new)
+            .extend(threads);
+    }
+    (listeners, running)
+}
+
+/// Whether two paths are the same directory, i.e. a wallet data dir found by a walk of its
+/// data dir and the one of a config
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Whether the wallet of a data dir has running services, listeners or threads, which might
+/// write into it
+pub fn is_wallet_open(dir: &Path) -> bool {
+    let services_wallet = SERVICES.lock().as_ref().and_then(|s| {
+        MobileWalletCfg::from_str(&s.json_cfg)
+            .and_then(|config| wallet_data_dir(&config))
+            .ok()
+    });
+    if services_wallet.map_or(false, |wallet| same_dir(Path::new(&wallet), dir)) {
+        return true;
+    }
+    if relay::listeners()
+        .iter()
+        .any(|l| same_dir(Path::new(&l.wallet), dir))
+    {
+        return true;
+    }
+    WALLET_THREADS.lock().iter().any(|(wallet, threads)| {
+        same_dir(Path::new(wallet), dir) && threads.iter().any(|t| !t.done.load(Ordering::SeqCst))
+    })
+}
+
+/// The background threads, alive or not
+pub fn tasks() -> Vec<TaskInfo> {
+    let mut tasks: Vec<TaskInfo> = TASKS.lock().values().cloned().collect();
+    tasks.sort_by(|a, b| a.name.cmp(&b.name));
+    tasks
+}
+
+/// Re-announce the relay addresses of the running listeners after a network change of the
+/// device, i.e. Wi-Fi to cellular, of the wallet of the config. The relay service keeps the slates sent to an address
+/// while it's offline, and delivers them once the address is registered again. A listener is
+/// given the configured grace period to reconnect by itself, then it's closed and restarted on
+/// the same address and account, unless it was stopped meanwhile.
+pub fn reannounce(json_cfg: &str) -> Result<(), Error> {
+    let config = MobileWalletCfg::from_str(json_cfg)?;
+    let wallet = wallet_data_dir(&config)?;
+    let grace = Duration::from_secs(config.reannounce_grace_secs);
+    let json_cfg = json_cfg.to_owned();
+    spawn("reannounce", move || {
+        // The listeners of the other wallets are restarted with their own config
+        let listeners: Vec<_> = relay::listeners()
+            .into_iter()
+            .filter(|l| l.wallet == wallet)
+            .collect();
+        if listeners.iter().any(|l| !l.connected) {
+            thread::sleep(grace);
+        }
+        for l in listeners {
+            if relay::is_listener_connected(&l.address) {
+                events::emit(
+                    "listener_reconnected",
+                    json!({ "address": l.address, "account": l.account }),
+                );
+                continue;
+            }
+            if !relay::is_current_listener(&l.address, &l.id) {
+                continue;
+            }
+            // The replaced listener still processes the slates it already received
+            relay::unregister_listener(&l.address);
+            let res = listen(&json_cfg, Some(&l.account), Some(l.key_index));
+            events::emit(
+                "listener_restarted",
+                json!({
+                    "address": l.address,
+                    "account": l.account,
+                    "error": res.err().map(|e| e.to_string()),
+                }),
+            );
+        }
+    })
+}
@@ -0,0 +1,238 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The external signer protocol: the blinding factor operations of a slate delegated to an
+//! external device, i.e. a hardware wallet, through a serialized challenge and response.
+//! The payloads are split in frames small enough for a QR code or a BLE characteristic.
+//!
+//! Only the partial signature is delegated so far, the device holds the blinding factor and
+//! the nonce of its participant, the lib builds the challenge and verifies the response.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::Slate;
+use grin_wallet_util::grin_core::libtx::aggsig;
+use grin_wallet_util::grin_util::secp::key::PublicKey;
+use grin_wallet_util::grin_util::secp::{Message, Signature};
+use grin_wallet_util::grin_util::{from_hex, static_secp_instance, to_hex, Mutex};
+
+/// Version of the signer payloads, bumped on any incompatible change
+pub const SIGNER_PROTOCOL_VERSION: u32 = 1;
+
+/// Prefix of a payload frame, followed by "<index>/<count>:<data>"
+const FRAME_PREFIX: &str = "grinsign:";
+
+/// Min and default frame data sizes, the default fits a version 10 QR code
+const MIN_FRAME_SIZE: usize = 32;
+const DEFAULT_FRAME_SIZE: usize = 200;
+
+/// Max number of challenges waiting for a response, the oldest ones are dropped
+const MAX_PENDING_CHALLENGES: usize = 16;
+
+/// A partial signature challenge for the external device
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SignChallenge {
+    pub version: u32,
+    pub id: String,
+    pub op: String,
+    pub slate_id: String,
+    pub participant_id: usize,
+    pub amount: u64,
+    pub fee: u64,
+    pub lock_height: u64,
+    /// The kernel message to sign, hex
+    pub msg: String,
+    /// The sums of the public nonces and public blind excesses of all participants, hex
+    pub pub_nonce_sum: String,
+    pub pub_blind_sum: String,
+}
+
+/// The partial signature of the external device
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SignResponse {
+    pub version: u32,
+    pub id: String,
+    /// The public blind excess of the device participant, hex
+    pub public_blind_excess: String,
+    pub part_sig: String,
+}
+
+lazy_static! {
+    /// The challenges waiting for a response, the latest last
+    static ref PENDING: Mutex<Vec<SignChallenge>> = Mutex::new(vec![]);
+}
+
+fn invalid(msg: &str) -> Error {
+    ErrorKind::ArgumentError(msg.to_owned()).into()
+}
+
+fn public_key_hex(key: &PublicKey) -> String {
+    let secp = static_secp_instance();
+    let secp = secp.lock();
+    to_hex(key.serialize_vec(&secp, true).to_vec())
+}
+
+fn parse_public_key(hex: &str) -> Result<PublicKey, Error> {
+    let secp = static_secp_instance();
+    let secp = secp.lock();
+    let bytes = from_hex(hex.to_owned()).map_err(|_| invalid("invalid public key hex"))?;
+    PublicKey::from_slice(&secp, &bytes).map_err(|_| invalid("invalid public key"))
+}
+
+/// Split a payload in frames of at most `frame_size` data bytes
+pub fn encode_frames(payload: &str, frame_size: usize) -> Vec<String> {
+    let frame_size = if frame_size == 0 {
+        DEFAULT_FRAME_SIZE
+    } else {
+        frame_size.max(MIN_FRAME_SIZE)
+    };
+    // The payload is json, so ascii, and any byte index is a char boundary
+    let chunks: Vec<&str> = payload
+        .as_bytes()
+        .chunks(frame_size)
+        .map(|c| std::str::from_utf8(c).unwrap_or_default())
+        .collect();
+    let count = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, data)| format!("{}{}/{}:{}", FRAME_PREFIX, i + 1, count, data))
+        .collect()
+}
+
+/// Join the frames of a payload, in any order
+pub fn decode_frames(frames: &[String]) -> Result<String, Error> {
+    let mut parts: Vec<(usize, &str)> = vec![];
+    let mut expected = None;
+    for frame in frames {
+        if !frame.starts_with(FRAME_PREFIX) {
+            return Err(invalid("not a signer frame"));
+        }
+        let mut split = frame[FRAME_PREFIX.len()..].splitn(2, ':');
+        let header = split.next().unwrap_or_default();
+        let data = split
+            .next()
+            .ok_or_else(|| invalid("invalid signer frame"))?;
+        let mut numbers = header.splitn(2, '/').map(|n| n.parse::<usize>().ok());
+        let (index, count) = match (numbers.next(), numbers.next()) {
+            (Some(Some(index)), Some(Some(count))) => (index, count),
+            _ => return Err(invalid("invalid signer frame header")),
+        };
+        if *expected.get_or_insert(count) != count || index == 0 || index > count {
+            return Err(invalid("inconsistent signer frames"));
+        }
+        if !parts.iter().any(|(i, _)| *i == index) {
+            parts.push((index, data));
+        }
+    }
+    match expected {
+        Some(count) if parts.len() == count => {}
+        _ => return Err(invalid("missing signer frames")),
+    }
+    parts.sort_by_key(|(i, _)| *i);
+    Ok(parts.into_iter().map(|(_, data)| data).collect())
+}
+
+/// Build the partial signature challenge of a participant of a slate, once all the public
+/// nonces and blind excesses are in. It waits for its response until `take_response`.
+pub fn challenge(slate: &Slate, participant_id: usize) -> Result<SignChallenge, Error> {
+    if !slate
+        .participant_data
+        .iter()
+        .any(|p| p.id as usize == participant_id)
+    {
+        return Err(invalid("participant not found in the slate"));
+    }
+    let (msg, pub_nonce_sum, pub_blind_sum) = {
+        let secp = static_secp_instance();
+        let secp = secp.lock();
+        let msg = slate
+            .msg_to_sign()
+            .map_err(|e| ErrorKind::GenericError(format!("invalid slate: {}", e)))?;
+        let pub_nonce_sum = slate
+            .pub_nonce_sum(&secp)
+            .map_err(|e| ErrorKind::GenericError(format!("invalid slate: {}", e)))?;
+        let pub_blind_sum = slate
+            .pub_blind_sum(&secp)
+            .map_err(|e| ErrorKind::GenericError(format!("invalid slate: {}", e)))?;
+        (msg, pub_nonce_sum, pub_blind_sum)
+    };
+    let challenge = SignChallenge {
+        version: SIGNER_PROTOCOL_VERSION,
+        id: Uuid::new_v4().to_string(),
+        op: "partial_sig".to_owned(),
+        slate_id: slate.id.to_string(),
+        participant_id,
+        amount: slate.amount,
+        fee: slate.fee,
+        lock_height: slate.lock_height,
+        msg: to_hex(msg[..].to_vec()),
+        pub_nonce_sum: public_key_hex(&pub_nonce_sum),
+        pub_blind_sum: public_key_hex(&pub_blind_sum),
+    };
+
+    let mut pending = PENDING.lock();
+    pending.push(challenge.clone());
+    let excess = pending.len().saturating_sub(MAX_PENDING_CHALLENGES);
+    pending.drain(..excess);
+    Ok(challenge)
+}
+
+/// Take the response of a pending challenge, once its partial signature is verified
+pub fn take_response(response: &SignResponse) -> Result<SignChallenge, Error> {
+    if response.version != SIGNER_PROTOCOL_VERSION {
+        return Err(invalid("unsupported signer protocol version"));
+    }
+    let challenge = PENDING
+        .lock()
+        .iter()
+        .find(|c| c.id == response.id)
+        .cloned()
+        .ok_or_else(|| invalid("signer challenge not found"))?;
+
+    let public_blind_excess = parse_public_key(&response.public_blind_excess)?;
+    let pub_nonce_sum = parse_public_key(&challenge.pub_nonce_sum)?;
+    let pub_blind_sum = parse_public_key(&challenge.pub_blind_sum)?;
+    {
+        let secp = static_secp_instance();
+        let secp = secp.lock();
+        let msg_bytes = from_hex(challenge.msg.clone()).map_err(|_| invalid("invalid msg"))?;
+        let msg = Message::from_slice(&msg_bytes).map_err(|_| invalid("invalid msg"))?;
+        let sig_bytes =
+            from_hex(response.part_sig.clone()).map_err(|_| invalid("invalid signature hex"))?;
+        let sig = Signature::from_raw_data(&secp, &sig_bytes)
+            .map_err(|_| invalid("invalid signature"))?;
+        aggsig::verify_partial_sig(
+            &secp,
+            &sig,
+            &pub_nonce_sum,
+            &public_blind_excess,
+            Some(&pub_blind_sum),
+            &msg,
+        )
+        .map_err(|_| invalid("partial signature verification failed"))?;
+    }
+
+    PENDING.lock().retain(|c| c.id != response.id);
+    Ok(challenge)
+}
+
+/// Drop the pending challenges, i.e. on a cancelled signing
+pub fn clear() {
+    PENDING.lock().clear();
+}
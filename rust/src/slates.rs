@@ -0,0 +1,114 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The slate version matrix: the versions this build reads and writes, and the version of a
+//! reply. A slate is answered in the version its sender created it in, so the downgrade only
+//! happens for a counterparty which requires it.
+//!
+//! The compact V3 and V4 slates are types of the newer upstream wallets, the pinned wallet
+//! libs stop at V2. A request for them is refused with the supported versions, rather than
+//! silently answered in another version.
+
+use std::fs;
+
+use serde_json::Value;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{Slate, SlateVersion, VersionedSlate};
+
+/// The newest supported slate version
+const NEWEST_VERSION: u16 = 2;
+
+/// The supported slate versions, the newest first
+pub const SUPPORTED: &[SlateVersion] = &[SlateVersion::V2, SlateVersion::V1, SlateVersion::V0];
+
+fn from_number(version: u16) -> Option<SlateVersion> {
+    match version {
+        0 => Some(SlateVersion::V0),
+        1 => Some(SlateVersion::V1),
+        2 => Some(SlateVersion::V2),
+        _ => None,
+    }
+}
+
+fn supported_list() -> String {
+    (0..=NEWEST_VERSION)
+        .rev()
+        .map(|v: u16| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Check a requested target slate version, None for the newest one
+pub fn check_target(version: Option<u16>) -> Result<Option<u16>, Error> {
+    match version {
+        Some(v) if from_number(v).is_none() => Err(ErrorKind::ArgumentError(format!(
+            "slate version {} not supported, supported versions: {}",
+            v,
+            supported_list()
+        ))
+        .into()),
+        _ => Ok(version),
+    }
+}
+
+/// The version of a slate json, from its version info or the fields of the V0 slates
+pub fn version_of(slate_json: &str) -> Result<u16, Error> {
+    let value: Value = serde_json::from_str(slate_json)
+        .map_err(|e| ErrorKind::ArgumentError(format!("invalid slate json: {}", e)))?;
+    let version = match value.get("version_info") {
+        Some(info) => info["version"].as_u64().unwrap_or(0),
+        None => value["version"].as_u64().unwrap_or(0),
+    };
+    Ok(version as u16)
+}
+
+/// Refuse a slate json in a version this build can't read, before its deserialization fails
+/// with a confusing error
+pub fn check_readable(slate_json: &str) -> Result<(), Error> {
+    let version = version_of(slate_json)?;
+    match from_number(version) {
+        Some(_) => Ok(()),
+        None => Err(ErrorKind::ArgumentError(format!(
+            "slate version {} not supported, supported versions: {}",
+            version,
+            supported_list()
+        ))
+        .into()),
+    }
+}
+
+/// Refuse a slate file in a version this build can't read
+pub fn check_file_readable(path: &str) -> Result<(), Error> {
+    let slate_json = fs::read_to_string(path)
+        .map_err(|e| ErrorKind::ArgumentError(format!("fail to read slate file: {}", e)))?;
+    check_readable(&slate_json)
+}
+
+/// The version to write a slate in: the one its sender created it in, the newest supported
+/// one if that's newer
+pub fn reply_version(slate: &Slate) -> SlateVersion {
+    from_number(slate.version_info.orig_version).unwrap_or(SUPPORTED[0])
+}
+
+/// A reply slate in the version of its sender
+pub fn reply(slate: Slate) -> VersionedSlate {
+    let version = reply_version(&slate);
+    VersionedSlate::into_version(slate, version)
+}
+
+/// The json of a slate in the version of its sender, for the app to hand it over
+pub fn reply_json(slate: Slate) -> String {
+    serde_json::to_string(&reply(slate)).expect("fail to serialize slate to json string")
+}
@@ -0,0 +1,169 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The split of a payment needing more inputs than a tx spends, i.e. after receiving many
+//! small payments, into sequential txs each spending at most `MAX_TX_INPUTS` outputs.
+//!
+//! On the receive side, the split of a received amount into several outputs, so a merchant
+//! can make parallel payments without waiting for a change to confirm. The extra outputs
+//! weigh on the tx, so a slate is split into no more outputs than its fee pays for.
+
+use std::sync::Arc;
+
+use grin_wallet_impls::{Error, ErrorKind};
+use grin_wallet_libwallet::{
+    Context, NodeClient, OutputData, OutputStatus, Slate, TxLogEntry, TxLogEntryType, WalletInst,
+};
+use grin_wallet_util::grin_core::libtx::{build, tx_fee};
+use grin_wallet_util::grin_keychain::{ExtKeychain, Keychain};
+use grin_wallet_util::grin_util::Mutex;
+
+/// Max inputs of a send tx, the `max_outputs` of its selection
+pub const MAX_TX_INPUTS: usize = 500;
+
+/// Max txs of a split payment
+pub const MAX_SPLIT_TXS: usize = 20;
+
+/// The most a single tx can send from the spendable outputs, spending the largest
+/// `MAX_TX_INPUTS` ones without change
+pub fn capacity(spendable: &[OutputData]) -> u64 {
+    let mut values: Vec<u64> = spendable.iter().map(|o| o.value).collect();
+    values.sort_unstable_by(|a, b| b.cmp(a));
+    values.truncate(MAX_TX_INPUTS);
+    let total: u64 = values.iter().sum();
+    let fee = tx_fee(values.len(), 2, 1, None);
+    total.saturating_sub(fee)
+}
+
+/// Max outputs of a received amount
+pub const MAX_RECEIVE_OUTPUTS: usize = 10;
+
+/// The number of outputs a slate can be received into, at most `wanted`: the fee must pay
+/// for the weight of the extra outputs, and each output be at least `min_value`
+pub fn receive_outputs(slate: &Slate, wanted: usize, min_value: u64) -> usize {
+    let inputs = slate.tx.inputs().len();
+    let outputs = slate.tx.outputs().len();
+    let kernels = slate.tx.kernels().len().max(1);
+    let mut n = wanted.min(MAX_RECEIVE_OUTPUTS).max(1);
+    while n > 1
+        && (tx_fee(inputs, outputs + n, kernels, None) > slate.fee
+            || slate.amount / (n as u64) < min_value.max(1))
+    {
+        n -= 1;
+    }
+    n
+}
+
+/// The values of the outputs of an amount split in `n`, the remainder on the first one
+fn split_amount(amount: u64, n: usize) -> Vec<u64> {
+    let value = amount / n as u64;
+    let mut values = vec![value; n];
+    values[0] += amount - value * n as u64;
+    values
+}
+
+/// Receive a slate into `n` outputs of the `dest_acct_name` account, as the receive of the
+/// foreign api does with a single output. The wallet is closed on every path.
+pub fn receive_into_outputs<C>(
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+    slate: &Slate,
+    dest_acct_name: &str,
+    message: Option<String>,
+    n: usize,
+) -> Result<Slate, Error>
+where
+    C: NodeClient + 'static,
+{
+    let mut w = wallet.lock();
+    w.open_with_credentials()?;
+    let res = receive_opened(&mut *w, slate, dest_acct_name, message, n);
+    let closed = w.close();
+    let slate = res?;
+    closed?;
+    Ok(slate)
+}
+
+fn receive_opened<C>(
+    w: &mut dyn WalletInst<C, ExtKeychain>,
+    slate: &Slate,
+    dest_acct_name: &str,
+    message: Option<String>,
+    n: usize,
+) -> Result<Slate, Error>
+where
+    C: NodeClient + 'static,
+{
+    let mut slate = slate.clone();
+    w.set_parent_key_id_by_name(dest_acct_name)?;
+    let parent_key_id = w.parent_key_id();
+    if w.tx_log_iter()
+        .any(|t| t.tx_slate_id == Some(slate.id) && t.tx_type == TxLogEntryType::TxReceived)
+    {
+        return Err(ErrorKind::ArgumentError("slate already received".to_owned()).into());
+    }
+
+    let keychain = w.keychain().clone();
+    let mut outputs = vec![];
+    for value in split_amount(slate.amount, n) {
+        let key_id = w.next_child()?;
+        let commit = w.calc_commit_for_cache(value, &key_id)?;
+        outputs.push((key_id, value, commit));
+    }
+    let elems = outputs
+        .iter()
+        .map(|(key_id, value, _)| build::output(*value, key_id.clone()))
+        .collect();
+    let blinding = slate.add_transaction_elements(&keychain, elems)?;
+    let sec_key = blinding
+        .secret_key(keychain.secp())
+        .map_err(|e| ErrorKind::GenericError(format!("invalid blinding factor: {}", e)))?;
+    let mut context = Context::new(keychain.secp(), sec_key, &parent_key_id, false, 1);
+    slate.fill_round_1(
+        &keychain,
+        &mut context.sec_key,
+        &context.sec_nonce,
+        1,
+        message,
+        false,
+    )?;
+    slate.fill_round_2(&keychain, &context.sec_key, &context.sec_nonce, 1)?;
+
+    {
+        let mut batch = w.batch()?;
+        let log_id = batch.next_tx_log_id(&parent_key_id)?;
+        let mut t = TxLogEntry::new(parent_key_id.clone(), TxLogEntryType::TxReceived, log_id);
+        t.tx_slate_id = Some(slate.id);
+        t.amount_credited = slate.amount;
+        t.num_outputs = outputs.len();
+        for (key_id, value, commit) in outputs {
+            batch.save(OutputData {
+                root_key_id: parent_key_id.clone(),
+                n_child: key_id.to_path().last_path_index(),
+                key_id,
+                mmr_index: None,
+                commit,
+                value,
+                status: OutputStatus::Unconfirmed,
+                height: slate.height,
+                lock_height: 0,
+                is_coinbase: false,
+                tx_log_entry: Some(log_id),
+            })?;
+        }
+        batch.save_tx_log_entry(t, &parent_key_id)?;
+        batch.save_private_context(slate.id.as_bytes(), 1, &context)?;
+        batch.commit()?;
+    }
+    Ok(slate)
+}
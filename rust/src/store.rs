@@ -0,0 +1,60 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Small JSON files kept next to the wallet LMDB data, for the state owned by this lib.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use grin_wallet_impls::{Error, ErrorKind};
+
+/// Path of a lib-owned file inside the wallet data directory
+pub fn data_file(data_file_dir: &str, name: &str) -> PathBuf {
+    Path::new(data_file_dir).join(name)
+}
+
+/// Load a json file, or the default value if the file doesn't exist yet
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> Result<T, Error> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to read {:?}: {}", path, e)))?;
+    serde_json::from_str(&content)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to parse {:?}: {}", path, e)).into())
+}
+
+/// Save a json file, via a temporary file and rename, so a crash never leaves a half-written file
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let content =
+        serde_json::to_string_pretty(value).map_err(|e| ErrorKind::GenericError(e.to_string()))?;
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to write {:?}: {}", tmp_path, e)))?;
+    fs::rename(&tmp_path, path)
+        .map_err(|e| ErrorKind::GenericError(format!("fail to write {:?}: {}", path, e)))?;
+    Ok(())
+}
+
+/// Current unix timestamp in seconds
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,205 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracing of the multi-step operations, like a send: each step is a span, started and ended
+//! with a log line to the log callback, and correlated by the slate id once known, so a send
+//! hanging in a step has a timeline for the support. The last spans are also kept in memory,
+//! for `grin_trace_timeline`.
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use grin_wallet_util::grin_util::Mutex;
+
+/// Max number of kept spans, the oldest ones are dropped first
+const MAX_KEPT_SPANS: usize = 500;
+
+/// The log callback, called with the log line json. The string is only valid during the call.
+pub type LogCallback = extern "C" fn(log_json: *const c_char);
+
+/// The steps of a send
+pub const INIT: &str = "init";
+pub const EXCHANGE: &str = "exchange";
+pub const LOCK: &str = "lock";
+/// Waiting on the send approval callback
+pub const APPROVAL: &str = "approval";
+pub const FINALIZE: &str = "finalize";
+pub const POST: &str = "post";
+
+/// A step of an operation, open until `ended_at` is set
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Span {
+    pub trace_id: String,
+    pub operation: &'static str,
+    pub step: &'static str,
+    pub slate_id: Option<String>,
+    /// In milliseconds since the epoch
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub duration_ms: Option<u64>,
+    /// "ok", "error", or "aborted" if the operation ended without a result
+    pub status: Option<&'static str>,
+    pub error: Option<String>,
+}
+
+lazy_static! {
+    static ref CALLBACK: Mutex<Option<LogCallback>> = Mutex::new(None);
+    static ref SPANS: Mutex<VecDeque<Span>> = Mutex::new(VecDeque::new());
+    /// The open span of each trace
+    static ref OPEN: Mutex<HashMap<String, Span>> = Mutex::new(HashMap::new());
+}
+
+/// Set or clear the log callback
+pub fn set_callback(callback: Option<LogCallback>) {
+    *CALLBACK.lock() = callback;
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn log(event: &str, span: &Span) {
+    // Not called under the lock, the callback may set another one
+    let callback = *CALLBACK.lock();
+    if let Some(callback) = callback {
+        let level = match span.status {
+            Some("ok") | None => "info",
+            _ => "error",
+        };
+        let mut line = serde_json::to_value(span).unwrap();
+        line["target"] = "trace".into();
+        line["level"] = level.into();
+        line["event"] = event.into();
+        if let Ok(log_json) = CString::new(line.to_string()) {
+            callback(log_json.as_ptr());
+        }
+    }
+}
+
+struct State {
+    slate_id: Option<String>,
+    /// The open step, with its start
+    current: Option<(Span, Instant)>,
+}
+
+/// A traced operation, its steps are entered in order. The open step is ended by the next
+/// one, or by `finish` with the result of the operation.
+pub struct Trace {
+    trace_id: String,
+    operation: &'static str,
+    state: State,
+}
+
+/// Start tracing an operation
+pub fn start(operation: &'static str) -> Trace {
+    Trace {
+        trace_id: Uuid::new_v4().to_string(),
+        operation,
+        state: State {
+            slate_id: None,
+            current: None,
+        },
+    }
+}
+
+impl Trace {
+    /// Correlate the spans of the operation with its slate, also the open one
+    pub fn set_slate_id(&mut self, slate_id: &str) {
+        let state = &mut self.state;
+        state.slate_id = Some(slate_id.to_owned());
+        if let Some((span, _)) = state.current.as_mut() {
+            span.slate_id = Some(slate_id.to_owned());
+            OPEN.lock().insert(self.trace_id.clone(), span.clone());
+        }
+    }
+
+    /// Enter a step, ending the open one as ok
+    pub fn enter(&mut self, step: &'static str) {
+        let state = &mut self.state;
+        if let Some(current) = state.current.take() {
+            end(current, "ok", None);
+        }
+        let span = Span {
+            trace_id: self.trace_id.clone(),
+            operation: self.operation,
+            step,
+            slate_id: state.slate_id.clone(),
+            started_at: now_ms(),
+            ended_at: None,
+            duration_ms: None,
+            status: None,
+            error: None,
+        };
+        log("span_start", &span);
+        OPEN.lock().insert(self.trace_id.clone(), span.clone());
+        state.current = Some((span, Instant::now()));
+    }
+
+    /// End the open step with the result of the operation
+    pub fn finish<T, E: ToString>(&mut self, res: &Result<T, E>) {
+        if let Some(current) = self.state.current.take() {
+            match res {
+                Ok(_) => end(current, "ok", None),
+                Err(e) => end(current, "error", Some(e.to_string())),
+            }
+        }
+    }
+}
+
+impl Drop for Trace {
+    fn drop(&mut self) {
+        if let Some(current) = self.state.current.take() {
+            end(current, "aborted", None);
+        }
+    }
+}
+
+fn end((mut span, started): (Span, Instant), status: &'static str, error: Option<String>) {
+    span.ended_at = Some(now_ms());
+    span.duration_ms = Some(started.elapsed().as_millis() as u64);
+    span.status = Some(status);
+    span.error = error;
+    log("span_end", &span);
+    OPEN.lock().remove(&span.trace_id);
+    let mut spans = SPANS.lock();
+    if spans.len() >= MAX_KEPT_SPANS {
+        spans.pop_front();
+    }
+    spans.push_back(span);
+}
+
+/// The spans of a slate, or of a trace id, oldest first, with the open step of a running
+/// operation last. The steps before the slate is known are included by their trace id.
+pub fn timeline(id: &str) -> Vec<Span> {
+    let open: Vec<Span> = OPEN.lock().values().cloned().collect();
+    let mut spans: Vec<Span> = SPANS.lock().iter().cloned().collect();
+    spans.extend(open);
+    let trace_ids: Vec<String> = spans
+        .iter()
+        .filter(|s| s.slate_id.as_deref() == Some(id) || s.trace_id == id)
+        .map(|s| s.trace_id.clone())
+        .collect();
+    spans.retain(|s| trace_ids.contains(&s.trace_id));
+    spans.sort_by_key(|s| s.started_at);
+    spans
+}
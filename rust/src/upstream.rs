@@ -0,0 +1,252 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The seam between the extern calls and the upstream wallet api. The calls go through the
+//! `WalletApi` trait instead of the `Owner` and `Foreign` apis, so a build against another
+//! upstream grin or gotts wallet version, selected by a cargo feature, only needs another
+//! adapter here, whatever its slate and tx formats. The mock node builds its coinbases with
+//! the `Foreign` api directly, being tied to the one upstream version it's built against.
+
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use grin_wallet_api::{Foreign, Owner};
+use grin_wallet_impls::Error;
+use grin_wallet_libwallet::{
+    AcctPathMapping, InitTxArgs, NodeClient, OutputCommitMapping, Slate, TxLogEntry, TxProof,
+    VersionInfo, WalletInfo, WalletInst,
+};
+use grin_wallet_util::grin_core::core::Transaction;
+use grin_wallet_util::grin_keychain::{ExtKeychain, ExtKeychainPath, Identifier};
+use grin_wallet_util::grin_util::Mutex;
+
+#[cfg(not(feature = "grin-wallet-v2"))]
+compile_error!("an upstream wallet version feature is required, i.e. \"grin-wallet-v2\"");
+
+/// The upstream wallet operations of the extern calls
+pub trait WalletApi {
+    /// The chain height, and whether it's fresh from the node
+    fn node_height(&self) -> Result<(u64, bool), Error>;
+
+    /// The balance with some minimum confirmations, and whether it was refreshed
+    fn summary_info(
+        &self,
+        refresh: bool,
+        minimum_confirmations: u64,
+    ) -> Result<(bool, WalletInfo), Error>;
+
+    /// The txs, all of them or the one of an id or slate id, and whether they were refreshed
+    fn txs(
+        &self,
+        refresh: bool,
+        tx_id: Option<u32>,
+        tx_slate_id: Option<Uuid>,
+    ) -> Result<(bool, Vec<TxLogEntry>), Error>;
+
+    /// The outputs, all of them or the ones of a tx, and whether they were refreshed
+    fn outputs(
+        &self,
+        include_spent: bool,
+        refresh: bool,
+        tx_id: Option<u32>,
+    ) -> Result<(bool, Vec<OutputCommitMapping>), Error>;
+
+    /// Receive a slate into an account, the relay key path being the one it was sent to
+    fn receive(
+        &self,
+        slate: &Slate,
+        account: &str,
+        message: Option<String>,
+        key_path: Option<ExtKeychainPath>,
+    ) -> Result<Slate, Error>;
+
+    /// The accounts of the wallet
+    fn accounts(&self) -> Result<Vec<AcctPathMapping>, Error>;
+
+    /// Create an account of a label, returns its parent key id
+    fn create_account(&self, label: &str) -> Result<Identifier, Error>;
+
+    /// Init a send tx, its outputs not locked yet
+    fn init_send(&self, args: InitTxArgs) -> Result<Slate, Error>;
+
+    /// Lock the inputs of an initiated send, the participant being the sender
+    fn lock_outputs(&self, slate: &Slate, participant_id: usize) -> Result<(), Error>;
+
+    /// Finalize a send with the slate back from the recipient
+    fn finalize(
+        &self,
+        slate: &Slate,
+        tx_proof: Option<TxProof>,
+        key_path: Option<ExtKeychainPath>,
+    ) -> Result<Slate, Error>;
+
+    /// Post a tx to the node, the slate id being the one of its tx log entry
+    fn post(&self, tx_slate_id: Option<Uuid>, tx: &Transaction, fluff: bool) -> Result<(), Error>;
+
+    /// Re-post the last unconfirmed txs, with the upstream flags, and whether one got posted
+    fn repost_last(&self, fluff: bool, include_last: bool) -> Result<bool, Error>;
+
+    /// Cancel a tx by its id or slate id, its outputs unlocked
+    fn cancel(&self, tx_id: Option<u32>, tx_slate_id: Option<Uuid>) -> Result<(), Error>;
+
+    /// The tx stored with a tx log entry, if any
+    fn stored_tx(&self, tx: &TxLogEntry) -> Result<Option<Transaction>, Error>;
+
+    /// Verify the messages signed in a slate
+    fn verify_messages(&self, slate: &Slate) -> Result<(), Error>;
+
+    /// Check and repair a batch of outputs from a mmr index, returns the highest index and
+    /// the last one retrieved
+    fn check_repair_batch(
+        &self,
+        delete_unconfirmed: bool,
+        start_index: u64,
+        batch_size: u64,
+        update_outputs: bool,
+    ) -> Result<(u64, u64), Error>;
+
+    /// Restore the outputs of a batch from a mmr index, returns the highest index, the last
+    /// one retrieved and the number of outputs found
+    fn restore_batch(&self, start_index: u64, batch_size: u64) -> Result<(u64, u64, u64), Error>;
+
+    /// The foreign api version and its supported slate versions
+    fn version(&self) -> Result<VersionInfo, Error>;
+}
+
+/// The adapter of the grin-wallet v2 apis
+#[cfg(feature = "grin-wallet-v2")]
+pub struct WalletV2<C: NodeClient + 'static> {
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+}
+
+#[cfg(feature = "grin-wallet-v2")]
+impl<C: NodeClient + 'static> WalletApi for WalletV2<C> {
+    fn node_height(&self) -> Result<(u64, bool), Error> {
+        let height = Owner::new(self.wallet.clone()).node_height()?;
+        Ok((height.height, height.updated_from_node))
+    }
+
+    fn summary_info(
+        &self,
+        refresh: bool,
+        minimum_confirmations: u64,
+    ) -> Result<(bool, WalletInfo), Error> {
+        Ok(
+            Owner::new(self.wallet.clone())
+                .retrieve_summary_info(refresh, minimum_confirmations)?,
+        )
+    }
+
+    fn txs(
+        &self,
+        refresh: bool,
+        tx_id: Option<u32>,
+        tx_slate_id: Option<Uuid>,
+    ) -> Result<(bool, Vec<TxLogEntry>), Error> {
+        Ok(Owner::new(self.wallet.clone()).retrieve_txs(refresh, tx_id, tx_slate_id)?)
+    }
+
+    fn outputs(
+        &self,
+        include_spent: bool,
+        refresh: bool,
+        tx_id: Option<u32>,
+    ) -> Result<(bool, Vec<OutputCommitMapping>), Error> {
+        Ok(Owner::new(self.wallet.clone()).retrieve_outputs(include_spent, refresh, tx_id)?)
+    }
+
+    fn receive(
+        &self,
+        slate: &Slate,
+        account: &str,
+        message: Option<String>,
+        key_path: Option<ExtKeychainPath>,
+    ) -> Result<Slate, Error> {
+        let api = Foreign::new(self.wallet.clone(), None);
+        Ok(api.receive_tx(slate, Some(account), message, key_path)?)
+    }
+
+    fn accounts(&self) -> Result<Vec<AcctPathMapping>, Error> {
+        Ok(Owner::new(self.wallet.clone()).accounts()?)
+    }
+
+    fn create_account(&self, label: &str) -> Result<Identifier, Error> {
+        Ok(Owner::new(self.wallet.clone()).create_account_path(label)?)
+    }
+
+    fn init_send(&self, args: InitTxArgs) -> Result<Slate, Error> {
+        Ok(Owner::new(self.wallet.clone()).init_send_tx(args)?)
+    }
+
+    fn lock_outputs(&self, slate: &Slate, participant_id: usize) -> Result<(), Error> {
+        Ok(Owner::new(self.wallet.clone()).tx_lock_outputs(slate, participant_id)?)
+    }
+
+    fn finalize(
+        &self,
+        slate: &Slate,
+        tx_proof: Option<TxProof>,
+        key_path: Option<ExtKeychainPath>,
+    ) -> Result<Slate, Error> {
+        Ok(Owner::new(self.wallet.clone()).finalize_tx(slate, tx_proof, key_path)?)
+    }
+
+    fn post(&self, tx_slate_id: Option<Uuid>, tx: &Transaction, fluff: bool) -> Result<(), Error> {
+        Ok(Owner::new(self.wallet.clone()).post_tx(tx_slate_id, tx, fluff)?)
+    }
+
+    fn repost_last(&self, fluff: bool, include_last: bool) -> Result<bool, Error> {
+        Ok(Owner::new(self.wallet.clone()).repost_last_txs(fluff, include_last)?)
+    }
+
+    fn cancel(&self, tx_id: Option<u32>, tx_slate_id: Option<Uuid>) -> Result<(), Error> {
+        Ok(Owner::new(self.wallet.clone()).cancel_tx(tx_id, tx_slate_id)?)
+    }
+
+    fn stored_tx(&self, tx: &TxLogEntry) -> Result<Option<Transaction>, Error> {
+        Ok(Owner::new(self.wallet.clone()).get_stored_tx(tx)?)
+    }
+
+    fn verify_messages(&self, slate: &Slate) -> Result<(), Error> {
+        Ok(Foreign::new(self.wallet.clone(), None).verify_slate_messages(slate)?)
+    }
+
+    fn check_repair_batch(
+        &self,
+        delete_unconfirmed: bool,
+        start_index: u64,
+        batch_size: u64,
+        update_outputs: bool,
+    ) -> Result<(u64, u64), Error> {
+        let api = Owner::new(self.wallet.clone());
+        Ok(api.check_repair_batch(delete_unconfirmed, start_index, batch_size, update_outputs)?)
+    }
+
+    fn restore_batch(&self, start_index: u64, batch_size: u64) -> Result<(u64, u64, u64), Error> {
+        Ok(Owner::new(self.wallet.clone()).restore_batch(start_index, batch_size)?)
+    }
+
+    fn version(&self) -> Result<VersionInfo, Error> {
+        Ok(Foreign::new(self.wallet.clone(), None).check_version()?)
+    }
+}
+
+/// The upstream api of a wallet instance
+#[cfg(feature = "grin-wallet-v2")]
+pub fn api<C: NodeClient + 'static>(
+    wallet: Arc<Mutex<dyn WalletInst<C, ExtKeychain>>>,
+) -> WalletV2<C> {
+    WalletV2 { wallet }
+}
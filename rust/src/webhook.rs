@@ -0,0 +1,198 @@
+// Copyright 2019 The Gotts Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The webhook of the received payments: when the listener accepts a payment, a signed json
+//! notification is posted to the url of the config, i.e. a shop backend. The notifications
+//! are queued in the wallet data directory and retried with a backoff until delivered.
+//!
+//! The posted body is `{"payload": "<notification json>", "signature": "<hex>"}`, the
+//! signature a HMAC-SHA256 of the payload string by the webhook secret.
+
+use std::path::PathBuf;
+
+use ring::{digest, hmac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use grin_wallet_impls::Error;
+use grin_wallet_util::grin_api;
+use grin_wallet_util::grin_util::{to_hex, Mutex};
+
+use crate::{events, new_wallet_config, services, store, MobileWalletCfg};
+
+const WEBHOOK_QUEUE_FILE: &str = "webhook_queue.json";
+
+/// Max delivery attempts of a notification before it's dropped
+const MAX_ATTEMPTS: u32 = 10;
+
+/// The delay before the second attempt, doubled after each failed attempt
+const BASE_DELAY_SECS: u64 = 30;
+const MAX_DELAY_SECS: u64 = 3600;
+
+/// The webhook of a wallet
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// The HMAC key of the notification signatures, shared with the webhook backend
+    pub secret: String,
+}
+
+/// A notification waiting for its delivery
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    id: String,
+    /// The notification json, as signed
+    payload: String,
+    attempts: u32,
+    next_attempt_at: u64,
+    last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct WebhookQueue {
+    notifications: Vec<Notification>,
+}
+
+#[derive(Serialize)]
+struct SignedNotification<'a> {
+    payload: &'a str,
+    signature: String,
+}
+
+lazy_static! {
+    /// Serializes the read-modify-write of the queue file
+    static ref QUEUE_LOCK: Mutex<()> = Mutex::new(());
+    /// Serializes the deliveries, so a notification isn't posted twice at once
+    static ref DELIVERY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn queue_path(config: &MobileWalletCfg) -> Result<PathBuf, Error> {
+    let wallet_config = new_wallet_config(config.clone())?;
+    Ok(store::data_file(
+        &wallet_config.data_file_dir,
+        WEBHOOK_QUEUE_FILE,
+    ))
+}
+
+/// The HMAC-SHA256 of a payload, hex
+pub fn sign(secret: &str, payload: &str) -> String {
+    let key = hmac::SigningKey::new(&digest::SHA256, secret.as_bytes());
+    to_hex(hmac::sign(&key, payload.as_bytes()).as_ref().to_vec())
+}
+
+fn retry_delay(attempts: u32) -> u64 {
+    BASE_DELAY_SECS
+        .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(MAX_DELAY_SECS)
+}
+
+/// Queue the notification of a received payment, and try to deliver it right away
+pub fn notify_received(
+    config: &MobileWalletCfg,
+    slate_id: &str,
+    amount: u64,
+    sender: &str,
+) -> Result<(), Error> {
+    if config.webhook.is_none() {
+        return Ok(());
+    }
+    let id = Uuid::new_v4().to_string();
+    let payload = json!({
+        "id": id,
+        "event": "payment_received",
+        "account": config.account,
+        "slateId": slate_id,
+        "amount": amount,
+        "sender": sender,
+        "timestamp": store::now_secs(),
+    });
+    {
+        let _guard = QUEUE_LOCK.lock();
+        let path = queue_path(config)?;
+        let mut queue: WebhookQueue = store::load(&path)?;
+        queue.notifications.push(Notification {
+            id,
+            payload: payload.to_string(),
+            attempts: 0,
+            next_attempt_at: 0,
+            last_error: None,
+        });
+        store::save(&path, &queue)?;
+    }
+    let config = config.clone();
+    services::spawn("webhook", move || {
+        let _ = deliver(&config);
+    })
+}
+
+/// Post the due notifications, rescheduling the failed ones. Returns the number delivered.
+/// Run on each receive and by the background services.
+pub fn deliver(config: &MobileWalletCfg) -> Result<usize, Error> {
+    let webhook = match config.webhook.as_ref() {
+        Some(webhook) => webhook,
+        None => return Ok(0),
+    };
+    let _delivery = DELIVERY_LOCK.lock();
+    let path = queue_path(config)?;
+    let now = store::now_secs();
+    let due: Vec<Notification> = {
+        let _guard = QUEUE_LOCK.lock();
+        let queue: WebhookQueue = store::load(&path)?;
+        queue
+            .notifications
+            .into_iter()
+            .filter(|n| n.next_attempt_at <= now)
+            .collect()
+    };
+
+    // Posted without the queue lock, so a receive meanwhile isn't held by a slow backend
+    let outcomes: Vec<(String, Option<String>)> = due
+        .iter()
+        .map(|notification| {
+            let body = SignedNotification {
+                payload: &notification.payload,
+                signature: sign(&webhook.secret, &notification.payload),
+            };
+            let res = grin_api::client::post_no_ret(&webhook.url, None, &body);
+            (notification.id.clone(), res.err().map(|e| e.to_string()))
+        })
+        .collect();
+
+    let _guard = QUEUE_LOCK.lock();
+    let mut queue: WebhookQueue = store::load(&path)?;
+    let mut delivered = 0;
+    // The attempted ones are put back only if they're to be retried
+    queue
+        .notifications
+        .retain(|n| !outcomes.iter().any(|(id, _)| *id == n.id));
+    for (mut notification, (_, error)) in due.into_iter().zip(outcomes) {
+        notification.attempts += 1;
+        match error {
+            None => delivered += 1,
+            Some(error) if notification.attempts >= MAX_ATTEMPTS => events::emit(
+                "webhook_failed",
+                json!({ "id": notification.id, "error": error }),
+            ),
+            Some(error) => {
+                notification.next_attempt_at = now + retry_delay(notification.attempts);
+                notification.last_error = Some(error);
+                queue.notifications.push(notification);
+            }
+        }
+    }
+    store::save(&path, &queue)?;
+    Ok(delivered)
+}